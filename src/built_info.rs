@@ -0,0 +1,3 @@
+//! Build metadata (version, git commit, build time, features) generated by `build.rs`.
+
+include!(concat!(env!("OUT_DIR"), "/built.rs"));
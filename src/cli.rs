@@ -0,0 +1,223 @@
+//! Output of the command-line modes that run once and exit, such as `--check`.
+//!
+//! `--output json` prints a single JSON document on stdout, so automation can
+//! parse it without scraping logs. Text output prints a one-line summary on
+//! stdout and diagnostics on stderr. Either way the exit code is an
+//! [`ExitStatus`].
+
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+use std::io::{self, Write};
+
+use crate::error::ProvisionrError;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// Process exit codes. These are stable; automation branches on them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    Success = 0,
+    /// The input or stored data is invalid
+    Validation = 2,
+    /// Rendering or value generation failed
+    Render = 3,
+    /// A file or the database could not be read or written
+    Io = 4,
+}
+
+impl ExitStatus {
+    pub fn for_error(error: &ProvisionrError) -> Self {
+        match error {
+            ProvisionrError::TemplateRender(_)
+            | ProvisionrError::OutputTooLarge { .. }
+            | ProvisionrError::Generation(_)
+            | ProvisionrError::UniqueValueExhausted(_) => Self::Render,
+            ProvisionrError::Database(_) | ProvisionrError::PersistenceUnavailable(_) => Self::Io,
+            _ => Self::Validation,
+        }
+    }
+
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CliError {
+    pub code: String,
+    pub message: String,
+}
+
+/// Result of one command-line run
+#[derive(Debug, Serialize)]
+pub struct CliOutput {
+    /// "ok" or "error"
+    status: &'static str,
+    exit_code: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    warnings: Vec<String>,
+    errors: Vec<CliError>,
+    /// Printed on stdout in text mode; the JSON document carries `result` instead
+    #[serde(skip)]
+    summary: String,
+    /// Printed on stderr in text mode, one per line
+    #[serde(skip)]
+    diagnostics: Vec<String>,
+}
+
+impl CliOutput {
+    /// A successful run producing `result`, described in text mode by `summary`.
+    pub fn new(result: &impl Serialize, summary: String) -> Self {
+        Self {
+            status: "ok",
+            exit_code: ExitStatus::Success.code(),
+            result: Some(serde_json::to_value(result).expect("CLI results serialise to JSON")),
+            warnings: Vec::new(),
+            errors: Vec::new(),
+            summary,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// A run that failed before producing a result.
+    pub fn failed(error: &ProvisionrError) -> Self {
+        Self {
+            status: "error",
+            exit_code: 0,
+            result: None,
+            warnings: Vec::new(),
+            errors: Vec::new(),
+            summary: String::new(),
+            diagnostics: Vec::new(),
+        }
+        .error(ExitStatus::for_error(error), error.code(), error.to_string())
+    }
+
+    pub fn warning(mut self, message: String) -> Self {
+        self.warnings.push(message);
+        self
+    }
+
+    /// Adds an error. The first error decides the exit code.
+    pub fn error(mut self, status: ExitStatus, code: &str, message: String) -> Self {
+        if self.errors.is_empty() {
+            self.exit_code = status.code();
+        }
+        self.status = "error";
+        self.errors.push(CliError {
+            code: code.to_string(),
+            message,
+        });
+        self
+    }
+
+    /// Adds a detail line shown on stderr in text mode only.
+    pub fn diagnostic(mut self, line: String) -> Self {
+        self.diagnostics.push(line);
+        self
+    }
+
+    /// Prints the output to stdout and stderr and returns the exit code.
+    pub fn emit(self, format: OutputFormat) -> i32 {
+        let result = self.write(format, &mut io::stdout().lock(), &mut io::stderr().lock());
+        if let Err(e) = result {
+            eprintln!("Failed to write output: {}", e);
+            return ExitStatus::Io.code();
+        }
+        self.exit_code
+    }
+
+    fn write(&self, format: OutputFormat, stdout: &mut impl Write, stderr: &mut impl Write) -> io::Result<()> {
+        match format {
+            OutputFormat::Json => {
+                serde_json::to_writer_pretty(&mut *stdout, self)?;
+                writeln!(stdout)
+            }
+            OutputFormat::Text => {
+                for line in &self.diagnostics {
+                    writeln!(stderr, "{}", line)?;
+                }
+                for warning in &self.warnings {
+                    writeln!(stderr, "warning: {}", warning)?;
+                }
+                for error in &self.errors {
+                    writeln!(stderr, "error[{}]: {}", error.code, error.message)?;
+                }
+                if !self.summary.is_empty() {
+                    writeln!(stdout, "{}", self.summary)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn written(output: &CliOutput, format: OutputFormat) -> (String, String) {
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        output.write(format, &mut stdout, &mut stderr).unwrap();
+        (String::from_utf8(stdout).unwrap(), String::from_utf8(stderr).unwrap())
+    }
+
+    fn sample() -> CliOutput {
+        CliOutput::new(&serde_json::json!({"rows_checked": 3}), "Checked 3 rows".to_string())
+            .diagnostic("row 2: bad".to_string())
+            .warning("1 repaired".to_string())
+            .error(ExitStatus::Validation, "consistency_violations", "1 left".to_string())
+            .error(ExitStatus::Io, "other", "later".to_string())
+    }
+
+    #[test]
+    fn json_output_is_one_document_on_stdout() {
+        let (stdout, stderr) = written(&sample(), OutputFormat::Json);
+
+        assert!(stderr.is_empty());
+        let document: Value = serde_json::from_str(&stdout).unwrap();
+        assert_eq!(document["status"], "error");
+        assert_eq!(document["exit_code"], 2);
+        assert_eq!(document["result"]["rows_checked"], 3);
+        assert_eq!(document["warnings"], serde_json::json!(["1 repaired"]));
+        assert_eq!(document["errors"][0]["code"], "consistency_violations");
+        assert!(document.get("summary").is_none());
+    }
+
+    #[test]
+    fn text_output_keeps_diagnostics_on_stderr() {
+        let (stdout, stderr) = written(&sample(), OutputFormat::Text);
+
+        assert_eq!(stdout, "Checked 3 rows\n");
+        assert_eq!(
+            stderr,
+            "row 2: bad\nwarning: 1 repaired\nerror[consistency_violations]: 1 left\nerror[other]: later\n"
+        );
+    }
+
+    #[test]
+    fn failures_exit_by_error_kind() {
+        let failed = CliOutput::failed(&ProvisionrError::Database("unable to open database file".to_string()));
+        assert_eq!(failed.exit_code, ExitStatus::Io.code());
+        let (stdout, _) = written(&failed, OutputFormat::Json);
+        let document: Value = serde_json::from_str(&stdout).unwrap();
+        assert_eq!(document["errors"][0]["code"], "database");
+        assert!(document.get("result").is_none());
+
+        assert_eq!(
+            ExitStatus::for_error(&ProvisionrError::TemplateRender("x".to_string())),
+            ExitStatus::Render
+        );
+        assert_eq!(
+            ExitStatus::for_error(&ProvisionrError::TemplateValidation("x".to_string())),
+            ExitStatus::Validation
+        );
+    }
+}
@@ -0,0 +1,285 @@
+//! Template archives for backup and for moving templates between instances.
+//!
+//! An archive is a gzip-compressed tarball holding, for each template, its content
+//! as `<name>` and a `<name>.config.yaml` sidecar with its configuration, values,
+//! test cases and metadata.
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::storage::models::{
+    validate_template_name, TemplateBundle, TemplateConfig, TemplateMetadata, TemplateTestCase,
+};
+
+/// Suffix of the entry holding a template's sidecar
+pub const SIDECAR_SUFFIX: &str = ".config.yaml";
+
+/// Everything stored for a template besides its content
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateSidecar {
+    #[serde(flatten)]
+    pub config: TemplateConfig,
+    /// Stored values as canonical YAML
+    #[serde(default)]
+    pub values_yaml: Option<String>,
+    #[serde(default)]
+    pub test_cases: Vec<TemplateTestCase>,
+    #[serde(default)]
+    pub metadata: TemplateMetadata,
+}
+
+impl From<&TemplateBundle> for TemplateSidecar {
+    fn from(bundle: &TemplateBundle) -> Self {
+        Self {
+            config: bundle.config.clone(),
+            values_yaml: bundle.values_yaml.clone(),
+            test_cases: bundle.test_cases.clone(),
+            metadata: bundle.metadata.clone(),
+        }
+    }
+}
+
+/// Writes `templates` to a gzip-compressed tarball, a content entry followed by its
+/// sidecar for each. No templates give a valid empty archive.
+pub fn write_archive(templates: &[(String, TemplateBundle)]) -> Result<Vec<u8>, String> {
+    let mtime = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for (name, bundle) in templates {
+        let sidecar = serde_yaml::to_string(&TemplateSidecar::from(bundle))
+            .map_err(|e| format!("Failed to write the config of template '{}': {}", name, e))?;
+        for (path, data) in [
+            (name.clone(), bundle.template_content.as_bytes()),
+            (format!("{}{}", name, SIDECAR_SUFFIX), sidecar.as_bytes()),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(mtime);
+            builder
+                .append_data(&mut header, &path, data)
+                .map_err(|e| format!("Failed to add '{}' to the archive: {}", path, e))?;
+        }
+    }
+    builder
+        .into_inner()
+        .and_then(GzEncoder::finish)
+        .map_err(|e| format!("Failed to finish the archive: {}", e))
+}
+
+/// A template read from an archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchivedTemplate {
+    pub name: String,
+    /// The template, or why its entries cannot be imported
+    pub bundle: Result<TemplateBundle, String>,
+}
+
+#[derive(Default)]
+struct ArchiveEntries {
+    content: Option<Vec<u8>>,
+    sidecar: Option<Vec<u8>>,
+    error: Option<String>,
+}
+
+/// Reads the templates of a gzip-compressed tarball, sorted by name. A template
+/// without a sidecar gets the default configuration. Directories are ignored.
+///
+/// Problems with one template's entries are returned with that template; an archive
+/// that cannot be decompressed or unpacked is an error.
+pub fn read_archive(archive: &[u8]) -> Result<Vec<ArchivedTemplate>, String> {
+    let invalid = |e: std::io::Error| format!("Invalid archive: {}", e);
+    let mut tarball = tar::Archive::new(GzDecoder::new(archive));
+    let mut templates: BTreeMap<String, ArchiveEntries> = BTreeMap::new();
+    for entry in tarball.entries().map_err(invalid)? {
+        let mut entry = entry.map_err(invalid)?;
+        if entry.header().entry_type().is_dir() {
+            continue;
+        }
+        let path = entry.path().map_err(invalid)?.to_string_lossy().into_owned();
+        let path = path.trim_start_matches("./");
+        let (name, is_sidecar) = match path.strip_suffix(SIDECAR_SUFFIX) {
+            Some(name) => (name, true),
+            None => (path, false),
+        };
+        let entries = templates.entry(name.to_string()).or_default();
+
+        if validate_template_name(name).is_err() {
+            entries.error = Some(format!("'{}' is not a valid template name", name));
+        } else if !entry.header().entry_type().is_file() {
+            entries.error = Some(format!("'{}' is not a regular file", path));
+        } else {
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).map_err(invalid)?;
+            match is_sidecar {
+                true => entries.sidecar = Some(data),
+                false => entries.content = Some(data),
+            }
+        }
+    }
+
+    Ok(templates
+        .into_iter()
+        .map(|(name, entries)| ArchivedTemplate {
+            bundle: bundle_from_entries(&name, entries),
+            name,
+        })
+        .collect())
+}
+
+fn bundle_from_entries(name: &str, entries: ArchiveEntries) -> Result<TemplateBundle, String> {
+    if let Some(error) = entries.error {
+        return Err(error);
+    }
+    let content = entries
+        .content
+        .ok_or_else(|| format!("archive has '{}{}' but no content for it", name, SIDECAR_SUFFIX))?;
+    let template_content = String::from_utf8(content).map_err(|_| "content is not valid UTF-8".to_string())?;
+    let sidecar: TemplateSidecar = serde_yaml::from_slice(entries.sidecar.as_deref().unwrap_or(b"{}"))
+        .map_err(|e| format!("invalid {}: {}", SIDECAR_SUFFIX, e))?;
+    Ok(TemplateBundle {
+        template_content,
+        values_yaml: sidecar.values_yaml,
+        config: sidecar.config,
+        test_cases: sidecar.test_cases,
+        metadata: sidecar.metadata,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// A gzip-compressed tarball of `files`, given as path and content
+    fn tarball(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (path, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            builder.append_data(&mut header, path, *data).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn entries(archive: &[u8]) -> Vec<(String, String)> {
+        let mut tarball = tar::Archive::new(GzDecoder::new(archive));
+        tarball
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let path = entry.path().unwrap().to_string_lossy().into_owned();
+                let mut content = String::new();
+                entry.read_to_string(&mut content).unwrap();
+                (path, content)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn archive_holds_content_and_sidecar_of_each_template() {
+        let bundle = TemplateBundle {
+            template_content: "hostname {{ hostname }}\n".to_string(),
+            values_yaml: Some("hostname: sw01\n".to_string()),
+            config: TemplateConfig {
+                id_field: "serial".to_string(),
+                param_defaults: HashMap::from([("vlan".to_string(), "100".to_string())]),
+                ..Default::default()
+            },
+            test_cases: Vec::new(),
+            metadata: TemplateMetadata::default(),
+        };
+
+        let archive = write_archive(&[("switch".to_string(), bundle.clone())]).unwrap();
+        let entries = entries(&archive);
+
+        let paths: Vec<&str> = entries.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(paths, vec!["switch", "switch.config.yaml"]);
+        assert_eq!(entries[0].1, "hostname {{ hostname }}\n");
+        let sidecar: TemplateSidecar = serde_yaml::from_str(&entries[1].1).unwrap();
+        assert_eq!(sidecar, TemplateSidecar::from(&bundle));
+        assert!(entries[1].1.contains("id_field: serial"));
+    }
+
+    #[test]
+    fn no_templates_give_an_empty_archive() {
+        let archive = write_archive(&[]).unwrap();
+
+        assert!(entries(&archive).is_empty());
+    }
+
+    #[test]
+    fn exported_templates_read_back_unchanged() {
+        let bundle = TemplateBundle {
+            template_content: "vlan {{ vlan }}\n".to_string(),
+            values_yaml: Some("vlan: 100\n".to_string()),
+            config: TemplateConfig {
+                id_field: "serial".to_string(),
+                ..Default::default()
+            },
+            test_cases: Vec::new(),
+            metadata: TemplateMetadata {
+                owner: Some("network".to_string()),
+                tags: vec!["access".to_string(), "switch".to_string()],
+                ..Default::default()
+            },
+        };
+
+        let archive = write_archive(&[("switch".to_string(), bundle.clone())]).unwrap();
+
+        assert_eq!(
+            read_archive(&archive).unwrap(),
+            vec![ArchivedTemplate {
+                name: "switch".to_string(),
+                bundle: Ok(bundle),
+            }]
+        );
+    }
+
+    #[test]
+    fn bad_entries_are_returned_with_their_template() {
+        let archive = tarball(&[
+            ("./plain", b"Hello {{ name }}"),
+            ("binary", &[0xff, 0xfe]),
+            ("broken.config.yaml", b"id_field: [serial"),
+            ("broken", b"Hi"),
+            ("lonely.config.yaml", b"id_field: serial\n"),
+            ("nested/switch", b"Hi"),
+            ("nested/.hidden", b"Hi"),
+        ]);
+
+        let templates = read_archive(&archive).unwrap();
+
+        let outcomes: Vec<(&str, Result<&str, &str>)> = templates
+            .iter()
+            .map(|t| {
+                let outcome = t.bundle.as_ref().map(|b| b.template_content.as_str()).map_err(String::as_str);
+                (t.name.as_str(), outcome)
+            })
+            .collect();
+        assert_eq!(outcomes[0], ("binary", Err("content is not valid UTF-8")));
+        assert_eq!(outcomes[1].0, "broken");
+        assert!(outcomes[1].1.unwrap_err().starts_with("invalid .config.yaml"));
+        assert_eq!(outcomes[2], ("lonely", Err("archive has 'lonely.config.yaml' but no content for it")));
+        assert_eq!(outcomes[3], ("nested/.hidden", Err("'nested/.hidden' is not a valid template name")));
+        assert_eq!(outcomes[4], ("nested/switch", Ok("Hi")));
+        assert_eq!(outcomes[5], ("plain", Ok("Hello {{ name }}")));
+        assert_eq!(templates[5].bundle.as_ref().unwrap().config.id_field, "mac_address");
+    }
+
+    #[test]
+    fn malformed_archive_is_an_error() {
+        let error = read_archive(b"not a tarball").unwrap_err();
+
+        assert!(error.starts_with("Invalid archive"), "{}", error);
+    }
+}
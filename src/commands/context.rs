@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+use crate::storage::models::{ValueSource, ValueSources};
+
+/// Largest serialised provenance map stored with a render
+pub const MAX_VALUE_SOURCES_BYTES: usize = 8 * 1024;
+
+/// Render context together with the layer each key was taken from.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenderContext {
+    pub values: HashMap<String, String>,
+    pub sources: ValueSources,
+}
+
+impl RenderContext {
+    /// Merges `layers` in order, lowest precedence first: a key in a later layer
+    /// replaces the value and source recorded by an earlier one.
+    pub fn merge<'a, I>(layers: I) -> Self
+    where
+        I: IntoIterator<Item = (ValueSource, &'a HashMap<String, String>)>,
+    {
+        let mut context = Self::default();
+        for (source, layer) in layers {
+            context.push(source, layer);
+        }
+        context
+    }
+
+    /// Adds `layer` above every layer merged so far.
+    pub fn push(&mut self, source: ValueSource, layer: &HashMap<String, String>) {
+        for (key, value) in layer {
+            self.values.insert(key.clone(), value.clone());
+            self.sources.sources.insert(key.clone(), source);
+        }
+    }
+
+    /// Names from `required` that are absent or blank, in the order given.
+    pub fn missing(&self, required: &[String]) -> Vec<String> {
+        required
+            .iter()
+            .filter(|field| self.values.get(*field).is_none_or(|value| value.trim().is_empty()))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Trims `sources` until its JSON form fits in `max_bytes`. Keys from higher
+/// precedence layers are kept first, so bulk values YAML is dropped before the
+/// query parameters and generated values that differ per device.
+pub fn cap_sources(sources: ValueSources, max_bytes: usize) -> ValueSources {
+    let entry_len = |key: &String, source: &ValueSource| {
+        // "key":"source", with the separating comma
+        serde_json::to_string(key).map_or(key.len(), |k| k.len()) + json_len(source) + 2
+    };
+    let total: usize = sources.sources.iter().map(|(k, s)| entry_len(k, s)).sum();
+    if total + envelope_len(sources.omitted) <= max_bytes {
+        return sources;
+    }
+
+    let mut entries: Vec<_> = sources.sources.into_iter().collect();
+    entries.sort_by(|(ka, sa), (kb, sb)| sb.cmp(sa).then_with(|| ka.cmp(kb)));
+
+    let count = entries.len();
+    let mut kept = ValueSources::default();
+    let mut used = envelope_len(count);
+    for (key, source) in entries {
+        let len = entry_len(&key, &source);
+        if used + len > max_bytes {
+            break;
+        }
+        used += len;
+        kept.sources.insert(key, source);
+    }
+    kept.omitted = sources.omitted + count - kept.sources.len();
+    kept
+}
+
+fn json_len(source: &ValueSource) -> usize {
+    serde_json::to_string(source).map_or(0, |s| s.len())
+}
+
+/// Length of `{"sources":{},"omitted":N}` around the entries
+fn envelope_len(omitted: usize) -> usize {
+    r#"{"sources":{},"omitted":}"#.len() + omitted.to_string().len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn later_layers_override_values_and_sources() {
+        let yaml = map(&[("hostname", "sw01"), ("vlan", "10"), ("site", "london")]);
+        let defaults = map(&[("vlan", "100"), ("ntp", "pool")]);
+        let query = map(&[("mac_address", "AA"), ("ntp", "10.0.0.1")]);
+        let generated = map(&[("password", "secret")]);
+
+        let context = RenderContext::merge([
+            (ValueSource::ValuesYaml, &yaml),
+            (ValueSource::ParamDefault, &defaults),
+            (ValueSource::Query, &query),
+            (ValueSource::Generated, &generated),
+        ]);
+
+        assert_eq!(context.values["vlan"], "100");
+        assert_eq!(context.values["ntp"], "10.0.0.1");
+        assert_eq!(context.values.len(), 6);
+
+        let sources = &context.sources.sources;
+        assert_eq!(sources["hostname"], ValueSource::ValuesYaml);
+        assert_eq!(sources["vlan"], ValueSource::ParamDefault);
+        assert_eq!(sources["ntp"], ValueSource::Query);
+        assert_eq!(sources["mac_address"], ValueSource::Query);
+        assert_eq!(sources["password"], ValueSource::Generated);
+        assert_eq!(context.sources.omitted, 0);
+    }
+
+    #[test]
+    fn merge_of_no_layers_is_empty() {
+        assert_eq!(RenderContext::merge([]), RenderContext::default());
+    }
+
+    #[test]
+    fn missing_lists_absent_and_blank_fields_in_order() {
+        let yaml = map(&[("hostname", "sw01"), ("site", " ")]);
+        let query = map(&[("mac_address", "AA"), ("vlan", "")]);
+        let context = RenderContext::merge([(ValueSource::ValuesYaml, &yaml), (ValueSource::Query, &query)]);
+
+        let required: Vec<String> = ["vlan", "hostname", "ip_address", "site", "mac_address"]
+            .iter()
+            .map(|field| field.to_string())
+            .collect();
+        assert_eq!(context.missing(&required), vec!["vlan", "ip_address", "site"]);
+        assert!(context.missing(&[]).is_empty());
+    }
+
+    #[test]
+    fn small_maps_are_not_capped() {
+        let query = map(&[("mac_address", "AA")]);
+        let sources = RenderContext::merge([(ValueSource::Query, &query)]).sources;
+        assert_eq!(cap_sources(sources.clone(), MAX_VALUE_SOURCES_BYTES), sources);
+    }
+
+    #[test]
+    fn capping_keeps_high_precedence_keys_and_counts_the_rest() {
+        let yaml: HashMap<String, String> = (0..500)
+            .map(|i| (format!("value_{:03}", i), "x".to_string()))
+            .collect();
+        let query = map(&[("mac_address", "AA")]);
+        let generated = map(&[("password", "secret")]);
+        let sources = RenderContext::merge([
+            (ValueSource::ValuesYaml, &yaml),
+            (ValueSource::Query, &query),
+            (ValueSource::Generated, &generated),
+        ])
+        .sources;
+
+        let capped = cap_sources(sources, 1024);
+
+        assert!(serde_json::to_string(&capped).unwrap().len() <= 1024);
+        assert_eq!(capped.sources["password"], ValueSource::Generated);
+        assert_eq!(capped.sources["mac_address"], ValueSource::Query);
+        assert!(capped.sources.contains_key("value_000"));
+        assert!(!capped.sources.contains_key("value_499"));
+        assert_eq!(capped.sources.len() + capped.omitted, 502);
+    }
+}
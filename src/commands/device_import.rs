@@ -0,0 +1,349 @@
+//! CSV parsing and row validation for device value imports.
+//!
+//! A file has a header row naming its columns. One column holds the id value of
+//! each device, the first unless another is named; the non-empty cells of the
+//! other columns become that device's value overrides.
+
+use csv_core::{ReadRecordResult, Reader};
+use std::collections::HashMap;
+
+use crate::commands::models::DeviceImportError;
+use crate::commands::transforms::apply_transforms;
+
+/// Data rows accepted in one import.
+pub const MAX_IMPORT_ROWS: usize = 100_000;
+
+/// A parsed CSV file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CsvTable {
+    pub header: Vec<String>,
+    pub rows: Vec<CsvRow>,
+}
+
+/// A data row with its cells trimmed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvRow {
+    /// Line the row starts on; the header is line 1
+    pub line: u64,
+    pub cells: Vec<String>,
+}
+
+/// Parses CSV from the chunks of a request body as they arrive, so the raw file
+/// is never held in memory at once.
+pub struct CsvStream {
+    reader: Reader,
+    output: Vec<u8>,
+    output_len: usize,
+    ends: Vec<usize>,
+    ends_len: usize,
+    /// Last byte the parser consumed, to tell whether a record's `\n` terminator was read
+    last_byte: Option<u8>,
+    table: Option<CsvTable>,
+}
+
+impl Default for CsvStream {
+    fn default() -> Self {
+        Self {
+            reader: Reader::new(),
+            output: vec![0; 1024],
+            output_len: 0,
+            ends: vec![0; 16],
+            ends_len: 0,
+            last_byte: None,
+            table: None,
+        }
+    }
+}
+
+impl CsvStream {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<(), String> {
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        self.read(chunk)
+    }
+
+    /// Reads the last record and returns the table; an empty file is an error.
+    pub fn finish(mut self) -> Result<CsvTable, String> {
+        self.read(&[])?;
+        self.table.ok_or_else(|| "CSV file is empty".to_string())
+    }
+
+    /// Feeds `input` to the parser; empty input marks the end of the file.
+    fn read(&mut self, mut input: &[u8]) -> Result<(), String> {
+        loop {
+            let (result, read, written, ended) = self.reader.read_record(
+                input,
+                &mut self.output[self.output_len..],
+                &mut self.ends[self.ends_len..],
+            );
+            if read > 0 {
+                self.last_byte = Some(input[read - 1]);
+            }
+            input = &input[read..];
+            self.output_len += written;
+            self.ends_len += ended;
+            match result {
+                ReadRecordResult::InputEmpty | ReadRecordResult::End => return Ok(()),
+                ReadRecordResult::OutputFull => self.output.resize(self.output.len() * 2, 0),
+                ReadRecordResult::OutputEndsFull => self.ends.resize(self.ends.len() * 2, 0),
+                ReadRecordResult::Record => self.take_record()?,
+            }
+        }
+    }
+
+    fn take_record(&mut self) -> Result<(), String> {
+        // The parser counts lines from 1 and has not always read the terminator yet
+        // (a `\r\n` ending or the end of the file), so work back from the last line
+        let last_line = self.reader.line() - u64::from(self.last_byte == Some(b'\n'));
+        let line_breaks = self.output[..self.output_len].iter().filter(|&&b| b == b'\n').count();
+        let line = last_line - line_breaks as u64;
+
+        let mut cells = Vec::with_capacity(self.ends_len);
+        let mut start = 0;
+        for &end in &self.ends[..self.ends_len] {
+            let cell = std::str::from_utf8(&self.output[start..end])
+                .map_err(|_| format!("line {} is not valid UTF-8", line))?;
+            cells.push(cell.trim().to_string());
+            start = end;
+        }
+        self.output_len = 0;
+        self.ends_len = 0;
+
+        match &mut self.table {
+            None => self.table = Some(CsvTable { header: cells, rows: Vec::new() }),
+            Some(table) if table.rows.len() >= MAX_IMPORT_ROWS => {
+                return Err(format!("CSV file has more than {} rows", MAX_IMPORT_ROWS));
+            }
+            Some(table) => table.rows.push(CsvRow { line, cells }),
+        }
+        Ok(())
+    }
+}
+
+/// Devices of an import that passed validation, and the rows that did not
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CheckedImport {
+    /// Id value and value overrides of each valid row, in file order
+    pub devices: Vec<(String, HashMap<String, String>)>,
+    pub errors: Vec<DeviceImportError>,
+}
+
+/// Checks each row of `table`. The id is read from `id_column` (the first column
+/// when `None`) and goes through the `id_field` input transforms, as a query
+/// parameter would at render time; the other cells go through the transforms of
+/// their column. A row is rejected when its column count differs from the
+/// header's, its id is empty or contains control characters, an earlier row has
+/// the same id, or a `required` column is empty.
+///
+/// Problems with the header itself fail the whole import.
+pub fn check(
+    table: &CsvTable,
+    id_column: Option<&str>,
+    required: &[String],
+    id_field: &str,
+    input_transforms: &HashMap<String, Vec<String>>,
+) -> Result<CheckedImport, String> {
+    let header = &table.header;
+    if header.iter().all(String::is_empty) {
+        return Err("CSV header is empty".to_string());
+    }
+    if let Some((index, column)) = header
+        .iter()
+        .enumerate()
+        .find(|(index, column)| column.is_empty() || header[..*index].contains(column))
+    {
+        return Err(if column.is_empty() {
+            format!("column {} has no name in the CSV header", index + 1)
+        } else {
+            format!("column '{}' appears more than once in the CSV header", column)
+        });
+    }
+    let id_index = match id_column {
+        None => 0,
+        Some(name) => header
+            .iter()
+            .position(|column| column == name)
+            .ok_or_else(|| format!("id column '{}' is not in the CSV header", name))?,
+    };
+    let missing: Vec<&str> = required
+        .iter()
+        .filter(|column| !header.contains(column))
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("required column(s) missing from the CSV header: {}", missing.join(", ")));
+    }
+
+    let mut checked = CheckedImport::default();
+    let mut first_lines: HashMap<String, u64> = HashMap::new();
+    for row in &table.rows {
+        let reject = |id_value: Option<&String>, error: String| DeviceImportError {
+            line: row.line,
+            id_value: id_value.cloned(),
+            error,
+        };
+        if row.cells.len() != header.len() {
+            let error = format!("row has {} column(s), the header has {}", row.cells.len(), header.len());
+            checked.errors.push(reject(row.cells.get(id_index), error));
+            continue;
+        }
+
+        let id = HashMap::from([(id_field.to_string(), row.cells[id_index].clone())]);
+        let id_value = apply_transforms(input_transforms, &id)?.remove(id_field).unwrap_or_default();
+        if id_value.is_empty() {
+            checked.errors.push(reject(None, "id value is empty".to_string()));
+            continue;
+        }
+        if id_value.chars().any(char::is_control) {
+            checked.errors.push(reject(Some(&id_value), "id value contains control characters".to_string()));
+            continue;
+        }
+        if let Some(first_line) = first_lines.get(&id_value) {
+            let error = format!("duplicate id, first seen on line {}", first_line);
+            checked.errors.push(reject(Some(&id_value), error));
+            continue;
+        }
+        first_lines.insert(id_value.clone(), row.line);
+
+        if let Some(column) = required.iter().find(|column| {
+            let index = header.iter().position(|c| c == *column).unwrap_or(id_index);
+            row.cells[index].is_empty()
+        }) {
+            checked.errors.push(reject(Some(&id_value), format!("required column '{}' is empty", column)));
+            continue;
+        }
+
+        let values: HashMap<String, String> = header
+            .iter()
+            .zip(&row.cells)
+            .enumerate()
+            .filter(|(index, (_, cell))| *index != id_index && !cell.is_empty())
+            .map(|(_, (column, cell))| (column.clone(), cell.clone()))
+            .collect();
+        checked.devices.push((id_value, apply_transforms(input_transforms, &values)?));
+    }
+    Ok(checked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses `csv` fed in chunks of `chunk_size` bytes.
+    fn parse(csv: &str, chunk_size: usize) -> Result<CsvTable, String> {
+        let mut stream = CsvStream::new();
+        for chunk in csv.as_bytes().chunks(chunk_size) {
+            stream.feed(chunk)?;
+        }
+        stream.finish()
+    }
+
+    fn check_csv(csv: &str, id_column: Option<&str>, required: &[&str]) -> Result<CheckedImport, String> {
+        let required: Vec<String> = required.iter().map(|c| c.to_string()).collect();
+        let transforms = HashMap::from([("serial".to_string(), vec!["uppercase".to_string()])]);
+        check(&parse(csv, 7).unwrap(), id_column, &required, "serial", &transforms)
+    }
+
+    fn errors(checked: &CheckedImport) -> Vec<(u64, Option<&str>, &str)> {
+        checked
+            .errors
+            .iter()
+            .map(|e| (e.line, e.id_value.as_deref(), e.error.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_quoted_cells_across_chunk_boundaries() {
+        let csv = "serial,hostname,notes\nsn1, sw01 ,\"rack 4, \"\"top\"\"\"\r\n\nsn2,sw02,\"two\nlines\"\nsn3,sw03,x";
+
+        for chunk_size in [1, 5, csv.len()] {
+            let table = parse(csv, chunk_size).unwrap();
+            assert_eq!(table.header, vec!["serial", "hostname", "notes"]);
+            let rows: Vec<(u64, Vec<&str>)> = table
+                .rows
+                .iter()
+                .map(|r| (r.line, r.cells.iter().map(String::as_str).collect()))
+                .collect();
+            assert_eq!(
+                rows,
+                vec![
+                    (2, vec!["sn1", "sw01", "rack 4, \"top\""]),
+                    (4, vec!["sn2", "sw02", "two\nlines"]),
+                    (6, vec!["sn3", "sw03", "x"]),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn empty_file_is_rejected() {
+        assert_eq!(parse("", 4), Err("CSV file is empty".to_string()));
+    }
+
+    #[test]
+    fn good_file_yields_transformed_ids_and_non_empty_overrides() {
+        let checked = check_csv("serial,hostname,vlan\nsn1,sw01,100\nsn2,sw02,\n", None, &["hostname"]).unwrap();
+
+        assert!(checked.errors.is_empty());
+        assert_eq!(
+            checked.devices,
+            vec![
+                (
+                    "SN1".to_string(),
+                    HashMap::from([
+                        ("hostname".to_string(), "sw01".to_string()),
+                        ("vlan".to_string(), "100".to_string()),
+                    ])
+                ),
+                ("SN2".to_string(), HashMap::from([("hostname".to_string(), "sw02".to_string())])),
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_rows_are_reported_by_line() {
+        let csv = "hostname,serial,ip\n\
+                   sw01,sn1,10.0.0.1\n\
+                   sw02,SN1,10.0.0.2\n\
+                   sw03,,10.0.0.3\n\
+                   sw04,sn4\n\
+                   ,sn5,10.0.0.5\n\
+                   sw06,sn6,10.0.0.6\n";
+
+        let checked = check_csv(csv, Some("serial"), &["hostname"]).unwrap();
+
+        assert_eq!(
+            errors(&checked),
+            vec![
+                (3, Some("SN1"), "duplicate id, first seen on line 2"),
+                (4, None, "id value is empty"),
+                (5, Some("sn4"), "row has 2 column(s), the header has 3"),
+                (6, Some("SN5"), "required column 'hostname' is empty"),
+            ]
+        );
+        let ids: Vec<&str> = checked.devices.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["SN1", "SN6"]);
+        assert_eq!(checked.devices[0].1.get("serial"), None);
+    }
+
+    #[test]
+    fn header_problems_fail_the_import() {
+        assert_eq!(
+            check_csv("serial,hostname\nsn1,sw01\n", Some("mac"), &[]),
+            Err("id column 'mac' is not in the CSV header".to_string())
+        );
+        assert_eq!(
+            check_csv("serial,hostname\nsn1,sw01\n", None, &["ip", "vlan"]),
+            Err("required column(s) missing from the CSV header: ip, vlan".to_string())
+        );
+        assert_eq!(
+            check_csv("serial,serial\nsn1,sn1\n", None, &[]),
+            Err("column 'serial' appears more than once in the CSV header".to_string())
+        );
+    }
+}
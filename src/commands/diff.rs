@@ -0,0 +1,103 @@
+//! Text and key/value diffing shared by the diff endpoints.
+
+use similar::TextDiff;
+use std::collections::{BTreeSet, HashMap};
+
+use crate::commands::models::{ValueChange, ValueDiff};
+use crate::redact::REDACTED;
+
+/// Largest rendered content the rendered diff compares, since line diffing
+/// slows down sharply on big inputs
+pub const MAX_DIFF_BYTES: usize = 4 * 1024 * 1024;
+
+/// Unified diff of two texts with three lines of context. Empty when they are equal.
+pub fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(3)
+        .header(old_label, new_label)
+        .to_string()
+}
+
+/// Key-level comparison of two value maps, sorted by key. Values are compared in full
+/// and replaced with [`REDACTED`] in the output unless `include_values` is set.
+pub fn diff_values(
+    old: &HashMap<String, String>,
+    new: &HashMap<String, String>,
+    include_values: bool,
+) -> Vec<ValueDiff> {
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    let shown = |value: Option<&String>| {
+        value.map(|v| if include_values { v.clone() } else { REDACTED.to_string() })
+    };
+
+    keys.into_iter()
+        .map(|key| {
+            let (a, b) = (old.get(key), new.get(key));
+            let change = match (a, b) {
+                (Some(a), Some(b)) if a == b => ValueChange::Unchanged,
+                (Some(_), Some(_)) => ValueChange::Changed,
+                (Some(_), None) => ValueChange::Removed,
+                _ => ValueChange::Added,
+            };
+            ValueDiff {
+                key: key.clone(),
+                change,
+                a: shown(a),
+                b: shown(b),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn unified_diff_is_empty_for_identical_text() {
+        assert_eq!(unified_diff("a\nb\n", "a\nb\n", "x", "y"), "");
+    }
+
+    #[test]
+    fn unified_diff_marks_changed_lines() {
+        let diff = unified_diff("host a\nvlan 1\n", "host a\nvlan 2\n", "AA", "BB");
+        assert!(diff.starts_with("--- AA\n+++ BB\n"));
+        assert!(diff.contains("-vlan 1\n"));
+        assert!(diff.contains("+vlan 2\n"));
+        assert!(diff.contains(" host a\n"));
+    }
+
+    #[test]
+    fn diff_values_classifies_keys() {
+        let old = map(&[("same", "1"), ("changed", "x"), ("removed", "r")]);
+        let new = map(&[("same", "1"), ("changed", "y"), ("added", "n")]);
+
+        let diff = diff_values(&old, &new, true);
+        let changes: Vec<(&str, ValueChange)> = diff.iter().map(|d| (d.key.as_str(), d.change)).collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("added", ValueChange::Added),
+                ("changed", ValueChange::Changed),
+                ("removed", ValueChange::Removed),
+                ("same", ValueChange::Unchanged),
+            ]
+        );
+        assert_eq!(diff[1].a.as_deref(), Some("x"));
+        assert_eq!(diff[1].b.as_deref(), Some("y"));
+        assert_eq!(diff[0].a, None);
+    }
+
+    #[test]
+    fn diff_values_redacts_but_still_compares() {
+        let diff = diff_values(&map(&[("password", "one")]), &map(&[("password", "two")]), false);
+        assert_eq!(diff[0].change, ValueChange::Changed);
+        assert_eq!(diff[0].a.as_deref(), Some(REDACTED));
+        assert_eq!(diff[0].b.as_deref(), Some(REDACTED));
+    }
+}
@@ -1,5 +1,12 @@
+pub mod archive;
 pub mod commander;
+pub mod context;
+pub mod device_import;
+pub mod diff;
 pub mod models;
+pub mod params_schema;
+pub mod rendered_export;
+pub mod transforms;
 
 #[cfg(test)]
 pub use commander::MockCommander;
@@ -1,44 +1,932 @@
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Instant;
 use tokio::sync::oneshot;
+use utoipa::ToSchema;
 
-use crate::storage::models::{RenderedTemplate, RenderedTemplateSummary, TemplateConfig};
+use crate::commands::archive::ArchivedTemplate;
+use crate::commands::device_import::CsvTable;
+use crate::commands::rendered_export::RenderedExportRow;
+use crate::error::ProvisionrError;
+use crate::storage::models::{
+    ConfigHistoryEntry, DynamicFieldConfig, FetchSource, ConsistencyReport, RenderedCursor, RenderedField, RenderedFilter, RenderedTemplate, RenderedTemplateSummary, TemplateBundle, TemplateConfig, TemplateMetadata, TemplateSummary, TemplateTestCase,
+    TemplateValues, ValuesFormat,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LintCode {
+    /// Variable is not supplied by stored values, dynamic fields or the id field
+    UndefinedVariable,
+    /// Password-like variable is rendered without a hashing algorithm
+    UnhashedSecret,
+    TrailingWhitespace,
+}
+
+/// Where a render gets the value of a template variable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VariableSource {
+    /// Generated at render time; a supplied value would be replaced
+    DynamicField,
+    /// The query parameter that identifies the device
+    IdField,
+    /// Configured default, which a query parameter can override
+    ParamDefault,
+    /// Stored values, which a query parameter can override
+    StoredValue,
+    /// Nothing provides it, so it has to come from a query parameter
+    QueryParam,
+}
+
+/// A variable a template references, by its root name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct TemplateVariable {
+    #[schema(example = "hostname")]
+    pub name: String,
+    #[schema(example = "query_param")]
+    pub source: VariableSource,
+}
+
+/// Non-fatal issue found in a template at upload time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct LintWarning {
+    #[schema(example = "undefined_variable")]
+    pub code: LintCode,
+    #[schema(example = "Variable 'hostname' is not provided by stored values or dynamic fields")]
+    pub message: String,
+    /// 1-based line number, where applicable
+    #[schema(example = 3)]
+    pub line: Option<usize>,
+}
+
+/// Options controlling how a template upload is checked before it is stored
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SetTemplateOptions {
+    /// Reject the upload if linting produces any warnings
+    pub strict: bool,
+    /// Run the template's stored test cases against the new content and reject it on failure
+    pub run_tests: bool,
+    /// Accept content using filters, tests or functions this server does not know
+    pub allow_unknown_filters: bool,
+    /// Replace the content of an existing template
+    pub overwrite: bool,
+}
+
+/// Result of storing a template
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetTemplateResult {
+    pub checksum: String,
+    pub warnings: Vec<LintWarning>,
+    /// The content matched what was already stored, so nothing was written
+    pub unchanged: bool,
+}
+
+/// Result of changing a template's configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetConfigResult {
+    /// History version recorded for the new configuration
+    pub version: i64,
+    /// Cached renders that can no longer be reached because the id field changed
+    pub orphaned_renders: i64,
+    pub warnings: Vec<String>,
+}
+
+/// Result of bumping a template's cache generation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheGenerationBump {
+    /// Generation new renders are stored under
+    pub cache_generation: u64,
+    pub config: SetConfigResult,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AssertionKind {
+    MustContain,
+    MustNotContain,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct AssertionResult {
+    pub kind: AssertionKind,
+    #[schema(example = "hostname sw01")]
+    pub text: String,
+    pub passed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct TestCaseResult {
+    /// Position of the case in the template's test case list
+    pub index: usize,
+    pub passed: bool,
+    /// Render error, if the case failed to render at all
+    pub error: Option<String>,
+    pub assertions: Vec<AssertionResult>,
+}
+
+/// Outcome of running a template's stored test cases
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct TestRunReport {
+    pub passed: bool,
+    pub cases: Vec<TestCaseResult>,
+}
+
+/// How a key differs between the two sides of a values diff
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueChange {
+    Added,
+    Removed,
+    Changed,
+    Unchanged,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ValueDiff {
+    #[schema(example = "root_password")]
+    pub key: String,
+    pub change: ValueChange,
+    /// Value on the `a` side, redacted unless secrets were requested
+    pub a: Option<String>,
+    /// Value on the `b` side, redacted unless secrets were requested
+    pub b: Option<String>,
+}
+
+/// A cached render with its generated values parsed for clients without a YAML parser
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RenderedDetail {
+    #[serde(flatten)]
+    pub rendered: RenderedTemplate,
+    /// `generated_values` as name/value pairs; null when the stored YAML cannot be parsed
+    #[schema(example = json!({"root_password": "s3cr3t"}))]
+    pub generated_values_parsed: Option<HashMap<String, String>>,
+}
+
+/// Differences between two cached renders of the same template
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RenderedDiff {
+    pub template_name: String,
+    #[schema(example = "AA:BB:CC:DD:EE:01")]
+    pub a: String,
+    #[schema(example = "AA:BB:CC:DD:EE:02")]
+    pub b: String,
+    /// True when both rendered content and generated values are equal
+    pub identical: bool,
+    /// Unified diff of the rendered content; empty when identical
+    pub content_diff: String,
+    pub generated_values: Vec<ValueDiff>,
+}
+
+/// One page of a template's cached renders, newest first
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RenderedPage {
+    pub items: Vec<RenderedTemplateSummary>,
+    /// Cached renders of the template across all pages
+    #[schema(example = 12500)]
+    pub total: i64,
+    /// `offset` of the next page; absent on the last page
+    #[schema(example = 100)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<u32>,
+}
+
+/// Cached renders whose template no longer exists
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct OrphanedTemplate {
+    #[schema(example = "old-kickstart")]
+    pub template_name: String,
+    #[schema(example = 12)]
+    pub rendered_count: i64,
+}
+
+/// Outcome of renaming a template
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct TemplateRename {
+    #[schema(example = "kickstart")]
+    pub old_name: String,
+    #[schema(example = "kickstart-rhel9")]
+    pub new_name: String,
+    /// Cached renders moved to the new name
+    #[schema(example = 12)]
+    pub renders_moved: i64,
+}
+
+/// An alias and the template it resolves to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct TemplateAlias {
+    #[schema(example = "boot.j2")]
+    pub alias: String,
+    /// The template or alias it points at
+    #[schema(example = "boot-prod.j2")]
+    pub target: String,
+    /// The template it resolves to once aliases are followed
+    #[schema(example = "boot-prod.j2")]
+    pub template: String,
+}
+
+/// A template of an archive that was not imported
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct TemplateImportRejection {
+    #[schema(example = "access-switch")]
+    pub name: String,
+    #[schema(example = "Template validation failed: unexpected end of input")]
+    pub error: String,
+}
+
+/// Outcome of importing a template archive, by template name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct TemplateImportReport {
+    pub imported: Vec<String>,
+    /// Templates that already existed and were left as they are
+    pub skipped: Vec<String>,
+    pub rejected: Vec<TemplateImportRejection>,
+}
+
+/// What deleting a template removed, or would remove on a dry run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateDeletion {
+    /// The template existed
+    pub deleted: bool,
+    /// Cached renders of the template, which stay in the database as orphans
+    pub orphaned_renders: i64,
+    /// Aliases resolving to the template, deleted with it
+    pub removed_aliases: Vec<String>,
+}
+
+/// Rendered content together with how it was produced, returned by the render
+/// endpoint when the client asks for JSON
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct RenderResult {
+    pub content: String,
+    /// SHA-256 of `content`, sent quoted as the `ETag` of raw responses
+    #[schema(example = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08")]
+    pub content_hash: String,
+    /// True when the content was served from the rendered store
+    pub cache_hit: bool,
+    /// RFC 3339 UTC time the render was first stored
+    #[schema(example = "2024-01-01T12:00:00.000Z")]
+    pub created_at: String,
+    /// Names of the dynamic fields generated for this render; values are never included
+    pub generated_keys: Vec<String>,
+    /// SHA-256 of the template content as currently stored
+    #[schema(example = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")]
+    pub template_checksum: String,
+    /// Problems the render worked around, also sent as `X-Provisionr-Warning` headers
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// Media type of raw responses, from the template's `content_type`; absent for the
+    /// default plain text
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "application/json")]
+    pub content_type: Option<String>,
+    /// Server's fallback template, rendered because the requested one does not exist;
+    /// also sent as the `X-Provisionr-Template` header
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "default.j2")]
+    pub fallback_template: Option<String>,
+}
+
+/// Output of a render that was neither cached nor stored
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct RenderPreview {
+    pub content: String,
+    /// Dynamic field values generated for this preview. A real render would
+    /// generate new ones, so showing them gives nothing away.
+    #[schema(example = json!({"luks_password": "correct-horse-battery"}))]
+    pub generated_values: BTreeMap<String, String>,
+    /// Problems the render worked around
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// Whether a render would be served from the cache, found without rendering
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct CacheStatus {
+    pub cached: bool,
+    /// Creation time of the cached render as an RFC 3339 UTC timestamp
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "2024-01-01T12:00:00.000Z", format = DateTime)]
+    pub created_at: Option<String>,
+    /// Whether the cached render was made from the current template content. Absent
+    /// when nothing is cached or the render was stored before checksums were recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub template_hash_matches: Option<bool>,
+}
+
+/// A cached render after some of its dynamic fields were regenerated
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct RegeneratedRender {
+    /// New rendered content, with generated values redacted unless secrets were requested
+    pub content: String,
+    /// New values of the regenerated fields, redacted unless secrets were requested
+    #[schema(example = json!({"admin_password": "<redacted>"}))]
+    pub generated_values: BTreeMap<String, String>,
+    /// Problems the render worked around
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// What pre-rendering did for one id
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PrerenderStatus {
+    /// Rendered and stored, not yet served
+    Rendered,
+    /// Already had a current cached render, which was left as it was
+    Cached,
+    /// The render failed; see `error`
+    Failed,
+    /// Not attempted because the server is shutting down
+    Cancelled,
+}
+
+/// Outcome of pre-rendering one id
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct PrerenderResult {
+    #[schema(example = "AA:BB:CC:DD:EE:01")]
+    pub id: String,
+    pub status: PrerenderStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "Missing required field: hostname")]
+    pub error: Option<String>,
+}
+
+/// Templates of a group, which share generated values per id
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct GroupMembers {
+    #[schema(example = json!(["switch-config", "switch-certs", "post-install"]))]
+    pub members: Vec<String>,
+}
+
+/// Outcome of warming one template at startup
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct WarmupResult {
+    #[schema(example = "pxe-default")]
+    pub template: String,
+    /// False when the template is missing, empty or fails to compile
+    pub ok: bool,
+    pub error: Option<String>,
+    /// Sample ids whose cached render matches a fresh render from the stored values
+    pub verified_ids: Vec<String>,
+    /// Sample ids whose cached render differs from a fresh render, e.g. because the
+    /// template or values changed after it was cached
+    pub stale_ids: Vec<String>,
+    /// Sample ids with no cached render
+    pub missing_ids: Vec<String>,
+}
+
+/// Failures of the rendered store met by renders since startup
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct PersistenceStatus {
+    /// Cached renders that could not be read
+    pub read_errors: u64,
+    /// New renders that could not be stored
+    pub write_errors: u64,
+    #[schema(example = "Database error: disk I/O error")]
+    pub last_error: Option<String>,
+    #[schema(example = "2024-01-01T12:00:00.000Z")]
+    pub last_error_at: Option<String>,
+}
+
+/// Step of a render whose duration is tracked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderPhase {
+    /// From sending the render command until the handler takes it
+    QueueWait,
+    CacheLookup,
+    /// Parsing the values YAML and reading device values
+    ValuesParse,
+    /// Generating and hashing dynamic values
+    Generation,
+    Render,
+    /// Storing the render and indexing its unique values
+    Store,
+}
+
+impl RenderPhase {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::QueueWait => "queue_wait",
+            Self::CacheLookup => "cache_lookup",
+            Self::ValuesParse => "values_parse",
+            Self::Generation => "generation",
+            Self::Render => "render",
+            Self::Store => "store",
+        }
+    }
+}
+
+/// Durations of one render phase over the most recent renders, in milliseconds
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct PhaseLatency {
+    pub phase: RenderPhase,
+    /// Samples the percentiles are taken from
+    #[schema(example = 256)]
+    pub samples: usize,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+    /// The samples, oldest first
+    pub recent_ms: Vec<f64>,
+}
+
+/// Server status beyond what `/api/version` reports
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct ServerStatus {
+    /// Templates warmed since startup, sorted by name
+    pub warmup: Vec<WarmupResult>,
+    pub persistence: PersistenceStatus,
+}
+
+/// A CSV row rejected by a device import
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct DeviceImportError {
+    /// Line of the file the row starts on; the header is line 1
+    #[schema(example = 7)]
+    pub line: u64,
+    /// Id value of the row after input transforms, when it has one
+    #[schema(example = "SN1234")]
+    pub id_value: Option<String>,
+    #[schema(example = "duplicate id, first seen on line 3")]
+    pub error: String,
+}
+
+/// Outcome of importing device values from a CSV file
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct DeviceImportReport {
+    /// Data rows in the file
+    pub rows: usize,
+    /// Rows that passed validation
+    pub valid: usize,
+    /// Devices whose values were written; 0 for a dry run or a rejected import
+    pub applied: usize,
+    pub dry_run: bool,
+    pub errors: Vec<DeviceImportError>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DeviceImportOptions {
+    /// Header of the column holding id values; the first column when `None`
+    pub id_column: Option<String>,
+    /// Columns that must be present and non-empty in every row
+    pub required_columns: Vec<String>,
+    /// Validate only; nothing is written
+    pub dry_run: bool,
+    /// Write the valid rows even when other rows are rejected
+    pub partial: bool,
+}
+
+/// Periodic job run by the maintenance scheduler, named in the `maintenance` config list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceJob {
+    /// Deletes the cached renders of templates that no longer exist
+    PurgeOrphans,
+    /// Deletes cached renders older than their template's `cache_ttl_seconds`
+    ExpireRenders,
+}
+
+/// One run of a maintenance job
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct MaintenanceRun {
+    pub job: MaintenanceJob,
+    #[schema(example = "2024-01-01T00:00:00.000Z")]
+    pub started_at: String,
+    pub duration_ms: u64,
+    /// Rows deleted or changed; 0 when the run failed
+    pub rows_affected: u64,
+    pub error: Option<String>,
+}
+
+/// A maintenance job and its most recent runs
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct MaintenanceJobStatus {
+    pub job: MaintenanceJob,
+    /// Seconds between runs; null for a job that is not scheduled
+    pub interval_seconds: Option<u64>,
+    /// Most recent run first
+    pub runs: Vec<MaintenanceRun>,
+}
 
 pub enum Command {
     SetTemplate {
         name: String,
         content: String,
-        response: oneshot::Sender<Result<(), String>>,
+        options: SetTemplateOptions,
+        response: oneshot::Sender<Result<SetTemplateResult, ProvisionrError>>,
+    },
+    SetTestCases {
+        name: String,
+        cases: Vec<TemplateTestCase>,
+        response: oneshot::Sender<Result<(), ProvisionrError>>,
+    },
+    /// Replaces the template's metadata and returns it normalized; `None` if the
+    /// template does not exist
+    SetMetadata {
+        name: String,
+        metadata: TemplateMetadata,
+        response: oneshot::Sender<Result<Option<TemplateMetadata>, ProvisionrError>>,
+    },
+    GetTestCases {
+        name: String,
+        response: oneshot::Sender<Result<Option<Vec<TemplateTestCase>>, ProvisionrError>>,
+    },
+    RunTemplateTests {
+        name: String,
+        response: oneshot::Sender<Result<TestRunReport, ProvisionrError>>,
+    },
+    GetChecksum {
+        name: String,
+        response: oneshot::Sender<Result<Option<String>, ProvisionrError>>,
     },
     SetValues {
         name: String,
-        yaml: String,
-        response: oneshot::Sender<Result<(), String>>,
+        /// Raw values document; TOML is converted to YAML before it is stored
+        content: String,
+        format: ValuesFormat,
+        response: oneshot::Sender<Result<(), ProvisionrError>>,
+    },
+    GetValues {
+        name: String,
+        response: oneshot::Sender<Result<Option<TemplateValues>, ProvisionrError>>,
+    },
+    /// Removes a template's stored values; `false` if the template does not exist
+    ClearValues {
+        name: String,
+        response: oneshot::Sender<Result<bool, ProvisionrError>>,
     },
     SetConfig {
         name: String,
-        config: TemplateConfig,
-        response: oneshot::Sender<Result<(), String>>,
+        config: Box<TemplateConfig>,
+        /// Recorded in the config history
+        changed_by: Option<String>,
+        response: oneshot::Sender<Result<SetConfigResult, ProvisionrError>>,
+    },
+    GetConfigHistory {
+        name: String,
+        response: oneshot::Sender<Result<Option<Vec<ConfigHistoryEntry>>, ProvisionrError>>,
+    },
+    /// Re-applies a recorded config as a new version; `None` if the template or version is unknown
+    RollbackConfig {
+        name: String,
+        version: i64,
+        changed_by: Option<String>,
+        response: oneshot::Sender<Result<Option<SetConfigResult>, ProvisionrError>>,
+    },
+    /// Increments the template's cache generation so every device re-renders on
+    /// its next fetch; `None` if the template does not exist
+    BumpCacheGeneration {
+        name: String,
+        changed_by: Option<String>,
+        response: oneshot::Sender<Result<Option<CacheGenerationBump>, ProvisionrError>>,
     },
     GetConfig {
         name: String,
-        response: oneshot::Sender<Result<Option<TemplateConfig>, String>>,
+        response: oneshot::Sender<Result<Option<TemplateConfig>, ProvisionrError>>,
+    },
+    GetIdField {
+        name: String,
+        response: oneshot::Sender<Result<Option<String>, ProvisionrError>>,
+    },
+    GetDynamicFields {
+        name: String,
+        response: oneshot::Sender<Result<Option<Vec<DynamicFieldConfig>>, ProvisionrError>>,
+    },
+    GetBundle {
+        name: String,
+        response: oneshot::Sender<Result<Option<TemplateBundle>, ProvisionrError>>,
+    },
+    /// Whether a template or an alias resolving to one is stored, without reading it
+    TemplateExists {
+        name: String,
+        response: oneshot::Sender<Result<bool, ProvisionrError>>,
+    },
+    /// Stored template content as uploaded; `None` if the template does not exist
+    GetTemplateContent {
+        name: String,
+        response: oneshot::Sender<Result<Option<String>, ProvisionrError>>,
+    },
+    /// JSON Schema of the template's render parameters; `None` if the template does not exist
+    GetParamsSchema {
+        name: String,
+        response: oneshot::Sender<Result<Option<serde_json::Value>, ProvisionrError>>,
+    },
+    /// Renders like `RenderTemplate` without the cache or storing the result; the id
+    /// field is optional. `None` if the template does not exist
+    PreviewRender {
+        name: String,
+        values: HashMap<String, String>,
+        response: oneshot::Sender<Result<Option<RenderPreview>, ProvisionrError>>,
     },
     RenderTemplate {
         name: String,
         query_values: HashMap<String, String>,
-        response: oneshot::Sender<Result<String, String>>,
+        /// Client of the request, recorded with the render
+        source: FetchSource,
+        /// When the command was sent, to measure its wait in the queue
+        sent_at: Instant,
+        response: oneshot::Sender<Result<RenderResult, ProvisionrError>>,
+    },
+    /// Looks up the cached render a render with `query_values` would serve, without
+    /// rendering, generating values or counting a serve; `None` when the template
+    /// does not exist
+    GetCacheStatus {
+        name: String,
+        query_values: HashMap<String, String>,
+        response: oneshot::Sender<Result<Option<CacheStatus>, ProvisionrError>>,
+    },
+    /// Renders and stores one id ahead of its first fetch; render failures are
+    /// reported in the result rather than as an error
+    PrerenderTemplate {
+        name: String,
+        id_value: String,
+        values: HashMap<String, String>,
+        response: oneshot::Sender<Result<Option<PrerenderResult>, ProvisionrError>>,
     },
     ListRendered {
         template_name: String,
-        response: oneshot::Sender<Result<Vec<RenderedTemplateSummary>, String>>,
+        filter: RenderedFilter,
+        limit: u32,
+        offset: u32,
+        response: oneshot::Sender<Result<RenderedPage, ProvisionrError>>,
     },
+    /// A render with its generated values masked, also inside the content, unless
+    /// `include_secrets` or the server does not redact
     GetRendered {
         template_name: String,
         id_value: String,
-        response: oneshot::Sender<Result<Option<RenderedTemplate>, String>>,
+        include_secrets: bool,
+        response: oneshot::Sender<Result<Option<RenderedDetail>, ProvisionrError>>,
+    },
+    /// A render with only the identifiers and `fields`, as a JSON object, masked
+    /// like [`Command::GetRendered`]
+    GetRenderedFields {
+        template_name: String,
+        id_value: String,
+        fields: Vec<RenderedField>,
+        include_secrets: bool,
+        response: oneshot::Sender<Result<Option<serde_json::Map<String, serde_json::Value>>, ProvisionrError>>,
     },
+    DiffRendered {
+        template_name: String,
+        a: String,
+        b: String,
+        include_secrets: bool,
+        response: oneshot::Sender<Result<Option<RenderedDiff>, ProvisionrError>>,
+    },
+    /// One page of a template's renders older than `after`, newest first, with their
+    /// generated values, redacted unless `include_secrets`
+    ExportRendered {
+        template_name: String,
+        limit: u32,
+        after: Option<RenderedCursor>,
+        include_secrets: bool,
+        response: oneshot::Sender<Result<Vec<RenderedExportRow>, ProvisionrError>>,
+    },
+    /// Removes one cached render; `false` when there was none
+    DeleteRendered {
+        template_name: String,
+        id_value: String,
+        response: oneshot::Sender<Result<bool, ProvisionrError>>,
+    },
+    /// Renders the current template content for an existing cached render with its
+    /// stored generated values, and replaces the cached content; `None` when the
+    /// template or the render does not exist
+    RerenderRendered {
+        template_name: String,
+        id_value: String,
+        values: HashMap<String, String>,
+        response: oneshot::Sender<Result<Option<RenderResult>, ProvisionrError>>,
+    },
+    /// Generates new values for `fields` of an existing render, keeping its other
+    /// generated values, and re-renders it in place
+    RegenerateRendered {
+        template_name: String,
+        id_value: String,
+        fields: Vec<String>,
+        values: HashMap<String, String>,
+        include_secrets: bool,
+        response: oneshot::Sender<Result<Option<RegeneratedRender>, ProvisionrError>>,
+    },
+    /// Copies a render to a new id, e.g. for a replacement device, optionally
+    /// re-rendering it for the new id with the same generated values
+    CloneRendered {
+        template_name: String,
+        id_value: String,
+        new_id_value: String,
+        rerender: bool,
+        overwrite: bool,
+        include_secrets: bool,
+        response: oneshot::Sender<Result<Option<RenderedDetail>, ProvisionrError>>,
+    },
+    /// Removes every cached render of a template and returns how many there were; a
+    /// dry run only counts them
+    PurgeRendered {
+        template_name: String,
+        dry_run: bool,
+        response: oneshot::Sender<Result<i64, ProvisionrError>>,
+    },
+    /// Removes a template, its config and its aliases; a dry run only reports what
+    /// would go. An alias is resolved to its template first.
     DeleteTemplate {
         name: String,
-        response: oneshot::Sender<Result<(), String>>,
+        dry_run: bool,
+        response: oneshot::Sender<Result<TemplateDeletion, ProvisionrError>>,
+    },
+    /// Copies a template's content, values, configuration and test cases to a new
+    /// name, without its cached renders; `None` if the template does not exist
+    CopyTemplate {
+        name: String,
+        new_name: String,
+        /// Replace a template already stored under `new_name`
+        overwrite: bool,
+        response: oneshot::Sender<Result<Option<TemplateSummary>, ProvisionrError>>,
+    },
+    /// Moves a template and everything stored for it to a new name; `None` if the
+    /// template does not exist
+    RenameTemplate {
+        name: String,
+        new_name: String,
+        response: oneshot::Sender<Result<Option<TemplateRename>, ProvisionrError>>,
+    },
+    /// Points an alias at a template or another alias; `None` if the target does not
+    /// resolve to a template
+    SetAlias {
+        alias: String,
+        target: String,
+        response: oneshot::Sender<Result<Option<TemplateAlias>, ProvisionrError>>,
+    },
+    /// Removes an alias, leaving its template; false if `alias` is not one
+    RemoveAlias {
+        alias: String,
+        response: oneshot::Sender<Result<bool, ProvisionrError>>,
+    },
+    /// Names of all templates, sorted
+    ListTemplateNames {
+        response: oneshot::Sender<Result<Vec<String>, ProvisionrError>>,
+    },
+    /// Stores the templates of an archive, each validated on its own; existing
+    /// templates are replaced only when `overwrite` is set
+    ImportTemplates {
+        templates: Vec<ArchivedTemplate>,
+        overwrite: bool,
+        changed_by: Option<String>,
+        response: oneshot::Sender<Result<TemplateImportReport, ProvisionrError>>,
+    },
+    /// Variables the template references and where each gets its value; `None` if
+    /// the template does not exist
+    GetTemplateVariables {
+        name: String,
+        response: oneshot::Sender<Result<Option<Vec<TemplateVariable>>, ProvisionrError>>,
     },
+    /// Checks template content the way an upload would, without storing it, and
+    /// returns the root names of the variables it references
+    ValidateTemplate {
+        content: String,
+        allow_unknown_filters: bool,
+        response: oneshot::Sender<Result<Vec<String>, ProvisionrError>>,
+    },
+    /// Bundles of all templates, sorted by name
+    ExportTemplates {
+        response: oneshot::Sender<Result<Vec<(String, TemplateBundle)>, ProvisionrError>>,
+    },
+    /// Summaries of all templates, sorted by name
+    ListTemplates {
+        response: oneshot::Sender<Result<Vec<TemplateSummary>, ProvisionrError>>,
+    },
+    ListOrphans {
+        response: oneshot::Sender<Result<Vec<OrphanedTemplate>, ProvisionrError>>,
+    },
+    /// Deletes the cached renders of every template that no longer exists; a dry
+    /// run only lists them
+    PurgeOrphans {
+        dry_run: bool,
+        response: oneshot::Sender<Result<Vec<OrphanedTemplate>, ProvisionrError>>,
+    },
+    /// Compiles a template and checks the cached renders of `ids`; failures are
+    /// reported in the result rather than as an error
+    WarmTemplate {
+        name: String,
+        ids: Vec<String>,
+        response: oneshot::Sender<Result<WarmupResult, ProvisionrError>>,
+    },
+    GetStatus {
+        response: oneshot::Sender<Result<ServerStatus, ProvisionrError>>,
+    },
+    /// Checks rendered-store invariants, repairing what can be fixed when `repair` is set
+    CheckConsistency {
+        repair: bool,
+        response: oneshot::Sender<Result<ConsistencyReport, ProvisionrError>>,
+    },
+    /// Runs a maintenance job and records it in the job's history; a failed job is
+    /// reported in the run rather than as an error
+    RunMaintenance {
+        job: MaintenanceJob,
+        response: oneshot::Sender<Result<MaintenanceRun, ProvisionrError>>,
+    },
+    GetMaintenance {
+        response: oneshot::Sender<Result<Vec<MaintenanceJobStatus>, ProvisionrError>>,
+    },
+    /// Validates CSV rows and stores them as per-device value overrides; `None`
+    /// when the template does not exist
+    ImportDevices {
+        name: String,
+        table: CsvTable,
+        options: DeviceImportOptions,
+        response: oneshot::Sender<Result<Option<DeviceImportReport>, ProvisionrError>>,
+    },
+    /// Replaces the members of a group; every member must be an existing template
+    SetGroupMembers {
+        group: String,
+        members: Vec<String>,
+        response: oneshot::Sender<Result<GroupMembers, ProvisionrError>>,
+    },
+    GetGroupMembers {
+        group: String,
+        response: oneshot::Sender<Result<Option<GroupMembers>, ProvisionrError>>,
+    },
+    /// Renders `member` with the dynamic values its group shares for the id; `None`
+    /// when the group does not exist or does not list `member`
+    RenderGroupMember {
+        group: String,
+        member: String,
+        query_values: HashMap<String, String>,
+        source: FetchSource,
+        /// When the command was sent, to measure its wait in the queue
+        sent_at: Instant,
+        response: oneshot::Sender<Result<Option<RenderResult>, ProvisionrError>>,
+    },
+    /// Recent durations of each render phase
+    GetRenderLatency {
+        response: oneshot::Sender<Result<Vec<PhaseLatency>, ProvisionrError>>,
+    },
+}
+
+
+impl Command {
+    /// Label of the command kind in metrics
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SetTemplate { .. } => "set_template",
+            Self::SetTestCases { .. } => "set_test_cases",
+            Self::SetMetadata { .. } => "set_metadata",
+            Self::GetTestCases { .. } => "get_test_cases",
+            Self::RunTemplateTests { .. } => "run_template_tests",
+            Self::GetChecksum { .. } => "get_checksum",
+            Self::SetValues { .. } => "set_values",
+            Self::GetValues { .. } => "get_values",
+            Self::ClearValues { .. } => "clear_values",
+            Self::SetConfig { .. } => "set_config",
+            Self::GetConfigHistory { .. } => "get_config_history",
+            Self::RollbackConfig { .. } => "rollback_config",
+            Self::BumpCacheGeneration { .. } => "bump_cache_generation",
+            Self::GetConfig { .. } => "get_config",
+            Self::GetIdField { .. } => "get_id_field",
+            Self::GetDynamicFields { .. } => "get_dynamic_fields",
+            Self::GetBundle { .. } => "get_bundle",
+            Self::TemplateExists { .. } => "template_exists",
+            Self::GetTemplateContent { .. } => "get_template_content",
+            Self::GetParamsSchema { .. } => "get_params_schema",
+            Self::PreviewRender { .. } => "preview_render",
+            Self::RenderTemplate { .. } => "render_template",
+            Self::GetCacheStatus { .. } => "get_cache_status",
+            Self::PrerenderTemplate { .. } => "prerender_template",
+            Self::ListRendered { .. } => "list_rendered",
+            Self::GetRendered { .. } => "get_rendered",
+            Self::GetRenderedFields { .. } => "get_rendered_fields",
+            Self::DiffRendered { .. } => "diff_rendered",
+            Self::ExportRendered { .. } => "export_rendered",
+            Self::DeleteRendered { .. } => "delete_rendered",
+            Self::RerenderRendered { .. } => "rerender_rendered",
+            Self::RegenerateRendered { .. } => "regenerate_rendered",
+            Self::CloneRendered { .. } => "clone_rendered",
+            Self::PurgeRendered { .. } => "purge_rendered",
+            Self::DeleteTemplate { .. } => "delete_template",
+            Self::CopyTemplate { .. } => "copy_template",
+            Self::RenameTemplate { .. } => "rename_template",
+            Self::SetAlias { .. } => "set_alias",
+            Self::RemoveAlias { .. } => "remove_alias",
+            Self::ListTemplateNames { .. } => "list_template_names",
+            Self::GetTemplateVariables { .. } => "get_template_variables",
+            Self::ValidateTemplate { .. } => "validate_template",
+            Self::ImportTemplates { .. } => "import_templates",
+            Self::ExportTemplates { .. } => "export_templates",
+            Self::ListTemplates { .. } => "list_templates",
+            Self::ListOrphans { .. } => "list_orphans",
+            Self::PurgeOrphans { .. } => "purge_orphans",
+            Self::WarmTemplate { .. } => "warm_template",
+            Self::GetStatus { .. } => "get_status",
+            Self::CheckConsistency { .. } => "check_consistency",
+            Self::RunMaintenance { .. } => "run_maintenance",
+            Self::GetMaintenance { .. } => "get_maintenance",
+            Self::ImportDevices { .. } => "import_devices",
+            Self::SetGroupMembers { .. } => "set_group_members",
+            Self::GetGroupMembers { .. } => "get_group_members",
+            Self::RenderGroupMember { .. } => "render_group_member",
+            Self::GetRenderLatency { .. } => "get_render_latency",
+        }
+    }
 }
@@ -0,0 +1,152 @@
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+use crate::storage::models::TemplateData;
+
+const DRAFT_07: &str = "http://json-schema.org/draft-07/schema#";
+
+/// Draft-07 JSON Schema of the query parameters a render of `name` accepts, for
+/// form generators. `variables` are the template's undeclared variables and
+/// `stored_values` its values YAML as a map.
+///
+/// The id field and every variable no stored value, param default or dynamic field
+/// provides are required. Variables with a stored value or param default are
+/// optional with that `default`, the param default winning as it does at render
+/// time. Dynamic fields are `readOnly`, since a supplied value would be replaced.
+pub fn build(
+    name: &str,
+    variables: &[String],
+    stored_values: &HashMap<String, String>,
+    data: &TemplateData,
+) -> Value {
+    let mut properties = Map::new();
+    let mut required = vec![data.id_field.clone()];
+
+    properties.insert(
+        data.id_field.clone(),
+        json!({
+            "type": "string",
+            "description": "Identifies the device; renders with the same value are served from the cache",
+        }),
+    );
+
+    let parameters = variables
+        .iter()
+        .chain(data.param_defaults.keys())
+        .filter(|param| **param != data.id_field);
+    for param in parameters {
+        if properties.contains_key(param) {
+            continue;
+        }
+        let default = data.param_defaults.get(param).or_else(|| stored_values.get(param));
+        let is_generated = data.dynamic_fields.iter().any(|field| field.generates(param));
+        let schema = match (is_generated, default) {
+            (true, _) => continue,
+            (false, Some(default)) => json!({ "type": "string", "default": default }),
+            (false, None) => {
+                required.push(param.clone());
+                json!({ "type": "string" })
+            }
+        };
+        properties.insert(param.clone(), schema);
+    }
+
+    for field in &data.dynamic_fields {
+        for value_name in field.value_names() {
+            properties.insert(
+                value_name,
+                json!({
+                    "type": "string",
+                    "readOnly": true,
+                    "description": format!("Generated at render time ({})", field.generator_type.kind()),
+                }),
+            );
+        }
+    }
+
+    for (param, transforms) in &data.input_transforms {
+        if let Some(Value::Object(schema)) = properties.get_mut(param) {
+            let note = format!("Transformed before use: {}", transforms.join(", "));
+            let description = match schema.get("description").and_then(Value::as_str) {
+                Some(existing) => format!("{}. {}", existing, note),
+                None => note,
+            };
+            schema.insert("description".to_string(), Value::String(description));
+        }
+    }
+
+    required[1..].sort();
+    json!({
+        "$schema": DRAFT_07,
+        "title": format!("Render parameters of template '{}'", name),
+        "type": "object",
+        "properties": properties,
+        "required": required,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::models::{DynamicFieldConfig, GeneratorType, HashingAlgorithm};
+
+    fn kickstart() -> TemplateData {
+        TemplateData {
+            id_field: "serial".to_string(),
+            dynamic_fields: vec![DynamicFieldConfig {
+                field_name: "root_password".to_string(),
+                generator_type: GeneratorType::Passphrase { word_count: 4 },
+                hashing_algorithm: HashingAlgorithm::Sha512,
+                unique: false,
+            }],
+            param_defaults: HashMap::from([("vlan".to_string(), "100".to_string())]),
+            input_transforms: HashMap::from([("serial".to_string(), vec!["trim".to_string(), "uppercase".to_string()])]),
+            ..TemplateData::default()
+        }
+    }
+
+    fn schema() -> Value {
+        let variables: Vec<String> = ["hostname", "ntp_server", "root_password", "serial", "site", "vlan"]
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        let stored = HashMap::from([
+            ("ntp_server".to_string(), "10.0.0.1".to_string()),
+            ("vlan".to_string(), "1".to_string()),
+        ]);
+        build("kickstart", &variables, &stored, &kickstart())
+    }
+
+    #[test]
+    fn schema_is_valid_draft_07() {
+        let schema = schema();
+        assert_eq!(schema["$schema"], DRAFT_07);
+        assert!(jsonschema::draft7::meta::is_valid(&schema));
+    }
+
+    #[test]
+    fn schema_marks_required_optional_and_generated_parameters() {
+        let schema = schema();
+        let properties = &schema["properties"];
+
+        assert_eq!(schema["required"], json!(["serial", "hostname", "site"]));
+        assert_eq!(properties["vlan"]["default"], "100");
+        assert_eq!(properties["ntp_server"]["default"], "10.0.0.1");
+        assert_eq!(properties["root_password"]["readOnly"], true);
+        assert!(properties["root_password"].get("default").is_none());
+        assert_eq!(
+            properties["serial"]["description"],
+            "Identifies the device; renders with the same value are served from the cache. \
+             Transformed before use: trim, uppercase"
+        );
+    }
+
+    #[test]
+    fn schema_validates_render_parameters() {
+        let validator = jsonschema::draft7::new(&schema()).unwrap();
+
+        assert!(validator.is_valid(&json!({"serial": "SN1", "hostname": "sw01", "site": "lon"})));
+        assert!(!validator.is_valid(&json!({"hostname": "sw01", "site": "lon"})));
+        assert!(!validator.is_valid(&json!({"serial": "SN1", "hostname": 7, "site": "lon"})));
+    }
+}
@@ -0,0 +1,100 @@
+//! CSV writing for exports of cached renders.
+//!
+//! Each render is one record: its ID field value and creation time, then one
+//! column per generated value, left blank where the render has no such value.
+
+use std::collections::HashMap;
+
+use crate::storage::models::RenderedCursor;
+
+/// Columns every export starts with, ahead of the generated values
+pub const EXPORT_COLUMNS: [&str; 2] = ["id_field_value", "created_at"];
+
+/// A cached render as exported: its identifiers and parsed generated values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedExportRow {
+    /// Row id of the render, not exported
+    pub id: i64,
+    pub id_field_value: String,
+    pub created_at: String,
+    pub generated_values: HashMap<String, String>,
+}
+
+impl RenderedExportRow {
+    /// Where the next page of an export continues after this row
+    pub fn cursor(&self) -> RenderedCursor {
+        RenderedCursor {
+            created_at: self.created_at.clone(),
+            id: self.id,
+        }
+    }
+
+    /// The row as a CSV record with a column per entry of `keys`
+    pub fn csv_record(&self, keys: &[String]) -> String {
+        let values = keys
+            .iter()
+            .map(|key| self.generated_values.get(key).map_or("", String::as_str));
+        csv_record([self.id_field_value.as_str(), self.created_at.as_str()].into_iter().chain(values))
+    }
+}
+
+/// The header record of an export with generated value columns `keys`
+pub fn csv_header(keys: &[String]) -> String {
+    csv_record(EXPORT_COLUMNS.into_iter().chain(keys.iter().map(String::as_str)))
+}
+
+/// Joins `cells` into a CRLF-terminated record. Cells holding a comma, quote or
+/// line break are quoted, with their quotes doubled (RFC 4180).
+pub fn csv_record<'a>(cells: impl IntoIterator<Item = &'a str>) -> String {
+    let mut record = String::new();
+    for (i, cell) in cells.into_iter().enumerate() {
+        if i > 0 {
+            record.push(',');
+        }
+        if cell.contains([',', '"', '\r', '\n']) {
+            record.push('"');
+            record.push_str(&cell.replace('"', "\"\""));
+            record.push('"');
+        } else {
+            record.push_str(cell);
+        }
+    }
+    record.push_str("\r\n");
+    record
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_cells_are_written_as_is() {
+        assert_eq!(csv_record(["a", "b c", ""]), "a,b c,\r\n");
+    }
+
+    #[test]
+    fn special_cells_are_quoted() {
+        assert_eq!(
+            csv_record(["a,b", "say \"hi\"", "two\nlines", "cr\r"]),
+            "\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\",\"cr\r\"\r\n"
+        );
+    }
+
+    #[test]
+    fn header_leads_with_identifier_columns() {
+        let keys = vec!["api_token".to_string(), "root_password".to_string()];
+        assert_eq!(csv_header(&keys), "id_field_value,created_at,api_token,root_password\r\n");
+    }
+
+    #[test]
+    fn missing_values_are_blank() {
+        let row = RenderedExportRow {
+            id: 1,
+            id_field_value: "AA:BB".to_string(),
+            created_at: "2024-01-01T00:00:00.000Z".to_string(),
+            generated_values: HashMap::from([("root_password".to_string(), "p,w".to_string())]),
+        };
+        let keys = vec!["api_token".to_string(), "root_password".to_string()];
+        assert_eq!(row.csv_record(&keys), "AA:BB,2024-01-01T00:00:00.000Z,,\"p,w\"\r\n");
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Longest width `zero_pad` accepts
+const MAX_ZERO_PAD: usize = 64;
+
+/// One step of a parameter's `input_transforms` list, written as `trim`,
+/// `lowercase`, `uppercase`, `strip_prefix:<prefix>` or `zero_pad:<width>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputTransform {
+    Trim,
+    Lowercase,
+    Uppercase,
+    /// Removes the prefix if present; matching is case-sensitive
+    StripPrefix(String),
+    /// Left-pads with `0` to at least this many characters
+    ZeroPad(usize),
+}
+
+impl FromStr for InputTransform {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = match spec.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (spec, None),
+        };
+        match (name, arg) {
+            ("trim", None) => Ok(Self::Trim),
+            ("lowercase", None) => Ok(Self::Lowercase),
+            ("uppercase", None) => Ok(Self::Uppercase),
+            ("strip_prefix", Some(prefix)) if !prefix.is_empty() => Ok(Self::StripPrefix(prefix.to_string())),
+            ("zero_pad", Some(width)) => match width.parse::<usize>() {
+                Ok(width) if (1..=MAX_ZERO_PAD).contains(&width) => Ok(Self::ZeroPad(width)),
+                _ => Err(format!(
+                    "zero_pad width must be a number between 1 and {}, got '{}'",
+                    MAX_ZERO_PAD, width
+                )),
+            },
+            ("strip_prefix", _) => Err("strip_prefix needs a prefix, e.g. 'strip_prefix:SN'".to_string()),
+            ("trim" | "lowercase" | "uppercase", Some(_)) => {
+                Err(format!("Input transform '{}' takes no argument", name))
+            }
+            _ => Err(format!(
+                "Unknown input transform '{}' (expected trim, lowercase, uppercase, strip_prefix:<prefix> or zero_pad:<width>)",
+                spec
+            )),
+        }
+    }
+}
+
+impl InputTransform {
+    pub fn apply(&self, value: &str) -> String {
+        match self {
+            Self::Trim => value.trim().to_string(),
+            Self::Lowercase => value.to_lowercase(),
+            Self::Uppercase => value.to_uppercase(),
+            Self::StripPrefix(prefix) => value.strip_prefix(prefix.as_str()).unwrap_or(value).to_string(),
+            Self::ZeroPad(width) => format!("{:0>width$}", value, width = *width),
+        }
+    }
+}
+
+/// Parses every transform list, failing on the first invalid entry.
+pub fn parse_transforms(
+    transforms: &HashMap<String, Vec<String>>,
+) -> Result<HashMap<&str, Vec<InputTransform>>, String> {
+    transforms
+        .iter()
+        .map(|(param, specs)| {
+            specs
+                .iter()
+                .map(|spec| spec.parse())
+                .collect::<Result<Vec<_>, _>>()
+                .map(|parsed| (param.as_str(), parsed))
+                .map_err(|e| format!("Input transforms for '{}': {}", param, e))
+        })
+        .collect()
+}
+
+/// Applies each parameter's transforms, in order, to the matching incoming values.
+/// Parameters without transforms are passed through unchanged.
+pub fn apply_transforms(
+    transforms: &HashMap<String, Vec<String>>,
+    values: &HashMap<String, String>,
+) -> Result<HashMap<String, String>, String> {
+    let parsed = parse_transforms(transforms)?;
+    Ok(values
+        .iter()
+        .map(|(key, value)| {
+            let value = parsed.get(key.as_str()).map_or_else(
+                || value.clone(),
+                |steps| steps.iter().fold(value.clone(), |acc, step| step.apply(&acc)),
+            );
+            (key.clone(), value)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(spec: &str, value: &str) -> String {
+        spec.parse::<InputTransform>().unwrap().apply(value)
+    }
+
+    #[test]
+    fn each_transform() {
+        assert_eq!(apply("trim", "  sn123 \t\n"), "sn123");
+        assert_eq!(apply("lowercase", "AA:bb:CC"), "aa:bb:cc");
+        assert_eq!(apply("uppercase", "aa:bb:cc"), "AA:BB:CC");
+        assert_eq!(apply("strip_prefix:SN-", "SN-0042"), "0042");
+        assert_eq!(apply("strip_prefix:SN-", "sn-0042"), "sn-0042");
+        assert_eq!(apply("strip_prefix:a:b", "a:bc"), "c");
+        assert_eq!(apply("zero_pad:6", "42"), "000042");
+        assert_eq!(apply("zero_pad:2", "1234"), "1234");
+    }
+
+    #[test]
+    fn invalid_specs_are_rejected() {
+        for spec in ["reverse", "", "trim:1", "strip_prefix", "strip_prefix:", "zero_pad", "zero_pad:0", "zero_pad:x", "zero_pad:65"] {
+            assert!(spec.parse::<InputTransform>().is_err(), "{} should be rejected", spec);
+        }
+    }
+
+    #[test]
+    fn transforms_apply_in_order_to_named_params_only() {
+        let transforms = HashMap::from([(
+            "serial".to_string(),
+            vec!["trim".to_string(), "uppercase".to_string(), "strip_prefix:SN".to_string()],
+        )]);
+        let values = HashMap::from([
+            ("serial".to_string(), " sn00a1 ".to_string()),
+            ("hostname".to_string(), " sw01 ".to_string()),
+        ]);
+
+        let result = apply_transforms(&transforms, &values).unwrap();
+        assert_eq!(result["serial"], "00A1");
+        assert_eq!(result["hostname"], " sw01 ");
+
+        // Stripping before upper-casing misses the lowercase prefix
+        let reordered = HashMap::from([(
+            "serial".to_string(),
+            vec!["trim".to_string(), "strip_prefix:SN".to_string(), "uppercase".to_string()],
+        )]);
+        assert_eq!(apply_transforms(&reordered, &values).unwrap()["serial"], "SN00A1");
+    }
+
+    #[test]
+    fn invalid_transform_names_the_parameter() {
+        let transforms = HashMap::from([("serial".to_string(), vec!["trim".to_string(), "rot13".to_string()])]);
+        let error = parse_transforms(&transforms).unwrap_err();
+        assert!(error.contains("'serial'") && error.contains("'rot13'"), "{}", error);
+    }
+}
@@ -8,18 +8,121 @@ pub enum ProvisionrError {
     #[error("YAML parse error: {0}")]
     YamlParse(String),
 
+    #[error("TOML parse error: {0}")]
+    TomlParse(String),
+
     #[error("Template render failed: {0}")]
     TemplateRender(String),
 
+    #[error("Rendered output exceeds the limit of {limit} bytes")]
+    OutputTooLarge { limit: usize },
+
+    #[error("Rendered content exceeds the diff limit of {limit} bytes")]
+    DiffTooLarge { limit: usize },
+
     #[error("Database error: {0}")]
     Database(String),
 
+    #[error("Persistence unavailable: {0}")]
+    PersistenceUnavailable(String),
+
     #[error("Template not found: {0}")]
     TemplateNotFound(String),
 
+    #[error("Invalid template name: {0}")]
+    InvalidTemplateName(String),
+
+    #[error("Template already exists: {0}")]
+    TemplateExists(String),
+
+    #[error("Rendered template already exists: {0}")]
+    RenderExists(String),
+
+    #[error("Alias cycle: {0}")]
+    AliasCycle(String),
+
     #[error("Template has no content: {0}")]
     TemplateEmpty(String),
 
     #[error("Missing required field: {0}")]
     MissingField(String),
+
+    #[error("Missing required fields: {}", .0.join(", "))]
+    MissingFields(Vec<String>),
+
+    #[error("Unexpected query parameters: {}", .0.join(", "))]
+    UnexpectedParams(Vec<String>),
+
+    #[error("Unknown dynamic fields: {}", .0.join(", "))]
+    UnknownDynamicFields(Vec<String>),
+
+    #[error("Template lint failed: {0}")]
+    LintFailed(String),
+
+    #[error("Template tests failed: {0}")]
+    TemplateTestsFailed(String),
+
+    #[error("Invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    #[error("Invalid metadata: {0}")]
+    InvalidMetadata(String),
+
+    #[error("Invalid import: {0}")]
+    InvalidImport(String),
+
+    #[error("Too many requests: {0}")]
+    RateLimited(String),
+
+    #[error("Could not generate a unique value: {0}")]
+    UniqueValueExhausted(String),
+
+    #[error("Value generation failed: {0}")]
+    Generation(String),
+
+    #[error("Template updated too recently: {message}")]
+    UpdateThrottled { message: String, retry_after_seconds: u64 },
+}
+
+impl ProvisionrError {
+    /// Stable machine-readable identifier for the error kind.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::TemplateValidation(_) => "template_validation",
+            Self::YamlParse(_) => "yaml_parse",
+            Self::TomlParse(_) => "toml_parse",
+            Self::TemplateRender(_) => "template_render",
+            Self::OutputTooLarge { .. } => "output_too_large",
+            Self::DiffTooLarge { .. } => "diff_too_large",
+            Self::Database(_) => "database",
+            Self::PersistenceUnavailable(_) => "persistence_unavailable",
+            Self::TemplateNotFound(_) => "template_not_found",
+            Self::InvalidTemplateName(_) => "invalid_template_name",
+            Self::TemplateExists(_) => "template_exists",
+            Self::RenderExists(_) => "render_exists",
+            Self::AliasCycle(_) => "alias_cycle",
+            Self::TemplateEmpty(_) => "template_empty",
+            Self::MissingField(_) => "missing_field",
+            Self::MissingFields(_) => "missing_fields",
+            Self::UnexpectedParams(_) => "unexpected_params",
+            Self::UnknownDynamicFields(_) => "unknown_dynamic_fields",
+            Self::LintFailed(_) => "lint_failed",
+            Self::TemplateTestsFailed(_) => "template_tests_failed",
+            Self::InvalidConfig(_) => "invalid_config",
+            Self::InvalidMetadata(_) => "invalid_metadata",
+            Self::InvalidImport(_) => "invalid_import",
+            Self::RateLimited(_) => "rate_limited",
+            Self::UniqueValueExhausted(_) => "unique_value_exhausted",
+            Self::Generation(_) => "generation_failed",
+            Self::UpdateThrottled { .. } => "update_throttled",
+        }
+    }
+
+    /// Seconds a client should wait before retrying, for errors that clear with time.
+    pub fn retry_after(&self) -> Option<u64> {
+        match self {
+            Self::UpdateThrottled { retry_after_seconds, .. } => Some(*retry_after_seconds),
+            _ => None,
+        }
+    }
 }
@@ -1,6 +1,6 @@
 use crate::generators::traits::ValueGenerator;
 use rand::distr::Alphanumeric;
-use rand::Rng;
+use rand::{Rng, RngCore};
 
 pub struct AlphanumericGenerator {
     length: usize,
@@ -13,9 +13,8 @@ impl AlphanumericGenerator {
 }
 
 impl ValueGenerator for AlphanumericGenerator {
-    fn generate(&self) -> String {
-        rand::rng()
-            .sample_iter(&Alphanumeric)
+    fn generate_with(&self, rng: &mut dyn RngCore) -> String {
+        rng.sample_iter(&Alphanumeric)
             .take(self.length)
             .map(char::from)
             .collect()
@@ -31,22 +30,31 @@ mod tests {
     fn generates_correct_length(len: u8) -> bool {
         let len = (len as usize).max(1);
         let generator = AlphanumericGenerator::new(len);
-        generator.generate().len() == len
+        generator.generate_with(&mut rand::rng()).len() == len
     }
 
     #[quickcheck]
     fn generates_alphanumeric_only(len: u8) -> bool {
         let len = (len as usize).max(1);
         let generator = AlphanumericGenerator::new(len);
-        generator.generate().chars().all(|c| c.is_ascii_alphanumeric())
+        generator.generate_with(&mut rand::rng()).chars().all(|c| c.is_ascii_alphanumeric())
+    }
+
+    #[quickcheck]
+    fn same_seed_generates_same_value(seed: u64) -> bool {
+        use rand::SeedableRng;
+        let generator = AlphanumericGenerator::new(16);
+        let a = generator.generate_with(&mut rand::rngs::StdRng::seed_from_u64(seed));
+        let b = generator.generate_with(&mut rand::rngs::StdRng::seed_from_u64(seed));
+        a == b
     }
 
     #[quickcheck]
     fn generates_unique_values_with_sufficient_length(seed: u8) -> bool {
         let _ = seed;
         let generator = AlphanumericGenerator::new(32);
-        let a = generator.generate();
-        let b = generator.generate();
+        let a = generator.generate_with(&mut rand::rng());
+        let b = generator.generate_with(&mut rand::rng());
         a != b
     }
 }
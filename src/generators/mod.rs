@@ -1,9 +1,19 @@
 pub mod alphanumeric;
 pub mod hasher;
+pub mod hex;
 pub mod passphrase;
+pub mod self_signed_cert;
+pub mod ssh_keypair;
 pub mod traits;
+pub mod uuid;
+pub mod wireguard_keypair;
 
 pub use alphanumeric::AlphanumericGenerator;
 pub use hasher::create_hasher;
+pub use hex::HexGenerator;
 pub use passphrase::PassphraseGenerator;
-pub use traits::ValueGenerator;
+pub use self_signed_cert::SelfSignedCertGenerator;
+pub use ssh_keypair::SshKeypairGenerator;
+pub use traits::LinkedValueGenerator;
+pub use uuid::UuidGenerator;
+pub use wireguard_keypair::WireguardKeypairGenerator;
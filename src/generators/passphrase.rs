@@ -1,6 +1,7 @@
 use crate::generators::traits::ValueGenerator;
 use once_cell::sync::Lazy;
 use rand::seq::IndexedRandom;
+use rand::RngCore;
 
 const WORDLIST: &str = include_str!("../assets/eff_short_wordlist.txt");
 
@@ -22,10 +23,9 @@ impl PassphraseGenerator {
 }
 
 impl ValueGenerator for PassphraseGenerator {
-    fn generate(&self) -> String {
-        let mut rng = rand::rng();
+    fn generate_with(&self, rng: &mut dyn RngCore) -> String {
         (0..self.word_count)
-            .map(|_| *FILTERED_WORDS.choose(&mut rng).unwrap_or(&"word"))
+            .map(|_| *FILTERED_WORDS.choose(rng).unwrap_or(&"word"))
             .collect::<Vec<_>>()
             .join("-")
     }
@@ -47,15 +47,15 @@ mod tests {
         // Use % 9 + 1 to guarantee range 1-9 (avoids edge case of 0)
         let count = (count as usize % 9) + 1;
         let generator = PassphraseGenerator::new(count);
-        generator.generate().split('-').count() == count
+        generator.generate_with(&mut rand::rng()).split('-').count() == count
     }
 
     #[quickcheck]
     fn generates_unique_passphrases(seed: u8) -> bool {
         let _ = seed;
         let generator = PassphraseGenerator::new(6);
-        let a = generator.generate();
-        let b = generator.generate();
+        let a = generator.generate_with(&mut rand::rng());
+        let b = generator.generate_with(&mut rand::rng());
         a != b
     }
 
@@ -64,7 +64,7 @@ mod tests {
         // Use % 9 + 1 to guarantee range 1-9
         let count = (count as usize % 9) + 1;
         let generator = PassphraseGenerator::new(count);
-        let result = generator.generate();
+        let result = generator.generate_with(&mut rand::rng());
         result
             .split('-')
             .all(|word| word.chars().all(|c| c.is_ascii_lowercase()))
@@ -1,4 +1,22 @@
+use rand::RngCore;
+
 #[cfg_attr(test, mockall::automock)]
 pub trait ValueGenerator: Send + Sync {
-    fn generate(&self) -> String;
+    /// Generates a value using the supplied random source, so callers can
+    /// make generation deterministic by passing a seeded RNG.
+    fn generate_with(&self, rng: &mut dyn RngCore) -> String;
+}
+
+/// A generator producing several linked values for one dynamic field, such as
+/// the two halves of a keypair.
+pub trait LinkedValueGenerator: Send + Sync {
+    /// Generates the field's values, each paired with the suffix appended to the
+    /// field name to name it. Single-value generators use an empty suffix.
+    fn generate_values_with(&self, rng: &mut dyn RngCore) -> Result<Vec<(&'static str, String)>, String>;
+}
+
+impl<G: ValueGenerator> LinkedValueGenerator for G {
+    fn generate_values_with(&self, rng: &mut dyn RngCore) -> Result<Vec<(&'static str, String)>, String> {
+        Ok(vec![("", self.generate_with(rng))])
+    }
 }
@@ -0,0 +1,58 @@
+use crate::generators::traits::ValueGenerator;
+use rand::RngCore;
+
+/// Generates random (version 4) UUIDs in lowercase hyphenated form
+pub struct UuidGenerator;
+
+impl ValueGenerator for UuidGenerator {
+    fn generate_with(&self, rng: &mut dyn RngCore) -> String {
+        let mut bytes = [0u8; 16];
+        rng.fill_bytes(&mut bytes);
+        // Version 4 in the high nibble of byte 6, RFC 4122 variant in byte 8
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+        let mut result = String::with_capacity(36);
+        for (i, byte) in bytes.iter().enumerate() {
+            if matches!(i, 4 | 6 | 8 | 10) {
+                result.push('-');
+            }
+            result.push_str(&format!("{:02x}", byte));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[quickcheck]
+    fn generates_lowercase_hyphenated_v4(seed: u64) -> bool {
+        use rand::SeedableRng;
+        let uuid = UuidGenerator.generate_with(&mut rand::rngs::StdRng::seed_from_u64(seed));
+        let groups: Vec<&str> = uuid.split('-').collect();
+        groups.iter().map(|g| g.len()).eq([8, 4, 4, 4, 12])
+            && groups
+                .iter()
+                .all(|g| g.chars().all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c)))
+            && groups[2].starts_with('4')
+            && groups[3].starts_with(['8', '9', 'a', 'b'])
+    }
+
+    #[quickcheck]
+    fn same_seed_generates_same_value(seed: u64) -> bool {
+        use rand::SeedableRng;
+        let a = UuidGenerator.generate_with(&mut rand::rngs::StdRng::seed_from_u64(seed));
+        let b = UuidGenerator.generate_with(&mut rand::rngs::StdRng::seed_from_u64(seed));
+        a == b
+    }
+
+    #[test]
+    fn generates_unique_values() {
+        let uuids: std::collections::HashSet<String> =
+            (0..1000).map(|_| UuidGenerator.generate_with(&mut rand::rng())).collect();
+        assert_eq!(uuids.len(), 1000);
+    }
+}
@@ -0,0 +1,87 @@
+//! X25519 keypairs in the base64 form WireGuard configs use, as `wg genkey`
+//! and `wg pubkey` produce them.
+
+use crate::generators::traits::LinkedValueGenerator;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rand::RngCore;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// Generates a WireGuard keypair as base64 `_private` and `_public` values
+pub struct WireguardKeypairGenerator;
+
+impl LinkedValueGenerator for WireguardKeypairGenerator {
+    fn generate_values_with(&self, rng: &mut dyn RngCore) -> Result<Vec<(&'static str, String)>, String> {
+        let mut secret = [0u8; 32];
+        rng.fill_bytes(&mut secret);
+        let (private_key, public_key) = keypair(secret);
+        Ok(vec![("_private", private_key), ("_public", public_key)])
+    }
+}
+
+/// The base64 private and public keys for `secret`. The private key is clamped
+/// first, so it is stored the way `wg genkey` prints it.
+fn keypair(mut secret: [u8; 32]) -> (String, String) {
+    secret[0] &= 248;
+    secret[31] &= 127;
+    secret[31] |= 64;
+    let secret = StaticSecret::from(secret);
+    let public = PublicKey::from(&secret);
+    (STANDARD.encode(secret.as_bytes()), STANDARD.encode(public.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use curve25519_dalek::MontgomeryPoint;
+    use quickcheck_macros::quickcheck;
+    use rand::SeedableRng;
+
+    fn decode_key(key: &str) -> [u8; 32] {
+        STANDARD.decode(key).unwrap().try_into().unwrap()
+    }
+
+    fn from_hex(hex: &str) -> [u8; 32] {
+        let bytes: Vec<u8> = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect();
+        bytes.try_into().unwrap()
+    }
+
+    #[test]
+    fn matches_rfc_7748_test_vector() {
+        let (_, public_key) =
+            keypair(from_hex("77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a"));
+        assert_eq!(
+            decode_key(&public_key),
+            from_hex("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a")
+        );
+    }
+
+    #[quickcheck]
+    fn public_key_corresponds_to_private_key(seed: u64) -> bool {
+        let values = WireguardKeypairGenerator
+            .generate_values_with(&mut rand::rngs::StdRng::seed_from_u64(seed))
+            .unwrap();
+        let private_key = decode_key(&values[0].1);
+        let public_key = decode_key(&values[1].1);
+        MontgomeryPoint::mul_base_clamped(private_key).to_bytes() == public_key
+    }
+
+    #[quickcheck]
+    fn private_key_is_clamped(seed: u64) -> bool {
+        let values = WireguardKeypairGenerator
+            .generate_values_with(&mut rand::rngs::StdRng::seed_from_u64(seed))
+            .unwrap();
+        let private_key = decode_key(&values[0].1);
+        private_key[0] & 7 == 0 && private_key[31] & 0xc0 == 0x40
+    }
+
+    #[test]
+    fn keys_are_44_char_base64() {
+        let values = WireguardKeypairGenerator.generate_values_with(&mut rand::rng()).unwrap();
+        assert_eq!(values.iter().map(|(suffix, _)| *suffix).collect::<Vec<_>>(), ["_private", "_public"]);
+        assert!(values.iter().all(|(_, key)| key.len() == 44 && key.ends_with('=')));
+    }
+}
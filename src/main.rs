@@ -1,45 +1,86 @@
+mod built_info;
+mod cli;
 mod commands;
 mod error;
 mod generators;
+mod metrics;
+mod redact;
 mod rest;
 mod statics;
 mod storage;
 mod templating;
 mod threads;
+mod tftp;
+mod upstream;
 
 use std::collections::HashMap;
 use std::fs;
 use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 
 use clap::Parser;
 use serde::Deserialize;
 
 use axum::{
+    extract::DefaultBodyLimit,
+    middleware,
     response::{Html, IntoResponse},
-    routing::{get, post, put},
+    routing::{any, get, post, put},
     Router,
 };
 use axum_server::Handle;
-use log::{debug, info};
+use log::{debug, info, warn};
 use rust_embed::Embed;
 use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 use utoipa::OpenApi;
-use utoipa_swagger_ui::SwaggerUi;
+use utoipa_swagger_ui::{Config as SwaggerConfig, SwaggerUi};
 
+use crate::cli::{CliOutput, ExitStatus, OutputFormat};
 use crate::commands::commander::ConcreteCommander;
 use crate::commands::models::Command;
-use crate::rest::config::{get_config, set_config};
-use crate::rest::rendered::{get_rendered, list_rendered};
+use crate::error::ProvisionrError;
+use crate::redact::REDACT_SECRETS_ENV;
+use crate::rest::admin::{consistency_check, get_maintenance};
+use crate::rest::client_ip::{client_ip_middleware, TrustedProxies};
+use crate::rest::config::{
+    bump_cache_generation, get_config, get_config_history, get_dynamic_fields, get_id_field, rollback_config,
+    set_config,
+};
+use crate::rest::devices::{import_devices, MAX_IMPORT_BYTES};
+use crate::rest::groups::{get_group_members, render_group_member, set_group_members};
+use crate::rest::metrics::get_metrics;
+use crate::rest::openapi::{get_openapi, ApiDocState, ApiDocument};
+use crate::rest::rendered::{
+    delete_rendered, diff_rendered, export_rendered, get_rendered, get_rendered_content, list_orphans, list_rendered,
+    purge_orphans, purge_rendered, regenerate_rendered, rerender_rendered, clone_rendered,
+};
 use crate::rest::state::AppState;
-use crate::rest::template::{delete_template, render_template, set_template, set_values};
+use crate::rest::status::{get_render_latency, get_status};
+use crate::rest::version::{get_version, VersionInfo};
+use crate::rest::template::{
+    clear_values, copy_template, delete_alias, delete_template, export_templates, get_bundle, get_cache_status,
+    get_checksum, get_params_schema, get_template_source, get_template_variables, get_test_cases, get_values,
+    head_template, import_templates, list_templates, nested_template, prerender_template, preview_template, rename_template, render_template,
+    render_template_post, run_template_tests, set_alias, set_metadata, set_template, set_test_cases, set_values,
+    template_exists, upload_templates, validate_template, DEFAULT_MAX_TEMPLATE_BYTES, MAX_TEMPLATE_BYTES_ENV,
+};
 use crate::statics::shutdown::{global_cancellation_token, request_shutdown};
-use crate::storage::models::{DynamicFieldConfig, TemplateData};
+use crate::storage::models::{
+    content_checksum, validate_template_name, CachePolicy, DynamicFieldConfig, FailureWebhook, TemplateConfig, TemplateData,
+    TemplateMetadata, ValuesErrorPolicy, ValuesFormat,
+};
 use crate::storage::{DashMapTemplateStore, RenderedStore, SqliteRenderedStore, TemplateStore};
-use crate::templating::MiniJinjaEngine;
-use crate::threads::handler::{ConcreteHandler, Handler};
+use crate::templating::secrets::Secrets;
+use crate::templating::{MiniJinjaEngine, DEFAULT_MAX_OUTPUT_BYTES};
+use crate::threads::handler::{ConcreteHandler, Handler, MAX_RENDERED_ENV};
+use crate::threads::maintenance::{self, MaintenanceSchedule};
+use crate::threads::warmup::{self, WarmupTarget};
+use crate::threads::webhook::{deliver_failures, FailureNotifier};
+use crate::tftp::TftpConfig;
+use crate::upstream::UpstreamConfig;
 
 #[derive(Parser, Debug)]
 #[command(name = "provisionr")]
@@ -60,6 +101,18 @@ struct Args {
     /// Database path
     #[arg(long)]
     db: Option<String>,
+
+    /// Check the database's cached renders for consistency, print the report and exit
+    #[arg(long)]
+    check: bool,
+
+    /// With --check, repair what can be fixed
+    #[arg(long, requires = "check")]
+    repair: bool,
+
+    /// Output of --check: a summary with diagnostics on stderr, or one JSON document on stdout
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
 }
 
 fn default_id_field() -> String {
@@ -74,6 +127,33 @@ struct FileTemplateConfig {
     id_field: String,
     #[serde(default)]
     dynamic_fields: Vec<DynamicFieldConfig>,
+    min_interval_seconds: Option<u64>,
+    min_update_interval_seconds: Option<u64>,
+    #[serde(default)]
+    param_defaults: HashMap<String, String>,
+    failure_webhook: Option<FailureWebhook>,
+    #[serde(default)]
+    input_transforms: HashMap<String, Vec<String>>,
+    max_output_bytes: Option<usize>,
+    #[serde(default)]
+    on_values_error: ValuesErrorPolicy,
+    #[serde(default)]
+    cache_generation: u64,
+    #[serde(default)]
+    carry_forward_generated: bool,
+    #[serde(default)]
+    cache_policy: CachePolicy,
+    cache_ttl_seconds: Option<u64>,
+    max_rendered: Option<u64>,
+    content_type: Option<String>,
+    #[serde(default)]
+    required_fields: Vec<String>,
+    #[serde(default)]
+    optional_fields: Vec<String>,
+    #[serde(default)]
+    strict_params: bool,
+    #[serde(default)]
+    input_values_exclude: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Default)]
@@ -82,6 +162,30 @@ struct FileConfig {
     port: Option<u16>,
     db: Option<String>,
     #[serde(default)]
+    trusted_proxies: Vec<String>,
+    upstream: Option<UpstreamConfig>,
+    tftp: Option<TftpConfig>,
+    /// YAML mapping of secret names to values, read by templates with `secret("name")`
+    secrets_file: Option<PathBuf>,
+    /// Templates compiled, and optionally checked against cached renders, after startup
+    #[serde(default)]
+    warmup: Vec<WarmupTarget>,
+    /// Jobs the maintenance scheduler runs periodically
+    #[serde(default)]
+    maintenance: Vec<MaintenanceSchedule>,
+    /// Record the client address and User-Agent of every serve, not only the first render
+    #[serde(default)]
+    track_last_fetch: bool,
+    /// Largest rendered output in bytes, unless a template sets its own limit
+    max_output_bytes: Option<usize>,
+    /// Serve renders when their cached copy cannot be read or written, instead of failing with 503
+    #[serde(default)]
+    serve_without_persistence: bool,
+    /// Template rendered, under the requested name, when the requested template does not exist
+    fallback_template: Option<String>,
+    /// Base URL clients reach the server at, published as the OpenAPI document's server
+    external_url: Option<String>,
+    #[serde(default)]
     templates: HashMap<String, FileTemplateConfig>,
 }
 
@@ -90,6 +194,20 @@ struct Config {
     port: u16,
     db: String,
     config_file: Option<PathBuf>,
+    trusted_proxies: TrustedProxies,
+    upstream: Option<UpstreamConfig>,
+    tftp: Option<TftpConfig>,
+    secrets: Secrets,
+    warmup: Vec<WarmupTarget>,
+    maintenance: Vec<MaintenanceSchedule>,
+    track_last_fetch: bool,
+    max_output_bytes: usize,
+    max_template_bytes: usize,
+    external_url: Option<String>,
+    serve_without_persistence: bool,
+    fallback_template: Option<String>,
+    redact_secrets: bool,
+    max_rendered: Option<u64>,
     templates: HashMap<String, TemplateData>,
 }
 
@@ -104,14 +222,68 @@ impl Config {
                 let content = fs::read_to_string(path)
                     .unwrap_or_else(|e| panic!("Failed to read config file {:?}: {}", path, e));
                 serde_yaml::from_str::<FileConfig>(&content)
-                    .unwrap_or_else(|e| panic!("Failed to parse config file {:?}: {}", path, e))
+                    .unwrap_or_else(|e| {
+                        panic!("Failed to parse config file {:?}: {}", path, redact::redact_quoted(&e.to_string()))
+                    })
             })
             .unwrap_or_default();
 
+        let trusted_proxies = TrustedProxies::parse(&file_config.trusted_proxies)
+            .unwrap_or_else(|e| panic!("Invalid trusted_proxies entry: {}", e));
+
+        let secrets_file = file_config
+            .secrets_file
+            .as_ref()
+            .map(|p| resolve_path(&config_dir, p));
+        let secrets = Secrets::load(secrets_file.as_deref(), std::env::vars())
+            .unwrap_or_else(|e| panic!("{}", e));
+
+        let max_output_bytes = file_config.max_output_bytes.unwrap_or(DEFAULT_MAX_OUTPUT_BYTES);
+        if max_output_bytes == 0 {
+            panic!("Invalid config: max_output_bytes must be greater than 0");
+        }
+
+        let max_template_bytes = match std::env::var(MAX_TEMPLATE_BYTES_ENV) {
+            Ok(value) => value.parse().ok().filter(|&bytes: &usize| bytes > 0).unwrap_or_else(|| {
+                panic!("Invalid {}: expected a number of bytes greater than 0, got '{}'", MAX_TEMPLATE_BYTES_ENV, value)
+            }),
+            Err(_) => DEFAULT_MAX_TEMPLATE_BYTES,
+        };
+
+        let redact_secrets = match std::env::var(REDACT_SECRETS_ENV) {
+            Ok(value) => value.parse().unwrap_or_else(|_| {
+                panic!("Invalid {}: expected true or false, got '{}'", REDACT_SECRETS_ENV, value)
+            }),
+            Err(_) => true,
+        };
+
+        let max_rendered = std::env::var(MAX_RENDERED_ENV).ok().map(|value| {
+            value.parse().ok().filter(|&cap: &u64| cap > 0).unwrap_or_else(|| {
+                panic!("Invalid {}: expected a number of renders greater than 0, got '{}'", MAX_RENDERED_ENV, value)
+            })
+        });
+
+        let external_url = file_config.external_url.map(|url| url.trim_end_matches('/').to_string());
+        if let Some(url) = &external_url
+            && !url.starts_with("http://")
+            && !url.starts_with("https://")
+        {
+            panic!("Invalid config: external_url must start with http:// or https://, got '{}'", url);
+        }
+
+        if let Some(fallback) = &file_config.fallback_template {
+            validate_template_name(fallback).unwrap_or_else(|e| panic!("Invalid fallback_template: {}", e));
+        }
+
+        for schedule in &file_config.maintenance {
+            schedule.validate().unwrap_or_else(|e| panic!("Invalid maintenance config: {}", e));
+        }
+
         let templates = file_config
             .templates
             .into_iter()
             .map(|(name, file_template)| {
+                validate_template_name(&name).unwrap_or_else(|e| panic!("Invalid template name: {}", e));
                 let template_content = file_template
                     .template_path
                     .map(|p| {
@@ -127,11 +299,58 @@ impl Config {
                         .unwrap_or_else(|e| panic!("Failed to read values file {:?}: {}", path, e))
                 });
 
+                let config = TemplateConfig {
+                    id_field: file_template.id_field,
+                    dynamic_fields: file_template.dynamic_fields,
+                    min_interval_seconds: file_template.min_interval_seconds,
+                    min_update_interval_seconds: file_template.min_update_interval_seconds,
+                    param_defaults: file_template.param_defaults,
+                    failure_webhook: file_template.failure_webhook,
+                    input_transforms: file_template.input_transforms,
+                    max_output_bytes: file_template.max_output_bytes,
+                    on_values_error: file_template.on_values_error,
+                    cache_generation: file_template.cache_generation,
+                    carry_forward_generated: file_template.carry_forward_generated,
+                    cache_policy: file_template.cache_policy,
+                    cache_ttl_seconds: file_template.cache_ttl_seconds,
+                    max_rendered: file_template.max_rendered,
+                    content_type: file_template.content_type,
+                    required_fields: file_template.required_fields,
+                    optional_fields: file_template.optional_fields,
+                    strict_params: file_template.strict_params,
+                    input_values_exclude: file_template.input_values_exclude,
+                };
+                config
+                    .validate()
+                    .unwrap_or_else(|e| panic!("Invalid config for template '{}': {}", name, e));
+
                 let data = TemplateData {
+                    checksum: content_checksum(&template_content),
                     template_content,
-                    id_field: file_template.id_field,
+                    id_field: config.id_field,
                     values_yaml,
-                    dynamic_fields: file_template.dynamic_fields,
+                    values_format: ValuesFormat::Yaml,
+                    dynamic_fields: config.dynamic_fields,
+                    min_interval_seconds: config.min_interval_seconds,
+                    min_update_interval_seconds: config.min_update_interval_seconds,
+                    param_defaults: config.param_defaults,
+                    failure_webhook: config.failure_webhook,
+                    input_transforms: config.input_transforms,
+                    max_output_bytes: config.max_output_bytes,
+                    on_values_error: config.on_values_error,
+                    cache_generation: config.cache_generation,
+                    carry_forward_generated: config.carry_forward_generated,
+                    cache_policy: config.cache_policy,
+                    cache_ttl_seconds: config.cache_ttl_seconds,
+                    max_rendered: config.max_rendered,
+                    content_type: config.content_type,
+                    required_fields: config.required_fields,
+                    optional_fields: config.optional_fields,
+                    strict_params: config.strict_params,
+                    input_values_exclude: config.input_values_exclude,
+                    test_cases: Vec::new(),
+                    content_updated_at: None,
+                    metadata: TemplateMetadata::default(),
                 };
 
                 (name, data)
@@ -149,6 +368,20 @@ impl Config {
                 .or(file_config.db)
                 .unwrap_or_else(|| "provisionr.db".to_string()),
             config_file: args.config,
+            trusted_proxies,
+            upstream: file_config.upstream,
+            tftp: file_config.tftp,
+            secrets,
+            warmup: file_config.warmup,
+            maintenance: file_config.maintenance,
+            track_last_fetch: file_config.track_last_fetch,
+            max_output_bytes,
+            max_template_bytes,
+            external_url,
+            serve_without_persistence: file_config.serve_without_persistence,
+            fallback_template: file_config.fallback_template,
+            redact_secrets,
+            max_rendered,
             templates,
         }
     }
@@ -167,34 +400,155 @@ fn resolve_path(config_dir: &Option<PathBuf>, path: &PathBuf) -> PathBuf {
 #[derive(OpenApi)]
 #[openapi(
     paths(
+        rest::template::list_templates,
+        rest::template::upload_templates,
+        rest::template::export_templates,
+        rest::template::import_templates,
+        rest::template::validate_template,
         rest::template::set_template,
         rest::template::render_template,
+        rest::template::render_template_post,
+        rest::template::preview_template,
+        rest::template::prerender_template,
         rest::template::delete_template,
+        rest::template::copy_template,
+        rest::template::rename_template,
+        rest::template::set_alias,
+        rest::template::delete_alias,
         rest::template::set_values,
+        rest::template::get_values,
+        rest::template::clear_values,
+        rest::template::get_checksum,
+        rest::template::head_template,
+        rest::template::template_exists,
+        rest::template::get_template_source,
+        rest::template::get_bundle,
+        rest::template::get_template_variables,
+        rest::template::get_params_schema,
+        rest::template::get_cache_status,
+        rest::devices::import_devices,
+        rest::groups::set_group_members,
+        rest::groups::get_group_members,
+        rest::groups::render_group_member,
+        rest::template::set_test_cases,
+        rest::template::set_metadata,
+        rest::template::get_test_cases,
+        rest::template::run_template_tests,
         rest::config::get_config,
+        rest::config::get_id_field,
+        rest::config::get_dynamic_fields,
         rest::config::set_config,
+        rest::config::get_config_history,
+        rest::config::rollback_config,
+        rest::config::bump_cache_generation,
         rest::rendered::list_rendered,
+        rest::rendered::purge_rendered,
         rest::rendered::get_rendered,
+        rest::rendered::get_rendered_content,
+        rest::rendered::delete_rendered,
+        rest::rendered::rerender_rendered,
+        rest::rendered::regenerate_rendered,
+        rest::rendered::clone_rendered,
+        rest::rendered::diff_rendered,
+        rest::rendered::export_rendered,
+        rest::rendered::list_orphans,
+        rest::rendered::purge_orphans,
+        rest::version::get_version,
+        rest::metrics::get_metrics,
+        rest::status::get_status,
+        rest::status::get_render_latency,
+        rest::admin::consistency_check,
+        rest::admin::get_maintenance,
     ),
     components(schemas(
         storage::models::GeneratorType,
         storage::models::DynamicFieldConfig,
         storage::models::HashingAlgorithm,
+        storage::models::FailureWebhook,
         storage::models::TemplateConfig,
         storage::models::TemplateData,
+        storage::models::TemplateTestCase,
         storage::models::RenderedTemplate,
+        storage::models::ValueSource,
+        storage::models::ValueSources,
         storage::models::RenderedTemplateSummary,
         rest::command::ApiErrorResponse,
         rest::command::ApiSuccessMessage,
+        rest::template::TemplateSetResponse,
+        rest::template::DeleteTemplateResponse,
+        rest::template::CopyTemplateRequest,
+        rest::template::TemplateUploadResult,
+        rest::template::BulkUploadReport,
+        rest::template::ImportMode,
+        rest::template::TemplateValidationResponse,
+        commands::models::TemplateImportReport,
+        commands::models::TemplateImportRejection,
+        commands::models::TemplateVariable,
+        commands::models::RenderPreview,
+        rest::template::RenderRequest,
+        rest::template::PrerenderRequest,
+        rest::template::PrerenderReport,
+        commands::models::PrerenderResult,
+        commands::models::PrerenderStatus,
+        commands::models::VariableSource,
+        rest::template::RenameTemplateRequest,
+        rest::template::SetAliasRequest,
+        commands::models::TemplateAlias,
+        commands::models::TemplateRename,
+        commands::models::LintCode,
+        commands::models::LintWarning,
+        commands::models::AssertionKind,
+        commands::models::AssertionResult,
+        commands::models::TestCaseResult,
+        commands::models::TestRunReport,
+        rest::template::TemplateChecksum,
+        rest::template::TemplateExists,
+        storage::models::TemplateBundle,
+        storage::models::TemplateSummary,
+        storage::models::TemplateMetadata,
+        commands::models::RenderedDetail,
+        rest::rendered::RegenerateRequest,
+        commands::models::RegeneratedRender,
+        rest::rendered::CloneRenderedRequest,
+        commands::models::RenderedDiff,
+        commands::models::RenderedPage,
+        commands::models::ValueDiff,
+        commands::models::ValueChange,
+        commands::models::OrphanedTemplate,
+        rest::rendered::PurgeOrphansResponse,
+        rest::rendered::PurgeRenderedResponse,
+        rest::rendered::ExportFormat,
+        commands::models::RenderResult,
+        commands::models::CacheStatus,
+        commands::models::GroupMembers,
+        commands::models::WarmupResult,
+        commands::models::ServerStatus,
+        commands::models::RenderPhase,
+        commands::models::PhaseLatency,
+        commands::models::PersistenceStatus,
+        rest::version::VersionInfo,
+        rest::version::StoreBackends,
+        storage::models::ConfigHistoryEntry,
+        storage::models::ConsistencyCheck,
+        storage::models::ConsistencyViolation,
+        storage::models::ConsistencyReport,
+        commands::models::DeviceImportError,
+        commands::models::DeviceImportReport,
+        commands::models::MaintenanceJob,
+        commands::models::MaintenanceRun,
+        commands::models::MaintenanceJobStatus,
+        rest::config::ConfigSetResponse,
+        rest::config::CacheGenerationResponse,
+        rest::config::IdFieldResponse,
     )),
     tags(
         (name = "templates", description = "Template management endpoints"),
         (name = "config", description = "Template configuration endpoints"),
-        (name = "rendered", description = "Rendered template retrieval endpoints")
+        (name = "rendered", description = "Rendered template retrieval endpoints"),
+        (name = "system", description = "Server information endpoints")
     ),
     info(
         title = "Provisionr API",
-        version = "1.0.0",
         description = "REST API for template provisioning with dynamic value generation"
     )
 )]
@@ -226,23 +580,72 @@ async fn static_handler(
     }
 }
 
+/// Runs the consistency check against the database file without starting the
+/// server. Violations left unrepaired fail the run as a validation error.
+fn check_database(db_path: &str, repair: bool) -> CliOutput {
+    let report = match SqliteRenderedStore::open_existing(db_path)
+        .map_err(ProvisionrError::Database)
+        .and_then(|store| store.check_consistency(repair))
+    {
+        Ok(report) => report,
+        Err(e) => return CliOutput::failed(&e),
+    };
+
+    let summary = format!(
+        "Checked {} cached render(s) and {} unique index entr(ies): {} violation(s), {} repaired",
+        report.rows_checked, report.index_entries_checked, report.violation_count, report.repaired
+    );
+    let mut output = CliOutput::new(&report, summary);
+    for violation in &report.violations {
+        let row = violation.row_id.map_or("-".to_string(), |id| id.to_string());
+        output = output.diagnostic(format!(
+            "row {} {}/{}: {}{}",
+            row,
+            violation.template_name,
+            violation.id_field_value,
+            violation.detail,
+            if violation.repaired { " (repaired)" } else { "" }
+        ));
+    }
+    if report.repaired > 0 {
+        output = output.warning(format!("{} violation(s) repaired", report.repaired));
+    }
+    if report.outstanding() > 0 {
+        output = output.error(
+            ExitStatus::Validation,
+            "consistency_violations",
+            format!("{} violation(s) not repaired", report.outstanding()),
+        );
+    }
+    output
+}
+
 #[tokio::main]
 async fn main() {
-    let config = Config::from_args(Args::parse());
+    let args = Args::parse();
+    let (check, repair, output) = (args.check, args.repair, args.output);
+    let config = Config::from_args(args);
 
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(&config.log_level))
         .init();
 
+    if check {
+        std::process::exit(check_database(&config.db, repair).emit(output));
+    }
+
     if let Some(path) = &config.config_file {
         info!("Loaded configuration from {:?}", path);
     } else {
         info!("Using default configuration");
     }
 
-    info!("Starting up");
+    info!("Starting {}", VersionInfo::current().banner());
+
+    let metrics_handle = metrics::install().expect("Failed to install metrics recorder");
 
     let port = config.port;
     let db_path = config.db;
+    let trusted_proxies = Arc::new(config.trusted_proxies);
 
     let mut template_store = DashMapTemplateStore::new();
 
@@ -257,11 +660,31 @@ async fn main() {
 
     let (tx, rx) = mpsc::channel::<Command>(128);
 
+    #[cfg(not(feature = "http-client"))]
+    if config.upstream.is_some() {
+        panic!("An upstream is configured but this build lacks the http-client feature");
+    }
+    #[cfg(not(feature = "tftp"))]
+    if config.tftp.is_some() {
+        panic!("A TFTP listener is configured but this build lacks the tftp feature");
+    }
+
     let app_state = AppState {
         command_tx: tx.clone(),
+        max_template_bytes: config.max_template_bytes,
+        #[cfg(feature = "http-client")]
+        upstream: config.upstream.as_ref().map(|upstream| {
+            info!("Reading unknown templates through from {}", upstream.url);
+            Arc::new(
+                upstream::Upstream::new(upstream).unwrap_or_else(|e| panic!("Invalid upstream config: {}", e)),
+            )
+        }),
     };
 
-    let engine = MiniJinjaEngine::new();
+    if !config.secrets.is_empty() {
+        info!("Loaded secrets: {}", config.secrets.names().join(", "));
+    }
+    let engine = MiniJinjaEngine::with_secrets(config.secrets);
     let commander = ConcreteCommander::new(engine);
 
     ctrlc::set_handler(move || {
@@ -269,23 +692,147 @@ async fn main() {
     })
     .expect("Error setting Ctrl-C handler");
 
+    let (failure_notifier, failure_deliveries) = FailureNotifier::new();
+    tokio::spawn(deliver_failures(failure_deliveries));
+
+    let maintenance_jobs = maintenance::with_default_jobs(config.maintenance);
+    let maintenance_schedule = maintenance_jobs.clone();
+    let track_last_fetch = config.track_last_fetch;
+    let max_output_bytes = config.max_output_bytes;
+    let serve_without_persistence = config.serve_without_persistence;
+    if serve_without_persistence {
+        warn!("serve_without_persistence is set: renders that cannot be stored are served anyway");
+    }
+    let fallback_template = config.fallback_template.clone();
+    if let Some(fallback) = &fallback_template {
+        warn!("fallback_template is set: renders of missing templates use '{}'", fallback);
+    }
+    let max_rendered = config.max_rendered;
+    let redact_secrets = config.redact_secrets;
+    if !redact_secrets {
+        warn!("{} is false: the rendered API returns generated values in clear text", REDACT_SECRETS_ENV);
+    }
     tokio::spawn(async move {
-        let mut handler = ConcreteHandler::new(commander, template_store, rendered_store, rx);
+        let mut handler = ConcreteHandler::new(commander, template_store, rendered_store, rx)
+            .with_failure_notifier(failure_notifier)
+            .with_maintenance_schedule(&maintenance_schedule)
+            .with_last_fetch_tracking(track_last_fetch)
+            .with_max_output_bytes(max_output_bytes)
+            .with_serve_without_persistence(serve_without_persistence)
+            .with_fallback_template(fallback_template)
+            .with_secret_redaction(redact_secrets)
+            .with_max_rendered(max_rendered);
         handler.main_loop().await;
     });
 
+    if !config.warmup.is_empty() {
+        tokio::spawn(warmup::run(tx.clone(), config.warmup, global_cancellation_token()));
+    }
+    tokio::spawn(maintenance::run(tx.clone(), maintenance_jobs, global_cancellation_token()));
+
+    #[cfg(feature = "tftp")]
+    if let Some(tftp) = &config.tftp {
+        let server = tftp::TftpServer::bind(tftp, app_state.clone())
+            .await
+            .unwrap_or_else(|e| panic!("{}", e));
+        info!(
+            "Serving TFTP on {} ({})",
+            server.local_addr().map_or(tftp.bind, |addr| addr),
+            tftp.filename_pattern
+        );
+        tokio::spawn(server.run(global_cancellation_token()));
+    }
+
+    let max_template_bytes = config.max_template_bytes;
+    let api_doc_state = ApiDocState {
+        app: app_state.clone(),
+        document: Arc::new(ApiDocument::new(ApiDoc::openapi(), config.external_url.as_deref())),
+    };
     let app = Router::new()
         .route("/", get(index))
+        .route("/api/v1/templates", get(list_templates).post(upload_templates))
+        .route("/api/v1/templates/export", get(export_templates))
+        .route(
+            "/api/v1/templates/import",
+            post(import_templates).layer(DefaultBodyLimit::max(MAX_IMPORT_BYTES)),
+        )
+        .route(
+            "/api/v1/templates/validate",
+            post(validate_template).layer(DefaultBodyLimit::max(max_template_bytes)),
+        )
         .route(
             "/api/v1/template/{name}",
-            post(set_template).get(render_template).delete(delete_template),
+            post(set_template)
+                .layer(DefaultBodyLimit::max(max_template_bytes))
+                .get(render_template)
+                .head(head_template)
+                .delete(delete_template),
         )
-        .route("/api/v1/template/{name}/values", put(set_values))
+        // Nested names with literal slashes, and their values. Without it they fall
+        // through to the static assets; other endpoints take them percent-encoded
+        .route(
+            "/api/v1/template/{first}/{*rest}",
+            any(nested_template).layer(DefaultBodyLimit::max(max_template_bytes)),
+        )
+        .route(
+            "/api/v1/template/{name}/values",
+            get(get_values)
+                .put(set_values)
+                .layer(DefaultBodyLimit::max(max_template_bytes))
+                .delete(clear_values),
+        )
+        .route("/api/v1/template/{name}/checksum", get(get_checksum))
+        .route("/api/v1/template/{name}/exists", get(template_exists))
+        .route("/api/v1/template/{name}/copy", post(copy_template))
+        .route("/api/v1/template/{name}/rename", post(rename_template))
+        .route("/api/v1/template/{name}/alias", put(set_alias).delete(delete_alias))
+        .route("/api/v1/template/{name}/source", get(get_template_source))
+        .route("/api/v1/template/{name}/bundle", get(get_bundle))
+        .route("/api/v1/template/{name}/variables", get(get_template_variables))
+        .route("/api/v1/template/{name}/render", post(render_template_post))
+        .route("/api/v1/template/{name}/preview", post(preview_template))
+        .route("/api/v1/template/{name}/prerender", post(prerender_template))
+        .route("/api/v1/template/{name}/params-schema", get(get_params_schema))
+        .route("/api/v1/template/{name}/cache-status", get(get_cache_status))
+        .route(
+            "/api/v1/template/{name}/devices/import",
+            post(import_devices).layer(DefaultBodyLimit::max(MAX_IMPORT_BYTES)),
+        )
+        .route("/api/v1/template/{name}/tests", get(get_test_cases).put(set_test_cases))
+        .route("/api/v1/template/{name}/metadata", put(set_metadata))
+        .route("/api/v1/template/{name}/tests/run", post(run_template_tests))
+        .route("/api/v1/group/{group}/members", get(get_group_members).put(set_group_members))
+        .route("/api/v1/group/{group}/{member}", get(render_group_member))
+        .route("/api/version", get(get_version))
+        .route("/api/v1/status", get(get_status))
+        .route("/api/v1/status/render-latency", get(get_render_latency))
+        .route("/api/admin/consistency-check", post(consistency_check))
+        .route("/api/admin/maintenance", get(get_maintenance))
+        .merge(Router::new().route("/metrics", get(get_metrics)).with_state(metrics_handle))
         .route("/api/v1/config/{name}", get(get_config).put(set_config))
-        .route("/api/v1/rendered/{name}", get(list_rendered))
-        .route("/api/v1/rendered/{name}/{id_value}", get(get_rendered))
-        .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
+        .route("/api/v1/config/{first}/{*rest}", get(get_config).put(set_config))
+        .route("/api/v1/template/{name}/id-field", get(get_id_field))
+        .route("/api/v1/template/{name}/dynamic-fields", get(get_dynamic_fields))
+        .route("/api/v1/config/{name}/history", get(get_config_history))
+        .route("/api/v1/config/{name}/rollback/{version}", post(rollback_config))
+        .route("/api/v1/template/{name}/bump-generation", post(bump_cache_generation))
+        .route("/api/v1/rendered/orphans", get(list_orphans).delete(purge_orphans))
+        .route("/api/v1/rendered/{name}", get(list_rendered).delete(purge_rendered))
+        .route("/api/v1/rendered/{name}/diff", get(diff_rendered))
+        .route("/api/v1/rendered/{name}/export", get(export_rendered))
+        .route("/api/v1/rendered/{name}/{id_value}", get(get_rendered).delete(delete_rendered))
+        .route("/api/v1/rendered/{name}/{id_value}/content", get(get_rendered_content))
+        .route("/api/v1/rendered/{name}/{id_value}/rerender", post(rerender_rendered))
+        .route("/api/v1/rendered/{name}/{id_value}/regenerate", post(regenerate_rendered))
+        .route("/api/v1/rendered/{name}/{id_value}/clone", post(clone_rendered))
+        .merge(
+            Router::new()
+                .route("/api-docs/openapi.json", get(get_openapi))
+                .with_state(api_doc_state),
+        )
+        .merge(SwaggerUi::new("/swagger-ui").config(SwaggerConfig::from("/api-docs/openapi.json")))
         .route("/{*path}", get(static_handler))
+        .layer(middleware::from_fn_with_state(trusted_proxies, client_ip_middleware))
         .with_state(app_state);
 
     let addr: SocketAddr = format!("0.0.0.0:{port}").parse().unwrap();
@@ -296,7 +843,7 @@ async fn main() {
 
     axum_server::bind(addr)
         .handle(handle)
-        .serve(app.into_make_service())
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
         .await
         .unwrap();
     info!("Shutting down");
@@ -311,6 +858,7 @@ async fn shutdown_axum(token: CancellationToken, handle: Handle<SocketAddr>) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::commands::models::MaintenanceJob;
     use crate::storage::models::{GeneratorType, HashingAlgorithm};
 
     fn fixtures_path() -> PathBuf {
@@ -325,6 +873,9 @@ mod tests {
             log_level: None,
             port: None,
             db: None,
+            check: false,
+            repair: false,
+            output: OutputFormat::Text,
         };
 
         let config = Config::from_args(args);
@@ -349,6 +900,9 @@ mod tests {
             log_level: None,
             port: None,
             db: None,
+            check: false,
+            repair: false,
+            output: OutputFormat::Text,
         };
 
         let config = Config::from_args(args);
@@ -369,6 +923,9 @@ mod tests {
             log_level: None,
             port: None,
             db: None,
+            check: false,
+            repair: false,
+            output: OutputFormat::Text,
         };
 
         let config = Config::from_args(args);
@@ -396,6 +953,9 @@ mod tests {
             log_level: None,
             port: None,
             db: None,
+            check: false,
+            repair: false,
+            output: OutputFormat::Text,
         };
 
         let config = Config::from_args(args);
@@ -412,6 +972,27 @@ mod tests {
         let second = config.templates.get("second").expect("second template should exist");
         assert_eq!(second.id_field, "serial");
         assert!(second.values_yaml.is_none());
+
+        assert_eq!(
+            config.warmup,
+            vec![
+                WarmupTarget {
+                    template: "first".to_string(),
+                    ids: vec!["A1".to_string(), "A2".to_string()],
+                },
+                WarmupTarget {
+                    template: "second".to_string(),
+                    ids: vec![],
+                },
+            ]
+        );
+        assert_eq!(
+            config.maintenance,
+            vec![MaintenanceSchedule {
+                job: MaintenanceJob::PurgeOrphans,
+                interval_seconds: 3600,
+            }]
+        );
     }
 
     #[test]
@@ -422,6 +1003,9 @@ mod tests {
             log_level: None,
             port: None,
             db: None,
+            check: false,
+            repair: false,
+            output: OutputFormat::Text,
         };
 
         let config = Config::from_args(args);
@@ -440,6 +1024,9 @@ mod tests {
             log_level: Some("trace".to_string()),
             port: Some(9999),
             db: Some("override.db".to_string()),
+            check: false,
+            repair: false,
+            output: OutputFormat::Text,
         };
 
         let config = Config::from_args(args);
@@ -456,6 +1043,9 @@ mod tests {
             log_level: None,
             port: None,
             db: None,
+            check: false,
+            repair: false,
+            output: OutputFormat::Text,
         };
 
         let config = Config::from_args(args);
@@ -467,6 +1057,11 @@ mod tests {
         assert!(config.config_file.is_none());
     }
 
+    #[test]
+    fn openapi_version_matches_crate_version() {
+        assert_eq!(ApiDoc::openapi().info.version, env!("CARGO_PKG_VERSION"));
+    }
+
     #[test]
     fn resolve_path_handles_absolute_paths() {
         let config_dir = Some(PathBuf::from("/some/config/dir"));
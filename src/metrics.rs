@@ -0,0 +1,149 @@
+//! Prometheus metrics. Code records through the `metrics` facade, so nothing is
+//! collected until [`install`] sets the global recorder, and tests can capture
+//! values with a local recorder.
+
+use metrics::{counter, histogram};
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder, PrometheusHandle};
+use std::time::Duration;
+
+/// Dynamic field values generated, by generator kind
+pub const GENERATOR_INVOCATIONS: &str = "provisionr_generator_invocations_total";
+/// Time to generate and hash one dynamic field value, by generator kind
+pub const GENERATOR_DURATION: &str = "provisionr_generator_duration_seconds";
+/// Failed dynamic field generations, by generator kind and error class
+pub const GENERATOR_FAILURES: &str = "provisionr_generator_failures_total";
+/// Renders stopped for exceeding the output size limit, by template
+pub const OUTPUT_LIMIT_EXCEEDED: &str = "provisionr_render_output_limit_exceeded_total";
+/// Time the handler spent on one command, by command kind
+pub const COMMAND_DURATION: &str = "provisionr_command_duration_seconds";
+/// Time spent in one phase of a render, by phase
+pub const RENDER_PHASE_DURATION: &str = "provisionr_render_phase_duration_seconds";
+/// Failed reads and writes of cached renders during renders, by operation
+pub const PERSISTENCE_ERRORS: &str = "provisionr_persistence_errors_total";
+
+/// Spans cheap random strings up to slow key generation and remote fetches.
+const GENERATOR_DURATION_BUCKETS: &[f64] = &[0.0001, 0.001, 0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+/// Spans in-memory lookups up to renders that generate keys or loop over big inputs.
+const HANDLER_DURATION_BUCKETS: &[f64] = &[0.00005, 0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+/// Installs the process-wide recorder. The handle renders the text exposition
+/// served on `/metrics`.
+pub fn install() -> Result<PrometheusHandle, BuildError> {
+    PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Full(GENERATOR_DURATION.to_string()), GENERATOR_DURATION_BUCKETS)?
+        .set_buckets_for_metric(Matcher::Full(COMMAND_DURATION.to_string()), HANDLER_DURATION_BUCKETS)?
+        .set_buckets_for_metric(Matcher::Full(RENDER_PHASE_DURATION.to_string()), HANDLER_DURATION_BUCKETS)?
+        .install_recorder()
+}
+
+/// Records one generation of a `kind` value. Labels are the generator kind and a
+/// fixed error class, never field names, so cardinality stays bounded.
+pub fn record_generation(kind: &'static str, elapsed: Duration, error_class: Option<&'static str>) {
+    counter!(GENERATOR_INVOCATIONS, "generator" => kind).increment(1);
+    histogram!(GENERATOR_DURATION, "generator" => kind).record(elapsed.as_secs_f64());
+    if let Some(error_class) = error_class {
+        counter!(GENERATOR_FAILURES, "generator" => kind, "error" => error_class).increment(1);
+    }
+}
+
+/// Records a render of `template` stopped at the output size limit.
+pub fn record_output_limit_exceeded(template: &str) {
+    counter!(OUTPUT_LIMIT_EXCEEDED, "template" => template.to_string()).increment(1);
+}
+
+/// Records the handling of one `command`.
+pub fn record_command(command: &'static str, elapsed: Duration) {
+    histogram!(COMMAND_DURATION, "command" => command).record(elapsed.as_secs_f64());
+}
+
+/// Records the duration of one render `phase`.
+pub fn record_render_phase(phase: &'static str, elapsed: Duration) {
+    histogram!(RENDER_PHASE_DURATION, "phase" => phase).record(elapsed.as_secs_f64());
+}
+
+/// Records a failed `operation` ("read" or "write") of the rendered store.
+pub fn record_persistence_error(operation: &'static str) {
+    counter!(PERSISTENCE_ERRORS, "operation" => operation).increment(1);
+}
+
+#[cfg(test)]
+pub mod testing {
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+    /// Metric name, its labels and the recorded value
+    type Entry = (String, Vec<(String, String)>, DebugValue);
+
+    /// Metric values recorded while running [`capture`].
+    pub struct Captured(Vec<Entry>);
+
+    /// Runs `f` with a local recorder and returns everything it recorded.
+    pub fn capture(f: impl FnOnce()) -> Captured {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, f);
+        Captured(
+            snapshotter
+                .snapshot()
+                .into_vec()
+                .into_iter()
+                .map(|(key, _, _, value)| {
+                    let labels = key
+                        .key()
+                        .labels()
+                        .map(|label| (label.key().to_string(), label.value().to_string()))
+                        .collect();
+                    (key.key().name().to_string(), labels, value)
+                })
+                .collect(),
+        )
+    }
+
+    impl Captured {
+        fn find(&self, name: &str, labels: &[(&str, &str)]) -> Option<&DebugValue> {
+            self.0.iter().find_map(|(key, key_labels, value)| {
+                let matches = key == name
+                    && key_labels.len() == labels.len()
+                    && labels.iter().all(|(k, v)| key_labels.iter().any(|(lk, lv)| lk == k && lv == v));
+                matches.then_some(value)
+            })
+        }
+
+        /// Counter value for `name` with exactly `labels`, or 0 if it was never recorded.
+        pub fn counter(&self, name: &str, labels: &[(&str, &str)]) -> u64 {
+            match self.find(name, labels) {
+                Some(DebugValue::Counter(count)) => *count,
+                _ => 0,
+            }
+        }
+
+        /// Number of samples recorded in histogram `name` with exactly `labels`.
+        pub fn histogram_samples(&self, name: &str, labels: &[(&str, &str)]) -> usize {
+            match self.find(name, labels) {
+                Some(DebugValue::Histogram(samples)) => samples.len(),
+                _ => 0,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::capture;
+    use super::*;
+
+    #[test]
+    fn failures_are_counted_by_kind_and_error_class() {
+        let captured = capture(|| {
+            record_generation("passphrase", Duration::from_millis(1), None);
+            record_generation("passphrase", Duration::from_millis(2), Some("hash"));
+        });
+
+        assert_eq!(captured.counter(GENERATOR_INVOCATIONS, &[("generator", "passphrase")]), 2);
+        assert_eq!(
+            captured.counter(GENERATOR_FAILURES, &[("generator", "passphrase"), ("error", "hash")]),
+            1
+        );
+        assert_eq!(captured.histogram_samples(GENERATOR_DURATION, &[("generator", "passphrase")]), 2);
+    }
+}
@@ -0,0 +1,157 @@
+//! Redaction of secrets from text that leaves the handler.
+//!
+//! Policy: log lines name templates, ids and keys, never rendered content or
+//! values. Error messages returned to clients, sent to failure webhooks or logged
+//! must not contain generated values or secrets; anything that may echo them is
+//! passed through this module first.
+
+use std::collections::HashMap;
+
+use crate::error::ProvisionrError;
+
+/// Placeholder shown instead of secret values.
+pub const REDACTED: &str = "<redacted>";
+
+/// Environment variable that, set to `false`, returns cached renders from the
+/// rendered API in clear text without `include_secrets`.
+pub const REDACT_SECRETS_ENV: &str = "PROVISIONR_REDACT_SECRETS";
+
+/// Replaces every occurrence of the given secrets in `text` with [`REDACTED`].
+/// Longer secrets are replaced first so one secret containing another is fully hidden.
+pub fn redact<'a>(text: &str, secrets: impl IntoIterator<Item = &'a String>) -> String {
+    let mut secrets: Vec<&String> = secrets.into_iter().filter(|s| !s.is_empty()).collect();
+    secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    secrets
+        .into_iter()
+        .fold(text.to_string(), |acc, secret| acc.replace(secret.as_str(), REDACTED))
+}
+
+/// Like [`redact`], but each value is replaced with `<redacted:key>` so the
+/// reader can still tell which value the message was about.
+pub fn redact_named<'a>(text: &str, values: impl IntoIterator<Item = (&'a String, &'a String)>) -> String {
+    let mut values: Vec<(&String, &String)> = values.into_iter().filter(|(_, v)| !v.is_empty()).collect();
+    values.sort_by_key(|(_, v)| std::cmp::Reverse(v.len()));
+    values.into_iter().fold(text.to_string(), |acc, (key, value)| {
+        acc.replace(value.as_str(), &format!("<redacted:{}>", key))
+    })
+}
+
+/// Replaces the contents of every double-quoted string in `text` with
+/// [`REDACTED`]. Parser errors quote the offending scalar, e.g.
+/// `invalid type: string "hunter2", expected a map`, which is the value itself.
+pub fn redact_quoted(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut parts = text.split('"');
+    if let Some(first) = parts.next() {
+        out.push_str(first);
+    }
+    let rest: Vec<&str> = parts.collect();
+    for (i, part) in rest.iter().enumerate() {
+        // Odd parts sit inside quotes; an unmatched trailing quote is left as is
+        let inside = i % 2 == 0 && i + 1 < rest.len();
+        out.push('"');
+        out.push_str(if inside { REDACTED } else { part });
+    }
+    out
+}
+
+/// Replaces generated values in the message of a render error. Template engines
+/// echo values in some errors, e.g. the name of a template that could not be
+/// included.
+pub fn redact_render_error(error: ProvisionrError, generated: &HashMap<String, String>) -> ProvisionrError {
+    match error {
+        ProvisionrError::TemplateRender(message) => ProvisionrError::TemplateRender(redact_named(&message, generated)),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+pub mod testing {
+    use log::{Log, Metadata, Record};
+    use std::cell::RefCell;
+
+    thread_local! {
+        static CAPTURED: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+    }
+
+    struct CaptureLogger;
+
+    impl Log for CaptureLogger {
+        fn enabled(&self, _: &Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &Record) {
+            CAPTURED.with(|captured| {
+                if let Some(lines) = captured.borrow_mut().as_mut() {
+                    lines.push(format!("{} {}", record.level(), record.args()));
+                }
+            });
+        }
+
+        fn flush(&self) {}
+    }
+
+    /// Installed before any test runs: quickcheck otherwise installs env_logger
+    /// on first use, and only one logger can ever be set.
+    #[ctor::ctor]
+    fn install() {
+        if log::set_logger(&CaptureLogger).is_ok() {
+            log::set_max_level(log::LevelFilter::Trace);
+        }
+    }
+
+    /// Runs `f` and returns every log line it emitted on this thread, at any level.
+    pub fn capture_logs(f: impl FnOnce()) -> Vec<String> {
+        CAPTURED.with(|captured| *captured.borrow_mut() = Some(Vec::new()));
+        f();
+        CAPTURED.with(|captured| captured.borrow_mut().take().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_replaces_longest_secrets_first() {
+        let secrets = ["abc".to_string(), "abcdef".to_string(), String::new()];
+        assert_eq!(
+            redact("pw=abcdef other=abc", secrets.iter()),
+            "pw=<redacted> other=<redacted>"
+        );
+    }
+
+    #[test]
+    fn redact_named_labels_values_with_their_key() {
+        let values = HashMap::from([
+            ("token".to_string(), "Xy7".to_string()),
+            ("password".to_string(), "Xy7Xy7".to_string()),
+            ("empty".to_string(), String::new()),
+        ]);
+        assert_eq!(
+            redact_named("included \"Xy7Xy7\" and Xy7", &values),
+            "included \"<redacted:password>\" and <redacted:token>"
+        );
+    }
+
+    #[test]
+    fn redact_quoted_hides_quoted_scalars() {
+        assert_eq!(
+            redact_quoted("a: invalid type: string \"hunter2\", expected u32 at line 1 column 4"),
+            "a: invalid type: string \"<redacted>\", expected u32 at line 1 column 4"
+        );
+        assert_eq!(redact_quoted("\"a\" and \"b\" then \"c"), "\"<redacted>\" and \"<redacted>\" then \"c");
+        assert_eq!(redact_quoted("no quotes"), "no quotes");
+    }
+
+    #[test]
+    fn only_render_errors_are_rewritten() {
+        let generated = HashMap::from([("token".to_string(), "SENTINEL".to_string())]);
+        let error = redact_render_error(ProvisionrError::TemplateRender("SENTINEL".to_string()), &generated);
+        assert_eq!(error.to_string(), "Template render failed: <redacted:token>");
+
+        let error = redact_render_error(ProvisionrError::MissingField("SENTINEL".to_string()), &generated);
+        assert_eq!(error.to_string(), "Missing required field: SENTINEL");
+    }
+}
@@ -0,0 +1,61 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::commands::models::{Command, MaintenanceJobStatus};
+use crate::rest::command::{send_command, ApiErrorResponse, CommandError};
+use crate::rest::state::AppState;
+use crate::storage::models::ConsistencyReport;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ConsistencyCheckQuery {
+    /// Reset unparseable generated values to an empty map and delete unique-index
+    /// entries of renders that no longer exist
+    #[serde(default)]
+    pub repair: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/admin/consistency-check",
+    description = "Check the rendered store for broken invariants: generated values that do not parse as a YAML mapping, malformed timestamps, and unique-field index entries that belong to no render or do not match its generated value. Rows are read in batches. The report counts every violation but lists at most the first 1000. The same check runs offline with `provisionr --check`.",
+    params(ConsistencyCheckQuery),
+    responses(
+        (status = 200, description = "Consistency report", body = ConsistencyReport),
+        (status = 400, description = "Database error while checking", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "system"
+)]
+pub async fn consistency_check(
+    State(state): State<AppState>,
+    Query(query): Query<ConsistencyCheckQuery>,
+) -> Result<impl IntoResponse, CommandError> {
+    let report = send_command(&state, |tx| Command::CheckConsistency {
+        repair: query.repair,
+        response: tx,
+    })
+    .await?;
+    Ok((StatusCode::OK, Json(report)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/admin/maintenance",
+    description = "Maintenance jobs and their most recent runs, newest first. Jobs named in the `maintenance` config are listed from startup with their interval, and run one interval after startup and then every interval. Each job keeps its last 20 runs; history is in memory and starts empty on every restart. A failed run records its error and 0 rows affected.",
+    responses(
+        (status = 200, description = "Maintenance jobs", body = Vec<MaintenanceJobStatus>),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "system"
+)]
+pub async fn get_maintenance(State(state): State<AppState>) -> Result<impl IntoResponse, CommandError> {
+    let jobs = send_command(&state, |tx| Command::GetMaintenance { response: tx }).await?;
+    Ok((StatusCode::OK, Json(jobs)))
+}
@@ -0,0 +1,287 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::HeaderMap,
+    middleware::Next,
+    response::Response,
+};
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Client address resolved by [`client_ip_middleware`], available to handlers
+/// as `Extension<ClientIp>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// An IPv4 or IPv6 network in CIDR notation. A bare address is treated as a
+/// single-host network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix_len as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix_len as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let network = IpAddr::from_str(addr.trim())
+            .map_err(|_| format!("Invalid address in CIDR '{}'", s))?
+            .to_canonical();
+        let max_len = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix {
+            Some(p) => p
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|len| *len <= max_len)
+                .ok_or_else(|| format!("Invalid prefix length in CIDR '{}'", s))?,
+            None => max_len,
+        };
+        Ok(Self { network, prefix_len })
+    }
+}
+
+/// Proxies whose forwarding headers are believed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrustedProxies(Vec<Cidr>);
+
+impl TrustedProxies {
+    pub fn parse(cidrs: &[String]) -> Result<Self, String> {
+        cidrs
+            .iter()
+            .map(|c| c.parse())
+            .collect::<Result<Vec<_>, _>>()
+            .map(Self)
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.0.iter().any(|cidr| cidr.contains(ip))
+    }
+}
+
+/// Parses a single forwarding hop, tolerating quotes, IPv6 brackets and ports.
+fn parse_hop(hop: &str) -> Option<IpAddr> {
+    let hop = hop.trim().trim_matches('"');
+    if let Ok(ip) = IpAddr::from_str(hop) {
+        return Some(ip);
+    }
+    if let Ok(addr) = SocketAddr::from_str(hop) {
+        return Some(addr.ip());
+    }
+    hop.strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .and_then(|h| IpAddr::from_str(h).ok())
+}
+
+/// Forwarding chain from `X-Forwarded-For`, falling back to the `for=`
+/// parameters of `Forwarded`. Entries are ordered client first; unparseable
+/// hops are kept as `None` so the walk can stop at them.
+fn forwarded_chain(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    let xff: Vec<&str> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .collect();
+    if !xff.is_empty() {
+        return xff
+            .iter()
+            .flat_map(|v| v.split(','))
+            .map(parse_hop)
+            .collect();
+    }
+
+    headers
+        .get_all("forwarded")
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (key, value) = pair.split_once('=')?;
+                key.trim().eq_ignore_ascii_case("for").then(|| parse_hop(value))
+            })
+        })
+        .collect()
+}
+
+/// Resolves the real client address. Forwarding headers are only consulted when
+/// the socket peer is a trusted proxy; the chain is then walked from the right
+/// and the first untrusted hop wins, so clients cannot spoof their address by
+/// prepending entries.
+pub fn resolve_client_ip(peer: IpAddr, headers: &HeaderMap, trusted: &TrustedProxies) -> IpAddr {
+    if !trusted.contains(peer) {
+        return peer;
+    }
+
+    let mut client = peer;
+    for hop in forwarded_chain(headers).into_iter().rev() {
+        match hop {
+            Some(ip) => {
+                client = ip;
+                if !trusted.contains(ip) {
+                    break;
+                }
+            }
+            None => break,
+        }
+    }
+    client
+}
+
+/// Resolves the client address for every request and stores it as a [`ClientIp`] extension.
+pub async fn client_ip_middleware(
+    State(trusted): State<Arc<TrustedProxies>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if let Some(ConnectInfo(peer)) = request.extensions().get::<ConnectInfo<SocketAddr>>().copied() {
+        let ip = resolve_client_ip(peer.ip(), request.headers(), &trusted);
+        request.extensions_mut().insert(ClientIp(ip));
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn trusted(cidrs: &[&str]) -> TrustedProxies {
+        TrustedProxies::parse(&cidrs.iter().map(|c| c.to_string()).collect::<Vec<_>>()).unwrap()
+    }
+
+    fn headers(name: &'static str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn cidr_matches_ipv4_and_ipv6_networks() {
+        let v4: Cidr = "10.0.0.0/8".parse().unwrap();
+        assert!(v4.contains(ip("10.1.2.3")));
+        assert!(!v4.contains(ip("11.0.0.1")));
+        assert!(v4.contains(ip("::ffff:10.1.2.3")));
+
+        let v6: Cidr = "fd00::/8".parse().unwrap();
+        assert!(v6.contains(ip("fd12::1")));
+        assert!(!v6.contains(ip("fe80::1")));
+        assert!(!v6.contains(ip("10.1.2.3")));
+
+        let host: Cidr = "127.0.0.1".parse().unwrap();
+        assert!(host.contains(ip("127.0.0.1")));
+        assert!(!host.contains(ip("127.0.0.2")));
+
+        let all: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(all.contains(ip("203.0.113.9")));
+    }
+
+    #[test]
+    fn cidr_rejects_invalid_input() {
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("not-an-ip/8".parse::<Cidr>().is_err());
+        assert!("fd00::/129".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn untrusted_peer_ignores_spoofed_forwarded_for() {
+        let resolved = resolve_client_ip(
+            ip("203.0.113.5"),
+            &headers("x-forwarded-for", "1.2.3.4"),
+            &trusted(&["127.0.0.1"]),
+        );
+        assert_eq!(resolved, ip("203.0.113.5"));
+    }
+
+    #[test]
+    fn trusted_peer_uses_forwarded_for() {
+        let resolved = resolve_client_ip(
+            ip("127.0.0.1"),
+            &headers("x-forwarded-for", "198.51.100.7"),
+            &trusted(&["127.0.0.1"]),
+        );
+        assert_eq!(resolved, ip("198.51.100.7"));
+    }
+
+    #[test]
+    fn chained_proxies_pick_rightmost_untrusted_entry() {
+        // Client prepended a spoofed address; the edge proxy appended the real one
+        let resolved = resolve_client_ip(
+            ip("127.0.0.1"),
+            &headers("x-forwarded-for", "6.6.6.6, 198.51.100.7, 10.0.0.2"),
+            &trusted(&["127.0.0.1", "10.0.0.0/8"]),
+        );
+        assert_eq!(resolved, ip("198.51.100.7"));
+    }
+
+    #[test]
+    fn all_trusted_chain_resolves_to_leftmost() {
+        let resolved = resolve_client_ip(
+            ip("127.0.0.1"),
+            &headers("x-forwarded-for", "10.0.0.3, 10.0.0.2"),
+            &trusted(&["127.0.0.1", "10.0.0.0/8"]),
+        );
+        assert_eq!(resolved, ip("10.0.0.3"));
+    }
+
+    #[test]
+    fn unparseable_hop_stops_the_walk() {
+        let resolved = resolve_client_ip(
+            ip("127.0.0.1"),
+            &headers("x-forwarded-for", "198.51.100.7, garbage, 10.0.0.2"),
+            &trusted(&["127.0.0.1", "10.0.0.0/8"]),
+        );
+        assert_eq!(resolved, ip("10.0.0.2"));
+    }
+
+    #[test]
+    fn ipv6_forwarded_for_with_trusted_ipv6_proxy() {
+        let resolved = resolve_client_ip(
+            ip("::1"),
+            &headers("x-forwarded-for", "2001:db8::42, [fd00::2]:8080"),
+            &trusted(&["::1", "fd00::/8"]),
+        );
+        assert_eq!(resolved, ip("2001:db8::42"));
+    }
+
+    #[test]
+    fn forwarded_header_is_used_without_x_forwarded_for() {
+        let resolved = resolve_client_ip(
+            ip("127.0.0.1"),
+            &headers("forwarded", r#"for=192.0.2.60;proto=http, for="[2001:db8:cafe::17]:4711""#),
+            &trusted(&["127.0.0.1"]),
+        );
+        assert_eq!(resolved, ip("2001:db8:cafe::17"));
+    }
+
+    #[test]
+    fn no_forwarding_headers_uses_peer() {
+        let resolved = resolve_client_ip(ip("127.0.0.1"), &HeaderMap::new(), &trusted(&["127.0.0.1"]));
+        assert_eq!(resolved, ip("127.0.0.1"));
+    }
+}
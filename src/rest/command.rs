@@ -1,5 +1,5 @@
 use axum::{
-    http::StatusCode,
+    http::{header, HeaderName, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -9,6 +9,7 @@ use tokio::{sync::oneshot, time};
 use utoipa::ToSchema;
 
 use crate::commands::models::Command;
+use crate::error::ProvisionrError;
 use crate::rest::state::AppState;
 
 const TIMEOUT_SECS: u64 = 5;
@@ -52,35 +53,75 @@ impl ApiSuccessMessage {
 pub enum CommandError {
     Timeout,
     ChannelClosed,
-    Handler(String),
+    Handler(ProvisionrError),
     HandlerUnavailable,
 }
 
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Timeout => write!(f, "timeout"),
+            Self::ChannelClosed => write!(f, "channel closed"),
+            Self::Handler(e) => write!(f, "{}", e),
+            Self::HandlerUnavailable => write!(f, "handler unavailable"),
+        }
+    }
+}
+
+/// Maps handler errors onto HTTP status codes; anything not listed is a bad request.
+fn handler_status(error: &ProvisionrError) -> StatusCode {
+    match error {
+        ProvisionrError::LintFailed(_)
+        | ProvisionrError::TemplateTestsFailed(_)
+        | ProvisionrError::OutputTooLarge { .. }
+        | ProvisionrError::DiffTooLarge { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+        ProvisionrError::RateLimited(_) | ProvisionrError::UpdateThrottled { .. } => {
+            StatusCode::TOO_MANY_REQUESTS
+        }
+        ProvisionrError::UniqueValueExhausted(_)
+        | ProvisionrError::TemplateExists(_)
+        | ProvisionrError::RenderExists(_) => StatusCode::CONFLICT,
+        ProvisionrError::Generation(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        ProvisionrError::PersistenceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::BAD_REQUEST,
+    }
+}
+
 impl CommandError {
+    /// `Retry-After` header for errors that clear with time.
+    fn retry_after(&self) -> Option<[(HeaderName, String); 1]> {
+        match self {
+            Self::Handler(e) => e.retry_after().map(|secs| [(header::RETRY_AFTER, secs.to_string())]),
+            _ => None,
+        }
+    }
+
     pub fn into_plain_response(self) -> Response {
         let (status, message) = match &self {
-            Self::Timeout => (StatusCode::GATEWAY_TIMEOUT, "Request timeout"),
-            Self::ChannelClosed => (StatusCode::INTERNAL_SERVER_ERROR, "Channel closed"),
-            Self::Handler(e) => (StatusCode::BAD_REQUEST, e.as_str()),
-            Self::HandlerUnavailable => (StatusCode::SERVICE_UNAVAILABLE, "Handler unavailable"),
+            Self::Timeout => (StatusCode::GATEWAY_TIMEOUT, "Request timeout".to_string()),
+            Self::ChannelClosed => (StatusCode::INTERNAL_SERVER_ERROR, "Channel closed".to_string()),
+            Self::Handler(e) => (handler_status(e), e.to_string()),
+            Self::HandlerUnavailable => (StatusCode::SERVICE_UNAVAILABLE, "Handler unavailable".to_string()),
         };
-        (status, message.to_string()).into_response()
+        (status, self.retry_after(), message).into_response()
     }
 }
 
 impl IntoResponse for CommandError {
     fn into_response(self) -> Response {
         let (status, message) = match &self {
-            Self::Timeout => (StatusCode::GATEWAY_TIMEOUT, "timeout"),
-            Self::ChannelClosed => (StatusCode::INTERNAL_SERVER_ERROR, "channel closed"),
-            Self::Handler(e) => (StatusCode::BAD_REQUEST, e.as_str()),
-            Self::HandlerUnavailable => (StatusCode::SERVICE_UNAVAILABLE, "handler-unavailable"),
+            Self::Timeout => (StatusCode::GATEWAY_TIMEOUT, "timeout".to_string()),
+            Self::ChannelClosed => (StatusCode::INTERNAL_SERVER_ERROR, "channel closed".to_string()),
+            Self::Handler(e) => (handler_status(e), e.to_string()),
+            Self::HandlerUnavailable => (StatusCode::SERVICE_UNAVAILABLE, "handler-unavailable".to_string()),
         };
-        (status, Json(ApiErrorResponse::new(message))).into_response()
+        (status, self.retry_after(), Json(ApiErrorResponse::new(message))).into_response()
     }
 }
 
-pub async fn await_response<T>(rx: oneshot::Receiver<Result<T, String>>) -> Result<T, CommandError> {
+pub async fn await_response<T>(
+    rx: oneshot::Receiver<Result<T, ProvisionrError>>,
+) -> Result<T, CommandError> {
     match time::timeout(Duration::from_secs(TIMEOUT_SECS), rx).await {
         Ok(Ok(Ok(value))) => Ok(value),
         Ok(Ok(Err(e))) => Err(CommandError::Handler(e)),
@@ -91,7 +132,7 @@ pub async fn await_response<T>(rx: oneshot::Receiver<Result<T, String>>) -> Resu
 
 pub async fn send_command<T>(
     state: &AppState,
-    cmd_fn: impl FnOnce(oneshot::Sender<Result<T, String>>) -> Command,
+    cmd_fn: impl FnOnce(oneshot::Sender<Result<T, ProvisionrError>>) -> Command,
 ) -> Result<T, CommandError> {
     let (tx, rx) = oneshot::channel();
     state
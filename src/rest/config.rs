@@ -2,20 +2,86 @@ use axum::{
     extract::{Path, State},
     http::StatusCode,
     response::IntoResponse,
-    Json,
+    Extension, Json,
 };
+use serde::Serialize;
+use utoipa::ToSchema;
 
-use crate::commands::models::Command;
-use crate::rest::command::{send_command, ApiErrorResponse, ApiSuccessMessage, CommandError};
+use crate::commands::models::{CacheGenerationBump, Command, SetConfigResult};
+use crate::rest::client_ip::ClientIp;
+use crate::rest::command::{send_command, ApiErrorResponse, CommandError};
 use crate::rest::state::AppState;
-use crate::storage::models::TemplateConfig;
+use crate::rest::template::{check_template_name, TemplatePath};
+use crate::storage::models::{ConfigHistoryEntry, DynamicFieldConfig, TemplateConfig};
+
+/// Response returned after a template's configuration changes
+#[derive(Serialize, ToSchema)]
+pub struct ConfigSetResponse {
+    #[schema(example = "ok")]
+    pub status: String,
+    #[schema(example = "config set")]
+    pub message: String,
+    /// History version of the configuration now in effect
+    #[schema(example = 3)]
+    pub version: i64,
+    /// Cached renders that will no longer be served because the id field changed
+    #[schema(example = 0)]
+    pub orphaned_renders: i64,
+    pub warnings: Vec<String>,
+}
+
+impl ConfigSetResponse {
+    fn new(message: &str, result: SetConfigResult) -> Self {
+        Self {
+            status: "ok".to_string(),
+            message: message.to_string(),
+            version: result.version,
+            orphaned_renders: result.orphaned_renders,
+            warnings: result.warnings,
+        }
+    }
+}
+
+/// Response returned after a template's cache generation is bumped
+#[derive(Serialize, ToSchema)]
+pub struct CacheGenerationResponse {
+    #[schema(example = "ok")]
+    pub status: String,
+    /// Generation new renders are stored under
+    #[schema(example = 2)]
+    pub cache_generation: u64,
+    /// History version of the configuration now in effect
+    #[schema(example = 3)]
+    pub version: i64,
+}
+
+impl From<CacheGenerationBump> for CacheGenerationResponse {
+    fn from(bump: CacheGenerationBump) -> Self {
+        Self {
+            status: "ok".to_string(),
+            cache_generation: bump.cache_generation,
+            version: bump.config.version,
+        }
+    }
+}
+
+/// A template's configured id field
+#[derive(Serialize, ToSchema)]
+pub struct IdFieldResponse {
+    #[schema(example = "serial_number")]
+    pub id_field: String,
+}
+
+pub(crate) fn changed_by(client_ip: Option<Extension<ClientIp>>) -> Option<String> {
+    client_ip.map(|Extension(ClientIp(ip))| ip.to_string())
+}
 
 #[utoipa::path(
     get,
     path = "/api/v1/config/{name}",
     description = "Get the configuration for a template including id_field, dynamic_fields, and hashing_algorithm.",
     params(
-        ("name" = String, Path, description = "Template name")
+        ("name" = String, Path, description = "Template name; nested names such as `switches/core/uplink.j2` may use literal slashes")
     ),
     responses(
         (status = 200, description = "Template configuration", body = TemplateConfig),
@@ -26,8 +92,9 @@ use crate::storage::models::TemplateConfig;
 )]
 pub async fn get_config(
     State(state): State<AppState>,
-    Path(name): Path<String>,
+    TemplatePath(name): TemplatePath,
 ) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
     let result = send_command(&state, |tx| Command::GetConfig { name, response: tx }).await?;
 
     match result {
@@ -40,32 +107,208 @@ pub async fn get_config(
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/v1/template/{name}/id-field",
+    description = "Get the query parameter that identifies a device for the template, `mac_address` unless configured otherwise.",
+    params(
+        ("name" = String, Path, description = "Template name")
+    ),
+    responses(
+        (status = 200, description = "Configured id field", body = IdFieldResponse),
+        (status = 404, description = "Template not found", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "config"
+)]
+pub async fn get_id_field(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let result = send_command(&state, |tx| Command::GetIdField { name, response: tx }).await?;
+
+    match result {
+        Some(id_field) => Ok((StatusCode::OK, Json(IdFieldResponse { id_field })).into_response()),
+        None => Ok((
+            StatusCode::NOT_FOUND,
+            Json(ApiErrorResponse::new("Template not found")),
+        )
+            .into_response()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/template/{name}/dynamic-fields",
+    description = "Get the template's dynamic fields exactly as configured, including generator parameters, hashing algorithm and uniqueness. Sending them back unchanged in a config PUT records no new version.",
+    params(
+        ("name" = String, Path, description = "Template name")
+    ),
+    responses(
+        (status = 200, description = "Configured dynamic fields", body = Vec<DynamicFieldConfig>),
+        (status = 404, description = "Template not found", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "config"
+)]
+pub async fn get_dynamic_fields(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let result = send_command(&state, |tx| Command::GetDynamicFields { name, response: tx }).await?;
+
+    match result {
+        Some(fields) => Ok((StatusCode::OK, Json(fields)).into_response()),
+        None => Ok((
+            StatusCode::NOT_FOUND,
+            Json(ApiErrorResponse::new("Template not found")),
+        )
+            .into_response()),
+    }
+}
+
 #[utoipa::path(
     put,
     path = "/api/v1/config/{name}",
     description = "Set the configuration for a template. Includes id_field (which query parameter identifies unique renders), dynamic_fields (auto-generated values), and hashing_algorithm (none, sha512, or yescrypt for hashing generated values).",
     params(
-        ("name" = String, Path, description = "Template name")
+        ("name" = String, Path, description = "Template name; nested names such as `switches/core/uplink.j2` may use literal slashes")
     ),
     request_body = TemplateConfig,
     responses(
-        (status = 200, description = "Configuration set", body = ApiSuccessMessage),
-        (status = 400, description = "Template not found", body = ApiErrorResponse),
+        (status = 200, description = "Configuration set; warnings report cached renders orphaned by an id_field change", body = ConfigSetResponse),
+        (status = 400, description = "Template not found or invalid dynamic fields (duplicate names, a name matching id_field, or not a valid identifier)", body = ApiErrorResponse),
         (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
     ),
     tag = "config"
 )]
 pub async fn set_config(
     State(state): State<AppState>,
-    Path(name): Path<String>,
+    TemplatePath(name): TemplatePath,
+    client_ip: Option<Extension<ClientIp>>,
     Json(config): Json<TemplateConfig>,
 ) -> Result<impl IntoResponse, CommandError> {
-    send_command(&state, |tx| Command::SetConfig {
+    check_template_name(&name)?;
+    let result = send_command(&state, |tx| Command::SetConfig {
+        name,
+        config: Box::new(config),
+        changed_by: changed_by(client_ip),
+        response: tx,
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(ConfigSetResponse::new("config set", result))))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/config/{name}/history",
+    description = "List recorded versions of a template's configuration, oldest first. History starts with the configuration in effect before the first change made through the API.",
+    params(
+        ("name" = String, Path, description = "Template name")
+    ),
+    responses(
+        (status = 200, description = "Configuration history", body = Vec<ConfigHistoryEntry>),
+        (status = 404, description = "Template not found", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "config"
+)]
+pub async fn get_config_history(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let result = send_command(&state, |tx| Command::GetConfigHistory { name, response: tx }).await?;
+
+    match result {
+        Some(history) => Ok((StatusCode::OK, Json(history)).into_response()),
+        None => Ok((
+            StatusCode::NOT_FOUND,
+            Json(ApiErrorResponse::new("Template not found")),
+        )
+            .into_response()),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/config/{name}/rollback/{version}",
+    description = "Re-apply a recorded configuration. The rollback is recorded as a new history version.",
+    params(
+        ("name" = String, Path, description = "Template name"),
+        ("version" = i64, Path, description = "History version to restore")
+    ),
+    responses(
+        (status = 200, description = "Configuration restored", body = ConfigSetResponse),
+        (status = 404, description = "Template or version not found", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "config"
+)]
+pub async fn rollback_config(
+    State(state): State<AppState>,
+    Path((name, version)): Path<(String, i64)>,
+    client_ip: Option<Extension<ClientIp>>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let result = send_command(&state, |tx| Command::RollbackConfig {
         name,
-        config,
+        version,
+        changed_by: changed_by(client_ip),
         response: tx,
     })
     .await?;
 
-    Ok((StatusCode::OK, Json(ApiSuccessMessage::new("config set"))))
+    match result {
+        Some(result) => Ok((
+            StatusCode::OK,
+            Json(ConfigSetResponse::new("config rolled back", result)),
+        )
+            .into_response()),
+        None => Ok((
+            StatusCode::NOT_FOUND,
+            Json(ApiErrorResponse::new("Template or config version not found")),
+        )
+            .into_response()),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/template/{name}/bump-generation",
+    description = "Increment the template's cache_generation so every device gets a fresh render on its next fetch, without changing the template content. Renders from an earlier generation are treated as cache misses and moved to the render history when replaced. Generated values are regenerated unless the template sets carry_forward_generated, in which case each device keeps the values it already has. The change is recorded in the config history.",
+    params(
+        ("name" = String, Path, description = "Template name")
+    ),
+    responses(
+        (status = 200, description = "Cache generation incremented", body = CacheGenerationResponse),
+        (status = 404, description = "Template not found", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "config"
+)]
+pub async fn bump_cache_generation(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    client_ip: Option<Extension<ClientIp>>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let result = send_command(&state, |tx| Command::BumpCacheGeneration {
+        name,
+        changed_by: changed_by(client_ip),
+        response: tx,
+    })
+    .await?;
+
+    match result {
+        Some(bump) => Ok((StatusCode::OK, Json(CacheGenerationResponse::from(bump))).into_response()),
+        None => Ok((
+            StatusCode::NOT_FOUND,
+            Json(ApiErrorResponse::new("Template not found")),
+        )
+            .into_response()),
+    }
 }
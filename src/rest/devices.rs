@@ -0,0 +1,114 @@
+use axum::{
+    extract::{Multipart, Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::commands::device_import::{CsvStream, CsvTable};
+use crate::commands::models::{Command, DeviceImportOptions, DeviceImportReport};
+use crate::rest::command::{send_command, ApiErrorResponse, CommandError};
+use crate::rest::state::AppState;
+use crate::rest::template::check_template_name;
+
+/// Largest upload accepted by the device and template imports
+pub const MAX_IMPORT_BYTES: usize = 64 * 1024 * 1024;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeviceImportQuery {
+    /// Header of the column holding id values; defaults to the first column
+    pub id_column: Option<String>,
+    /// Comma-separated columns that must be in the header and non-empty in every row
+    pub required: Option<String>,
+    /// Validate and report without writing anything
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Write the valid rows even when other rows are rejected
+    #[serde(default)]
+    pub partial: bool,
+}
+
+/// Parses the first multipart field as CSV, chunk by chunk.
+async fn read_csv(multipart: &mut Multipart) -> Result<CsvTable, String> {
+    let mut field = multipart
+        .next_field()
+        .await
+        .map_err(|e| format!("Failed to read multipart field: {}", e))?
+        .ok_or_else(|| "No file uploaded".to_string())?;
+
+    let mut stream = CsvStream::new();
+    while let Some(chunk) = field
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to read field bytes: {}", e))?
+    {
+        stream.feed(&chunk)?;
+    }
+    stream.finish()
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/template/{name}/devices/import",
+    description = "Import per-device values from a CSV file with a header row, one row per device. The id column (the first unless `id_column` names another) holds each device's id value and goes through the id field's input transforms; the non-empty cells of the other columns are stored as that device's value overrides, replacing any imported before. At render time they take precedence over the values YAML and param defaults and yield to query parameters. Overrides apply to new renders only; devices with a cached render keep it.\n\nRows are rejected when their column count differs from the header's, the id is empty or contains control characters, an earlier row has the same id, or a `required` column is empty. The import is all or nothing: when any row is rejected nothing is written and the report is returned with 422, unless `partial=true` writes the valid rows. `dry_run=true` only reports. The file may have up to 100000 rows.",
+    params(
+        ("name" = String, Path, description = "Template name"),
+        DeviceImportQuery
+    ),
+    request_body(content_type = "multipart/form-data", description = "CSV file upload"),
+    responses(
+        (status = 200, description = "Import applied, or validated for a dry run", body = DeviceImportReport),
+        (status = 400, description = "Missing or unreadable file, or a header without the id or a required column", body = ApiErrorResponse),
+        (status = 404, description = "Template not found", body = ApiErrorResponse),
+        (status = 422, description = "Rows were rejected and nothing was written", body = DeviceImportReport),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn import_devices(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(query): Query<DeviceImportQuery>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let table = match read_csv(&mut multipart).await {
+        Ok(table) => table,
+        Err(e) => {
+            return Ok((StatusCode::BAD_REQUEST, Json(ApiErrorResponse::new(format!("Invalid CSV: {}", e))))
+                .into_response());
+        }
+    };
+    let required_columns = query
+        .required
+        .iter()
+        .flat_map(|columns| columns.split(','))
+        .map(str::trim)
+        .filter(|column| !column.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    let report = send_command(&state, |tx| Command::ImportDevices {
+        name,
+        table,
+        options: DeviceImportOptions {
+            id_column: query.id_column,
+            required_columns,
+            dry_run: query.dry_run,
+            partial: query.partial,
+        },
+        response: tx,
+    })
+    .await?;
+
+    Ok(match report {
+        Some(report) if !report.dry_run && report.applied == 0 && !report.errors.is_empty() => {
+            (StatusCode::UNPROCESSABLE_ENTITY, Json(report)).into_response()
+        }
+        Some(report) => (StatusCode::OK, Json(report)).into_response(),
+        None => (StatusCode::NOT_FOUND, Json(ApiErrorResponse::new("Template not found"))).into_response(),
+    })
+}
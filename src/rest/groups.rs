@@ -0,0 +1,124 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use std::collections::HashMap;
+use std::time::Instant;
+
+use crate::commands::models::{Command, GroupMembers, RenderResult};
+use crate::rest::client_ip::ClientIp;
+use crate::rest::command::{send_command, ApiErrorResponse, CommandError};
+use crate::rest::state::AppState;
+use crate::rest::template::{conditional_render_response, wants_json};
+use crate::storage::models::FetchSource;
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/group/{group}/members",
+    description = "Create a template group or replace its members. Members rendered through the group share one record of generated values per id, so a password embedded in one member matches the one in another. Every member must be an existing template, listed once.",
+    params(
+        ("group" = String, Path, description = "Group name")
+    ),
+    request_body = GroupMembers,
+    responses(
+        (status = 200, description = "Members set", body = GroupMembers),
+        (status = 400, description = "A member is not an existing template or is listed twice", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn set_group_members(
+    State(state): State<AppState>,
+    Path(group): Path<String>,
+    Json(body): Json<GroupMembers>,
+) -> Result<impl IntoResponse, CommandError> {
+    let members = send_command(&state, |tx| Command::SetGroupMembers {
+        group,
+        members: body.members,
+        response: tx,
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(members)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/group/{group}/members",
+    description = "List the member templates of a group.",
+    params(
+        ("group" = String, Path, description = "Group name")
+    ),
+    responses(
+        (status = 200, description = "Group members", body = GroupMembers),
+        (status = 404, description = "Group not found", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn get_group_members(
+    State(state): State<AppState>,
+    Path(group): Path<String>,
+) -> Result<impl IntoResponse, CommandError> {
+    let members = send_command(&state, |tx| Command::GetGroupMembers { group, response: tx }).await?;
+
+    Ok(match members {
+        Some(members) => (StatusCode::OK, Json(members)).into_response(),
+        None => (StatusCode::NOT_FOUND, Json(ApiErrorResponse::new("Group not found"))).into_response(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/group/{group}/{member}",
+    description = "Render a member template of a group. Works like rendering the template directly, including the cache, except that dynamic values come from the group's record for the id: the first member rendered for an id generates them, and later members reuse every field already generated. A member already rendered directly for the id keeps its cached render. Returns the raw text unless the request sends `Accept: application/json`.",
+    params(
+        ("group" = String, Path, description = "Group name"),
+        ("member" = String, Path, description = "Member template name"),
+        ("mac_address" = Option<String>, Query, description = "Default ID field value (unless the member's id-field is customised). Required for rendering.")
+    ),
+    responses(
+        (status = 200, description = "Rendered member content, or content and metadata for Accept: application/json", content(
+            (String = "text/plain"),
+            (RenderResult = "application/json")
+        )),
+        (status = 400, description = "Member template not found or missing required ID field", body = String),
+        (status = 404, description = "Group not found or template not a member", body = String),
+        (status = 422, description = "Rendered output exceeds max_output_bytes", body = String),
+        (status = 429, description = "Same ID re-fetched within the template's min_interval_seconds", body = String),
+        (status = 503, description = "Handler unavailable", body = String)
+    ),
+    tag = "templates"
+)]
+pub async fn render_group_member(
+    State(state): State<AppState>,
+    Path((group, member)): Path<(String, String)>,
+    Query(query_values): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    client_ip: Option<Extension<ClientIp>>,
+) -> Response {
+    let json = wants_json(&headers);
+    let user_agent = headers.get(header::USER_AGENT).and_then(|ua| ua.to_str().ok());
+    let source = FetchSource::new(client_ip.map(|Extension(ClientIp(ip))| ip), user_agent);
+    let result = send_command(&state, |tx| Command::RenderGroupMember {
+        group,
+        member,
+        query_values,
+        source,
+        sent_at: Instant::now(),
+        response: tx,
+    })
+    .await;
+
+    match result {
+        Ok(Some(result)) => conditional_render_response(result, &headers),
+        Ok(None) if json => {
+            (StatusCode::NOT_FOUND, Json(ApiErrorResponse::new("Group member not found"))).into_response()
+        }
+        Ok(None) => (StatusCode::NOT_FOUND, "Group member not found").into_response(),
+        Err(e) if json => e.into_response(),
+        Err(e) => e.into_plain_response(),
+    }
+}
@@ -0,0 +1,21 @@
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use metrics_exporter_prometheus::PrometheusHandle;
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    description = "Prometheus text exposition of server metrics, including per-generator invocation counts, durations and failures, and renders stopped at the output size limit.",
+    responses(
+        (status = 200, description = "Metrics in Prometheus text format", body = String, content_type = "text/plain")
+    ),
+    tag = "system"
+)]
+pub async fn get_metrics(State(handle): State<PrometheusHandle>) -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        handle.render(),
+    )
+}
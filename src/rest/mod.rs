@@ -1,5 +1,13 @@
+pub mod admin;
+pub mod client_ip;
 pub mod command;
 pub mod config;
+pub mod devices;
+pub mod groups;
+pub mod metrics;
+pub mod openapi;
 pub mod rendered;
 pub mod state;
+pub mod status;
 pub mod template;
+pub mod version;
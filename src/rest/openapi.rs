@@ -0,0 +1,131 @@
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use utoipa::openapi::OpenApi;
+
+use crate::commands::models::Command;
+use crate::rest::command::{send_command, CommandError};
+use crate::rest::state::AppState;
+
+/// The OpenAPI document as served: the configured external URL as its server, and
+/// an existing template as the example of every `{name}` path parameter.
+pub struct ApiDocument {
+    base: Value,
+    /// Last document served, with the template name its examples use
+    cached: Mutex<Option<(Option<String>, Bytes)>>,
+}
+
+impl ApiDocument {
+    pub fn new(openapi: OpenApi, external_url: Option<&str>) -> Self {
+        let mut base = serde_json::to_value(openapi).expect("OpenAPI document serializes to JSON");
+        if let Some(url) = external_url {
+            base["servers"] = json!([{ "url": url }]);
+        }
+        Self {
+            base,
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Serialized document using `template` in examples; rebuilt only when the
+    /// template differs from the one last served.
+    pub fn render(&self, template: Option<&str>) -> Bytes {
+        let mut cached = self.cached.lock().unwrap();
+        if let Some((name, document)) = &*cached
+            && name.as_deref() == template
+        {
+            return document.clone();
+        }
+
+        let mut document = self.base.clone();
+        if let Some(template) = template {
+            set_name_examples(&mut document, template);
+        }
+        let document = Bytes::from(serde_json::to_vec(&document).expect("OpenAPI document serializes to JSON"));
+        *cached = Some((template.map(str::to_string), document.clone()));
+        document
+    }
+}
+
+fn set_name_examples(document: &mut Value, template: &str) {
+    let Some(paths) = document.get_mut("paths").and_then(Value::as_object_mut) else {
+        return;
+    };
+    let parameters = paths
+        .values_mut()
+        .filter_map(Value::as_object_mut)
+        .flat_map(|item| item.values_mut())
+        .filter_map(|operation| operation.get_mut("parameters"))
+        .filter_map(Value::as_array_mut)
+        .flatten();
+    for parameter in parameters {
+        if parameter["in"] == "path" && parameter["name"] == "name" {
+            parameter["example"] = json!(template);
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ApiDocState {
+    pub app: AppState,
+    pub document: Arc<ApiDocument>,
+}
+
+/// Serves the OpenAPI document; not itself part of it.
+pub async fn get_openapi(State(state): State<ApiDocState>) -> Result<impl IntoResponse, CommandError> {
+    let names = send_command(&state.app, |tx| Command::ListTemplateNames { response: tx }).await?;
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/json")],
+        state.document.render(names.first().map(String::as_str)),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utoipa::openapi::path::{HttpMethod, OperationBuilder, ParameterBuilder, ParameterIn, PathItem, PathsBuilder};
+    use utoipa::openapi::OpenApiBuilder;
+
+    fn openapi() -> OpenApi {
+        let operation = OperationBuilder::new()
+            .parameter(ParameterBuilder::new().name("name").parameter_in(ParameterIn::Path))
+            .parameter(ParameterBuilder::new().name("name").parameter_in(ParameterIn::Query))
+            .build();
+        OpenApiBuilder::new()
+            .paths(PathsBuilder::new().path("/api/v1/template/{name}", PathItem::new(HttpMethod::Get, operation)))
+            .build()
+    }
+
+    fn parse(document: Bytes) -> Value {
+        serde_json::from_slice(&document).unwrap()
+    }
+
+    #[test]
+    fn servers_come_from_the_external_url() {
+        let document = parse(ApiDocument::new(openapi(), Some("https://provision.example.com")).render(None));
+        assert_eq!(document["servers"], json!([{ "url": "https://provision.example.com" }]));
+
+        let document = parse(ApiDocument::new(openapi(), None).render(None));
+        assert!(document.get("servers").is_none());
+    }
+
+    #[test]
+    fn path_name_parameters_use_the_template_as_example() {
+        let api_document = ApiDocument::new(openapi(), None);
+        let parameters = |document: &Value| document["paths"]["/api/v1/template/{name}"]["get"]["parameters"].clone();
+
+        let document = parse(api_document.render(Some("kickstart")));
+        assert_eq!(parameters(&document)[0]["example"], "kickstart");
+        assert!(parameters(&document)[1].get("example").is_none());
+
+        let document = parse(api_document.render(None));
+        assert!(parameters(&document)[0].get("example").is_none());
+    }
+}
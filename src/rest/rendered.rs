@@ -1,24 +1,67 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::IntoResponse,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
+use futures_util::{stream, StreamExt};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
 
-use crate::commands::models::Command;
-use crate::rest::command::{send_command, ApiErrorResponse, CommandError};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::commands::models::{
+    Command, OrphanedTemplate, RegeneratedRender, RenderResult, RenderedDetail, RenderedDiff, RenderedPage,
+};
+use crate::commands::rendered_export::{csv_header, RenderedExportRow};
+use crate::rest::command::{send_command, ApiErrorResponse, ApiSuccessMessage, CommandError};
 use crate::rest::state::AppState;
-use crate::storage::models::{RenderedTemplate, RenderedTemplateSummary};
+use crate::rest::template::{render_response, wants_json, RenderRequest};
+use crate::storage::models::{RenderedCursor, RenderedField, RenderedFilter};
+use crate::storage::sqlite_store::normalize_timestamp;
+
+/// Renders per page of `GET /api/v1/rendered/{name}` unless `limit` is given
+pub const DEFAULT_RENDERED_PAGE_SIZE: u32 = 100;
+/// Largest `limit` accepted by `GET /api/v1/rendered/{name}`
+pub const MAX_RENDERED_PAGE_SIZE: u32 = 1000;
+
+fn default_rendered_page_size() -> u32 {
+    DEFAULT_RENDERED_PAGE_SIZE
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListRenderedQuery {
+    /// Renders per page, 1 to 1000 (default 100)
+    #[serde(default = "default_rendered_page_size")]
+    pub limit: u32,
+    /// Renders to skip, e.g. the `next` of the previous page
+    #[serde(default)]
+    pub offset: u32,
+    /// Only renders created at or after this RFC 3339 timestamp
+    #[param(example = "2024-01-02T00:00:00Z")]
+    pub since: Option<String>,
+    /// Only renders created at or before this RFC 3339 timestamp
+    #[param(example = "2024-01-02T23:59:59.999Z")]
+    pub until: Option<String>,
+    /// Only renders whose ID field value starts with this, ignoring ASCII case
+    #[param(example = "AA:BB:CC")]
+    pub q: Option<String>,
+}
 
 #[utoipa::path(
     get,
     path = "/api/v1/rendered/{name}",
-    description = "List all rendered instances of a template. Each instance is identified by its ID field value and creation timestamp.",
+    description = "List the rendered instances of a template, newest first, one page at a time. Each instance is identified by its ID field value and creation timestamp. `since` and `until` keep only renders created within that inclusive range, and `q` only those whose ID field value starts with it (`%` and `_` are literal). `total` counts all matching renders, and `next` is the `offset` of the following page, absent on the last one. An offset past the end returns an empty page.",
     params(
-        ("name" = String, Path, description = "Template name")
+        ("name" = String, Path, description = "Template name"),
+        ListRenderedQuery
     ),
     responses(
-        (status = 200, description = "List of rendered template instances", body = Vec<RenderedTemplateSummary>),
+        (status = 200, description = "One page of rendered template instances", body = RenderedPage),
+        (status = 400, description = "`limit` outside 1 to 1000, or `since`/`until` not an RFC 3339 timestamp or out of order", body = ApiErrorResponse),
         (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
     ),
     tag = "rendered"
@@ -26,26 +69,186 @@ use crate::storage::models::{RenderedTemplate, RenderedTemplateSummary};
 pub async fn list_rendered(
     State(state): State<AppState>,
     Path(name): Path<String>,
+    Query(query): Query<ListRenderedQuery>,
+) -> Result<impl IntoResponse, CommandError> {
+    if !(1..=MAX_RENDERED_PAGE_SIZE).contains(&query.limit) {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(ApiErrorResponse::new(format!("limit must be between 1 and {}", MAX_RENDERED_PAGE_SIZE))),
+        )
+            .into_response());
+    }
+
+    let mut filter = RenderedFilter {
+        id_prefix: query.q.filter(|q| !q.is_empty()),
+        ..RenderedFilter::default()
+    };
+    for (param, value, bound) in [("since", query.since, &mut filter.since), ("until", query.until, &mut filter.until)] {
+        let Some(value) = value else { continue };
+        match normalize_timestamp(&value) {
+            Some(timestamp) => *bound = Some(timestamp),
+            None => {
+                return Ok((
+                    StatusCode::BAD_REQUEST,
+                    Json(ApiErrorResponse::new(format!(
+                        "{} must be an RFC 3339 timestamp such as 2024-01-02T00:00:00Z, got '{}'",
+                        param, value
+                    ))),
+                )
+                    .into_response());
+            }
+        }
+    }
+    if let (Some(since), Some(until)) = (&filter.since, &filter.until)
+        && since > until
+    {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(ApiErrorResponse::new("since must not be after until")),
+        )
+            .into_response());
+    }
+
+    let page = send_command(&state, |tx| Command::ListRendered {
+        template_name: name,
+        filter,
+        limit: query.limit,
+        offset: query.offset,
+        response: tx,
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(page)).into_response())
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PurgeRenderedQuery {
+    /// Must be true unless `dry_run` is; guards against accidental deletes
+    #[serde(default)]
+    pub confirm: bool,
+    /// Count the renders that would be deleted without deleting them
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Cached renders removed by a purge of one template
+#[derive(Serialize, ToSchema)]
+pub struct PurgeRenderedResponse {
+    #[schema(example = "ok")]
+    pub status: String,
+    /// Nothing was deleted; `deleted` counts what a real run would delete
+    pub dry_run: bool,
+    #[schema(example = 42)]
+    pub deleted: i64,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/rendered/{name}",
+    description = "Delete every cached render of a template, e.g. after an overhaul, so each device gets a fresh render with new dynamic values on its next fetch. Requires confirm=true, unless dry_run=true counts the renders without deleting them. To re-render without losing the old renders, bump the cache generation instead.",
+    params(
+        ("name" = String, Path, description = "Template name"),
+        PurgeRenderedQuery
+    ),
+    responses(
+        (status = 200, description = "Cached renders deleted", body = PurgeRenderedResponse),
+        (status = 400, description = "Neither confirm=true nor dry_run=true was given", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "rendered"
+)]
+pub async fn purge_rendered(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(query): Query<PurgeRenderedQuery>,
 ) -> Result<impl IntoResponse, CommandError> {
-    let list = send_command(&state, |tx| Command::ListRendered {
+    if !query.confirm && !query.dry_run {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(ApiErrorResponse::new("Pass confirm=true to delete every cached render of the template")),
+        )
+            .into_response());
+    }
+
+    let deleted = send_command(&state, |tx| Command::PurgeRendered {
         template_name: name,
+        dry_run: query.dry_run,
         response: tx,
     })
     .await?;
 
-    Ok((StatusCode::OK, Json(list)))
+    Ok((
+        StatusCode::OK,
+        Json(PurgeRenderedResponse {
+            status: "ok".to_string(),
+            dry_run: query.dry_run,
+            deleted,
+        }),
+    )
+        .into_response())
+}
+
+/// `name` made safe as a download file name: anything but ASCII letters, digits,
+/// `-`, `_` and non-leading `.` becomes `_`.
+fn download_file_name(name: &str) -> String {
+    name.chars()
+        .enumerate()
+        .map(|(i, c)| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_') || (c == '.' && i > 0) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+async fn fetch_rendered(
+    state: &AppState,
+    name: String,
+    id_value: String,
+    include_secrets: bool,
+) -> Result<Option<RenderedDetail>, CommandError> {
+    send_command(state, |tx| Command::GetRendered {
+        template_name: name,
+        id_value,
+        include_secrets,
+        response: tx,
+    })
+    .await
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RenderedQuery {
+    /// Comma-separated fields to return, e.g. `generated_values,created_at`
+    pub fields: Option<String>,
+    /// Return generated values in clear text instead of redacting them
+    #[serde(default)]
+    pub include_secrets: bool,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RenderedContentQuery {
+    /// Return the content with generated values in clear text instead of redacted
+    #[serde(default)]
+    pub include_secrets: bool,
 }
 
 #[utoipa::path(
     get,
     path = "/api/v1/rendered/{name}/{id_value}",
-    description = "Get a specific rendered template instance including its content and any dynamically generated values.",
+    description = "Get a specific rendered template instance including its content and any dynamically generated values. `first_fetch_ip` and `first_fetch_user_agent` identify the request that created it; `last_fetch_ip` and `last_fetch_user_agent` are only recorded with `track_last_fetch` enabled.\n\nWith `fields`, only the listed fields are read from the store and returned, along with `id`, `template_name` and `id_field_value`. Leaving out `rendered_content` avoids reading large renders at all.\n\nThe full response also carries `generated_values_parsed`, the generated values as a JSON object, or null when the stored YAML cannot be parsed.\n\nGenerated values are redacted unless include_secrets=true: each value in `generated_values` and `generated_values_parsed` is replaced with `<redacted>`, as is every occurrence of one in `rendered_content`. A server started with `PROVISIONR_REDACT_SECRETS=false` never redacts.",
     params(
         ("name" = String, Path, description = "Template name"),
-        ("id_value" = String, Path, description = "ID field value used when rendering (e.g. MAC address)")
+        ("id_value" = String, Path, description = "ID field value used when rendering (e.g. MAC address)"),
+        RenderedQuery
     ),
     responses(
-        (status = 200, description = "Rendered template details including content and generated values", body = RenderedTemplate),
+        (status = 200, description = "Rendered template details including content and generated values, or only the selected fields", body = RenderedDetail),
+        (status = 400, description = "Unknown field in `fields`; the message lists the valid ones", body = ApiErrorResponse),
         (status = 404, description = "Rendered template not found", body = ApiErrorResponse),
         (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
     ),
@@ -54,16 +257,324 @@ pub async fn list_rendered(
 pub async fn get_rendered(
     State(state): State<AppState>,
     Path((name, id_value)): Path<(String, String)>,
+    Query(query): Query<RenderedQuery>,
+) -> Result<impl IntoResponse, CommandError> {
+    let not_found = || (StatusCode::NOT_FOUND, Json(ApiErrorResponse::new("Rendered template not found"))).into_response();
+
+    if let Some(fields) = query.fields {
+        let fields = match RenderedField::parse_list(&fields) {
+            Ok(fields) => fields,
+            Err(e) => return Ok((StatusCode::BAD_REQUEST, Json(ApiErrorResponse::new(e))).into_response()),
+        };
+        let result = send_command(&state, |tx| Command::GetRenderedFields {
+            template_name: name,
+            id_value,
+            fields,
+            include_secrets: query.include_secrets,
+            response: tx,
+        })
+        .await?;
+        return Ok(match result {
+            Some(object) => (StatusCode::OK, Json(object)).into_response(),
+            None => not_found(),
+        });
+    }
+
+    match fetch_rendered(&state, name, id_value, query.include_secrets).await? {
+        Some(detail) => Ok((StatusCode::OK, Json(detail)).into_response()),
+        None => Ok(not_found()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/rendered/{name}/{id_value}/content",
+    description = "Download the cached content of a render exactly as the device received it, as an attachment named after the sanitized ID field value. The content type is the template's `content_type`, or `text/plain` when it has none or no longer exists. The content is not re-rendered. Generated values in it are redacted unless include_secrets=true or the server was started with `PROVISIONR_REDACT_SECRETS=false`.",
+    params(
+        ("name" = String, Path, description = "Template name"),
+        ("id_value" = String, Path, description = "ID field value used when rendering (e.g. MAC address)"),
+        RenderedContentQuery
+    ),
+    responses(
+        (status = 200, description = "Cached rendered content", body = String, content_type = "text/plain"),
+        (status = 404, description = "Rendered template not found", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "rendered"
+)]
+pub async fn get_rendered_content(
+    State(state): State<AppState>,
+    Path((name, id_value)): Path<(String, String)>,
+    Query(query): Query<RenderedContentQuery>,
+) -> Result<Response, CommandError> {
+    let file_name = download_file_name(&id_value);
+    let Some(detail) = fetch_rendered(&state, name.clone(), id_value, query.include_secrets).await? else {
+        return Ok((StatusCode::NOT_FOUND, Json(ApiErrorResponse::new("Rendered template not found"))).into_response());
+    };
+    let config = send_command(&state, |tx| Command::GetConfig { name, response: tx }).await?;
+    let content_type = config
+        .and_then(|config| config.content_type)
+        .unwrap_or_else(|| "text/plain; charset=utf-8".to_string());
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name)),
+        ],
+        detail.rendered.rendered_content,
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/rendered/{name}/{id_value}",
+    description = "Delete one cached render so the next fetch for its ID renders afresh, with newly generated dynamic values. Use it when a device is re-imaged. Unique values the render held are released; other renders of the template are untouched.",
+    params(
+        ("name" = String, Path, description = "Template name"),
+        ("id_value" = String, Path, description = "ID field value used when rendering (e.g. MAC address)")
+    ),
+    responses(
+        (status = 200, description = "Cached render deleted", body = ApiSuccessMessage),
+        (status = 404, description = "Rendered template not found", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "rendered"
+)]
+pub async fn delete_rendered(
+    State(state): State<AppState>,
+    Path((name, id_value)): Path<(String, String)>,
+) -> Result<impl IntoResponse, CommandError> {
+    let deleted = send_command(&state, |tx| Command::DeleteRendered {
+        template_name: name,
+        id_value,
+        response: tx,
+    })
+    .await?;
+
+    if deleted {
+        Ok((StatusCode::OK, Json(ApiSuccessMessage::new("rendered template deleted"))).into_response())
+    } else {
+        Ok((StatusCode::NOT_FOUND, Json(ApiErrorResponse::new("Rendered template not found"))).into_response())
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/rendered/{name}/{id_value}/rerender",
+    description = "Render the template's current content for an existing cached render, keeping the generated values stored with it, and replace the cached content in place. Use it when a template changed but a generated secret is already baked into the device. Values are merged as for a render: stored values, parameter defaults, device values, the inputs recorded with the render, then the query parameters and the optional JSON body, which wins over the query. No new values are generated, so a dynamic field added since the render stays unset. The previous content is kept in the render history. The response is formatted like a render's.",
+    params(
+        ("name" = String, Path, description = "Template name"),
+        ("id_value" = String, Path, description = "ID field value used when rendering (e.g. MAC address)")
+    ),
+    request_body(content = Option<RenderRequest>, description = "Values to render with, in addition to the query parameters"),
+    responses(
+        (status = 200, description = "New content of the render, or content and metadata for Accept: application/json", content(
+            (String = "text/plain"),
+            (RenderResult = "application/json")
+        )),
+        (status = 400, description = "Empty template, missing required fields or unparseable stored values", body = String),
+        (status = 404, description = "Template or rendered template not found", body = ApiErrorResponse),
+        (status = 422, description = "Rendered output exceeds max_output_bytes", body = String),
+        (status = 503, description = "Handler unavailable", body = String)
+    ),
+    tag = "rendered"
+)]
+pub async fn rerender_rendered(
+    State(state): State<AppState>,
+    Path((name, id_value)): Path<(String, String)>,
+    Query(mut values): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    body: Option<Json<RenderRequest>>,
+) -> Response {
+    if let Some(Json(request)) = body {
+        values.extend(request.values);
+    }
+    let json = wants_json(&headers);
+    let result = send_command(&state, |tx| Command::RerenderRendered {
+        template_name: name,
+        id_value,
+        values,
+        response: tx,
+    })
+    .await;
+
+    match result {
+        Ok(Some(result)) => render_response(result, json),
+        Ok(None) => (StatusCode::NOT_FOUND, Json(ApiErrorResponse::new("Rendered template not found"))).into_response(),
+        Err(e) if json => e.into_response(),
+        Err(e) => e.into_plain_response(),
+    }
+}
+
+/// Dynamic fields to regenerate for one render
+#[derive(Deserialize, ToSchema)]
+pub struct RegenerateRequest {
+    #[schema(example = json!(["admin_password"]))]
+    pub fields: Vec<String>,
+    /// Values overriding the input values recorded with the render, e.g. for keys
+    /// left out by `input_values_exclude`
+    #[serde(default)]
+    pub values: HashMap<String, String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/rendered/{name}/{id_value}/regenerate",
+    description = "Generate new values for some dynamic fields of a cached render, such as a password being rotated, and re-render it in place. The other generated values are kept. New values come from the template's current dynamic field configuration, hashing included, and unique fields are checked against other renders. The render's recorded input values are used again, overridden by `values` in the body. The previous content and values are kept in the render history. The response holds the new content and the new values, both redacted unless include_secrets=true.",
+    params(
+        ("name" = String, Path, description = "Template name"),
+        ("id_value" = String, Path, description = "ID field value used when rendering (e.g. MAC address)"),
+        RenderedContentQuery
+    ),
+    request_body = RegenerateRequest,
+    responses(
+        (status = 200, description = "New content and values of the render", body = RegeneratedRender),
+        (status = 400, description = "No fields, a field that is not a dynamic field of the template, unparseable stored values, or the render failed", body = ApiErrorResponse),
+        (status = 404, description = "Template or rendered template not found", body = ApiErrorResponse),
+        (status = 409, description = "No unused value found for a unique field", body = ApiErrorResponse),
+        (status = 422, description = "Rendered output exceeds max_output_bytes", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "rendered"
+)]
+pub async fn regenerate_rendered(
+    State(state): State<AppState>,
+    Path((name, id_value)): Path<(String, String)>,
+    Query(query): Query<RenderedContentQuery>,
+    Json(request): Json<RegenerateRequest>,
+) -> Result<impl IntoResponse, CommandError> {
+    if request.fields.is_empty() {
+        return Ok((StatusCode::BAD_REQUEST, Json(ApiErrorResponse::new("fields must not be empty"))).into_response());
+    }
+    let result = send_command(&state, |tx| Command::RegenerateRendered {
+        template_name: name,
+        id_value,
+        fields: request.fields,
+        values: request.values,
+        include_secrets: query.include_secrets,
+        response: tx,
+    })
+    .await?;
+
+    match result {
+        Some(regenerated) => Ok((StatusCode::OK, Json(regenerated)).into_response()),
+        None => Ok((StatusCode::NOT_FOUND, Json(ApiErrorResponse::new("Rendered template not found"))).into_response()),
+    }
+}
+
+/// New id for a copy of a render
+#[derive(Deserialize, ToSchema)]
+pub struct CloneRenderedRequest {
+    #[schema(example = "AA:BB:CC:DD:EE:02")]
+    pub new_id: String,
+    /// Render the current template for the new id instead of copying the content as is
+    #[serde(default)]
+    pub rerender: bool,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CloneRenderedQuery {
+    /// Replace a render already stored under the new id
+    #[serde(default)]
+    pub overwrite: bool,
+    /// Return generated values in clear text instead of redacted
+    #[serde(default)]
+    pub include_secrets: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/rendered/{name}/{id_value}/clone",
+    description = "Copy a cached render to a new id, e.g. for a device replaced by hardware with a new MAC address that must receive the same credentials. The new id goes through the id field's input transforms. The copy keeps the generated values and, by default, the rendered content exactly; with `rerender: true` the current template is rendered for the new id with the same generated values and the original render's recorded inputs. The copy starts unserved. Unique values stay indexed to the original id. A render already stored under the new id is a 409 unless `overwrite=true`, which keeps it in the render history. The response is the new render, with generated values redacted unless include_secrets=true.",
+    params(
+        ("name" = String, Path, description = "Template name"),
+        ("id_value" = String, Path, description = "ID field value of the render to copy"),
+        CloneRenderedQuery
+    ),
+    request_body = CloneRenderedRequest,
+    responses(
+        (status = 200, description = "The new render", body = RenderedDetail),
+        (status = 400, description = "Empty new id, or the re-render failed", body = ApiErrorResponse),
+        (status = 404, description = "Template or rendered template not found", body = ApiErrorResponse),
+        (status = 409, description = "A render exists under the new id and `overwrite` is not set, or the new id is the original", body = ApiErrorResponse),
+        (status = 422, description = "Rendered output exceeds max_output_bytes", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "rendered"
+)]
+pub async fn clone_rendered(
+    State(state): State<AppState>,
+    Path((name, id_value)): Path<(String, String)>,
+    Query(query): Query<CloneRenderedQuery>,
+    Json(request): Json<CloneRenderedRequest>,
 ) -> Result<impl IntoResponse, CommandError> {
-    let result = send_command(&state, |tx| Command::GetRendered {
+    if request.new_id.trim().is_empty() {
+        return Ok((StatusCode::BAD_REQUEST, Json(ApiErrorResponse::new("new_id must not be empty"))).into_response());
+    }
+    let result = send_command(&state, |tx| Command::CloneRendered {
         template_name: name,
         id_value,
+        new_id_value: request.new_id,
+        rerender: request.rerender,
+        overwrite: query.overwrite,
+        include_secrets: query.include_secrets,
         response: tx,
     })
     .await?;
 
     match result {
-        Some(rendered) => Ok((StatusCode::OK, Json(rendered)).into_response()),
+        Some(detail) => Ok((StatusCode::OK, Json(detail)).into_response()),
+        None => Ok((StatusCode::NOT_FOUND, Json(ApiErrorResponse::new("Rendered template not found"))).into_response()),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DiffQuery {
+    /// ID field value of the first render
+    pub a: String,
+    /// ID field value of the second render
+    pub b: String,
+    /// Show generated values in clear text instead of redacting them
+    #[serde(default)]
+    pub include_secrets: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/rendered/{name}/diff",
+    description = "Compare two cached renders of a template: a unified diff of the rendered content and a key-level diff of the generated values. Generated values are compared in full but redacted in the output, including inside the content diff, unless include_secrets=true. Renders over 4 MiB are refused with 422.",
+    params(
+        ("name" = String, Path, description = "Template name"),
+        DiffQuery
+    ),
+    responses(
+        (status = 200, description = "Differences between the two renders", body = RenderedDiff),
+        (status = 404, description = "Either rendered template not found", body = ApiErrorResponse),
+        (status = 422, description = "Rendered content too large to diff", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "rendered"
+)]
+pub async fn diff_rendered(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(query): Query<DiffQuery>,
+) -> Result<impl IntoResponse, CommandError> {
+    let result = send_command(&state, |tx| Command::DiffRendered {
+        template_name: name,
+        a: query.a,
+        b: query.b,
+        include_secrets: query.include_secrets,
+        response: tx,
+    })
+    .await?;
+
+    match result {
+        Some(diff) => Ok((StatusCode::OK, Json(diff)).into_response()),
         None => Ok((
             StatusCode::NOT_FOUND,
             Json(ApiErrorResponse::new("Rendered template not found")),
@@ -71,3 +582,210 @@ pub async fn get_rendered(
             .into_response()),
     }
 }
+
+/// Renders fetched from the handler per command while exporting
+const EXPORT_PAGE_SIZE: u32 = 500;
+
+/// Formats `GET /api/v1/rendered/{name}/export` can write
+#[derive(Debug, Clone, Copy, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportRenderedQuery {
+    /// Export format; only `csv`
+    pub format: ExportFormat,
+    /// Comma-separated generated values to export as columns, in this order; by default
+    /// every generated value found across the renders, sorted by name
+    #[param(example = "root_password,api_token")]
+    pub fields: Option<String>,
+    /// Export generated values in clear text instead of redacting them
+    #[serde(default)]
+    pub include_secrets: bool,
+}
+
+async fn export_page(
+    state: &AppState,
+    name: &str,
+    after: Option<RenderedCursor>,
+    include_secrets: bool,
+) -> Result<Vec<RenderedExportRow>, CommandError> {
+    send_command(state, |tx| Command::ExportRendered {
+        template_name: name.to_string(),
+        limit: EXPORT_PAGE_SIZE,
+        after,
+        include_secrets,
+        response: tx,
+    })
+    .await
+}
+
+/// Cursor of the page after `rows`, continuing from their last row; `None` after a
+/// short page.
+fn next_export_cursor(rows: &[RenderedExportRow]) -> Option<RenderedCursor> {
+    rows.last()
+        .filter(|_| rows.len() as u32 == EXPORT_PAGE_SIZE)
+        .map(RenderedExportRow::cursor)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/rendered/{name}/export",
+    description = "Export a template's cached renders as CSV, newest first: one record per render with its ID field value, creation time and a column per generated value, blank where a render has none. The columns are those in `fields`, or else every generated value found across the renders, sorted. Values are redacted unless include_secrets=true. The file is streamed as renders are read, so an error part way through ends it early. Renders stored while the export runs are left out.",
+    params(
+        ("name" = String, Path, description = "Template name"),
+        ExportRenderedQuery
+    ),
+    responses(
+        (status = 200, description = "CSV file of the renders", content_type = "text/csv", body = String),
+        (status = 400, description = "Missing or unknown `format`", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "rendered"
+)]
+pub async fn export_rendered(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(query): Query<ExportRenderedQuery>,
+) -> Result<Response, CommandError> {
+    let ExportFormat::Csv = query.format;
+    let (keys, start): (Arc<[String]>, _) = match &query.fields {
+        Some(fields) => (
+            fields
+                .split(',')
+                .map(str::trim)
+                .filter(|field| !field.is_empty())
+                .map(String::from)
+                .collect(),
+            Some(None),
+        ),
+        None => {
+            // A first pass over the renders to learn the columns before any is written.
+            // The records start just above the newest render it saw, so renders stored
+            // since are left out rather than written without their columns.
+            let mut keys = BTreeSet::new();
+            let mut start = None;
+            let mut page = Some(None);
+            while let Some(after) = page {
+                let rows = export_page(&state, &name, after, false).await?;
+                keys.extend(rows.iter().flat_map(|row| row.generated_values.keys().cloned()));
+                start = start.or_else(|| {
+                    rows.first().map(|row| RenderedCursor {
+                        created_at: row.created_at.clone(),
+                        id: row.id + 1,
+                    })
+                });
+                page = next_export_cursor(&rows).map(Some);
+            }
+            (keys.into_iter().collect(), start.map(Some))
+        }
+    };
+
+    let header = stream::once(std::future::ready(Ok(csv_header(&keys))));
+    let file_name = format!("{}.csv", download_file_name(&name));
+    let include_secrets = query.include_secrets;
+    let records = stream::unfold(start, move |page| {
+        let (state, name, keys) = (state.clone(), name.clone(), keys.clone());
+        async move {
+            let after = page?;
+            match export_page(&state, &name, after, include_secrets).await {
+                Ok(rows) => {
+                    let chunk: String = rows.iter().map(|row| row.csv_record(&keys)).collect();
+                    Some((Ok(chunk), next_export_cursor(&rows).map(Some)))
+                }
+                Err(e) => Some((Err(std::io::Error::other(e.to_string())), None)),
+            }
+        }
+    });
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", file_name)),
+        ],
+        Body::from_stream(header.chain(records)),
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/rendered/orphans",
+    description = "List templates that no longer exist but still have cached renders in the database, with the number of renders each.",
+    responses(
+        (status = 200, description = "Orphaned templates", body = Vec<OrphanedTemplate>),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "rendered"
+)]
+pub async fn list_orphans(State(state): State<AppState>) -> Result<impl IntoResponse, CommandError> {
+    let orphans = send_command(&state, |tx| Command::ListOrphans { response: tx }).await?;
+
+    Ok((StatusCode::OK, Json(orphans)))
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PurgeOrphansQuery {
+    /// Must be true unless `dry_run` is; guards against accidental deletes
+    #[serde(default)]
+    pub confirm: bool,
+    /// List what would be deleted without deleting anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Cached renders removed by an orphan purge
+#[derive(Serialize, ToSchema)]
+pub struct PurgeOrphansResponse {
+    #[schema(example = "ok")]
+    pub status: String,
+    /// Nothing was deleted; `purged` lists what a real run would delete
+    pub dry_run: bool,
+    pub purged: Vec<OrphanedTemplate>,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/rendered/orphans",
+    description = "Delete the cached renders of every template that no longer exists. Requires confirm=true, unless dry_run=true lists what would be deleted without deleting it.",
+    params(PurgeOrphansQuery),
+    responses(
+        (status = 200, description = "Orphaned renders deleted", body = PurgeOrphansResponse),
+        (status = 400, description = "Neither confirm=true nor dry_run=true was given", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "rendered"
+)]
+pub async fn purge_orphans(
+    State(state): State<AppState>,
+    Query(query): Query<PurgeOrphansQuery>,
+) -> Result<impl IntoResponse, CommandError> {
+    if !query.confirm && !query.dry_run {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(ApiErrorResponse::new("Pass confirm=true to delete orphaned renders")),
+        )
+            .into_response());
+    }
+
+    let purged = send_command(&state, |tx| Command::PurgeOrphans {
+        dry_run: query.dry_run,
+        response: tx,
+    })
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(PurgeOrphansResponse {
+            status: "ok".to_string(),
+            dry_run: query.dry_run,
+            purged,
+        }),
+    )
+        .into_response())
+}
@@ -1,7 +1,17 @@
 use crate::commands::models::Command;
 use tokio::sync::mpsc;
 
+#[cfg(feature = "http-client")]
+use crate::upstream::Upstream;
+#[cfg(feature = "http-client")]
+use std::sync::Arc;
+
 #[derive(Clone)]
 pub struct AppState {
     pub command_tx: mpsc::Sender<Command>,
+    /// Largest template upload or values body accepted, in bytes
+    pub max_template_bytes: usize,
+    /// Upstream instance unknown templates are pulled from, if configured
+    #[cfg(feature = "http-client")]
+    pub upstream: Option<Arc<Upstream>>,
 }
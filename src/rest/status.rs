@@ -0,0 +1,35 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+
+use crate::commands::models::{Command, PhaseLatency, ServerStatus};
+use crate::rest::command::{send_command, ApiErrorResponse, CommandError};
+use crate::rest::state::AppState;
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/status",
+    description = "Runtime status of the server. `warmup` lists the outcome of warming each template named in the `warmup` config, including templates that failed to compile and cached renders that no longer match a fresh render. It is empty until warm-up reaches a template. `persistence` counts renders that could not read or store their cached render, with the most recent error.",
+    responses(
+        (status = 200, description = "Server status", body = ServerStatus),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "system"
+)]
+pub async fn get_status(State(state): State<AppState>) -> Result<impl IntoResponse, CommandError> {
+    let status = send_command(&state, |tx| Command::GetStatus { response: tx }).await?;
+    Ok((StatusCode::OK, Json(status)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/status/render-latency",
+    description = "Latency of each phase of a render over the last 256 renders that reached it: waiting in the handler queue, the cache lookup, parsing the values YAML, generating and hashing values, rendering the template and storing the result. Cache hits stop after the lookup. Durations are in milliseconds; `recent_ms` lists the kept samples oldest first. The same durations are exported as the `provisionr_render_phase_duration_seconds` histogram.",
+    responses(
+        (status = 200, description = "Latency of each render phase", body = Vec<PhaseLatency>),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "system"
+)]
+pub async fn get_render_latency(State(state): State<AppState>) -> Result<impl IntoResponse, CommandError> {
+    let latency = send_command(&state, |tx| Command::GetRenderLatency { response: tx }).await?;
+    Ok((StatusCode::OK, Json(latency)))
+}
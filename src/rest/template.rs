@@ -1,160 +1,1639 @@
 use axum::{
     body::Bytes,
-    extract::{Multipart, Path, Query, State},
-    http::StatusCode,
+    extract::{rejection::PathRejection, FromRequest, FromRequestParts, Multipart, Path, Query, Request, State},
+    handler::Handler,
+    Extension,
+    http::{header, request::Parts, HeaderMap, HeaderValue, Method, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
+use log::debug;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::Instant;
+use utoipa::{IntoParams, ToSchema};
 
-use crate::commands::models::Command;
+use crate::commands::archive;
+use crate::error::ProvisionrError;
+use crate::commands::models::{
+    CacheStatus, Command, LintWarning, PrerenderResult, PrerenderStatus, RenderPreview, RenderResult, SetTemplateOptions, TemplateAlias, TemplateImportReport, TemplateRename,
+    TemplateVariable, TestRunReport,
+};
+use crate::rest::client_ip::ClientIp;
 use crate::rest::command::{send_command, ApiErrorResponse, ApiSuccessMessage, CommandError};
+use crate::rest::config::changed_by;
+use crate::rest::devices::MAX_IMPORT_BYTES;
 use crate::rest::state::AppState;
+use crate::statics::shutdown::global_cancellation_token;
+use crate::storage::models::{
+    validate_template_name, FetchSource, TemplateBundle, TemplateMetadata, TemplateSummary, TemplateTestCase,
+    ValuesFormat,
+};
+
+/// Header on `GET .../values` naming the format the values were uploaded in
+pub const VALUES_FORMAT_HEADER: &str = "x-values-format";
+/// Header on render responses, once per problem the render worked around
+pub const WARNING_HEADER: &str = "x-provisionr-warning";
+/// Header on render responses naming the fallback template used in place of a missing one
+pub const FALLBACK_TEMPLATE_HEADER: &str = "x-provisionr-template";
+/// Header on render responses: `HIT` when served from the cache, `MISS` when rendered
+pub const CACHE_HEADER: &str = "x-provisionr-cache";
+
+/// Rejects a template name taken from the request path before it reaches the handler.
+/// The path extractor has already percent-decoded it, so `..%2Fetc` arrives as `../etc`.
+pub(crate) fn check_template_name(name: &str) -> Result<(), CommandError> {
+    validate_template_name(name).map_err(|e| CommandError::Handler(ProvisionrError::InvalidTemplateName(e)))
+}
+
+/// Template name taken from the request path. Routes for nested names with literal
+/// slashes capture them as `{first}/{*rest}`, which are joined back with `/`.
+pub struct TemplatePath(pub String);
+
+impl<S: Send + Sync> FromRequestParts<S> for TemplatePath {
+    type Rejection = PathRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(segments) = Path::<Vec<String>>::from_request_parts(parts, state).await?;
+        Ok(Self(segments.join("/")))
+    }
+}
+
+/// Response returned after a template is stored
+#[derive(Serialize, ToSchema)]
+pub struct TemplateSetResponse {
+    #[schema(example = "ok")]
+    pub status: String,
+    #[schema(example = "template set")]
+    pub message: String,
+    /// Hex-encoded SHA-256 of the stored template content
+    #[schema(example = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")]
+    pub checksum: String,
+    /// Non-fatal lint findings; the template was stored regardless
+    pub warnings: Vec<LintWarning>,
+    /// The uploaded content was identical to the stored content
+    pub unchanged: bool,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SetTemplateQuery {
+    /// Reject the upload with 422 if linting produces any warnings
+    #[serde(default)]
+    pub strict: bool,
+    /// Run the template's stored test cases against the new content and reject it with 422 if any fail
+    #[serde(default)]
+    pub run_tests: bool,
+    /// Accept filters, tests and functions this server does not know, e.g. for a newer engine version
+    #[serde(default)]
+    pub allow_unknown_filters: bool,
+    /// Replace the content of a template that already exists instead of returning 409
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+/// Checksum of a stored template's content
+#[derive(Serialize, ToSchema)]
+pub struct TemplateChecksum {
+    #[schema(example = "kickstart")]
+    pub name: String,
+    /// Hex-encoded SHA-256 of the stored template content
+    #[schema(example = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")]
+    pub checksum: String,
+}
 
-async fn extract_file_content(multipart: &mut Multipart) -> Result<String, String> {
+/// Largest template or values body accepted unless `PROVISIONR_MAX_TEMPLATE_BYTES` is set
+pub const DEFAULT_MAX_TEMPLATE_BYTES: usize = 4 * 1024 * 1024;
+
+/// Environment variable overriding [`DEFAULT_MAX_TEMPLATE_BYTES`]
+pub const MAX_TEMPLATE_BYTES_ENV: &str = "PROVISIONR_MAX_TEMPLATE_BYTES";
+
+/// The 413 answer to a body the route's `DefaultBodyLimit` cut off, or the extractor's
+/// own rejection for anything else
+fn upload_error(status: StatusCode, message: String, max_bytes: usize) -> Response {
+    if status == StatusCode::PAYLOAD_TOO_LARGE {
+        let message = format!("Upload exceeds the maximum size of {} bytes", max_bytes);
+        return (status, Json(ApiErrorResponse::new(message))).into_response();
+    }
+    (status, Json(ApiErrorResponse::new(message))).into_response()
+}
+
+async fn extract_file_bytes(multipart: &mut Multipart) -> Result<Bytes, (StatusCode, String)> {
     let field = multipart
         .next_field()
         .await
-        .map_err(|e| format!("Failed to read multipart field: {}", e))?
-        .ok_or_else(|| "No file uploaded".to_string())?;
+        .map_err(|e| (e.status(), format!("Failed to read multipart field: {}", e)))?
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, "No file uploaded".to_string()))?;
 
-    let bytes = field
+    field
         .bytes()
         .await
-        .map_err(|e| format!("Failed to read field bytes: {}", e))?;
+        .map_err(|e| (e.status(), format!("Failed to read field bytes: {}", e)))
+}
+
+/// The raw body of a request. The route's `DefaultBodyLimit` stops reading once it
+/// grows past `max_bytes`, so an oversized body is never buffered in full.
+async fn read_body(request: Request, max_bytes: usize) -> Result<Bytes, Response> {
+    Bytes::from_request(request, &())
+        .await
+        .map_err(|e| upload_error(e.status(), e.body_text(), max_bytes))
+}
+
+/// An uploaded file: the first field of a multipart form, or the raw body for any
+/// other content type, so `curl --data-binary` works as well as a form.
+async fn read_upload(request: Request, max_bytes: usize) -> Result<Bytes, Response> {
+    let is_multipart = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("multipart/"));
+
+    if is_multipart {
+        let mut multipart = Multipart::from_request(request, &())
+            .await
+            .map_err(|e| upload_error(e.status(), e.body_text(), max_bytes))?;
+        return extract_file_bytes(&mut multipart)
+            .await
+            .map_err(|(status, message)| upload_error(status, message, max_bytes));
+    }
+    read_body(request, max_bytes).await
+}
 
-    String::from_utf8(bytes.to_vec()).map_err(|_| "File content is not valid UTF-8".to_string())
+/// Template content of an upload, which must be UTF-8
+async fn read_template_upload(request: Request, max_bytes: usize) -> Result<String, Response> {
+    let bytes = read_upload(request, max_bytes).await?;
+    String::from_utf8(bytes.to_vec()).map_err(|_| {
+        (StatusCode::BAD_REQUEST, Json(ApiErrorResponse::new("File content is not valid UTF-8"))).into_response()
+    })
 }
 
 #[utoipa::path(
     post,
     path = "/api/v1/template/{name}",
-    description = "Upload a Jinja2 template file.",
+    description = "Upload a Jinja2 template, either as the first file of a multipart form or as the raw request body with any other content type, e.g. `text/plain`. Both are validated the same way. Replacing different content of an existing template needs `overwrite=true`; without it the upload returns 409. The response includes non-fatal lint warnings such as variables that no stored value or dynamic field provides, unhashed password variables and trailing whitespace.",
     params(
-        ("name" = String, Path, description = "Template name")
+        ("name" = String, Path, description = "Template name; nested names such as `switches/core/uplink.j2` may use literal slashes"),
+        SetTemplateQuery
+    ),
+    request_body(
+        description = "Template file upload, or the template as the raw body",
+        content((String = "multipart/form-data"), (String = "text/plain"))
     ),
-    request_body(content_type = "multipart/form-data", description = "Template file upload"),
     responses(
-        (status = 200, description = "Template created/updated", body = ApiSuccessMessage),
-        (status = 400, description = "Invalid template syntax or missing file", body = ApiErrorResponse),
+        (status = 200, description = "Template created/updated", body = TemplateSetResponse),
+        (status = 400, description = "Invalid template syntax, unknown filter, test or function (with its line), missing file, or content that is not valid UTF-8", body = ApiErrorResponse),
+        (status = 413, description = "Upload larger than the maximum template size", body = ApiErrorResponse),
+        (status = 409, description = "The template already has different content and `overwrite` was not set", body = ApiErrorResponse),
+        (status = 422, description = "Lint warnings found in strict mode, or stored test cases failed", body = ApiErrorResponse),
+        (status = 429, description = "Content changed within the template's min_update_interval_seconds; see Retry-After", body = ApiErrorResponse),
         (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
     ),
     tag = "templates"
 )]
 pub async fn set_template(
     State(state): State<AppState>,
-    Path(name): Path<String>,
-    mut multipart: Multipart,
+    TemplatePath(name): TemplatePath,
+    Query(query): Query<SetTemplateQuery>,
+    request: Request,
 ) -> Result<impl IntoResponse, CommandError> {
-    let content = match extract_file_content(&mut multipart).await {
+    check_template_name(&name)?;
+    let content = match read_template_upload(request, state.max_template_bytes).await {
         Ok(content) => content,
-        Err(e) => {
-            return Ok((StatusCode::BAD_REQUEST, Json(ApiErrorResponse::new(e))).into_response());
-        }
+        Err(response) => return Ok(response),
     };
 
-    send_command(&state, |tx| Command::SetTemplate {
+    let result = send_command(&state, |tx| Command::SetTemplate {
         name,
         content,
+        options: SetTemplateOptions {
+            strict: query.strict,
+            run_tests: query.run_tests,
+            allow_unknown_filters: query.allow_unknown_filters,
+            overwrite: query.overwrite,
+        },
         response: tx,
     })
     .await?;
 
-    Ok((StatusCode::OK, Json(ApiSuccessMessage::new("template set"))).into_response())
+    Ok((
+        StatusCode::OK,
+        Json(TemplateSetResponse {
+            status: "ok".to_string(),
+            message: if result.unchanged { "template unchanged" } else { "template set" }.to_string(),
+            checksum: result.checksum,
+            warnings: result.warnings,
+            unchanged: result.unchanged,
+        }),
+    )
+        .into_response())
+}
+
+/// Outcome of one file of a bulk upload
+#[derive(Serialize, ToSchema)]
+pub struct TemplateUploadResult {
+    #[schema(example = "access-switch.j2")]
+    pub file_name: String,
+    /// Template the file is stored as: its file name without a `.j2` extension
+    #[schema(example = "access-switch")]
+    pub name: String,
+    /// "ok" or "error"
+    #[schema(example = "ok")]
+    pub status: String,
+    /// Hex-encoded SHA-256 of the stored content; absent when the file was rejected
+    pub checksum: Option<String>,
+    /// Non-fatal lint findings of a stored file
+    pub warnings: Vec<LintWarning>,
+    /// The file was identical to the stored content
+    pub unchanged: bool,
+    /// Why the file was rejected
+    pub error: Option<String>,
+}
+
+/// Per-file outcome of a bulk upload, in upload order
+#[derive(Serialize, ToSchema)]
+pub struct BulkUploadReport {
+    pub uploaded: usize,
+    pub failed: usize,
+    pub results: Vec<TemplateUploadResult>,
+}
+
+/// Template name for an uploaded file: the file name without a `.j2` extension.
+fn template_name_from_file(file_name: &str) -> Result<String, String> {
+    let name = file_name.strip_suffix(".j2").unwrap_or(file_name);
+    if name.is_empty() {
+        return Err("file part has no file name to take the template name from".to_string());
+    }
+    validate_template_name(name).map_err(|e| ProvisionrError::InvalidTemplateName(e).to_string())?;
+    Ok(name.to_string())
 }
 
 #[utoipa::path(
-    put,
-    path = "/api/v1/template/{name}/values",
-    description = "Set default values for template variables. Values are provided as raw YAML or JSON (JSON is valid YAML). These defaults are used when rendering if not overridden by query parameters.",
-    params(
-        ("name" = String, Path, description = "Template name")
-    ),
-    request_body(content_type = "text/plain", description = "Raw YAML or JSON content with key-value pairs"),
+    post,
+    path = "/api/v1/templates",
+    description = "Upload several templates in one multipart form, one file part per template. Each file is stored under its file name without a `.j2` extension, e.g. `access-switch.j2` as `access-switch`. Every file is validated and stored on its own, exactly as a single upload with the same query parameters would be, so one bad file does not stop the others. The report lists every file in upload order. It comes with 200 when all were stored and 400 when any was rejected.",
+    params(SetTemplateQuery),
+    request_body(content_type = "multipart/form-data", description = "One file part per template"),
     responses(
-        (status = 200, description = "Values set", body = ApiSuccessMessage),
-        (status = 400, description = "Invalid YAML/JSON syntax", body = ApiErrorResponse),
+        (status = 200, description = "Every file was stored", body = BulkUploadReport),
+        (status = 400, description = "Some files were rejected, or the form could not be read", body = BulkUploadReport),
         (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
     ),
     tag = "templates"
 )]
-pub async fn set_values(
+pub async fn upload_templates(
     State(state): State<AppState>,
-    Path(name): Path<String>,
-    body: Bytes,
+    Query(query): Query<SetTemplateQuery>,
+    mut multipart: Multipart,
 ) -> Result<impl IntoResponse, CommandError> {
-    let yaml = match String::from_utf8(body.to_vec()) {
-        Ok(s) => s,
-        Err(_) => {
-            return Ok((
-                StatusCode::BAD_REQUEST,
-                Json(ApiErrorResponse::new("Request body is not valid UTF-8")),
-            )
-                .into_response());
+    let mut results: Vec<TemplateUploadResult> = Vec::new();
+    loop {
+        let field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => {
+                let error = format!("Failed to read multipart field: {}", e);
+                return Ok((StatusCode::BAD_REQUEST, Json(ApiErrorResponse::new(error))).into_response());
+            }
+        };
+        let file_name = field.file_name().unwrap_or_default().to_string();
+        let content = match field.bytes().await {
+            Ok(bytes) => String::from_utf8(bytes.to_vec()).map_err(|_| "File content is not valid UTF-8".to_string()),
+            Err(e) => Err(format!("Failed to read field bytes: {}", e)),
+        };
+        let name = template_name_from_file(&file_name);
+        let mut result = TemplateUploadResult {
+            file_name,
+            name: name.clone().unwrap_or_default(),
+            status: "error".to_string(),
+            checksum: None,
+            warnings: Vec::new(),
+            unchanged: false,
+            error: None,
+        };
+
+        let stored = match (name, content) {
+            (Err(e), _) | (_, Err(e)) => Err(e),
+            (Ok(name), Ok(_)) if results.iter().any(|r| r.name == name) => {
+                Err(format!("template '{}' appears more than once in the upload", name))
+            }
+            (Ok(name), Ok(content)) => {
+                let stored = send_command(&state, |tx| Command::SetTemplate {
+                    name,
+                    content,
+                    options: SetTemplateOptions {
+                        strict: query.strict,
+                        run_tests: query.run_tests,
+                        allow_unknown_filters: query.allow_unknown_filters,
+                        overwrite: query.overwrite,
+                    },
+                    response: tx,
+                })
+                .await;
+                match stored {
+                    Ok(stored) => Ok(stored),
+                    Err(CommandError::Handler(e)) => Err(e.to_string()),
+                    Err(e) => return Err(e),
+                }
+            }
+        };
+        match stored {
+            Ok(stored) => {
+                result.status = "ok".to_string();
+                result.checksum = Some(stored.checksum);
+                result.warnings = stored.warnings;
+                result.unchanged = stored.unchanged;
+            }
+            Err(e) => result.error = Some(e),
         }
+        results.push(result);
+    }
+
+    if results.is_empty() {
+        return Ok((StatusCode::BAD_REQUEST, Json(ApiErrorResponse::new("No file uploaded"))).into_response());
+    }
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    let status = if failed == 0 { StatusCode::OK } else { StatusCode::BAD_REQUEST };
+    let report = BulkUploadReport {
+        uploaded: results.len() - failed,
+        failed,
+        results,
+    };
+    Ok((status, Json(report)).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/templates",
+    description = "List every stored template, sorted by name, with whether values are set, its id field and how many dynamic fields it has. An empty store returns an empty array.",
+    responses(
+        (status = 200, description = "Stored templates", body = Vec<TemplateSummary>),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn list_templates(State(state): State<AppState>) -> Result<impl IntoResponse, CommandError> {
+    let templates = send_command(&state, |tx| Command::ListTemplates { response: tx }).await?;
+    Ok((StatusCode::OK, Json(templates)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/templates/export",
+    description = "Download every template as a gzip-compressed tarball for backup or for moving templates to another instance. Each template is stored as `<name>` with its content and `<name>.config.yaml` with its configuration, values and test cases. An empty store returns a valid empty archive.",
+    responses(
+        (status = 200, description = "Template archive", content_type = "application/gzip", body = Vec<u8>),
+        (status = 500, description = "The archive could not be built", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn export_templates(State(state): State<AppState>) -> Result<impl IntoResponse, CommandError> {
+    let templates = send_command(&state, |tx| Command::ExportTemplates { response: tx }).await?;
+    let archive = tokio::task::spawn_blocking(move || archive::write_archive(&templates))
+        .await
+        .unwrap_or_else(|e| Err(format!("Archive task failed: {}", e)));
+
+    match archive {
+        Ok(archive) => Ok((
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, "application/gzip"),
+                (header::CONTENT_DISPOSITION, "attachment; filename=\"templates.tar.gz\""),
+            ],
+            archive,
+        )
+            .into_response()),
+        Err(e) => Ok((StatusCode::INTERNAL_SERVER_ERROR, Json(ApiErrorResponse::new(e))).into_response()),
+    }
+}
+
+/// What an import does with templates that already exist
+#[derive(Debug, Clone, Copy, Default, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Leave existing templates as they are
+    #[default]
+    Skip,
+    /// Replace existing templates with the archived ones
+    Overwrite,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct ImportTemplatesQuery {
+    /// `skip` (the default) or `overwrite` templates that already exist
+    #[serde(default)]
+    pub mode: ImportMode,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/templates/import",
+    description = "Load templates from a gzip-compressed tarball as written by the export endpoint, sent as the first file of a multipart form or as the raw body. Each `<name>` entry is a template's content and an optional `<name>.config.yaml` holds its configuration, values and test cases. Every template is validated on its own like a separate upload, so one bad template does not stop the others. The report lists what was imported, skipped because it already existed, and rejected with the reason.",
+    params(ImportTemplatesQuery),
+    request_body(
+        description = "Template archive, as a multipart file or the raw body",
+        content((String = "multipart/form-data"), (Vec<u8> = "application/gzip"))
+    ),
+    responses(
+        (status = 200, description = "Archive read; see the report for each template", body = TemplateImportReport),
+        (status = 400, description = "The archive is not a gzip-compressed tarball, or no file was uploaded", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn import_templates(
+    State(state): State<AppState>,
+    Query(query): Query<ImportTemplatesQuery>,
+    client_ip: Option<Extension<ClientIp>>,
+    request: Request,
+) -> Result<impl IntoResponse, CommandError> {
+    let upload = match read_upload(request, MAX_IMPORT_BYTES).await {
+        Ok(upload) => upload,
+        Err(response) => return Ok(response),
+    };
+    let templates = tokio::task::spawn_blocking(move || archive::read_archive(&upload))
+        .await
+        .unwrap_or_else(|e| Err(format!("Archive task failed: {}", e)));
+    let templates = match templates {
+        Ok(templates) => templates,
+        Err(e) => return Ok((StatusCode::BAD_REQUEST, Json(ApiErrorResponse::new(e))).into_response()),
     };
 
-    send_command(&state, |tx| Command::SetValues {
-        name,
-        yaml,
+    let report = send_command(&state, |tx| Command::ImportTemplates {
+        templates,
+        overwrite: matches!(query.mode, ImportMode::Overwrite),
+        changed_by: changed_by(client_ip),
         response: tx,
     })
     .await?;
+    Ok((StatusCode::OK, Json(report)).into_response())
+}
 
-    Ok((StatusCode::OK, Json(ApiSuccessMessage::new("values set"))).into_response())
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ValidateTemplateQuery {
+    /// Accept filters, tests and functions this server does not know
+    #[serde(default)]
+    pub allow_unknown_filters: bool,
+}
+
+/// Result of validating template content that passed
+#[derive(Serialize, ToSchema)]
+pub struct TemplateValidationResponse {
+    pub valid: bool,
+    /// Root names of the variables the template reads, sorted, e.g. `device` for
+    /// `{{ device.name }}`
+    #[schema(example = json!(["hostname", "mac_address"]))]
+    pub variables: Vec<String>,
 }
 
 #[utoipa::path(
-    get,
+    post,
+    path = "/api/v1/templates/validate",
+    description = "Check template content exactly as an upload would, without storing anything, e.g. to lint candidate templates in CI. The content is sent as the first file of a multipart form or as the raw body. Valid content returns the variables it reads, for cross-checking against a values file.",
+    params(ValidateTemplateQuery),
+    request_body(
+        description = "Template file upload, or the template as the raw body",
+        content((String = "multipart/form-data"), (String = "text/plain"))
+    ),
+    responses(
+        (status = 200, description = "The template is valid", body = TemplateValidationResponse),
+        (status = 400, description = "Invalid syntax, or an unknown filter, test or function, with its line; missing file or content that is not valid UTF-8", body = ApiErrorResponse),
+        (status = 413, description = "Upload larger than the maximum template size", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn validate_template(
+    State(state): State<AppState>,
+    Query(query): Query<ValidateTemplateQuery>,
+    request: Request,
+) -> Result<impl IntoResponse, CommandError> {
+    let content = match read_template_upload(request, state.max_template_bytes).await {
+        Ok(content) => content,
+        Err(response) => return Ok(response),
+    };
+
+    let variables = send_command(&state, |tx| Command::ValidateTemplate {
+        content,
+        allow_unknown_filters: query.allow_unknown_filters,
+        response: tx,
+    })
+    .await?;
+    Ok((StatusCode::OK, Json(TemplateValidationResponse { valid: true, variables })).into_response())
+}
+
+/// Whether a template is stored
+#[derive(Serialize, ToSchema)]
+pub struct TemplateExists {
+    pub exists: bool,
+}
+
+#[utoipa::path(
+    head,
     path = "/api/v1/template/{name}",
-    description = "Render a template with provided values. If the same ID field value was used before, returns cached content. Query parameters override default values set via /values endpoint.",
+    description = "Check whether a template exists without rendering it. Names are matched exactly, as stored by the upload.",
+    params(
+        ("name" = String, Path, description = "Template name; nested names such as `switches/core/uplink.j2` may use literal slashes")
+    ),
+    responses(
+        (status = 200, description = "Template exists"),
+        (status = 404, description = "Template not found"),
+        (status = 503, description = "Handler unavailable")
+    ),
+    tag = "templates"
+)]
+pub async fn head_template(
+    State(state): State<AppState>,
+    TemplatePath(name): TemplatePath,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let exists = send_command(&state, |tx| Command::TemplateExists { name, response: tx }).await?;
+    Ok(if exists { StatusCode::OK } else { StatusCode::NOT_FOUND })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/template/{name}/exists",
+    description = "Check whether a template exists without rendering it, for clients that cannot send HEAD. A missing template is `{\"exists\": false}` with 200.",
+    params(
+        ("name" = String, Path, description = "Template name")
+    ),
+    responses(
+        (status = 200, description = "Whether the template exists", body = TemplateExists),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn template_exists(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let exists = send_command(&state, |tx| Command::TemplateExists { name, response: tx }).await?;
+    Ok((StatusCode::OK, Json(TemplateExists { exists })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/template/{name}/source",
+    description = "Get a template's content exactly as uploaded, for editing. A template stored with empty content returns an empty body.",
+    params(
+        ("name" = String, Path, description = "Template name")
+    ),
+    responses(
+        (status = 200, description = "Template source", body = String, content_type = "text/plain"),
+        (status = 404, description = "Template not found", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn get_template_source(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let result = send_command(&state, |tx| Command::GetTemplateContent { name, response: tx }).await?;
+
+    match result {
+        Some(content) => {
+            Ok((StatusCode::OK, [(header::CONTENT_TYPE, "text/plain; charset=utf-8")], content).into_response())
+        }
+        None => Ok((
+            StatusCode::NOT_FOUND,
+            Json(ApiErrorResponse::new("Template not found")),
+        )
+            .into_response()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/template/{name}/bundle",
+    description = "Get a template's content, stored values, configuration and test cases in one response. Used by edge instances to pull templates from an upstream.",
+    params(
+        ("name" = String, Path, description = "Template name")
+    ),
+    responses(
+        (status = 200, description = "Template bundle", body = TemplateBundle),
+        (status = 404, description = "Template not found", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn get_bundle(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let result = send_command(&state, |tx| Command::GetBundle { name, response: tx }).await?;
+
+    match result {
+        Some(bundle) => Ok((StatusCode::OK, Json(bundle)).into_response()),
+        None => Ok((
+            StatusCode::NOT_FOUND,
+            Json(ApiErrorResponse::new("Template not found")),
+        )
+            .into_response()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/template/{name}/variables",
+    description = "List the variables the stored template references, sorted by name, and where a render gets each one's value: a `dynamic_field`, the `id_field`, a `param_default`, a `stored_value`, or a `query_param` that has to be supplied. Nested access like `{{ device.name }}` is reported by its root name `device`.",
+    params(
+        ("name" = String, Path, description = "Template name")
+    ),
+    responses(
+        (status = 200, description = "Template variables", body = Vec<TemplateVariable>),
+        (status = 404, description = "Template not found", body = ApiErrorResponse),
+        (status = 400, description = "Stored template content cannot be parsed", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn get_template_variables(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let result = send_command(&state, |tx| Command::GetTemplateVariables { name, response: tx }).await?;
+
+    match result {
+        Some(variables) => Ok((StatusCode::OK, Json(variables)).into_response()),
+        None => Ok((
+            StatusCode::NOT_FOUND,
+            Json(ApiErrorResponse::new("Template not found")),
+        )
+            .into_response()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/template/{name}/params-schema",
+    description = "Get a draft-07 JSON Schema describing the query parameters a render of the template accepts, for building forms. The id field and every template variable without a stored value, param default or dynamic field are `required`. Variables with a stored value or param default are optional with that `default`. Dynamic fields are listed as `readOnly` because they are generated at render time. Parameters with input transforms name them in their `description`.",
+    params(
+        ("name" = String, Path, description = "Template name")
+    ),
+    responses(
+        (status = 200, description = "JSON Schema of the render parameters", body = Object),
+        (status = 404, description = "Template not found", body = ApiErrorResponse),
+        (status = 400, description = "Stored template content cannot be parsed", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn get_params_schema(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let result = send_command(&state, |tx| Command::GetParamsSchema { name, response: tx }).await?;
+
+    match result {
+        Some(schema) => Ok((StatusCode::OK, Json(schema)).into_response()),
+        None => Ok((
+            StatusCode::NOT_FOUND,
+            Json(ApiErrorResponse::new("Template not found")),
+        )
+            .into_response()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/template/{name}/cache-status",
+    description = "Report whether a render with the same query parameters would be served from the cache, without rendering. The id value is taken from the template's id field after input transforms, exactly as a render would, so `?serial=sn1` and `?serial=SN1` agree when the field is uppercased. Nothing is rendered, generated, stored or counted as a serve. `template_hash_matches` compares the checksum recorded with the cached render against the current template content, and is absent for renders stored before checksums were recorded.",
     params(
         ("name" = String, Path, description = "Template name"),
-        ("mac_address" = Option<String>, Query, description = "Default ID field value (unless id-field is customised). Required for rendering.")
+        ("params" = HashMap<String, String>, Query, description = "The query parameters a render would receive; only the id field is used")
     ),
     responses(
-        (status = 200, description = "Rendered template content", body = String),
-        (status = 400, description = "Template not found or missing required ID field", body = String),
-        (status = 503, description = "Handler unavailable", body = String)
+        (status = 200, description = "Cache status", body = CacheStatus),
+        (status = 400, description = "The id field is missing from the query", body = ApiErrorResponse),
+        (status = 404, description = "Template not found", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
     ),
     tag = "templates"
 )]
-pub async fn render_template(
+pub async fn get_cache_status(
     State(state): State<AppState>,
     Path(name): Path<String>,
     Query(params): Query<HashMap<String, String>>,
-) -> Response {
-    match send_command(&state, |tx| Command::RenderTemplate {
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let result = send_command(&state, |tx| Command::GetCacheStatus {
         name,
         query_values: params,
         response: tx,
     })
-    .await
-    {
-        Ok(content) => content.into_response(),
-        Err(e) => e.into_plain_response(),
+    .await?;
+
+    match result {
+        Some(status) => Ok((StatusCode::OK, Json(status)).into_response()),
+        None => Ok((
+            StatusCode::NOT_FOUND,
+            Json(ApiErrorResponse::new("Template not found")),
+        )
+            .into_response()),
     }
 }
 
 #[utoipa::path(
-    delete,
-    path = "/api/v1/template/{name}",
-    description = "Delete a template and its configuration. Note: Previously rendered instances in the database are not deleted.",
+    get,
+    path = "/api/v1/template/{name}/checksum",
+    description = "Get the SHA-256 checksum of a template's stored content, for detecting changes without downloading it.",
     params(
-        ("name" = String, Path, description = "Template name to delete")
+        ("name" = String, Path, description = "Template name")
     ),
     responses(
-        (status = 200, description = "Template deleted", body = ApiSuccessMessage),
-        (status = 400, description = "Template not found", body = ApiErrorResponse),
+        (status = 200, description = "Template checksum", body = TemplateChecksum),
+        (status = 404, description = "Template not found", body = ApiErrorResponse),
         (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
     ),
     tag = "templates"
 )]
-pub async fn delete_template(
+pub async fn get_checksum(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let result = send_command(&state, |tx| Command::GetChecksum {
+        name: name.clone(),
+        response: tx,
+    })
+    .await?;
+
+    match result {
+        Some(checksum) => Ok((StatusCode::OK, Json(TemplateChecksum { name, checksum })).into_response()),
+        None => Ok((
+            StatusCode::NOT_FOUND,
+            Json(ApiErrorResponse::new("Template not found")),
+        )
+            .into_response()),
+    }
+}
+
+/// Values are TOML when the request says so; anything else is treated as YAML/JSON.
+fn values_format(headers: &HeaderMap) -> ValuesFormat {
+    let is_toml = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/toml"));
+    if is_toml {
+        ValuesFormat::Toml
+    } else {
+        ValuesFormat::Yaml
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/template/{name}/values",
+    description = "Set default values for template variables. Values are provided as raw YAML or JSON (JSON is valid YAML), or as TOML with `Content-Type: application/toml`. TOML is converted to YAML before it is stored. These defaults are used when rendering if not overridden by query parameters.",
+    params(
+        ("name" = String, Path, description = "Template name; nested names such as `switches/core/uplink.j2` may use literal slashes")
+    ),
+    request_body(content_type = "text/plain", description = "Raw YAML, JSON or TOML content with key-value pairs"),
+    responses(
+        (status = 200, description = "Values set", body = ApiSuccessMessage),
+        (status = 400, description = "Invalid YAML/JSON/TOML syntax; TOML errors include line and column", body = ApiErrorResponse),
+        (status = 413, description = "Body larger than the maximum template size", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn set_values(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    request: Request,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let format = values_format(request.headers());
+    let body = match read_body(request, state.max_template_bytes).await {
+        Ok(body) => body,
+        Err(response) => return Ok(response),
+    };
+    let content = match String::from_utf8(body.to_vec()) {
+        Ok(s) => s,
+        Err(_) => {
+            return Ok((
+                StatusCode::BAD_REQUEST,
+                Json(ApiErrorResponse::new("Request body is not valid UTF-8")),
+            )
+                .into_response());
+        }
+    };
+
+    send_command(&state, |tx| Command::SetValues {
+        name,
+        content,
+        format,
+        response: tx,
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(ApiSuccessMessage::new("values set"))).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/template/{name}/values",
+    description = "Get a template's stored default values as YAML. Values uploaded as TOML are returned in their converted YAML form; the `X-Values-Format` header reports the format they were uploaded in.",
+    params(
+        ("name" = String, Path, description = "Template name; nested names such as `switches/core/uplink.j2` may use literal slashes")
+    ),
+    responses(
+        (status = 200, description = "Stored values YAML", body = String, content_type = "text/plain",
+            headers(("X-Values-Format" = String, description = "Format the values were uploaded in: yaml or toml"))),
+        (status = 204, description = "No values have been set"),
+        (status = 404, description = "Template not found", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn get_values(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let result = send_command(&state, |tx| Command::GetValues { name, response: tx }).await?;
+
+    match result {
+        Some(values) => match values.values_yaml {
+            Some(yaml) => Ok((
+                StatusCode::OK,
+                [
+                    (header::CONTENT_TYPE, "text/plain; charset=utf-8"),
+                    (header::HeaderName::from_static(VALUES_FORMAT_HEADER), values.format.as_str()),
+                ],
+                yaml,
+            )
+                .into_response()),
+            None => Ok(StatusCode::NO_CONTENT.into_response()),
+        },
+        None => Ok((
+            StatusCode::NOT_FOUND,
+            Json(ApiErrorResponse::new("Template not found")),
+        )
+            .into_response()),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/template/{name}/values",
+    description = "Remove a template's stored default values. Later renders use only param defaults, query parameters and dynamic fields, as for a template that never had values. Cached renders are not changed.",
+    params(
+        ("name" = String, Path, description = "Template name; nested names such as `switches/core/uplink.j2` may use literal slashes")
+    ),
+    responses(
+        (status = 200, description = "Values cleared", body = ApiSuccessMessage),
+        (status = 404, description = "Template not found", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn clear_values(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let cleared = send_command(&state, |tx| Command::ClearValues { name, response: tx }).await?;
+
+    if !cleared {
+        return Ok((StatusCode::NOT_FOUND, Json(ApiErrorResponse::new("Template not found"))).into_response());
+    }
+    Ok((StatusCode::OK, Json(ApiSuccessMessage::new("values cleared"))).into_response())
+}
+
+/// Requests for nested names with literal slashes, captured as `{first}/{*rest}`. A
+/// trailing `/values` addresses the values of the name before it, which validation
+/// keeps from being a template name of its own; anything else is the template itself.
+pub async fn nested_template(
+    State(state): State<AppState>,
+    TemplatePath(path): TemplatePath,
+    request: Request,
+) -> Response {
+    let method = request.method().clone();
+    if let Some(name) = path.strip_suffix("/values") {
+        let name = Path(name.to_string());
+        return match method {
+            Method::GET => get_values(State(state), name).await.into_response(),
+            Method::PUT => set_values(State(state), name, request).await.into_response(),
+            Method::DELETE => clear_values(State(state), name).await.into_response(),
+            _ => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+        };
+    }
+    match method {
+        Method::GET => render_template.call(request, state).await,
+        Method::HEAD => head_template.call(request, state).await,
+        Method::POST => set_template.call(request, state).await,
+        Method::DELETE => delete_template.call(request, state).await,
+        _ => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+    }
+}
+
+/// JSON when the client's `Accept` header lists `application/json`. Devices send no
+/// Accept header or `*/*` and keep getting the raw rendered text.
+pub(crate) fn wants_json(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|media| {
+            let mut parts = media.split(';');
+            let is_json = parts
+                .next()
+                .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"));
+            let refused = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .any(|q| q.trim().parse::<f32>().is_ok_and(|q| q == 0.0));
+            is_json && !refused
+        })
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/template/{name}",
+    description = "Render a template with provided values. If the same ID field value was used before, returns cached content. Query parameters override default values set via /values endpoint. The raw rendered text is returned unless the request sends `Accept: application/json`, in which case the content is wrapped with render metadata. Raw responses carry an ETag of the content; a request whose `If-None-Match` lists it gets 304 without a body.",
+    params(
+        ("name" = String, Path, description = "Template name; nested names such as `switches/core/uplink.j2` may use literal slashes"),
+        ("mac_address" = Option<String>, Query, description = "Default ID field value (unless id-field is customised). Required for rendering."),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a previously fetched render")
+    ),
+    responses(
+        (status = 200, description = "Rendered template content, or content and metadata for Accept: application/json", content(
+            (String = "text/plain"),
+            (RenderResult = "application/json")
+        ), headers(
+            ("etag" = String, description = "Quoted SHA-256 of the rendered content; raw responses only"),
+            ("x-provisionr-warning" = String, description = "Problem the render worked around, such as stored values that could not be parsed under on_values_error: ignore_with_warning, or a render that could not be stored under serve_without_persistence"),
+            ("x-provisionr-template" = String, description = "The server's fallback_template, rendered because the requested template does not exist"),
+            ("x-provisionr-cache" = String, description = "HIT when served from the cache, MISS when newly rendered")
+        )),
+        (status = 304, description = "The rendered content matches the If-None-Match ETag"),
+        (status = 400, description = "Template not found, missing required ID field or unparseable stored values", body = String),
+        (status = 422, description = "Rendered output exceeds max_output_bytes", body = String),
+        (status = 429, description = "Same ID re-fetched within the template's min_interval_seconds", body = String),
+        (status = 503, description = "Handler unavailable, or the cached render could not be read or stored", body = String)
+    ),
+    tag = "templates"
+)]
+pub async fn render_template(
+    State(state): State<AppState>,
+    TemplatePath(name): TemplatePath,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+    client_ip: Option<Extension<ClientIp>>,
+) -> Response {
+    let ip = client_ip.map(|Extension(ClientIp(ip))| ip);
+    if let Some(ip) = ip {
+        debug!("Render request for '{}' from {}", name, ip);
+    }
+    let json = wants_json(&headers);
+    let user_agent = headers.get(header::USER_AGENT).and_then(|ua| ua.to_str().ok());
+    let source = FetchSource::new(ip, user_agent);
+    match render(&state, name, params, source).await {
+        Ok(result) => conditional_render_response(result, &headers),
+        Err(e) if json => e.into_response(),
+        Err(e) => e.into_plain_response(),
+    }
+}
+
+/// Values to render with, for values that do not fit a query string
+#[derive(Deserialize, ToSchema)]
+pub struct RenderRequest {
+    #[schema(example = json!({"mac_address": "00:11:22:33:44:55", "hostname": "sw01"}))]
+    pub values: HashMap<String, String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/template/{name}/render",
+    description = "Render a template with the values in a JSON body instead of query parameters, for long or multi-line values such as certificates, or values containing `+` and `&`. Behaves exactly like the GET render, including the cache lookup on the id field and the response format chosen by the Accept header.",
+    params(
+        ("name" = String, Path, description = "Template name")
+    ),
+    request_body = RenderRequest,
+    responses(
+        (status = 200, description = "Rendered template content, or content and metadata for Accept: application/json", content(
+            (String = "text/plain"),
+            (RenderResult = "application/json")
+        ), headers(
+            ("x-provisionr-warning" = String, description = "Problem the render worked around"),
+            ("x-provisionr-template" = String, description = "The fallback template rendered in place of a missing one"),
+            ("x-provisionr-cache" = String, description = "HIT when served from the cache, MISS when newly rendered")
+        )),
+        (status = 400, description = "Template not found, missing required ID field or unparseable stored values", body = String),
+        (status = 422, description = "Rendered output exceeds max_output_bytes", body = String),
+        (status = 429, description = "Same ID re-fetched within the template's min_interval_seconds", body = String),
+        (status = 503, description = "Handler unavailable, or the cached render could not be read or stored", body = String)
+    ),
+    tag = "templates"
+)]
+pub async fn render_template_post(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    client_ip: Option<Extension<ClientIp>>,
+    Json(request): Json<RenderRequest>,
+) -> Response {
+    let ip = client_ip.map(|Extension(ClientIp(ip))| ip);
+    let json = wants_json(&headers);
+    let user_agent = headers.get(header::USER_AGENT).and_then(|ua| ua.to_str().ok());
+    let source = FetchSource::new(ip, user_agent);
+    match render(&state, name, request.values, source).await {
+        Ok(result) => render_response(result, json),
+        Err(e) if json => e.into_response(),
+        Err(e) => e.into_plain_response(),
+    }
+}
+
+/// Strong ETag of a render's raw content
+fn render_etag(result: &RenderResult) -> String {
+    format!("\"{}\"", result.content_hash)
+}
+
+/// Value of the [`CACHE_HEADER`] for a render
+fn cache_header(result: &RenderResult) -> HeaderValue {
+    HeaderValue::from_static(if result.cache_hit { "HIT" } else { "MISS" })
+}
+
+/// Whether the request's `If-None-Match` lists `etag` or `*`. A weak tag matches its
+/// strong counterpart, as the header calls for weak comparison.
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Successful render as raw text, or wrapped with its metadata when `json` is set,
+/// with a warning header for each of its warnings and a cache header. Raw text
+/// carries its ETag.
+pub(crate) fn render_response(result: RenderResult, json: bool) -> Response {
+    let cache = cache_header(&result);
+    let warnings: Vec<HeaderValue> = result
+        .warnings
+        .iter()
+        .filter_map(|warning| HeaderValue::from_str(warning).ok())
+        .collect();
+    let fallback = result
+        .fallback_template
+        .as_deref()
+        .and_then(|fallback| HeaderValue::from_str(fallback).ok());
+    let mut response = if json {
+        (StatusCode::OK, Json(result)).into_response()
+    } else {
+        let etag = render_etag(&result);
+        let content_type = result.content_type.as_deref().and_then(|ct| HeaderValue::from_str(ct).ok());
+        let mut response = ([(header::ETAG, etag)], result.content).into_response();
+        if let Some(content_type) = content_type {
+            response.headers_mut().insert(header::CONTENT_TYPE, content_type);
+        }
+        response
+    };
+    for warning in warnings {
+        response.headers_mut().append(WARNING_HEADER, warning);
+    }
+    if let Some(fallback) = fallback {
+        response.headers_mut().insert(FALLBACK_TEMPLATE_HEADER, fallback);
+    }
+    response.headers_mut().insert(CACHE_HEADER, cache);
+    response
+}
+
+/// [`render_response`] for a GET, or 304 Not Modified without a body when the
+/// request's `If-None-Match` already lists the raw content's ETag.
+pub(crate) fn conditional_render_response(result: RenderResult, headers: &HeaderMap) -> Response {
+    let json = wants_json(headers);
+    let etag = render_etag(&result);
+    if !json && if_none_match(headers, &etag) {
+        let cache = cache_header(&result);
+        return (StatusCode::NOT_MODIFIED, [(header::ETAG, etag)], [(CACHE_HEADER, cache)]).into_response();
+    }
+    render_response(result, json)
+}
+
+/// Renders locally, reading unknown templates through from the upstream when one is configured.
+pub(crate) async fn render(
+    state: &AppState,
+    name: String,
+    query_values: HashMap<String, String>,
+    source: FetchSource,
+) -> Result<RenderResult, CommandError> {
+    check_template_name(&name)?;
+    #[cfg(feature = "http-client")]
+    if let Some(upstream) = &state.upstream {
+        return upstream.render(state, name, query_values, source).await;
+    }
+    send_command(state, |tx| Command::RenderTemplate {
+        name,
+        query_values,
+        source,
+        sent_at: Instant::now(),
+        response: tx,
+    })
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/template/{name}/preview",
+    description = "Render a template with the values in the JSON body, merged like query parameters of a render, without reading the cache or storing the result. The id field is optional. Dynamic fields are generated afresh and returned with the content, since a real render generates its own.",
+    params(
+        ("name" = String, Path, description = "Template name")
+    ),
+    request_body(content = HashMap<String, String>, description = "Values to render with, as query parameters would be given", example = json!({"hostname": "sw01"})),
+    responses(
+        (status = 200, description = "Rendered content and the values generated for it", body = RenderPreview),
+        (status = 400, description = "Template has no content, unparseable stored values, or the render failed", body = ApiErrorResponse),
+        (status = 404, description = "Template not found", body = ApiErrorResponse),
+        (status = 422, description = "Rendered output exceeds max_output_bytes", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn preview_template(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(values): Json<HashMap<String, String>>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let result = send_command(&state, |tx| Command::PreviewRender {
+        name,
+        values,
+        response: tx,
+    })
+    .await?;
+
+    match result {
+        Some(preview) => Ok((StatusCode::OK, Json(preview)).into_response()),
+        None => Ok((
+            StatusCode::NOT_FOUND,
+            Json(ApiErrorResponse::new("Template not found")),
+        )
+            .into_response()),
+    }
+}
+
+/// Most ids one pre-render request may list
+pub const MAX_PRERENDER_IDS: usize = 1000;
+
+/// Ids to render ahead of their first fetch, with values shared by all of them
+#[derive(Deserialize, ToSchema)]
+pub struct PrerenderRequest {
+    #[schema(example = json!(["AA:BB:CC:DD:EE:01", "AA:BB:CC:DD:EE:02"]))]
+    pub ids: Vec<String>,
+    /// Values every render gets, as query parameters of a render would give them
+    #[serde(default)]
+    #[schema(example = json!({"site": "dc1"}))]
+    pub values: HashMap<String, String>,
+}
+
+/// Outcome of a pre-render request, with a result per id in request order
+#[derive(Serialize, ToSchema)]
+pub struct PrerenderReport {
+    #[schema(example = 498)]
+    pub rendered: usize,
+    #[schema(example = 2)]
+    pub cached: usize,
+    #[schema(example = 0)]
+    pub failed: usize,
+    #[schema(example = 0)]
+    pub cancelled: usize,
+    pub results: Vec<PrerenderResult>,
+}
+
+impl PrerenderReport {
+    fn new(results: Vec<PrerenderResult>) -> Self {
+        let count = |status| results.iter().filter(|result| result.status == status).count();
+        Self {
+            rendered: count(PrerenderStatus::Rendered),
+            cached: count(PrerenderStatus::Cached),
+            failed: count(PrerenderStatus::Failed),
+            cancelled: count(PrerenderStatus::Cancelled),
+            results,
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/template/{name}/prerender",
+    description = "Render and store a list of ids ahead of their first fetch, so their generated values exist and the first boot is served from the cache. Each id is rendered as a GET render with `values` and the id field set to the id would be, except that the stored render counts as never served. Ids with a current cached render are left alone. The ids are rendered one command at a time, so requests arriving meanwhile are not held up. A failed render is reported for its id and does not stop the rest; ids not yet reached when the server starts shutting down are reported as cancelled. At most 1000 ids per request.",
+    params(
+        ("name" = String, Path, description = "Template name")
+    ),
+    request_body = PrerenderRequest,
+    responses(
+        (status = 200, description = "Result for each id", body = PrerenderReport),
+        (status = 400, description = "No ids, or more than 1000", body = ApiErrorResponse),
+        (status = 404, description = "Template not found", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn prerender_template(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(request): Json<PrerenderRequest>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    if request.ids.is_empty() || request.ids.len() > MAX_PRERENDER_IDS {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(ApiErrorResponse::new(format!("ids must list between 1 and {} ids", MAX_PRERENDER_IDS))),
+        )
+            .into_response());
+    }
+
+    let cancel_token = global_cancellation_token();
+    let mut results = Vec::with_capacity(request.ids.len());
+    for id in request.ids {
+        if cancel_token.is_cancelled() {
+            results.push(PrerenderResult {
+                id,
+                status: PrerenderStatus::Cancelled,
+                error: None,
+            });
+            continue;
+        }
+        let result = send_command(&state, |tx| Command::PrerenderTemplate {
+            name: name.clone(),
+            id_value: id,
+            values: request.values.clone(),
+            response: tx,
+        })
+        .await?;
+        let Some(result) = result else {
+            return Ok((StatusCode::NOT_FOUND, Json(ApiErrorResponse::new("Template not found"))).into_response());
+        };
+        results.push(result);
+    }
+
+    Ok((StatusCode::OK, Json(PrerenderReport::new(results))).into_response())
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteTemplateQuery {
+    /// Report what would be deleted without deleting anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Response returned after a template is deleted
+#[derive(Serialize, ToSchema)]
+pub struct DeleteTemplateResponse {
+    #[schema(example = "ok")]
+    pub status: String,
+    #[schema(example = "template deleted")]
+    pub message: String,
+    /// Nothing was deleted; the response reports what a real run would do
+    pub dry_run: bool,
+    /// The template existed
+    pub deleted: bool,
+    /// Cached renders of the template, which stay in the database as orphans
+    #[schema(example = 12)]
+    pub orphaned_renders: i64,
+    /// Aliases of the template, deleted with it
+    #[schema(example = json!(["boot.j2"]))]
+    pub removed_aliases: Vec<String>,
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/template/{name}",
+    description = "Delete a template, its configuration and its aliases. Given an alias, the template it resolves to is deleted. Note: Previously rendered instances in the database are not deleted; `orphaned_renders` counts them. With `dry_run=true` nothing is deleted and the response reports what would be.",
+    params(
+        ("name" = String, Path, description = "Template name to delete; nested names such as `switches/core/uplink.j2` may use literal slashes"),
+        DeleteTemplateQuery
+    ),
+    responses(
+        (status = 200, description = "Template deleted, or checked for a dry run", body = DeleteTemplateResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn delete_template(
+    State(state): State<AppState>,
+    TemplatePath(name): TemplatePath,
+    Query(query): Query<DeleteTemplateQuery>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let deletion = send_command(&state, |tx| Command::DeleteTemplate {
+        name,
+        dry_run: query.dry_run,
+        response: tx,
+    })
+    .await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(DeleteTemplateResponse {
+            status: "ok".to_string(),
+            message: if query.dry_run { "dry run, nothing deleted" } else { "template deleted" }.to_string(),
+            dry_run: query.dry_run,
+            deleted: deletion.deleted,
+            orphaned_renders: deletion.orphaned_renders,
+            removed_aliases: deletion.removed_aliases,
+        }),
+    ))
+}
+
+/// Name to copy a template to
+#[derive(Deserialize, ToSchema)]
+pub struct CopyTemplateRequest {
+    #[schema(example = "kickstart-site-b")]
+    pub new_name: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CopyTemplateQuery {
+    /// Replace a template already stored under the new name
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/template/{name}/copy",
+    description = "Copy a template's content, values, configuration and test cases to a new name. Cached renders are not copied, so every device renders the copy afresh with newly generated values.",
+    params(
+        ("name" = String, Path, description = "Template to copy"),
+        CopyTemplateQuery
+    ),
+    request_body = CopyTemplateRequest,
+    responses(
+        (status = 200, description = "Template copied", body = TemplateSummary),
+        (status = 400, description = "Empty new name", body = ApiErrorResponse),
+        (status = 404, description = "Template not found", body = ApiErrorResponse),
+        (status = 409, description = "A template with the new name exists and `overwrite` is not set", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn copy_template(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(query): Query<CopyTemplateQuery>,
+    Json(body): Json<CopyTemplateRequest>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let result = send_command(&state, |tx| Command::CopyTemplate {
+        name,
+        new_name: body.new_name,
+        overwrite: query.overwrite,
+        response: tx,
+    })
+    .await?;
+
+    Ok(match result {
+        Some(summary) => (StatusCode::OK, Json(summary)).into_response(),
+        None => (StatusCode::NOT_FOUND, Json(ApiErrorResponse::new("Template not found"))).into_response(),
+    })
+}
+
+/// New name for a template
+#[derive(Deserialize, ToSchema)]
+pub struct RenameTemplateRequest {
+    #[schema(example = "kickstart-rhel9")]
+    pub new_name: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/template/{name}/rename",
+    description = "Rename a template. Its content, values, configuration and test cases move to the new name, along with its cached renders, render history, unique-field index, config history, device values and group memberships, all at once.",
+    params(
+        ("name" = String, Path, description = "Current template name")
+    ),
+    request_body = RenameTemplateRequest,
+    responses(
+        (status = 200, description = "Template renamed", body = TemplateRename),
+        (status = 400, description = "Empty new name", body = ApiErrorResponse),
+        (status = 404, description = "Template not found", body = ApiErrorResponse),
+        (status = 409, description = "A template with the new name exists, or renders, config history or device values of a deleted one remain under it", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn rename_template(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(body): Json<RenameTemplateRequest>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let result = send_command(&state, |tx| Command::RenameTemplate {
+        name,
+        new_name: body.new_name,
+        response: tx,
+    })
+    .await?;
+
+    Ok(match result {
+        Some(rename) => (StatusCode::OK, Json(rename)).into_response(),
+        None => (StatusCode::NOT_FOUND, Json(ApiErrorResponse::new("Template not found"))).into_response(),
+    })
+}
+
+/// Name an alias points at
+#[derive(Deserialize, ToSchema)]
+pub struct SetAliasRequest {
+    #[schema(example = "boot-prod.j2")]
+    pub target: String,
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/template/{name}/alias",
+    description = "Make `name` an alias of a template, or of another alias, so that renders, values and deletes of `name` act on the template it resolves to. Setting an existing alias points it elsewhere. Aliases are listed by `GET /api/v1/templates` with `alias_of`, and are deleted along with their template.",
+    params(
+        ("name" = String, Path, description = "Alias name")
+    ),
+    request_body = SetAliasRequest,
+    responses(
+        (status = 200, description = "Alias set", body = TemplateAlias),
+        (status = 400, description = "Empty target, or the alias would lead back to itself", body = ApiErrorResponse),
+        (status = 404, description = "The target does not resolve to a template", body = ApiErrorResponse),
+        (status = 409, description = "A template is stored under the alias name", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn set_alias(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(body): Json<SetAliasRequest>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let result = send_command(&state, |tx| Command::SetAlias {
+        alias: name,
+        target: body.target,
+        response: tx,
+    })
+    .await?;
+
+    Ok(match result {
+        Some(alias) => (StatusCode::OK, Json(alias)).into_response(),
+        None => (StatusCode::NOT_FOUND, Json(ApiErrorResponse::new("Template not found"))).into_response(),
+    })
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/template/{name}/alias",
+    description = "Remove an alias. The template it pointed at is kept.",
+    params(
+        ("name" = String, Path, description = "Alias name")
+    ),
+    responses(
+        (status = 200, description = "Alias removed", body = ApiSuccessMessage),
+        (status = 404, description = "No alias by that name", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn delete_alias(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let removed = send_command(&state, |tx| Command::RemoveAlias { alias: name, response: tx }).await?;
+
+    Ok(if removed {
+        (StatusCode::OK, Json(ApiSuccessMessage::new("alias removed"))).into_response()
+    } else {
+        (StatusCode::NOT_FOUND, Json(ApiErrorResponse::new("Alias not found"))).into_response()
+    })
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/template/{name}/tests",
+    description = "Replace the template's regression test cases. Each case renders the template with the given values and checks the output for strings that must or must not appear.",
+    params(
+        ("name" = String, Path, description = "Template name")
+    ),
+    request_body = Vec<TemplateTestCase>,
+    responses(
+        (status = 200, description = "Test cases set", body = ApiSuccessMessage),
+        (status = 400, description = "Template not found", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn set_test_cases(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(cases): Json<Vec<TemplateTestCase>>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    send_command(&state, |tx| Command::SetTestCases {
+        name,
+        cases,
+        response: tx,
+    })
+    .await?;
+
+    Ok((StatusCode::OK, Json(ApiSuccessMessage::new("test cases set"))))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/template/{name}/tests",
+    description = "Get the template's regression test cases.",
+    params(
+        ("name" = String, Path, description = "Template name")
+    ),
+    responses(
+        (status = 200, description = "Test cases", body = Vec<TemplateTestCase>),
+        (status = 404, description = "Template not found", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn get_test_cases(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let result = send_command(&state, |tx| Command::GetTestCases { name, response: tx }).await?;
+
+    match result {
+        Some(cases) => Ok((StatusCode::OK, Json(cases)).into_response()),
+        None => Ok((
+            StatusCode::NOT_FOUND,
+            Json(ApiErrorResponse::new("Template not found")),
+        )
+            .into_response()),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/template/{name}/metadata",
+    description = "Replace the template's description, owner and tags. Tags are lowercased, sorted and deduplicated. Metadata is kept when the content, values or config change.",
+    params(
+        ("name" = String, Path, description = "Template name")
+    ),
+    request_body = TemplateMetadata,
+    responses(
+        (status = 200, description = "Metadata as stored", body = TemplateMetadata),
+        (status = 400, description = "Invalid tag", body = ApiErrorResponse),
+        (status = 404, description = "Template not found", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn set_metadata(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(metadata): Json<TemplateMetadata>,
+) -> Result<impl IntoResponse, CommandError> {
+    check_template_name(&name)?;
+    let result = send_command(&state, |tx| Command::SetMetadata {
+        name,
+        metadata,
+        response: tx,
+    })
+    .await?;
+
+    Ok(match result {
+        Some(metadata) => (StatusCode::OK, Json(metadata)).into_response(),
+        None => (StatusCode::NOT_FOUND, Json(ApiErrorResponse::new("Template not found"))).into_response(),
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/template/{name}/tests/run",
+    description = "Run the template's test cases. Renders are never cached and dynamic values are generated from a fixed seed, so unhashed generated values are identical on every run.",
+    params(
+        ("name" = String, Path, description = "Template name")
+    ),
+    responses(
+        (status = 200, description = "Pass/fail report per case and assertion", body = TestRunReport),
+        (status = 400, description = "Template not found", body = ApiErrorResponse),
+        (status = 503, description = "Handler unavailable", body = ApiErrorResponse)
+    ),
+    tag = "templates"
+)]
+pub async fn run_template_tests(
     State(state): State<AppState>,
     Path(name): Path<String>,
 ) -> Result<impl IntoResponse, CommandError> {
-    send_command(&state, |tx| Command::DeleteTemplate { name, response: tx }).await?;
+    check_template_name(&name)?;
+    let report = send_command(&state, |tx| Command::RunTemplateTests { name, response: tx }).await?;
 
-    Ok((StatusCode::OK, Json(ApiSuccessMessage::new("template deleted"))))
+    Ok((StatusCode::OK, Json(report)))
 }
@@ -0,0 +1,96 @@
+use axum::{http::StatusCode, response::IntoResponse, Json};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::built_info;
+
+/// Storage implementations this build uses
+#[derive(Serialize, ToSchema)]
+pub struct StoreBackends {
+    #[schema(example = "dashmap")]
+    pub template_store: String,
+    #[schema(example = "sqlite")]
+    pub rendered_store: String,
+}
+
+/// Build metadata for the running server
+#[derive(Serialize, ToSchema)]
+pub struct VersionInfo {
+    #[schema(example = "0.1.0")]
+    pub version: String,
+    /// Git commit the binary was built from, if built from a checkout
+    #[schema(example = "3f2a9c1e5b7d8f0a1c2e3b4d5f6a7b8c9d0e1f2a")]
+    pub git_commit: Option<String>,
+    /// Whether the checkout had uncommitted changes at build time
+    pub git_dirty: Option<bool>,
+    /// Build time in RFC 2822 format
+    #[schema(example = "Mon, 01 Jan 2024 12:00:00 +0000")]
+    pub built_at: String,
+    /// Cargo features enabled at compile time
+    pub features: Vec<String>,
+    pub backends: StoreBackends,
+}
+
+impl VersionInfo {
+    pub fn current() -> Self {
+        Self {
+            version: built_info::PKG_VERSION.to_string(),
+            git_commit: built_info::GIT_COMMIT_HASH.map(str::to_string),
+            git_dirty: built_info::GIT_DIRTY,
+            built_at: built_info::BUILT_TIME_UTC.to_string(),
+            features: built_info::FEATURES_LOWERCASE.iter().map(|f| f.to_string()).collect(),
+            backends: StoreBackends {
+                template_store: "dashmap".to_string(),
+                rendered_store: "sqlite".to_string(),
+            },
+        }
+    }
+
+    /// One-line description for the startup log.
+    pub fn banner(&self) -> String {
+        format!(
+            "provisionr {} (commit {}{}, built {})",
+            self.version,
+            self.git_commit.as_deref().unwrap_or("unknown"),
+            if self.git_dirty == Some(true) { ", dirty" } else { "" },
+            self.built_at
+        )
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/version",
+    description = "Build metadata for the running server: crate version, git commit, build time, enabled cargo features and storage backends.",
+    responses(
+        (status = 200, description = "Build metadata", body = VersionInfo)
+    ),
+    tag = "system"
+)]
+pub async fn get_version() -> impl IntoResponse {
+    (StatusCode::OK, Json(VersionInfo::current()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_matches_cargo_package() {
+        let info = VersionInfo::current();
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(!info.built_at.is_empty());
+    }
+
+    #[test]
+    fn banner_includes_version_and_commit() {
+        let info = VersionInfo {
+            git_commit: Some("abc123".to_string()),
+            git_dirty: Some(true),
+            ..VersionInfo::current()
+        };
+        let banner = info.banner();
+        assert!(banner.contains(env!("CARGO_PKG_VERSION")));
+        assert!(banner.contains("commit abc123, dirty"));
+    }
+}
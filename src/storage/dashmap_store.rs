@@ -1,25 +1,61 @@
 use dashmap::DashMap;
+use std::time::SystemTime;
 
-use crate::storage::models::{TemplateConfig, TemplateData};
+use crate::storage::models::{
+    content_checksum, TemplateConfig, TemplateData, TemplateMetadata, TemplateSummary, TemplateTestCase,
+    ValuesFormat,
+};
 
 #[cfg_attr(test, mockall::automock)]
 pub trait TemplateStore: Send {
     fn init_template(&mut self, name: &str, data: TemplateData);
-    fn set_template_content(&mut self, name: &str, content: String);
-    fn set_values(&mut self, name: &str, yaml_str: String) -> Result<(), String>;
+    /// Stores the template content and returns its checksum.
+    fn set_template_content(&mut self, name: &str, content: String) -> String;
+    /// Stores canonical YAML values, remembering the format they were uploaded in.
+    fn set_values(&mut self, name: &str, yaml_str: String, format: ValuesFormat) -> Result<(), String>;
+    /// Removes the stored values, as if none had ever been set.
+    fn clear_values(&mut self, name: &str) -> Result<(), String>;
     fn set_config(&mut self, name: &str, config: TemplateConfig) -> Result<(), String>;
+    fn set_test_cases(&mut self, name: &str, cases: Vec<TemplateTestCase>) -> Result<(), String>;
+    fn set_metadata(&mut self, name: &str, metadata: TemplateMetadata) -> Result<(), String>;
     fn get_config(&self, name: &str) -> Option<TemplateConfig>;
     fn get(&self, name: &str) -> Option<TemplateData>;
+    fn exists(&self, name: &str) -> bool;
+    /// Names of all templates, sorted.
+    fn names(&self) -> Vec<String>;
+    /// Summaries of all templates, sorted by name.
+    /// Summaries of all templates and aliases, sorted by name.
+    fn list(&self) -> Vec<TemplateSummary>;
+    /// Deletes a template together with every alias resolving to it.
     fn delete(&mut self, name: &str);
+    /// Points `alias` at `target`, a template or another alias, replacing any
+    /// previous target.
+    fn set_alias(&mut self, alias: &str, target: &str);
+    /// Removes an alias, returning whether it existed.
+    fn remove_alias(&mut self, alias: &str) -> bool;
+    /// What `alias` points at directly, or `None` if it is not an alias.
+    fn alias_target(&self, alias: &str) -> Option<String>;
+    /// The name `name` ends at once aliases are followed; `name` itself if it is
+    /// not an alias.
+    fn resolve(&self, name: &str) -> String;
+    /// Aliases resolving to `name`, directly or through other aliases, sorted.
+    fn aliases_of(&self, name: &str) -> Vec<String>;
+    /// Points the aliases of `name` at `new_name` instead.
+    fn retarget_aliases(&mut self, name: &str, new_name: &str);
 }
 
 pub struct DashMapTemplateStore {
     map: DashMap<String, TemplateData>,
+    /// Alias names and what each points at
+    aliases: DashMap<String, String>,
 }
 
 impl DashMapTemplateStore {
     pub fn new() -> Self {
-        Self { map: DashMap::new() }
+        Self {
+            map: DashMap::new(),
+            aliases: DashMap::new(),
+        }
     }
 }
 
@@ -34,17 +70,31 @@ impl TemplateStore for DashMapTemplateStore {
         self.map.insert(name.to_string(), data);
     }
 
-    fn set_template_content(&mut self, name: &str, content: String) {
-        self.map
-            .entry(name.to_string())
-            .or_default()
-            .template_content = content;
+    fn set_template_content(&mut self, name: &str, content: String) -> String {
+        let checksum = content_checksum(&content);
+        let mut entry = self.map.entry(name.to_string()).or_default();
+        entry.template_content = content;
+        entry.checksum = checksum.clone();
+        entry.content_updated_at = Some(SystemTime::now());
+        checksum
     }
 
-    fn set_values(&mut self, name: &str, yaml_str: String) -> Result<(), String> {
+    fn set_values(&mut self, name: &str, yaml_str: String, format: ValuesFormat) -> Result<(), String> {
         match self.map.get_mut(name) {
             Some(mut entry) => {
                 entry.values_yaml = Some(yaml_str);
+                entry.values_format = format;
+                Ok(())
+            }
+            None => Err(format!("Template '{}' not found", name)),
+        }
+    }
+
+    fn clear_values(&mut self, name: &str) -> Result<(), String> {
+        match self.map.get_mut(name) {
+            Some(mut entry) => {
+                entry.values_yaml = None;
+                entry.values_format = ValuesFormat::default();
                 Ok(())
             }
             None => Err(format!("Template '{}' not found", name)),
@@ -56,6 +106,43 @@ impl TemplateStore for DashMapTemplateStore {
             Some(mut entry) => {
                 entry.id_field = config.id_field;
                 entry.dynamic_fields = config.dynamic_fields;
+                entry.min_interval_seconds = config.min_interval_seconds;
+                entry.min_update_interval_seconds = config.min_update_interval_seconds;
+                entry.param_defaults = config.param_defaults;
+                entry.failure_webhook = config.failure_webhook;
+                entry.input_transforms = config.input_transforms;
+                entry.max_output_bytes = config.max_output_bytes;
+                entry.on_values_error = config.on_values_error;
+                entry.cache_generation = config.cache_generation;
+                entry.carry_forward_generated = config.carry_forward_generated;
+                entry.cache_policy = config.cache_policy;
+                entry.cache_ttl_seconds = config.cache_ttl_seconds;
+                entry.max_rendered = config.max_rendered;
+                entry.content_type = config.content_type;
+                entry.required_fields = config.required_fields;
+                entry.optional_fields = config.optional_fields;
+                entry.strict_params = config.strict_params;
+                entry.input_values_exclude = config.input_values_exclude;
+                Ok(())
+            }
+            None => Err(format!("Template '{}' not found", name)),
+        }
+    }
+
+    fn set_test_cases(&mut self, name: &str, cases: Vec<TemplateTestCase>) -> Result<(), String> {
+        match self.map.get_mut(name) {
+            Some(mut entry) => {
+                entry.test_cases = cases;
+                Ok(())
+            }
+            None => Err(format!("Template '{}' not found", name)),
+        }
+    }
+
+    fn set_metadata(&mut self, name: &str, metadata: TemplateMetadata) -> Result<(), String> {
+        match self.map.get_mut(name) {
+            Some(mut entry) => {
+                entry.metadata = metadata;
                 Ok(())
             }
             None => Err(format!("Template '{}' not found", name)),
@@ -63,25 +150,89 @@ impl TemplateStore for DashMapTemplateStore {
     }
 
     fn get_config(&self, name: &str) -> Option<TemplateConfig> {
-        self.map.get(name).map(|data| TemplateConfig {
-            id_field: data.id_field.clone(),
-            dynamic_fields: data.dynamic_fields.clone(),
-        })
+        self.map.get(name).map(|data| TemplateConfig::from(&*data))
     }
 
     fn get(&self, name: &str) -> Option<TemplateData> {
         self.map.get(name).map(|r| r.clone())
     }
 
+    fn exists(&self, name: &str) -> bool {
+        self.map.contains_key(name)
+    }
+
+    fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.map.iter().map(|entry| entry.key().clone()).collect();
+        names.sort();
+        names
+    }
+
+    fn list(&self) -> Vec<TemplateSummary> {
+        let mut summaries: Vec<TemplateSummary> = self
+            .map
+            .iter()
+            .map(|entry| TemplateSummary::from((entry.key().as_str(), entry.value())))
+            .collect();
+        for alias in self.aliases.iter() {
+            if let Some(data) = self.map.get(&self.resolve(alias.key())) {
+                summaries.push(TemplateSummary {
+                    alias_of: Some(alias.value().clone()),
+                    ..TemplateSummary::from((alias.key().as_str(), &*data))
+                });
+            }
+        }
+        summaries.sort_by(|a, b| a.name.cmp(&b.name));
+        summaries
+    }
+
     fn delete(&mut self, name: &str) {
+        for alias in self.aliases_of(name) {
+            self.aliases.remove(&alias);
+        }
         self.map.remove(name);
     }
-}
 
-#[cfg(test)]
-impl DashMapTemplateStore {
-    pub fn exists(&self, name: &str) -> bool {
-        self.map.contains_key(name)
+    fn set_alias(&mut self, alias: &str, target: &str) {
+        self.aliases.insert(alias.to_string(), target.to_string());
+    }
+
+    fn remove_alias(&mut self, alias: &str) -> bool {
+        self.aliases.remove(alias).is_some()
+    }
+
+    fn alias_target(&self, alias: &str) -> Option<String> {
+        self.aliases.get(alias).map(|target| target.clone())
+    }
+
+    fn resolve(&self, name: &str) -> String {
+        // Cycles are refused when aliases are set; the bound only guards the loop
+        let mut name = name.to_string();
+        for _ in 0..=self.aliases.len() {
+            match self.alias_target(&name) {
+                Some(target) => name = target,
+                None => break,
+            }
+        }
+        name
+    }
+
+    fn aliases_of(&self, name: &str) -> Vec<String> {
+        let mut aliases: Vec<String> = self
+            .aliases
+            .iter()
+            .map(|alias| alias.key().clone())
+            .filter(|alias| self.resolve(alias) == name)
+            .collect();
+        aliases.sort();
+        aliases
+    }
+
+    fn retarget_aliases(&mut self, name: &str, new_name: &str) {
+        for mut alias in self.aliases.iter_mut() {
+            if alias.value() == name {
+                *alias.value_mut() = new_name.to_string();
+            }
+        }
     }
 }
 
@@ -102,22 +253,65 @@ mod tests {
         assert_eq!(data.template_content, "Hello {{ name }}");
     }
 
+    #[test]
+    fn set_template_content_computes_checksum() {
+        let mut store = DashMapTemplateStore::new();
+
+        let checksum = store.set_template_content("test", "hello".to_string());
+        assert_eq!(
+            checksum,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(store.get("test").unwrap().checksum, checksum);
+
+        let updated = store.set_template_content("test", "hello world".to_string());
+        assert_ne!(updated, checksum);
+        assert_eq!(store.get("test").unwrap().checksum, updated);
+    }
+
     #[test]
     fn set_values_is_immediately_readable() {
         let mut store = DashMapTemplateStore::new();
 
         store.set_template_content("test", "content".to_string());
-        store.set_values("test", "key: value".to_string()).unwrap();
+        store.set_values("test", "key: value".to_string(), ValuesFormat::Yaml).unwrap();
 
         let data = store.get("test").unwrap();
         assert_eq!(data.values_yaml, Some("key: value".to_string()));
+        assert_eq!(data.values_format, ValuesFormat::Yaml);
+    }
+
+    #[test]
+    fn set_values_records_upload_format() {
+        let mut store = DashMapTemplateStore::new();
+
+        store.set_template_content("test", "content".to_string());
+        store.set_values("test", "key: value".to_string(), ValuesFormat::Toml).unwrap();
+        assert_eq!(store.get("test").unwrap().values_format, ValuesFormat::Toml);
+
+        store.set_values("test", "key: other".to_string(), ValuesFormat::Yaml).unwrap();
+        assert_eq!(store.get("test").unwrap().values_format, ValuesFormat::Yaml);
+    }
+
+    #[test]
+    fn clear_values_forgets_values_and_their_format() {
+        let mut store = DashMapTemplateStore::new();
+
+        store.set_template_content("test", "content".to_string());
+        store.set_values("test", "key: value".to_string(), ValuesFormat::Toml).unwrap();
+        store.clear_values("test").unwrap();
+
+        let data = store.get("test").unwrap();
+        assert_eq!(data.values_yaml, None);
+        assert_eq!(data.values_format, ValuesFormat::Yaml);
+        assert!(store.clear_values("nonexistent").unwrap_err().contains("not found"));
     }
 
     #[test]
     fn set_values_fails_if_template_not_found() {
         let mut store = DashMapTemplateStore::new();
 
-        let result = store.set_values("nonexistent", "key: value".to_string());
+        let result = store.set_values("nonexistent", "key: value".to_string(), ValuesFormat::Yaml);
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("not found"));
     }
@@ -136,7 +330,9 @@ mod tests {
                         field_name: "password".to_string(),
                         generator_type: GeneratorType::Alphanumeric { length: 16 },
                         hashing_algorithm: HashingAlgorithm::Sha512,
+                        unique: false,
                     }],
+                    ..Default::default()
                 },
             )
             .unwrap();
@@ -157,12 +353,59 @@ mod tests {
             TemplateConfig {
                 id_field: "serial".to_string(),
                 dynamic_fields: vec![],
+                ..Default::default()
             },
         );
         assert!(result.is_err());
         assert!(result.unwrap_err().contains("not found"));
     }
 
+    #[test]
+    fn set_test_cases_survives_content_update() {
+        let mut store = DashMapTemplateStore::new();
+
+        store.set_template_content("test", "Hello".to_string());
+        store
+            .set_test_cases(
+                "test",
+                vec![TemplateTestCase {
+                    must_contain: vec!["Hello".to_string()],
+                    ..TemplateTestCase::default()
+                }],
+            )
+            .unwrap();
+        store.set_template_content("test", "Hello again".to_string());
+
+        let data = store.get("test").unwrap();
+        assert_eq!(data.test_cases.len(), 1);
+        assert_eq!(data.test_cases[0].must_contain, vec!["Hello".to_string()]);
+    }
+
+    #[test]
+    fn set_metadata_survives_content_update() {
+        let mut store = DashMapTemplateStore::new();
+
+        store.set_template_content("test", "Hello".to_string());
+        let metadata = TemplateMetadata {
+            owner: Some("network".to_string()),
+            tags: vec!["switch".to_string()],
+            ..TemplateMetadata::default()
+        };
+        store.set_metadata("test", metadata.clone()).unwrap();
+        store.set_template_content("test", "Hello again".to_string());
+
+        assert_eq!(store.get("test").unwrap().metadata, metadata);
+        assert!(store.set_metadata("nonexistent", metadata).unwrap_err().contains("not found"));
+    }
+
+    #[test]
+    fn set_test_cases_fails_if_template_not_found() {
+        let mut store = DashMapTemplateStore::new();
+
+        let result = store.set_test_cases("nonexistent", vec![]);
+        assert!(result.unwrap_err().contains("not found"));
+    }
+
     #[test]
     fn get_config_returns_template_config() {
         let mut store = DashMapTemplateStore::new();
@@ -177,7 +420,9 @@ mod tests {
                         field_name: "pass".to_string(),
                         generator_type: GeneratorType::Passphrase { word_count: 4 },
                         hashing_algorithm: HashingAlgorithm::Yescrypt,
+                        unique: false,
                     }],
+                    ..Default::default()
                 },
             )
             .unwrap();
@@ -195,6 +440,64 @@ mod tests {
         assert!(store.get_config("nonexistent").is_none());
     }
 
+    #[test]
+    fn names_are_sorted() {
+        let mut store = DashMapTemplateStore::new();
+        for name in ["pxe", "kickstart", "autoinstall"] {
+            store.set_template_content(name, String::new());
+        }
+
+        assert_eq!(store.names(), vec!["autoinstall", "kickstart", "pxe"]);
+    }
+
+    #[test]
+    fn list_summarises_each_template_by_name() {
+        let mut store = DashMapTemplateStore::new();
+        assert!(store.list().is_empty());
+
+        store.set_template_content("pxe", String::new());
+        store.set_template_content("kickstart", String::new());
+        store.set_values("kickstart", "hostname: web01\n".to_string(), ValuesFormat::Yaml).unwrap();
+        store
+            .set_config(
+                "kickstart",
+                TemplateConfig {
+                    id_field: "serial".to_string(),
+                    dynamic_fields: vec![DynamicFieldConfig {
+                        field_name: "root_password".to_string(),
+                        generator_type: GeneratorType::Alphanumeric { length: 16 },
+                        hashing_algorithm: HashingAlgorithm::Sha512,
+                        unique: false,
+                    }],
+                    ..TemplateConfig::default()
+                },
+            )
+            .unwrap();
+
+        let summaries = store.list();
+        assert_eq!(
+            summaries,
+            vec![
+                TemplateSummary {
+                    name: "kickstart".to_string(),
+                    has_values: true,
+                    id_field: "serial".to_string(),
+                    dynamic_field_count: 1,
+                    metadata: TemplateMetadata::default(),
+                    alias_of: None,
+                },
+                TemplateSummary {
+                    name: "pxe".to_string(),
+                    has_values: false,
+                    id_field: "mac_address".to_string(),
+                    dynamic_field_count: 0,
+                    metadata: TemplateMetadata::default(),
+                    alias_of: None,
+                },
+            ]
+        );
+    }
+
     #[test]
     fn delete_is_immediately_effective() {
         let mut store = DashMapTemplateStore::new();
@@ -211,13 +514,14 @@ mod tests {
         let mut store = DashMapTemplateStore::new();
 
         store.set_template_content("test", "Hello".to_string());
-        store.set_values("test", "name: World".to_string()).unwrap();
+        store.set_values("test", "name: World".to_string(), ValuesFormat::Yaml).unwrap();
         store
             .set_config(
                 "test",
                 TemplateConfig {
                     id_field: "mac".to_string(),
                     dynamic_fields: vec![],
+                    ..Default::default()
                 },
             )
             .unwrap();
@@ -242,4 +546,38 @@ mod tests {
 
         assert!(!store.exists("nonexistent"));
     }
+
+    #[test]
+    fn aliases_are_listed_with_their_target_and_deleted_with_it() {
+        let mut store = DashMapTemplateStore::new();
+        store.set_template_content("boot-prod", "content".to_string());
+        store.set_template_content("other", "content".to_string());
+        store.set_alias("boot", "boot-prod");
+        store.set_alias("pxe", "boot");
+        store.set_alias("unrelated", "other");
+
+        assert_eq!(store.resolve("pxe"), "boot-prod");
+        assert_eq!(store.resolve("boot-prod"), "boot-prod");
+        assert_eq!(store.aliases_of("boot-prod"), vec!["boot", "pxe"]);
+        assert_eq!(store.names(), vec!["boot-prod", "other"]);
+        let listed: Vec<(String, Option<String>)> = store.list().into_iter().map(|s| (s.name, s.alias_of)).collect();
+        assert_eq!(
+            listed,
+            vec![
+                ("boot".to_string(), Some("boot-prod".to_string())),
+                ("boot-prod".to_string(), None),
+                ("other".to_string(), None),
+                ("pxe".to_string(), Some("boot".to_string())),
+                ("unrelated".to_string(), Some("other".to_string())),
+            ]
+        );
+
+        store.delete("boot-prod");
+        assert_eq!(store.alias_target("boot"), None);
+        assert_eq!(store.alias_target("pxe"), None);
+        assert_eq!(store.alias_target("unrelated"), Some("other".to_string()));
+        assert!(store.remove_alias("unrelated"));
+        assert!(!store.remove_alias("unrelated"));
+    }
+
 }
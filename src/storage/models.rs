@@ -1,6 +1,15 @@
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::time::SystemTime;
 use utoipa::ToSchema;
 
+use crate::commands::transforms;
+
+/// Longest validity, in days, of a generated self-signed certificate
+pub const MAX_CERT_DAYS: u32 = 3650;
+
 /// Generator type with tagged serialisation
 /// Serialises to: {"type": "alphanumeric", "length": 16}
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, ToSchema)]
@@ -14,6 +23,56 @@ pub enum GeneratorType {
         #[schema(example = 4)]
         word_count: usize,
     },
+    /// Random (version 4) UUID, lowercase and hyphenated
+    Uuid,
+    /// `bytes` random bytes as lowercase hex (`bytes * 2` characters)
+    Hex {
+        #[schema(example = 32)]
+        bytes: usize,
+    },
+    /// Ed25519 SSH keypair, stored as `<field>_private` (OpenSSH PEM) and
+    /// `<field>_public` (authorized_keys line)
+    #[serde(rename = "ssh_keypair_ed25519")]
+    SshKeypairEd25519,
+    /// WireGuard (X25519) keypair, stored as base64 `<field>_private` and
+    /// `<field>_public` keys
+    #[serde(rename = "wireguard_keypair")]
+    WireguardKeypair,
+    /// Self-signed certificate and its ECDSA P-256 key, stored as PEM in
+    /// `<field>_cert_pem` and `<field>_key_pem`. The common name is read from the
+    /// render's `common_name_field` value.
+    #[serde(rename = "self_signed_cert")]
+    SelfSignedCert {
+        #[schema(example = "hostname")]
+        common_name_field: String,
+        #[schema(example = 825)]
+        days: u32,
+    },
+}
+
+impl GeneratorType {
+    /// Generator name without parameters, used as a metrics label.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Alphanumeric { .. } => "alphanumeric",
+            Self::Passphrase { .. } => "passphrase",
+            Self::Uuid => "uuid",
+            Self::Hex { .. } => "hex",
+            Self::SshKeypairEd25519 => "ssh_keypair_ed25519",
+            Self::WireguardKeypair => "wireguard_keypair",
+            Self::SelfSignedCert { .. } => "self_signed_cert",
+        }
+    }
+
+    /// Suffixes appended to the field name to name the generated values. Most
+    /// generators produce one value named after the field itself.
+    pub fn value_suffixes(&self) -> &'static [&'static str] {
+        match self {
+            Self::SshKeypairEd25519 | Self::WireguardKeypair => &["_private", "_public"],
+            Self::SelfSignedCert { .. } => &["_cert_pem", "_key_pem"],
+            _ => &[""],
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, ToSchema)]
@@ -27,6 +86,26 @@ pub struct DynamicFieldConfig {
     #[serde(default)]
     #[schema(example = "sha512")]
     pub hashing_algorithm: HashingAlgorithm,
+    /// Regenerate the value if another device of the same template already has it.
+    /// Cannot be combined with hashing, as salted hashes never collide.
+    #[serde(default)]
+    pub unique: bool,
+}
+
+impl DynamicFieldConfig {
+    /// Names of the values this field generates
+    pub fn value_names(&self) -> Vec<String> {
+        self.generator_type
+            .value_suffixes()
+            .iter()
+            .map(|suffix| format!("{}{}", self.field_name, suffix))
+            .collect()
+    }
+
+    /// Whether `name` is one of the values this field generates
+    pub fn generates(&self, name: &str) -> bool {
+        self.value_names().iter().any(|value_name| value_name == name)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, ToSchema, Default)]
@@ -38,10 +117,68 @@ pub enum HashingAlgorithm {
     Yescrypt,
 }
 
+/// Format a template's values were uploaded in. Values are always stored as YAML;
+/// this only records what the client sent.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSchema, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ValuesFormat {
+    #[default]
+    Yaml,
+    Toml,
+}
+
+/// What a render does when the template's stored values cannot be parsed
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ValuesErrorPolicy {
+    /// Fail the render
+    #[default]
+    Fail,
+    /// Render without the stored values and report a warning
+    IgnoreWithWarning,
+}
+
+/// Whether a cached render survives a change of its template's content
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSchema, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CachePolicy {
+    /// Keep serving the cached render until it is deleted or the generation is bumped
+    #[default]
+    Pin,
+    /// Re-render when the content changed, keeping the render's generated values
+    FollowTemplate,
+}
+
+impl ValuesFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Yaml => "yaml",
+            Self::Toml => "toml",
+        }
+    }
+}
+
 fn default_id_field() -> String {
     "mac_address".to_string()
 }
 
+fn default_dedup_window_seconds() -> u64 {
+    300
+}
+
+/// Webhook notified when rendering a template fails.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub struct FailureWebhook {
+    /// URL that receives a JSON POST for each render failure
+    #[schema(example = "https://alerts.example.com/provisionr")]
+    pub url: String,
+    /// Repeats of the same template, id value and error code within this many seconds
+    /// are not sent again, so boot-looping devices don't flood the receiver.
+    #[serde(default = "default_dedup_window_seconds")]
+    #[schema(example = 300)]
+    pub dedup_window_seconds: u64,
+}
+
 /// Configuration for template rendering behaviour including caching and dynamic value generation.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema, Default)]
 pub struct TemplateConfig {
@@ -57,27 +194,478 @@ pub struct TemplateConfig {
     /// hashing algorithm.
     #[serde(default)]
     pub dynamic_fields: Vec<DynamicFieldConfig>,
+    /// Minimum number of seconds between two serves of the same id. Re-fetching a cached
+    /// render sooner returns 429, which guards against boot loops and scraping.
+    #[serde(default)]
+    #[schema(example = 30)]
+    pub min_interval_seconds: Option<u64>,
+    /// Minimum number of seconds between two content uploads that change the template.
+    /// Faster changes return 429; re-uploading identical content is always accepted.
+    #[serde(default)]
+    #[schema(example = 60)]
+    pub min_update_interval_seconds: Option<u64>,
+    /// Default values for query parameters. They override the values YAML and are
+    /// overridden by query parameters, so operators can change them per device without
+    /// editing stored values.
+    #[serde(default)]
+    #[schema(example = json!({"vlan": "100"}))]
+    pub param_defaults: HashMap<String, String>,
+    /// Optional webhook called when a render of this template fails.
+    #[serde(default)]
+    pub failure_webhook: Option<FailureWebhook>,
+    /// Transforms applied, in order, to named query parameters before they are merged
+    /// into the context or used as the cache key: `trim`, `lowercase`, `uppercase`,
+    /// `strip_prefix:<prefix>` and `zero_pad:<width>`.
+    #[serde(default)]
+    #[schema(example = json!({"serial_number": ["trim", "uppercase", "strip_prefix:SN"]}))]
+    pub input_transforms: HashMap<String, Vec<String>>,
+    /// Largest rendered output in bytes; renders that would exceed it fail with 422.
+    /// Defaults to the server-wide `max_output_bytes`.
+    #[serde(default)]
+    #[schema(example = 1048576)]
+    pub max_output_bytes: Option<usize>,
+    /// What renders do when the stored values no longer parse: `fail` (the default),
+    /// or `ignore_with_warning` to render without them and return a warning.
+    #[serde(default)]
+    pub on_values_error: ValuesErrorPolicy,
+    /// Cached renders made under another generation are treated as misses, so bumping
+    /// it re-renders every device without changing the content. Replaced renders are
+    /// kept in the render history.
+    #[serde(default)]
+    pub cache_generation: u64,
+    /// Reuse the generated values of a render from an older cache generation when
+    /// re-rendering, so credentials survive a generation bump.
+    #[serde(default)]
+    pub carry_forward_generated: bool,
+    /// `pin` (the default) serves a cached render until it is deleted or the cache
+    /// generation is bumped. `follow_template` treats a render made from other
+    /// content as a miss and re-renders it with its generated values.
+    #[serde(default)]
+    pub cache_policy: CachePolicy,
+    /// Seconds a cached render is served for. An older render is a miss and is
+    /// rendered again with freshly generated values; the `expire_renders`
+    /// maintenance job, which always runs, deletes such renders. Unset keeps renders
+    /// indefinitely.
+    #[serde(default)]
+    #[schema(example = 86400)]
+    pub cache_ttl_seconds: Option<u64>,
+    /// Most cached renders kept for the template; storing a new render deletes the
+    /// oldest beyond it. Unset uses the server's `PROVISIONR_MAX_RENDERED`, if any.
+    #[serde(default)]
+    #[schema(example = 10000)]
+    pub max_rendered: Option<u64>,
+    /// Media type raw renders are served as, e.g. `application/json` for cloud-init
+    /// user data. Defaults to `text/plain; charset=utf-8`.
+    #[serde(default)]
+    #[schema(example = "application/x-yaml")]
+    pub content_type: Option<String>,
+    /// Variables that must have a non-blank value once stored values, query parameters
+    /// and generated values are merged. Renders missing any of them fail with 400
+    /// listing all of them, instead of rendering them as empty.
+    #[serde(default)]
+    #[schema(example = json!(["hostname", "ip_address"]))]
+    pub required_fields: Vec<String>,
+    /// Variables that may be passed as query parameters but need not be; only
+    /// consulted with `strict_params`.
+    #[serde(default)]
+    #[schema(example = json!(["vlan"]))]
+    pub optional_fields: Vec<String>,
+    /// Reject renders with query parameters other than the id field, required and
+    /// optional fields, `param_defaults` keys and keys of the stored values, so a typo
+    /// fails with 400 instead of rendering wrong output.
+    #[serde(default)]
+    pub strict_params: bool,
+    /// Keys left out of the `input_values` recorded with each render, such as
+    /// secrets passed as query parameters.
+    #[serde(default)]
+    #[schema(example = json!(["enrollment_token"]))]
+    pub input_values_exclude: Vec<String>,
+}
+
+fn is_template_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Last segments of a nested name that a `/api/v1/template/{name}/...` or
+/// `/api/v1/config/{name}/...` route would take for an endpoint of the name before it.
+const ENDPOINT_SEGMENTS: &[&str] = &[
+    "alias",
+    "bump-generation",
+    "bundle",
+    "cache-status",
+    "checksum",
+    "copy",
+    "dynamic-fields",
+    "exists",
+    "history",
+    "id-field",
+    "metadata",
+    "params-schema",
+    "prerender",
+    "preview",
+    "rename",
+    "render",
+    "source",
+    "tests",
+    "values",
+    "variables",
+];
+
+/// Rejects template names that are unsafe to use as a file path or log field: empty
+/// names, backslashes, control characters (NUL included), and empty segments or
+/// segments starting with a dot (`.` and `..` included). `/` separates the segments
+/// of nested names such as `switches/core/uplink.j2`, so each name has one spelling.
+/// Nested names may not end in an endpoint such as `/values` or `/tests/run`, whose
+/// route would shadow them.
+pub fn validate_template_name(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("name is empty".to_string());
+    }
+    if name.contains('\\') {
+        return Err(format!("{:?} contains a backslash", name));
+    }
+    if name.contains(char::is_control) {
+        return Err(format!("{:?} contains a control character", name));
+    }
+    if name.split('/').any(str::is_empty) {
+        return Err(format!("{:?} has an empty path segment", name));
+    }
+    if name.split('/').any(|segment| segment.starts_with('.')) {
+        return Err(format!("{:?} has a path segment starting with a dot", name));
+    }
+    let segments: Vec<&str> = name.split('/').collect();
+    if let [.., parent, last] = segments[..]
+        && (ENDPOINT_SEGMENTS.contains(&last)
+            || matches!((parent, last), ("tests", "run") | ("devices", "import") | ("rollback", _)))
+    {
+        return Err(format!("{:?} ends in an endpoint path, which would shadow the name", name));
+    }
+    Ok(())
+}
+
+impl TemplateConfig {
+    /// Rejects dynamic fields that would clash in the render context (duplicate names,
+    /// a name equal to the id field, or a name templates cannot reference), hashed or
+    /// unique keypairs, empty hex values, certificates with out-of-range validity or
+    /// a generated common name, non-HTTP webhook URLs, unknown input
+    /// transforms, malformed content types and required or optional fields templates
+    /// cannot reference.
+    pub fn validate(&self) -> Result<(), String> {
+        let mut seen = std::collections::HashSet::new();
+        for field in &self.dynamic_fields {
+            let name = field.field_name.as_str();
+            if !is_template_identifier(name) {
+                return Err(format!(
+                    "Dynamic field name '{}' is not a valid template identifier (use letters, digits and underscores, not starting with a digit)",
+                    name
+                ));
+            }
+            if field.generates(&self.id_field) {
+                return Err(format!(
+                    "Dynamic field '{}' has the same name as the id field and would overwrite it",
+                    name
+                ));
+            }
+            for value_name in field.value_names() {
+                if !seen.insert(value_name.clone()) {
+                    return Err(format!("Duplicate dynamic field name '{}'", value_name));
+                }
+            }
+            if field.unique && field.hashing_algorithm != HashingAlgorithm::None {
+                return Err(format!(
+                    "Dynamic field '{}' cannot be unique and hashed; uniqueness is checked on the stored value",
+                    name
+                ));
+            }
+            if field.generator_type.value_suffixes().len() > 1
+                && (field.unique || field.hashing_algorithm != HashingAlgorithm::None)
+            {
+                return Err(format!(
+                    "Dynamic field '{}' generates a keypair and cannot be unique or hashed",
+                    name
+                ));
+            }
+            if field.generator_type == (GeneratorType::Hex { bytes: 0 }) {
+                return Err(format!("Dynamic field '{}' must generate at least 1 hex byte", name));
+            }
+            if let GeneratorType::SelfSignedCert { common_name_field, days } = &field.generator_type {
+                if !(1..=MAX_CERT_DAYS).contains(days) {
+                    return Err(format!(
+                        "Dynamic field '{}' must be valid for 1 to {} days, not {}",
+                        name, MAX_CERT_DAYS, days
+                    ));
+                }
+                if !is_template_identifier(common_name_field) {
+                    return Err(format!(
+                        "Common name field '{}' of dynamic field '{}' is not a valid template identifier",
+                        common_name_field, name
+                    ));
+                }
+                if self.dynamic_fields.iter().any(|other| other.generates(common_name_field)) {
+                    return Err(format!(
+                        "Dynamic field '{}' cannot take its common name from generated value '{}'",
+                        name, common_name_field
+                    ));
+                }
+            }
+        }
+        if let Some(webhook) = &self.failure_webhook
+            && !(webhook.url.starts_with("http://") || webhook.url.starts_with("https://"))
+        {
+            return Err(format!("Failure webhook URL '{}' must use http or https", webhook.url));
+        }
+        transforms::parse_transforms(&self.input_transforms)?;
+        if self.max_output_bytes == Some(0) {
+            return Err("max_output_bytes must be greater than 0".to_string());
+        }
+        if self.cache_ttl_seconds == Some(0) {
+            return Err("cache_ttl_seconds must be greater than 0".to_string());
+        }
+        if self.max_rendered == Some(0) {
+            return Err("max_rendered must be greater than 0".to_string());
+        }
+        if let Some(content_type) = &self.content_type
+            && !content_type
+                .parse::<mime_guess::Mime>()
+                .is_ok_and(|mime| !mime.subtype().as_str().is_empty())
+        {
+            return Err(format!("content_type '{}' is not a valid media type", content_type));
+        }
+        if let Some(field) = self.required_fields.iter().find(|field| !is_template_identifier(field)) {
+            return Err(format!("Required field '{}' is not a valid template identifier", field));
+        }
+        if let Some(field) = self.optional_fields.iter().find(|field| !is_template_identifier(field)) {
+            return Err(format!("Optional field '{}' is not a valid template identifier", field));
+        }
+        Ok(())
+    }
+}
+
+/// Regression test case for a template: render with `values` and check the output.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema, Default)]
+pub struct TemplateTestCase {
+    /// Values supplied to the render, as if passed as query parameters
+    #[serde(default)]
+    pub values: HashMap<String, String>,
+    /// Strings that must appear in the rendered output
+    #[serde(default)]
+    #[schema(example = json!(["hostname sw01"]))]
+    pub must_contain: Vec<String>,
+    /// Strings that must not appear in the rendered output
+    #[serde(default)]
+    pub must_not_contain: Vec<String>,
+}
+
+/// Descriptive information about a template. It plays no part in rendering.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema, Default)]
+pub struct TemplateMetadata {
+    #[serde(default)]
+    #[schema(example = "Ubuntu autoinstall for lab servers")]
+    pub description: Option<String>,
+    #[serde(default)]
+    #[schema(example = "platform-team")]
+    pub owner: Option<String>,
+    /// Lowercase, sorted and without duplicates
+    #[serde(default)]
+    #[schema(example = json!(["lab", "ubuntu"]))]
+    pub tags: Vec<String>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, ToSchema)]
+impl TemplateMetadata {
+    /// Trims the description and owner, dropping them if blank, and lowercases, sorts
+    /// and deduplicates the tags. Tags must be non-empty and free of whitespace and
+    /// commas.
+    pub fn normalized(self) -> Result<Self, String> {
+        let text = |value: Option<String>| value.map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        let mut tags = Vec::with_capacity(self.tags.len());
+        for tag in &self.tags {
+            let tag = tag.trim().to_lowercase();
+            if tag.is_empty() {
+                return Err("Tags cannot be empty".to_string());
+            }
+            if tag.contains(|c: char| c.is_whitespace() || c == ',') {
+                return Err(format!("Tag '{}' cannot contain whitespace or commas", tag));
+            }
+            tags.push(tag);
+        }
+        tags.sort();
+        tags.dedup();
+        Ok(Self {
+            description: text(self.description),
+            owner: text(self.owner),
+            tags,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, ToSchema)]
 pub struct TemplateData {
     pub template_content: String,
+    /// Hex-encoded SHA-256 of `template_content`, computed whenever the content is set.
+    pub checksum: String,
     pub id_field: String,
     pub values_yaml: Option<String>,
+    pub values_format: ValuesFormat,
     pub dynamic_fields: Vec<DynamicFieldConfig>,
+    pub min_interval_seconds: Option<u64>,
+    pub min_update_interval_seconds: Option<u64>,
+    pub param_defaults: HashMap<String, String>,
+    pub failure_webhook: Option<FailureWebhook>,
+    pub input_transforms: HashMap<String, Vec<String>>,
+    pub max_output_bytes: Option<usize>,
+    pub on_values_error: ValuesErrorPolicy,
+    pub cache_generation: u64,
+    pub carry_forward_generated: bool,
+    pub cache_policy: CachePolicy,
+    pub cache_ttl_seconds: Option<u64>,
+    pub max_rendered: Option<u64>,
+    pub content_type: Option<String>,
+    pub required_fields: Vec<String>,
+    pub optional_fields: Vec<String>,
+    pub strict_params: bool,
+    pub input_values_exclude: Vec<String>,
+    pub test_cases: Vec<TemplateTestCase>,
+    /// When the content was last changed through the API; `None` for templates loaded
+    /// from the config file and not updated since.
+    #[schema(value_type = Option<String>, format = DateTime)]
+    pub content_updated_at: Option<SystemTime>,
+    pub metadata: TemplateMetadata,
 }
 
 impl Default for TemplateData {
     fn default() -> Self {
         Self {
             template_content: String::new(),
+            checksum: content_checksum(""),
             id_field: "mac_address".to_string(),
             values_yaml: None,
+            values_format: ValuesFormat::Yaml,
             dynamic_fields: Vec::new(),
+            min_interval_seconds: None,
+            min_update_interval_seconds: None,
+            param_defaults: HashMap::new(),
+            failure_webhook: None,
+            input_transforms: HashMap::new(),
+            max_output_bytes: None,
+            on_values_error: ValuesErrorPolicy::Fail,
+            cache_generation: 0,
+            carry_forward_generated: false,
+            cache_policy: CachePolicy::Pin,
+            cache_ttl_seconds: None,
+            max_rendered: None,
+            content_type: None,
+            required_fields: Vec::new(),
+            optional_fields: Vec::new(),
+            strict_params: false,
+            input_values_exclude: Vec::new(),
+            test_cases: Vec::new(),
+            content_updated_at: None,
+            metadata: TemplateMetadata::default(),
+        }
+    }
+}
+
+impl From<&TemplateData> for TemplateConfig {
+    fn from(data: &TemplateData) -> Self {
+        Self {
+            id_field: data.id_field.clone(),
+            dynamic_fields: data.dynamic_fields.clone(),
+            min_interval_seconds: data.min_interval_seconds,
+            min_update_interval_seconds: data.min_update_interval_seconds,
+            param_defaults: data.param_defaults.clone(),
+            failure_webhook: data.failure_webhook.clone(),
+            input_transforms: data.input_transforms.clone(),
+            max_output_bytes: data.max_output_bytes,
+            on_values_error: data.on_values_error,
+            cache_generation: data.cache_generation,
+            carry_forward_generated: data.carry_forward_generated,
+            cache_policy: data.cache_policy,
+            cache_ttl_seconds: data.cache_ttl_seconds,
+            max_rendered: data.max_rendered,
+            content_type: data.content_type.clone(),
+            required_fields: data.required_fields.clone(),
+            optional_fields: data.optional_fields.clone(),
+            strict_params: data.strict_params,
+            input_values_exclude: data.input_values_exclude.clone(),
+        }
+    }
+}
+
+/// A template's stored values in canonical YAML form
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateValues {
+    /// `None` if values were never set
+    pub values_yaml: Option<String>,
+    pub format: ValuesFormat,
+}
+
+/// Everything needed to recreate a template on another instance.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub struct TemplateBundle {
+    pub template_content: String,
+    pub values_yaml: Option<String>,
+    pub config: TemplateConfig,
+    #[serde(default)]
+    pub test_cases: Vec<TemplateTestCase>,
+    #[serde(default)]
+    pub metadata: TemplateMetadata,
+}
+
+impl From<TemplateData> for TemplateBundle {
+    fn from(data: TemplateData) -> Self {
+        Self {
+            config: TemplateConfig::from(&data),
+            template_content: data.template_content,
+            values_yaml: data.values_yaml,
+            test_cases: data.test_cases,
+            metadata: data.metadata,
         }
     }
 }
 
+impl From<TemplateBundle> for TemplateData {
+    fn from(bundle: TemplateBundle) -> Self {
+        let config = bundle.config;
+        Self {
+            checksum: content_checksum(&bundle.template_content),
+            template_content: bundle.template_content,
+            id_field: config.id_field,
+            values_yaml: bundle.values_yaml,
+            values_format: ValuesFormat::Yaml,
+            dynamic_fields: config.dynamic_fields,
+            min_interval_seconds: config.min_interval_seconds,
+            min_update_interval_seconds: config.min_update_interval_seconds,
+            param_defaults: config.param_defaults,
+            failure_webhook: config.failure_webhook,
+            input_transforms: config.input_transforms,
+            max_output_bytes: config.max_output_bytes,
+            on_values_error: config.on_values_error,
+            cache_generation: config.cache_generation,
+            carry_forward_generated: config.carry_forward_generated,
+            cache_policy: config.cache_policy,
+            cache_ttl_seconds: config.cache_ttl_seconds,
+            max_rendered: config.max_rendered,
+            content_type: config.content_type,
+            required_fields: config.required_fields,
+            optional_fields: config.optional_fields,
+            strict_params: config.strict_params,
+            input_values_exclude: config.input_values_exclude,
+            test_cases: bundle.test_cases,
+            content_updated_at: None,
+            metadata: bundle.metadata,
+        }
+    }
+}
+
+/// Hex-encoded SHA-256 of template content, used for change detection.
+pub fn content_checksum(content: &str) -> String {
+    format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct RenderedTemplate {
@@ -86,11 +674,292 @@ pub struct RenderedTemplate {
     pub id_field_value: String,
     pub rendered_content: String,
     pub generated_values: String,
+    /// Values the render used other than generated ones, as YAML: stored values,
+    /// parameter defaults, device values and query parameters, less the template's
+    /// `input_values_exclude`. Absent for renders stored before they were recorded.
+    pub input_values: Option<String>,
+    /// Creation time as an RFC 3339 UTC timestamp.
+    #[schema(example = "2024-01-01T12:00:00.000Z", format = DateTime)]
     pub created_at: String,
+    /// Number of times this render has been served, including the initial render.
+    #[schema(example = 3)]
+    pub render_count: i64,
+    /// Time of the most recent serve as an RFC 3339 UTC timestamp.
+    #[schema(example = "2024-01-01T12:05:00.000Z", format = DateTime)]
+    pub last_served_at: Option<String>,
+    /// Where each top-level context key came from; absent for renders stored before
+    /// provenance was recorded.
+    pub value_sources: Option<ValueSources>,
+    /// SHA-256 of the template content this was rendered from; absent for renders
+    /// stored before it was recorded.
+    pub template_checksum: Option<String>,
+    /// SHA-256 of `rendered_content`, served as its ETag; absent for renders stored
+    /// before it was recorded.
+    pub content_hash: Option<String>,
+    /// Client address of the request that created this render
+    #[schema(example = "10.0.0.15")]
+    pub first_fetch_ip: Option<String>,
+    /// User-Agent of the request that created this render
+    #[schema(example = "iPXE/1.21.1")]
+    pub first_fetch_user_agent: Option<String>,
+    /// Client address of the most recent serve; only recorded with `track_last_fetch`
+    pub last_fetch_ip: Option<String>,
+    /// User-Agent of the most recent serve; only recorded with `track_last_fetch`
+    pub last_fetch_user_agent: Option<String>,
+    /// The template's `cache_generation` when this was rendered; renders of other
+    /// generations are cache misses
+    pub cache_generation: u64,
+}
+
+/// Field of a [`RenderedTemplate`] that can be selected on its own. The
+/// identifiers `id`, `template_name` and `id_field_value` are always returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderedField {
+    RenderedContent,
+    GeneratedValues,
+    CreatedAt,
+    RenderCount,
+    LastServedAt,
+    ValueSources,
+    TemplateChecksum,
+    ContentHash,
+    FirstFetchIp,
+    FirstFetchUserAgent,
+    LastFetchIp,
+    LastFetchUserAgent,
+    CacheGeneration,
+    InputValues,
+}
+
+impl RenderedField {
+    pub const ALL: [RenderedField; 14] = [
+        Self::RenderedContent,
+        Self::GeneratedValues,
+        Self::CreatedAt,
+        Self::RenderCount,
+        Self::LastServedAt,
+        Self::ValueSources,
+        Self::TemplateChecksum,
+        Self::ContentHash,
+        Self::FirstFetchIp,
+        Self::FirstFetchUserAgent,
+        Self::LastFetchIp,
+        Self::LastFetchUserAgent,
+        Self::CacheGeneration,
+        Self::InputValues,
+    ];
+    pub const IDENTIFIERS: [&'static str; 3] = ["id", "template_name", "id_field_value"];
+
+    /// JSON key of the field, which is also its column in the rendered store
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::RenderedContent => "rendered_content",
+            Self::GeneratedValues => "generated_values",
+            Self::CreatedAt => "created_at",
+            Self::RenderCount => "render_count",
+            Self::LastServedAt => "last_served_at",
+            Self::ValueSources => "value_sources",
+            Self::TemplateChecksum => "template_checksum",
+            Self::ContentHash => "content_hash",
+            Self::FirstFetchIp => "first_fetch_ip",
+            Self::FirstFetchUserAgent => "first_fetch_user_agent",
+            Self::LastFetchIp => "last_fetch_ip",
+            Self::LastFetchUserAgent => "last_fetch_user_agent",
+            Self::CacheGeneration => "cache_generation",
+            Self::InputValues => "input_values",
+        }
+    }
+
+    /// Parses a comma-separated field list. Identifiers are accepted and dropped,
+    /// as they are always returned; an unknown name is an error listing the valid ones.
+    pub fn parse_list(list: &str) -> Result<Vec<RenderedField>, String> {
+        let mut fields = Vec::new();
+        for name in list.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            if Self::IDENTIFIERS.contains(&name) {
+                continue;
+            }
+            let field = Self::ALL.into_iter().find(|field| field.name() == name).ok_or_else(|| {
+                let valid: Vec<&str> = Self::IDENTIFIERS
+                    .into_iter()
+                    .chain(Self::ALL.iter().map(RenderedField::name))
+                    .collect();
+                format!("Unknown field '{}'; valid fields are {}", name, valid.join(", "))
+            })?;
+            if !fields.contains(&field) {
+                fields.push(field);
+            }
+        }
+        Ok(fields)
+    }
+}
+
+/// Client that fetched a render: its resolved address and User-Agent header
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FetchSource {
+    pub ip: Option<IpAddr>,
+    pub user_agent: Option<String>,
+}
+
+impl FetchSource {
+    /// Longest User-Agent stored; longer headers are truncated
+    pub const MAX_USER_AGENT_LEN: usize = 256;
+
+    pub fn new(ip: Option<IpAddr>, user_agent: Option<&str>) -> Self {
+        let user_agent = user_agent.map(|ua| match ua.char_indices().nth(Self::MAX_USER_AGENT_LEN) {
+            Some((end, _)) => ua[..end].to_string(),
+            None => ua.to_string(),
+        });
+        Self { ip, user_agent }
+    }
+}
+
+/// Layer of the render context a value was taken from, lowest precedence first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueSource {
+    ValuesYaml,
+    ParamDefault,
+    /// Per-device overrides imported from CSV
+    DeviceValues,
+    Query,
+    Generated,
+}
+
+/// Origin of each top-level key in a render's context
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ValueSources {
+    #[schema(example = json!({"hostname": "values_yaml", "vlan": "param_default", "mac_address": "query"}))]
+    pub sources: BTreeMap<String, ValueSource>,
+    /// Keys left out to keep the stored map under its size cap
+    #[serde(default)]
+    pub omitted: usize,
+}
+
+/// A stored template, as listed by `GET /api/v1/templates`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct TemplateSummary {
+    #[schema(example = "ubuntu-autoinstall")]
+    pub name: String,
+    /// Whether values YAML has been uploaded
+    pub has_values: bool,
+    #[schema(example = "mac_address")]
+    pub id_field: String,
+    /// Number of configured dynamic fields
+    #[schema(example = 2)]
+    pub dynamic_field_count: usize,
+    pub metadata: TemplateMetadata,
+    /// For an alias, the name it points at; the other fields describe the template
+    /// it resolves to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(example = "ubuntu-autoinstall-prod")]
+    pub alias_of: Option<String>,
+}
+
+impl From<(&str, &TemplateData)> for TemplateSummary {
+    fn from((name, data): (&str, &TemplateData)) -> Self {
+        Self {
+            name: name.to_string(),
+            has_values: data.values_yaml.is_some(),
+            id_field: data.id_field.clone(),
+            dynamic_field_count: data.dynamic_fields.len(),
+            metadata: data.metadata.clone(),
+            alias_of: None,
+        }
+    }
+}
+
+/// Position in a newest-first walk over a template's renders: the creation time and
+/// row id of a render, which together order renders even when their times are equal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedCursor {
+    pub created_at: String,
+    pub id: i64,
+}
+
+/// Narrows a listing of cached renders; `None` bounds match everything
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RenderedFilter {
+    /// Earliest `created_at`, inclusive, in the store's RFC 3339 UTC form
+    pub since: Option<String>,
+    /// Latest `created_at`, inclusive, in the store's RFC 3339 UTC form
+    pub until: Option<String>,
+    /// Start of `id_field_value`, matched literally and case-insensitively for ASCII
+    pub id_prefix: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct RenderedTemplateSummary {
     pub id_field_value: String,
+    /// Creation time as an RFC 3339 UTC timestamp.
+    #[schema(example = "2024-01-01T12:00:00.000Z", format = DateTime)]
     pub created_at: String,
+    /// Number of times this render has been served, including the initial render.
+    #[schema(example = 3)]
+    pub render_count: i64,
+    /// Time of the most recent serve as an RFC 3339 UTC timestamp.
+    #[schema(example = "2024-01-01T12:05:00.000Z", format = DateTime)]
+    pub last_served_at: Option<String>,
+}
+
+/// A recorded version of a template's configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ConfigHistoryEntry {
+    /// Per-template version number, starting at 1
+    #[schema(example = 2)]
+    pub version: i64,
+    pub config: TemplateConfig,
+    /// Client address that made the change; absent for the config in effect
+    /// before the first recorded change
+    #[schema(example = "10.0.0.5")]
+    pub changed_by: Option<String>,
+    /// Time of the change as an RFC 3339 UTC timestamp.
+    #[schema(example = "2024-01-01T12:00:00.000Z", format = DateTime)]
+    pub changed_at: String,
+}
+
+/// Invariant of the rendered store checked by the consistency checker
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsistencyCheck {
+    /// `generated_values` parses as a YAML mapping
+    GeneratedValues,
+    /// `created_at` and `last_served_at` are RFC 3339 UTC timestamps
+    Timestamp,
+    /// Unique-field index entries belong to an existing render and match its generated value
+    UniqueIndex,
+}
+
+/// A row that breaks a store invariant
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ConsistencyViolation {
+    pub check: ConsistencyCheck,
+    /// Id of the rendered row; absent for index entries whose render no longer exists
+    #[schema(example = 42)]
+    pub row_id: Option<i64>,
+    pub template_name: String,
+    pub id_field_value: String,
+    /// What is wrong, never including generated values
+    pub detail: String,
+    /// The violation was fixed by this run
+    pub repaired: bool,
+}
+
+/// Result of a consistency check over the rendered store
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ConsistencyReport {
+    pub rows_checked: i64,
+    pub index_entries_checked: i64,
+    /// Total violations found, including any beyond the listed ones
+    pub violation_count: i64,
+    /// Violations fixed by this run
+    pub repaired: i64,
+    /// The first violations found, in row order
+    pub violations: Vec<ConsistencyViolation>,
+}
+
+impl ConsistencyReport {
+    /// Violations found but not repaired.
+    pub fn outstanding(&self) -> i64 {
+        self.violation_count - self.repaired
+    }
 }
@@ -1,23 +1,354 @@
 use crate::error::ProvisionrError;
-use crate::storage::models::{RenderedTemplate, RenderedTemplateSummary};
-use rusqlite::{params, Connection, Result as SqliteResult};
+use crate::storage::models::{
+    content_checksum, ConfigHistoryEntry, ConsistencyCheck, ConsistencyReport, ConsistencyViolation, FetchSource,
+    RenderedCursor, RenderedField, RenderedFilter, RenderedTemplate, RenderedTemplateSummary, TemplateConfig,
+    ValueSources,
+};
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Result as SqliteResult};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+use yaml_rust2::{Yaml, YamlLoader};
+
+/// Rows read per query by the consistency checker, bounding its memory use
+const CONSISTENCY_BATCH_SIZE: i64 = 500;
+/// Violations listed in a consistency report; further ones are only counted
+const MAX_REPORTED_VIOLATIONS: usize = 1000;
+/// Renders of `?1` matching a [`RenderedFilter`] bound as `?2` (since), `?3` (until)
+/// and `?4`, `?5` (the [`id_prefix_range`] of `id_prefix`), which searches
+/// `idx_rendered_id_nocase`
+const RENDERED_FILTER_CLAUSE: &str = "template_name = ?1
+                 AND (?2 IS NULL OR created_at >= ?2)
+                 AND (?3 IS NULL OR created_at <= ?3)
+                 AND id_field_value COLLATE NOCASE >= ?4
+                 AND id_field_value COLLATE NOCASE < ?5";
+/// What unparseable `generated_values` are replaced with on repair
+const EMPTY_GENERATED_VALUES: &str = "{}";
+/// Columns of `rendered_templates` read by [`rendered_template`], in its order
+const RENDERED_COLUMNS: &str = "id, template_name, id_field_value, rendered_content, generated_values, created_at,
+                    render_count, last_served_at, value_sources, template_checksum,
+                    first_fetch_ip, first_fetch_user_agent, last_fetch_ip, last_fetch_user_agent,
+                    cache_generation, content_hash, input_values";
+
+/// A render read from the [`RENDERED_COLUMNS`] of a row
+fn rendered_template(row: &rusqlite::Row) -> SqliteResult<RenderedTemplate> {
+    let value_sources: Option<String> = row.get(8)?;
+    Ok(RenderedTemplate {
+        id: row.get(0)?,
+        template_name: row.get(1)?,
+        id_field_value: row.get(2)?,
+        rendered_content: row.get(3)?,
+        generated_values: row.get(4)?,
+        input_values: row.get(16)?,
+        created_at: row.get(5)?,
+        render_count: row.get(6)?,
+        last_served_at: row.get(7)?,
+        value_sources: value_sources.and_then(|json| serde_json::from_str(&json).ok()),
+        template_checksum: row.get(9)?,
+        content_hash: row.get(15)?,
+        first_fetch_ip: row.get(10)?,
+        first_fetch_user_agent: row.get(11)?,
+        last_fetch_ip: row.get(12)?,
+        last_fetch_user_agent: row.get(13)?,
+        cache_generation: row.get::<_, i64>(14)? as u64,
+    })
+}
+
+/// Value of `field` read from column `index`, typed as in [`RenderedTemplate`]
+fn rendered_field_value(row: &rusqlite::Row, index: usize, field: RenderedField) -> SqliteResult<Value> {
+    Ok(match field {
+        RenderedField::RenderedContent | RenderedField::GeneratedValues | RenderedField::CreatedAt => {
+            Value::from(row.get::<_, String>(index)?)
+        }
+        RenderedField::RenderCount => Value::from(row.get::<_, i64>(index)?),
+        RenderedField::CacheGeneration => Value::from(row.get::<_, i64>(index)? as u64),
+        RenderedField::ValueSources => row
+            .get::<_, Option<String>>(index)?
+            .and_then(|json| serde_json::from_str::<ValueSources>(&json).ok())
+            .and_then(|sources| serde_json::to_value(sources).ok())
+            .unwrap_or(Value::Null),
+        RenderedField::LastServedAt
+        | RenderedField::TemplateChecksum
+        | RenderedField::ContentHash
+        | RenderedField::FirstFetchIp
+        | RenderedField::FirstFetchUserAgent
+        | RenderedField::LastFetchIp
+        | RenderedField::LastFetchUserAgent
+        | RenderedField::InputValues => Value::from(row.get::<_, Option<String>>(index)?),
+    })
+}
 
 #[cfg_attr(test, mockall::automock)]
 pub trait RenderedStore: Send {
     fn init(&self) -> Result<(), ProvisionrError>;
+    #[allow(clippy::too_many_arguments)]
     fn store_rendered(
         &self,
         template_name: &str,
         id_field_value: &str,
         rendered_content: &str,
         generated_values: &str,
+        input_values: &str,
+        value_sources: &ValueSources,
+        template_checksum: &str,
+        cache_generation: u64,
     ) -> Result<i64, ProvisionrError>;
+    /// Counts a serve of an existing render. Returns `false` without counting when the
+    /// previous serve was less than `min_interval_seconds` ago.
+    fn record_serve(
+        &self,
+        template_name: &str,
+        id_field_value: &str,
+        min_interval_seconds: Option<u64>,
+    ) -> Result<bool, ProvisionrError>;
+    /// Resets a render's serve count and last serve time, for a render no device has
+    /// fetched yet. Returns `false` when there is no such render.
+    fn mark_unserved(&self, template_name: &str, id_field_value: &str) -> Result<bool, ProvisionrError>;
+    /// Records the client whose request created a render.
+    fn record_first_fetch(
+        &self,
+        template_name: &str,
+        id_field_value: &str,
+        source: &FetchSource,
+    ) -> Result<(), ProvisionrError>;
+    /// Records the client of the most recent serve of a render.
+    fn record_last_fetch(
+        &self,
+        template_name: &str,
+        id_field_value: &str,
+        source: &FetchSource,
+    ) -> Result<(), ProvisionrError>;
     fn get_rendered(
         &self,
         template_name: &str,
         id_field_value: &str,
     ) -> Result<Option<RenderedTemplate>, ProvisionrError>;
-    fn list_rendered(&self, template_name: &str) -> Result<Vec<RenderedTemplateSummary>, ProvisionrError>;
+    /// Reads only the identifiers and the columns of `fields` of one render, as a
+    /// JSON object keyed by field name.
+    fn get_rendered_fields(
+        &self,
+        template_name: &str,
+        id_field_value: &str,
+        fields: &[RenderedField],
+    ) -> Result<Option<Map<String, Value>>, ProvisionrError>;
+    /// Up to `limit` cached renders of a template, newest first, that come after
+    /// `after` in that order, i.e. are strictly older than it; from the newest without
+    /// one. Renders stored meanwhile are newer than any cursor, so a walk paging from
+    /// its last row never repeats or skips a render.
+    // Named lifetime for the mock, which cannot elide one inside `Option`
+    #[allow(clippy::needless_lifetimes)]
+    fn list_rendered_after<'a>(
+        &self,
+        template_name: &str,
+        after: Option<&'a RenderedCursor>,
+        limit: u32,
+    ) -> Result<Vec<RenderedTemplate>, ProvisionrError>;
+    /// Up to `limit` cached renders of a template matching `filter`, newest first,
+    /// skipping the first `offset`. An offset past the end is an empty page.
+    fn list_rendered_paged(
+        &self,
+        template_name: &str,
+        filter: &RenderedFilter,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<RenderedTemplateSummary>, ProvisionrError>;
+    /// Number of cached renders of a template.
+    fn count_rendered(&self, template_name: &str) -> Result<i64, ProvisionrError>;
+    /// Number of cached renders of a template matching `filter`.
+    fn count_rendered_matching(&self, template_name: &str, filter: &RenderedFilter) -> Result<i64, ProvisionrError>;
+    /// Number of cached renders of every template with at least one, ordered by name.
+    fn rendered_counts(&self) -> Result<Vec<(String, i64)>, ProvisionrError>;
+    /// Deletes every cached render of a template and returns how many were removed.
+    fn delete_rendered_for(&self, template_name: &str) -> Result<i64, ProvisionrError>;
+    /// Deletes the cached renders of a template created before `cutoff`, an RFC 3339
+    /// UTC timestamp in the stored form, and returns how many were removed.
+    fn delete_older_than(&self, template_name: &str, cutoff: &str) -> Result<i64, ProvisionrError>;
+    /// Deletes all but the `keep` newest cached renders of a template and returns how
+    /// many were removed.
+    fn trim_rendered(&self, template_name: &str, keep: u64) -> Result<i64, ProvisionrError>;
+    /// Copies a render to `new_id_field_value` with its content, generated and input
+    /// values, template checksum and cache generation. A render already stored under the
+    /// new id is archived to the render history and replaced, and its unique-field index
+    /// entries dropped. The copied render's unique values move to the copy, so deleting
+    /// the original does not release them. The copy starts unserved, with `input_values`
+    /// as its recorded inputs when given. Returns `false` when there is no render to copy.
+    // Named lifetime for the mock, which cannot elide one inside `Option`
+    #[allow(clippy::needless_lifetimes)]
+    fn clone_rendered<'a>(
+        &self,
+        template_name: &str,
+        id_field_value: &str,
+        new_id_field_value: &str,
+        input_values: Option<&'a str>,
+    ) -> Result<bool, ProvisionrError>;
+    /// Deletes one cached render, so the next fetch for its id renders afresh. Returns
+    /// `false` when there was none.
+    fn delete_rendered(&self, template_name: &str, id_field_value: &str) -> Result<bool, ProvisionrError>;
+    /// Replaces the content of an existing render in place, archiving the previous
+    /// content to the render history. Its generated values are kept unless
+    /// `generated_values` replaces them; its creation time and serve count are kept.
+    /// Returns `false` when there is no such render.
+    // Named lifetime for the mock, which cannot elide one inside `Option`
+    #[allow(clippy::too_many_arguments, clippy::needless_lifetimes)]
+    fn replace_rendered_content<'a>(
+        &self,
+        template_name: &str,
+        id_field_value: &str,
+        rendered_content: &str,
+        generated_values: Option<&'a str>,
+        value_sources: &ValueSources,
+        template_checksum: &str,
+        cache_generation: u64,
+    ) -> Result<bool, ProvisionrError>;
+    /// Moves everything stored under template `old_name` to `new_name` in one
+    /// transaction: cached renders and their history, the unique-field index, config
+    /// history, device values and group memberships. Fails with `TemplateExists` when
+    /// renders, config history or device values of a deleted template are still stored
+    /// under `new_name`. Returns the number of cached renders moved.
+    fn rename_template(&self, old_name: &str, new_name: &str) -> Result<i64, ProvisionrError>;
+    /// Appends `config` to the template's config history and returns its version.
+    fn record_config(
+        &self,
+        template_name: &str,
+        config: &TemplateConfig,
+        changed_by: Option<String>,
+    ) -> Result<i64, ProvisionrError>;
+    /// The template's recorded configs, oldest first.
+    fn config_history(&self, template_name: &str) -> Result<Vec<ConfigHistoryEntry>, ProvisionrError>;
+    /// Id field value of the render that holds `value` for a unique dynamic field, if any.
+    fn unique_value_owner(
+        &self,
+        template_name: &str,
+        field_name: &str,
+        value: &str,
+    ) -> Result<Option<String>, ProvisionrError>;
+    /// Replaces the unique-field index entries for one render with `values`
+    /// (field name to generated value).
+    fn index_unique_values(
+        &self,
+        template_name: &str,
+        id_field_value: &str,
+        values: &HashMap<String, String>,
+    ) -> Result<(), ProvisionrError>;
+    /// Walks every cached render and unique-field index entry in batches, reporting
+    /// broken invariants. With `repair`, unparseable generated values are reset to an
+    /// empty map and index entries of renders that no longer exist are deleted.
+    fn check_consistency(&self, repair: bool) -> Result<ConsistencyReport, ProvisionrError>;
+    /// Replaces the value overrides of each device in one transaction, so either all
+    /// are written or none. Returns the number of devices written.
+    fn store_device_values(
+        &self,
+        template_name: &str,
+        devices: &[(String, HashMap<String, String>)],
+    ) -> Result<usize, ProvisionrError>;
+    /// Value overrides imported for one device, if any.
+    fn device_values(
+        &self,
+        template_name: &str,
+        id_field_value: &str,
+    ) -> Result<Option<HashMap<String, String>>, ProvisionrError>;
+    /// Replaces the member templates of a group.
+    fn set_group_members(&self, group_name: &str, members: &[String]) -> Result<(), ProvisionrError>;
+    /// Member templates of a group, in the order they were listed; `None` for an unknown group.
+    fn group_members(&self, group_name: &str) -> Result<Option<Vec<String>>, ProvisionrError>;
+    /// Replaces the generated values shared by the members of a group for one id.
+    fn store_group_values(
+        &self,
+        group_name: &str,
+        id_field_value: &str,
+        values: &HashMap<String, String>,
+    ) -> Result<(), ProvisionrError>;
+    /// Generated values shared by the members of a group for one id, if any member was rendered for it.
+    fn group_values(
+        &self,
+        group_name: &str,
+        id_field_value: &str,
+    ) -> Result<Option<HashMap<String, String>>, ProvisionrError>;
+}
+
+/// Generated values of a stored render as field name to value, or why they cannot
+/// be read. An empty string is an empty map, as written for renders without dynamic fields.
+fn parse_generated_values(yaml_str: &str) -> Result<HashMap<String, String>, String> {
+    let docs = YamlLoader::load_from_str(yaml_str).map_err(|e| format!("not valid YAML: {}", e))?;
+    match docs.into_iter().next() {
+        None | Some(Yaml::Null) => Ok(HashMap::new()),
+        Some(Yaml::Hash(hash)) => Ok(hash
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.into_string()?, value.into_string()?)))
+            .collect()),
+        Some(_) => Err("not a YAML mapping".to_string()),
+    }
+}
+
+fn is_rfc3339(timestamp: &str) -> bool {
+    timestamp.ends_with('Z') && humantime::parse_rfc3339(timestamp).is_ok()
+}
+
+/// Bounds `[lo, hi)` of the values starting with `prefix` under `NOCASE`, which
+/// ignores ASCII case. Without a prefix, or when no character follows its last ones,
+/// the upper bound is a blob, which SQLite orders after every text value.
+fn id_prefix_range(prefix: Option<&str>) -> (String, SqlValue) {
+    let lo = prefix.unwrap_or_default().to_ascii_lowercase();
+    let mut chars: Vec<char> = lo.chars().collect();
+    while let Some(last) = chars.pop() {
+        // NOCASE compares lowercase, so `@` is followed by `[` rather than `A`
+        let next = match last {
+            '@' => Some('['),
+            _ => (last as u32 + 1..=char::MAX as u32).find_map(char::from_u32),
+        };
+        if let Some(next) = next {
+            chars.push(next);
+            return (lo, SqlValue::Text(chars.into_iter().collect()));
+        }
+    }
+    (lo, SqlValue::Blob(Vec::new()))
+}
+
+/// Converts an RFC 3339 timestamp with a `Z` or `+HH:MM`/`-HH:MM` offset to the UTC,
+/// millisecond form `created_at` is stored in, so the two compare as strings. `None`
+/// when it is not such a timestamp.
+pub fn normalize_timestamp(timestamp: &str) -> Option<String> {
+    let split = timestamp.len().checked_sub(6).filter(|&at| timestamp.is_char_boundary(at));
+    let (local, offset) = match split.map(|at| timestamp.split_at(at)) {
+        Some((local, offset)) if offset.starts_with(['+', '-']) => (local, offset),
+        _ => (timestamp.strip_suffix('Z')?, "+00:00"),
+    };
+    let (hours, minutes) = offset[1..].split_once(':')?;
+    let (hours, minutes) = (hours.parse::<u64>().ok()?, minutes.parse::<u64>().ok()?);
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    let shift = Duration::from_secs(hours * 3600 + minutes * 60);
+    let local = humantime::parse_rfc3339(&format!("{}Z", local)).ok()?;
+    let utc = if offset.starts_with('-') {
+        local.checked_add(shift)?
+    } else {
+        local.checked_sub(shift)?
+    };
+    Some(humantime::format_rfc3339_millis(utc).to_string())
+}
+
+/// Accumulates violations, listing only the first `MAX_REPORTED_VIOLATIONS`.
+impl ConsistencyReport {
+    fn record(&mut self, violation: ConsistencyViolation) {
+        self.violation_count += 1;
+        if violation.repaired {
+            self.repaired += 1;
+        }
+        if self.violations.len() < MAX_REPORTED_VIOLATIONS {
+            self.violations.push(violation);
+        }
+    }
+}
+
+struct RowToCheck {
+    id: i64,
+    template_name: String,
+    id_field_value: String,
+    generated_values: String,
+    created_at: String,
+    last_served_at: Option<String>,
 }
 
 pub struct SqliteRenderedStore {
@@ -30,6 +361,191 @@ impl SqliteRenderedStore {
             Connection::open(path).map_err(|e| format!("Failed to open database: {}", e))?;
         Ok(Self { conn })
     }
+
+    /// Opens a database that must already exist, for offline tools that should not
+    /// create an empty one when given a wrong path.
+    pub fn open_existing(path: &str) -> Result<Self, String> {
+        let conn = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_NO_MUTEX | OpenFlags::SQLITE_OPEN_URI,
+        )
+        .map_err(|e| format!("Failed to open database {}: {}", path, e))?;
+        Ok(Self { conn })
+    }
+
+    /// Adds a column to databases created before it was part of the schema.
+    fn add_column_if_missing(&self, column: &str, definition: &str) -> Result<(), ProvisionrError> {
+        let exists: bool = self
+            .conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('rendered_templates') WHERE name = ?1",
+                params![column],
+                |row| row.get(0),
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to inspect schema: {}", e)))?;
+
+        if !exists {
+            self.conn
+                .execute(
+                    &format!("ALTER TABLE rendered_templates ADD COLUMN {} {}", column, definition),
+                    [],
+                )
+                .map_err(|e| ProvisionrError::Database(format!("Failed to add column {}: {}", column, e)))?;
+        }
+        Ok(())
+    }
+
+    /// `check_consistency` reading `batch_size` rows per query.
+    fn check_consistency_in_batches(
+        &self,
+        repair: bool,
+        batch_size: i64,
+    ) -> Result<ConsistencyReport, ProvisionrError> {
+        let db_error = |e: rusqlite::Error| ProvisionrError::Database(format!("Consistency check failed: {}", e));
+        let mut report = ConsistencyReport::default();
+
+        let mut rows_stmt = self
+            .conn
+            .prepare(
+                "SELECT id, template_name, id_field_value, generated_values, created_at, last_served_at
+                 FROM rendered_templates
+                 WHERE id > ?1
+                 ORDER BY id
+                 LIMIT ?2",
+            )
+            .map_err(db_error)?;
+        let mut index_stmt = self
+            .conn
+            .prepare(
+                "SELECT field_name, value_hash FROM unique_values
+                 WHERE template_name = ?1 AND id_field_value = ?2",
+            )
+            .map_err(db_error)?;
+
+        let mut last_id = 0;
+        loop {
+            let batch: Vec<RowToCheck> = rows_stmt
+                .query_map(params![last_id, batch_size], |row| {
+                    Ok(RowToCheck {
+                        id: row.get(0)?,
+                        template_name: row.get(1)?,
+                        id_field_value: row.get(2)?,
+                        generated_values: row.get(3)?,
+                        created_at: row.get(4)?,
+                        last_served_at: row.get(5)?,
+                    })
+                })
+                .and_then(|rows| rows.collect())
+                .map_err(db_error)?;
+            let Some(last) = batch.last() else { break };
+            last_id = last.id;
+
+            for row in batch {
+                report.rows_checked += 1;
+                let violation = |check, detail: String, repaired| ConsistencyViolation {
+                    check,
+                    row_id: Some(row.id),
+                    template_name: row.template_name.clone(),
+                    id_field_value: row.id_field_value.clone(),
+                    detail,
+                    repaired,
+                };
+
+                let timestamps = [("created_at", Some(&row.created_at)), ("last_served_at", row.last_served_at.as_ref())];
+                for (column, value) in timestamps {
+                    if let Some(value) = value.filter(|value| !is_rfc3339(value)) {
+                        report.record(violation(
+                            ConsistencyCheck::Timestamp,
+                            format!("{} '{}' is not an RFC 3339 UTC timestamp", column, value),
+                            false,
+                        ));
+                    }
+                }
+
+                let generated = match parse_generated_values(&row.generated_values) {
+                    Ok(generated) => Some(generated),
+                    Err(reason) => {
+                        if repair {
+                            self.conn
+                                .execute(
+                                    "UPDATE rendered_templates SET generated_values = ?1 WHERE id = ?2",
+                                    params![EMPTY_GENERATED_VALUES, row.id],
+                                )
+                                .map_err(db_error)?;
+                        }
+                        report.record(violation(
+                            ConsistencyCheck::GeneratedValues,
+                            format!("generated_values is {}", reason),
+                            repair,
+                        ));
+                        None
+                    }
+                };
+
+                let entries: Vec<(String, String)> = index_stmt
+                    .query_map(params![row.template_name, row.id_field_value], |r| Ok((r.get(0)?, r.get(1)?)))
+                    .and_then(|entries| entries.collect())
+                    .map_err(db_error)?;
+                report.index_entries_checked += entries.len() as i64;
+                let Some(generated) = generated else { continue };
+                for (field_name, value_hash) in entries {
+                    let detail = match generated.get(&field_name) {
+                        None => format!("unique index entry for field '{}' has no generated value", field_name),
+                        Some(value) if content_checksum(value) != value_hash => {
+                            format!("unique index entry for field '{}' does not match the generated value", field_name)
+                        }
+                        Some(_) => continue,
+                    };
+                    report.record(violation(ConsistencyCheck::UniqueIndex, detail, false));
+                }
+            }
+        }
+
+        // Index entries left behind by a render that is gone, e.g. deleted by hand with
+        // the cleanup trigger dropped, block their values for no device.
+        let mut orphans_stmt = self
+            .conn
+            .prepare(
+                "SELECT rowid, template_name, field_name, id_field_value FROM unique_values AS u
+                 WHERE rowid > ?1 AND NOT EXISTS (
+                     SELECT 1 FROM rendered_templates AS r
+                     WHERE r.template_name = u.template_name AND r.id_field_value = u.id_field_value
+                 )
+                 ORDER BY rowid
+                 LIMIT ?2",
+            )
+            .map_err(db_error)?;
+        let mut last_rowid = 0;
+        loop {
+            let batch: Vec<(i64, String, String, String)> = orphans_stmt
+                .query_map(params![last_rowid, batch_size], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+                })
+                .and_then(|rows| rows.collect())
+                .map_err(db_error)?;
+            let Some((last, ..)) = batch.last() else { break };
+            last_rowid = *last;
+
+            for (rowid, template_name, field_name, id_field_value) in batch {
+                report.index_entries_checked += 1;
+                if repair {
+                    self.conn
+                        .execute("DELETE FROM unique_values WHERE rowid = ?1", params![rowid])
+                        .map_err(db_error)?;
+                }
+                report.record(ConsistencyViolation {
+                    check: ConsistencyCheck::UniqueIndex,
+                    row_id: None,
+                    template_name,
+                    id_field_value,
+                    detail: format!("unique index entry for field '{}' belongs to no cached render", field_name),
+                    repaired: repair,
+                });
+            }
+        }
+
+        Ok(report)
+    }
 }
 
 impl RenderedStore for SqliteRenderedStore {
@@ -42,7 +558,9 @@ impl RenderedStore for SqliteRenderedStore {
                     id_field_value TEXT NOT NULL,
                     rendered_content TEXT NOT NULL,
                     generated_values TEXT NOT NULL,
-                    created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+                    render_count INTEGER NOT NULL DEFAULT 0,
+                    last_served_at TEXT,
                     UNIQUE(template_name, id_field_value)
                 )",
                 [],
@@ -55,6 +573,130 @@ impl RenderedStore for SqliteRenderedStore {
                 [],
             )
             .map_err(|e| ProvisionrError::Database(format!("Failed to create index: {}", e)))?;
+        // Id prefix searches, which ignore ASCII case
+        self.conn
+            .execute(
+                "CREATE INDEX IF NOT EXISTS idx_rendered_id_nocase
+                 ON rendered_templates(template_name, id_field_value COLLATE NOCASE)",
+                [],
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to create index: {}", e)))?;
+
+        // Rows written before timestamps were stored as RFC 3339 use SQLite's
+        // `YYYY-MM-DD HH:MM:SS` format (implicitly UTC); rewrite them so string
+        // comparisons and ordering work across old and new rows.
+        self.conn
+            .execute(
+                "UPDATE rendered_templates
+                 SET created_at = strftime('%Y-%m-%dT%H:%M:%fZ', created_at)
+                 WHERE created_at NOT LIKE '%Z'",
+                [],
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to migrate timestamps: {}", e)))?;
+
+        self.add_column_if_missing("render_count", "INTEGER NOT NULL DEFAULT 0")?;
+        self.add_column_if_missing("last_served_at", "TEXT")?;
+        // JSON `ValueSources`; NULL for rows rendered before provenance was recorded
+        self.add_column_if_missing("value_sources", "TEXT")?;
+        // Checksum of the template content rendered; NULL for rows rendered before it was recorded
+        self.add_column_if_missing("template_checksum", "TEXT")?;
+        // SHA-256 of rendered_content; NULL for rows rendered before it was recorded
+        self.add_column_if_missing("content_hash", "TEXT")?;
+        self.add_column_if_missing("first_fetch_ip", "TEXT")?;
+        self.add_column_if_missing("first_fetch_user_agent", "TEXT")?;
+        self.add_column_if_missing("last_fetch_ip", "TEXT")?;
+        self.add_column_if_missing("last_fetch_user_agent", "TEXT")?;
+        self.add_column_if_missing("cache_generation", "INTEGER NOT NULL DEFAULT 0")?;
+        // YAML of the non-generated values rendered with; NULL for rows rendered before it was recorded
+        self.add_column_if_missing("input_values", "TEXT")?;
+
+        // Renders replaced after a cache generation bump or a rerender, kept for audit
+        self.conn
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS rendered_history (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    template_name TEXT NOT NULL,
+                    id_field_value TEXT NOT NULL,
+                    rendered_content TEXT NOT NULL,
+                    generated_values TEXT NOT NULL,
+                    created_at TEXT NOT NULL,
+                    cache_generation INTEGER NOT NULL,
+                    template_checksum TEXT,
+                    archived_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+                );
+                CREATE INDEX IF NOT EXISTS idx_rendered_history_render
+                    ON rendered_history(template_name, id_field_value);",
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to create render history table: {}", e)))?;
+
+        // Values of unique dynamic fields, keyed by SHA-256 so the index never holds
+        // plaintext secrets. The primary key is the uniqueness guarantee.
+        self.conn
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS unique_values (
+                    template_name TEXT NOT NULL,
+                    field_name TEXT NOT NULL,
+                    value_hash TEXT NOT NULL,
+                    id_field_value TEXT NOT NULL,
+                    PRIMARY KEY (template_name, field_name, value_hash)
+                );
+                CREATE INDEX IF NOT EXISTS idx_unique_values_owner
+                    ON unique_values(template_name, id_field_value);
+                CREATE TRIGGER IF NOT EXISTS unique_values_cleanup
+                    AFTER DELETE ON rendered_templates
+                BEGIN
+                    DELETE FROM unique_values
+                    WHERE template_name = OLD.template_name AND id_field_value = OLD.id_field_value;
+                END;",
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to create unique value index: {}", e)))?;
+
+        self.conn
+            .execute(
+                "CREATE TABLE IF NOT EXISTS config_history (
+                    template_name TEXT NOT NULL,
+                    version INTEGER NOT NULL,
+                    config TEXT NOT NULL,
+                    changed_by TEXT,
+                    changed_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+                    PRIMARY KEY (template_name, version)
+                )",
+                [],
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to create config history table: {}", e)))?;
+
+        // Per-device value overrides from CSV imports, as a JSON object
+        self.conn
+            .execute(
+                "CREATE TABLE IF NOT EXISTS device_values (
+                    template_name TEXT NOT NULL,
+                    id_field_value TEXT NOT NULL,
+                    vals TEXT NOT NULL,
+                    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+                    PRIMARY KEY (template_name, id_field_value)
+                )",
+                [],
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to create device values table: {}", e)))?;
+
+        // Template groups, with members as a JSON array, and the generated values their
+        // members share per id as a JSON object
+        self.conn
+            .execute_batch(
+                "CREATE TABLE IF NOT EXISTS template_groups (
+                    group_name TEXT PRIMARY KEY,
+                    members TEXT NOT NULL,
+                    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+                );
+                CREATE TABLE IF NOT EXISTS group_values (
+                    group_name TEXT NOT NULL,
+                    id_field_value TEXT NOT NULL,
+                    vals TEXT NOT NULL,
+                    updated_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+                    PRIMARY KEY (group_name, id_field_value)
+                );",
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to create template group tables: {}", e)))?;
 
         Ok(())
     }
@@ -65,17 +707,127 @@ impl RenderedStore for SqliteRenderedStore {
         id_field_value: &str,
         rendered_content: &str,
         generated_values: &str,
+        input_values: &str,
+        value_sources: &ValueSources,
+        template_checksum: &str,
+        cache_generation: u64,
     ) -> Result<i64, ProvisionrError> {
+        let sources_json = serde_json::to_string(value_sources)
+            .map_err(|e| ProvisionrError::Database(format!("Failed to serialise value sources: {}", e)))?;
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| ProvisionrError::Database(format!("Failed to start transaction: {}", e)))?;
+
+        // A render from another cache generation or other template content is kept in
+        // the history rather than lost
+        tx.execute(
+            "INSERT INTO rendered_history
+             (template_name, id_field_value, rendered_content, generated_values, created_at,
+              cache_generation, template_checksum)
+             SELECT template_name, id_field_value, rendered_content, generated_values, created_at,
+                    cache_generation, template_checksum
+             FROM rendered_templates
+             WHERE template_name = ?1 AND id_field_value = ?2
+               AND (cache_generation != ?3 OR template_checksum IS NOT ?4)",
+            params![template_name, id_field_value, cache_generation as i64, template_checksum],
+        )
+        .map_err(|e| ProvisionrError::Database(format!("Failed to archive rendered template: {}", e)))?;
+
+        tx.execute(
+            "INSERT OR REPLACE INTO rendered_templates
+             (template_name, id_field_value, rendered_content, generated_values, created_at,
+              render_count, last_served_at, value_sources, template_checksum, content_hash, cache_generation,
+              input_values)
+             VALUES (?1, ?2, ?3, ?4, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'),
+                     1, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'), ?5, ?6, ?7, ?8, ?9)",
+            params![
+                template_name,
+                id_field_value,
+                rendered_content,
+                generated_values,
+                sources_json,
+                template_checksum,
+                content_checksum(rendered_content),
+                cache_generation as i64,
+                input_values
+            ],
+        )
+        .map_err(|e| ProvisionrError::Database(format!("Failed to insert rendered template: {}", e)))?;
+        let id = tx.last_insert_rowid();
+
+        tx.commit()
+            .map_err(|e| ProvisionrError::Database(format!("Failed to commit rendered template: {}", e)))?;
+        Ok(id)
+    }
+
+    fn record_serve(
+        &self,
+        template_name: &str,
+        id_field_value: &str,
+        min_interval_seconds: Option<u64>,
+    ) -> Result<bool, ProvisionrError> {
+        // Single statement on the hot path: the throttle check and the increment
+        // happen together, so a throttled fetch updates nothing.
+        let updated = self
+            .conn
+            .execute(
+                "UPDATE rendered_templates
+                 SET render_count = render_count + 1,
+                     last_served_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+                 WHERE template_name = ?1 AND id_field_value = ?2
+                   AND (?3 IS NULL OR last_served_at IS NULL
+                        OR last_served_at <= strftime('%Y-%m-%dT%H:%M:%fZ', 'now', '-' || ?3 || ' seconds'))",
+                params![template_name, id_field_value, min_interval_seconds.map(|s| s as i64)],
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to record serve: {}", e)))?;
+
+        Ok(updated > 0)
+    }
+
+    fn mark_unserved(&self, template_name: &str, id_field_value: &str) -> Result<bool, ProvisionrError> {
+        let updated = self
+            .conn
+            .execute(
+                "UPDATE rendered_templates SET render_count = 0, last_served_at = NULL
+                 WHERE template_name = ?1 AND id_field_value = ?2",
+                params![template_name, id_field_value],
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to reset serves: {}", e)))?;
+        Ok(updated > 0)
+    }
+
+    fn record_first_fetch(
+        &self,
+        template_name: &str,
+        id_field_value: &str,
+        source: &FetchSource,
+    ) -> Result<(), ProvisionrError> {
         self.conn
             .execute(
-                "INSERT OR REPLACE INTO rendered_templates
-                 (template_name, id_field_value, rendered_content, generated_values, created_at)
-                 VALUES (?1, ?2, ?3, ?4, datetime('now'))",
-                params![template_name, id_field_value, rendered_content, generated_values],
+                "UPDATE rendered_templates SET first_fetch_ip = ?3, first_fetch_user_agent = ?4
+                 WHERE template_name = ?1 AND id_field_value = ?2",
+                params![template_name, id_field_value, source.ip.map(|ip| ip.to_string()), source.user_agent],
             )
-            .map_err(|e| ProvisionrError::Database(format!("Failed to insert rendered template: {}", e)))?;
+            .map_err(|e| ProvisionrError::Database(format!("Failed to record fetch source: {}", e)))?;
+        Ok(())
+    }
 
-        Ok(self.conn.last_insert_rowid())
+    fn record_last_fetch(
+        &self,
+        template_name: &str,
+        id_field_value: &str,
+        source: &FetchSource,
+    ) -> Result<(), ProvisionrError> {
+        self.conn
+            .execute(
+                "UPDATE rendered_templates SET last_fetch_ip = ?3, last_fetch_user_agent = ?4
+                 WHERE template_name = ?1 AND id_field_value = ?2",
+                params![template_name, id_field_value, source.ip.map(|ip| ip.to_string()), source.user_agent],
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to record fetch source: {}", e)))?;
+        Ok(())
     }
 
     fn get_rendered(
@@ -84,20 +836,12 @@ impl RenderedStore for SqliteRenderedStore {
         id_field_value: &str,
     ) -> Result<Option<RenderedTemplate>, ProvisionrError> {
         let result: SqliteResult<RenderedTemplate> = self.conn.query_row(
-            "SELECT id, template_name, id_field_value, rendered_content, generated_values, created_at
-             FROM rendered_templates
-             WHERE template_name = ?1 AND id_field_value = ?2",
+            &format!(
+                "SELECT {} FROM rendered_templates WHERE template_name = ?1 AND id_field_value = ?2",
+                RENDERED_COLUMNS
+            ),
             params![template_name, id_field_value],
-            |row| {
-                Ok(RenderedTemplate {
-                    id: row.get(0)?,
-                    template_name: row.get(1)?,
-                    id_field_value: row.get(2)?,
-                    rendered_content: row.get(3)?,
-                    generated_values: row.get(4)?,
-                    created_at: row.get(5)?,
-                })
-            },
+            rendered_template,
         );
 
         match result {
@@ -107,22 +851,100 @@ impl RenderedStore for SqliteRenderedStore {
         }
     }
 
-    fn list_rendered(&self, template_name: &str) -> Result<Vec<RenderedTemplateSummary>, ProvisionrError> {
+    fn get_rendered_fields(
+        &self,
+        template_name: &str,
+        id_field_value: &str,
+        fields: &[RenderedField],
+    ) -> Result<Option<Map<String, Value>>, ProvisionrError> {
+        let columns: Vec<&str> = RenderedField::IDENTIFIERS
+            .into_iter()
+            .chain(fields.iter().map(RenderedField::name))
+            .collect();
+        let sql = format!(
+            "SELECT {} FROM rendered_templates WHERE template_name = ?1 AND id_field_value = ?2",
+            columns.join(", ")
+        );
+        self.conn
+            .query_row(&sql, params![template_name, id_field_value], |row| {
+                let mut object = Map::new();
+                object.insert("id".to_string(), Value::from(row.get::<_, i64>(0)?));
+                object.insert("template_name".to_string(), Value::from(row.get::<_, String>(1)?));
+                object.insert("id_field_value".to_string(), Value::from(row.get::<_, String>(2)?));
+                for (offset, field) in fields.iter().enumerate() {
+                    object.insert(field.name().to_string(), rendered_field_value(row, offset + 3, *field)?);
+                }
+                Ok(object)
+            })
+            .optional()
+            .map_err(|e| ProvisionrError::Database(format!("Database query failed: {}", e)))
+    }
+
+    fn list_rendered_after(
+        &self,
+        template_name: &str,
+        after: Option<&RenderedCursor>,
+        limit: u32,
+    ) -> Result<Vec<RenderedTemplate>, ProvisionrError> {
         let mut stmt = self
             .conn
-            .prepare(
-                "SELECT id_field_value, created_at
-                 FROM rendered_templates
+            .prepare(&format!(
+                "SELECT {} FROM rendered_templates
                  WHERE template_name = ?1
-                 ORDER BY created_at DESC",
+                   AND (?2 IS NULL OR created_at < ?2 OR (created_at = ?2 AND id < ?3))
+                 ORDER BY created_at DESC, id DESC
+                 LIMIT ?4",
+                RENDERED_COLUMNS
+            ))
+            .map_err(|e| ProvisionrError::Database(format!("Failed to prepare statement: {}", e)))?;
+
+        let rows = stmt
+            .query_map(
+                params![
+                    template_name,
+                    after.map(|cursor| &cursor.created_at),
+                    after.map(|cursor| cursor.id),
+                    limit
+                ],
+                rendered_template,
             )
+            .map_err(|e| ProvisionrError::Database(format!("Query failed: {}", e)))?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            results.push(row.map_err(|e| ProvisionrError::Database(format!("Row error: {}", e)))?);
+        }
+
+        Ok(results)
+    }
+
+    fn list_rendered_paged(
+        &self,
+        template_name: &str,
+        filter: &RenderedFilter,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<RenderedTemplateSummary>, ProvisionrError> {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT id_field_value, created_at, render_count, last_served_at
+                 FROM rendered_templates
+                 WHERE {}
+                 ORDER BY created_at DESC, id DESC
+                 LIMIT ?6 OFFSET ?7",
+                RENDERED_FILTER_CLAUSE
+            ))
             .map_err(|e| ProvisionrError::Database(format!("Failed to prepare statement: {}", e)))?;
 
+        let (id_lo, id_hi) = id_prefix_range(filter.id_prefix.as_deref());
         let rows = stmt
-            .query_map(params![template_name], |row| {
+            .query_map(params![template_name, filter.since, filter.until, id_lo, id_hi, limit, offset], |row| {
                 Ok(RenderedTemplateSummary {
                     id_field_value: row.get(0)?,
                     created_at: row.get(1)?,
+                    render_count: row.get(2)?,
+                    last_served_at: row.get(3)?,
                 })
             })
             .map_err(|e| ProvisionrError::Database(format!("Query failed: {}", e)))?;
@@ -134,4 +956,1400 @@ impl RenderedStore for SqliteRenderedStore {
 
         Ok(results)
     }
+
+    fn count_rendered(&self, template_name: &str) -> Result<i64, ProvisionrError> {
+        self.conn
+            .query_row(
+                "SELECT COUNT(*) FROM rendered_templates WHERE template_name = ?1",
+                params![template_name],
+                |row| row.get(0),
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Database query failed: {}", e)))
+    }
+
+    fn count_rendered_matching(&self, template_name: &str, filter: &RenderedFilter) -> Result<i64, ProvisionrError> {
+        let (id_lo, id_hi) = id_prefix_range(filter.id_prefix.as_deref());
+        self.conn
+            .query_row(
+                &format!("SELECT COUNT(*) FROM rendered_templates WHERE {}", RENDERED_FILTER_CLAUSE),
+                params![template_name, filter.since, filter.until, id_lo, id_hi],
+                |row| row.get(0),
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Database query failed: {}", e)))
+    }
+
+    fn rendered_counts(&self) -> Result<Vec<(String, i64)>, ProvisionrError> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT template_name, COUNT(*)
+                 FROM rendered_templates
+                 GROUP BY template_name
+                 ORDER BY template_name",
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to prepare statement: {}", e)))?;
+
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| ProvisionrError::Database(format!("Query failed: {}", e)))?;
+
+        rows.collect::<Result<_, _>>()
+            .map_err(|e| ProvisionrError::Database(format!("Row error: {}", e)))
+    }
+
+    fn delete_rendered_for(&self, template_name: &str) -> Result<i64, ProvisionrError> {
+        self.conn
+            .execute(
+                "DELETE FROM rendered_templates WHERE template_name = ?1",
+                params![template_name],
+            )
+            .map(|deleted| deleted as i64)
+            .map_err(|e| ProvisionrError::Database(format!("Failed to delete rendered templates: {}", e)))
+    }
+
+    fn delete_older_than(&self, template_name: &str, cutoff: &str) -> Result<i64, ProvisionrError> {
+        self.conn
+            .execute(
+                "DELETE FROM rendered_templates WHERE template_name = ?1 AND created_at < ?2",
+                params![template_name, cutoff],
+            )
+            .map(|deleted| deleted as i64)
+            .map_err(|e| ProvisionrError::Database(format!("Failed to delete expired renders: {}", e)))
+    }
+
+    fn trim_rendered(&self, template_name: &str, keep: u64) -> Result<i64, ProvisionrError> {
+        // Renders created in the same millisecond are ordered by insertion
+        self.conn
+            .execute(
+                "DELETE FROM rendered_templates WHERE template_name = ?1 AND id NOT IN (
+                     SELECT id FROM rendered_templates WHERE template_name = ?1
+                     ORDER BY created_at DESC, id DESC LIMIT ?2
+                 )",
+                params![template_name, keep.min(i64::MAX as u64) as i64],
+            )
+            .map(|deleted| deleted as i64)
+            .map_err(|e| ProvisionrError::Database(format!("Failed to trim rendered templates: {}", e)))
+    }
+
+    fn clone_rendered(
+        &self,
+        template_name: &str,
+        id_field_value: &str,
+        new_id_field_value: &str,
+        input_values: Option<&str>,
+    ) -> Result<bool, ProvisionrError> {
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| ProvisionrError::Database(format!("Failed to start transaction: {}", e)))?;
+
+        tx.execute(
+            "INSERT INTO rendered_history
+             (template_name, id_field_value, rendered_content, generated_values, created_at,
+              cache_generation, template_checksum)
+             SELECT template_name, id_field_value, rendered_content, generated_values, created_at,
+                    cache_generation, template_checksum
+             FROM rendered_templates
+             WHERE template_name = ?1 AND id_field_value = ?2",
+            params![template_name, new_id_field_value],
+        )
+        .map_err(|e| ProvisionrError::Database(format!("Failed to archive rendered template: {}", e)))?;
+        // Deleting fires the trigger that drops the replaced render's unique values
+        tx.execute(
+            "DELETE FROM rendered_templates WHERE template_name = ?1 AND id_field_value = ?2",
+            params![template_name, new_id_field_value],
+        )
+        .map_err(|e| ProvisionrError::Database(format!("Failed to delete rendered template: {}", e)))?;
+
+        let inserted = tx
+            .execute(
+                "INSERT INTO rendered_templates
+                 (template_name, id_field_value, rendered_content, generated_values, created_at,
+                  render_count, last_served_at, value_sources, template_checksum, content_hash, cache_generation,
+                  input_values)
+                 SELECT template_name, ?3, rendered_content, generated_values, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'),
+                        0, NULL, value_sources, template_checksum, content_hash, cache_generation,
+                        COALESCE(?4, input_values)
+                 FROM rendered_templates
+                 WHERE template_name = ?1 AND id_field_value = ?2",
+                params![template_name, id_field_value, new_id_field_value, input_values],
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to clone rendered template: {}", e)))?;
+        tx.execute(
+            "UPDATE unique_values SET id_field_value = ?3 WHERE template_name = ?1 AND id_field_value = ?2",
+            params![template_name, id_field_value, new_id_field_value],
+        )
+        .map_err(|e| ProvisionrError::Database(format!("Failed to move unique values: {}", e)))?;
+
+        tx.commit()
+            .map_err(|e| ProvisionrError::Database(format!("Failed to commit rendered template: {}", e)))?;
+        Ok(inserted > 0)
+    }
+
+    fn delete_rendered(&self, template_name: &str, id_field_value: &str) -> Result<bool, ProvisionrError> {
+        // The unique_values_cleanup trigger releases the render's unique values
+        self.conn
+            .execute(
+                "DELETE FROM rendered_templates WHERE template_name = ?1 AND id_field_value = ?2",
+                params![template_name, id_field_value],
+            )
+            .map(|deleted| deleted > 0)
+            .map_err(|e| ProvisionrError::Database(format!("Failed to delete rendered template: {}", e)))
+    }
+
+    fn replace_rendered_content(
+        &self,
+        template_name: &str,
+        id_field_value: &str,
+        rendered_content: &str,
+        generated_values: Option<&str>,
+        value_sources: &ValueSources,
+        template_checksum: &str,
+        cache_generation: u64,
+    ) -> Result<bool, ProvisionrError> {
+        let sources_json = serde_json::to_string(value_sources)
+            .map_err(|e| ProvisionrError::Database(format!("Failed to serialise value sources: {}", e)))?;
+
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| ProvisionrError::Database(format!("Failed to start transaction: {}", e)))?;
+
+        tx.execute(
+            "INSERT INTO rendered_history
+             (template_name, id_field_value, rendered_content, generated_values, created_at,
+              cache_generation, template_checksum)
+             SELECT template_name, id_field_value, rendered_content, generated_values, created_at,
+                    cache_generation, template_checksum
+             FROM rendered_templates
+             WHERE template_name = ?1 AND id_field_value = ?2",
+            params![template_name, id_field_value],
+        )
+        .map_err(|e| ProvisionrError::Database(format!("Failed to archive rendered template: {}", e)))?;
+
+        let updated = tx
+            .execute(
+                "UPDATE rendered_templates
+                 SET rendered_content = ?3, value_sources = ?4, template_checksum = ?5,
+                     content_hash = ?6, cache_generation = ?7,
+                     generated_values = COALESCE(?8, generated_values)
+                 WHERE template_name = ?1 AND id_field_value = ?2",
+                params![
+                    template_name,
+                    id_field_value,
+                    rendered_content,
+                    sources_json,
+                    template_checksum,
+                    content_checksum(rendered_content),
+                    cache_generation as i64,
+                    generated_values
+                ],
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to update rendered template: {}", e)))?;
+
+        tx.commit()
+            .map_err(|e| ProvisionrError::Database(format!("Failed to commit rendered template: {}", e)))?;
+        Ok(updated > 0)
+    }
+
+    fn rename_template(&self, old_name: &str, new_name: &str) -> Result<i64, ProvisionrError> {
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| ProvisionrError::Database(format!("Failed to start transaction: {}", e)))?;
+
+        let leftovers: i64 = tx
+            .query_row(
+                "SELECT (SELECT COUNT(*) FROM rendered_templates WHERE template_name = ?1)
+                      + (SELECT COUNT(*) FROM config_history WHERE template_name = ?1)
+                      + (SELECT COUNT(*) FROM device_values WHERE template_name = ?1)",
+                params![new_name],
+                |row| row.get(0),
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Database query failed: {}", e)))?;
+        if leftovers > 0 {
+            return Err(ProvisionrError::TemplateExists(format!(
+                "'{}' still has cached renders, config history or device values of a deleted template",
+                new_name
+            )));
+        }
+
+        let rename_in = |table: &str| {
+            tx.execute(
+                &format!("UPDATE {} SET template_name = ?2 WHERE template_name = ?1", table),
+                params![old_name, new_name],
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to rename template in {}: {}", table, e)))
+        };
+        let moved = rename_in("rendered_templates")? as i64;
+        for table in ["rendered_history", "unique_values", "config_history", "device_values"] {
+            rename_in(table)?;
+        }
+
+        let groups: Vec<(String, String)> = {
+            let mut stmt = tx
+                .prepare("SELECT group_name, members FROM template_groups")
+                .map_err(|e| ProvisionrError::Database(format!("Failed to prepare statement: {}", e)))?;
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+                .and_then(|rows| rows.collect())
+                .map_err(|e| ProvisionrError::Database(format!("Query failed: {}", e)))?
+        };
+        for (group_name, members_json) in groups {
+            let mut members: Vec<String> = serde_json::from_str(&members_json)
+                .map_err(|e| ProvisionrError::Database(format!("Stored group members are not valid JSON: {}", e)))?;
+            if !members.iter().any(|member| member == old_name) {
+                continue;
+            }
+            for member in members.iter_mut().filter(|member| *member == old_name) {
+                *member = new_name.to_string();
+            }
+            let members_json = serde_json::to_string(&members)
+                .map_err(|e| ProvisionrError::Database(format!("Failed to serialise group members: {}", e)))?;
+            tx.execute(
+                "UPDATE template_groups SET members = ?2 WHERE group_name = ?1",
+                params![group_name, members_json],
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to store group members: {}", e)))?;
+        }
+
+        tx.commit()
+            .map_err(|e| ProvisionrError::Database(format!("Failed to commit template rename: {}", e)))?;
+        Ok(moved)
+    }
+
+    fn record_config(
+        &self,
+        template_name: &str,
+        config: &TemplateConfig,
+        changed_by: Option<String>,
+    ) -> Result<i64, ProvisionrError> {
+        let config_json = serde_json::to_string(config)
+            .map_err(|e| ProvisionrError::Database(format!("Failed to serialise config: {}", e)))?;
+
+        self.conn
+            .query_row(
+                "INSERT INTO config_history (template_name, version, config, changed_by)
+                 SELECT ?1, COALESCE(MAX(version), 0) + 1, ?2, ?3
+                 FROM config_history WHERE template_name = ?1
+                 RETURNING version",
+                params![template_name, config_json, changed_by],
+                |row| row.get(0),
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to record config: {}", e)))
+    }
+
+    fn config_history(&self, template_name: &str) -> Result<Vec<ConfigHistoryEntry>, ProvisionrError> {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT version, config, changed_by, changed_at
+                 FROM config_history
+                 WHERE template_name = ?1
+                 ORDER BY version",
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to prepare statement: {}", e)))?;
+
+        let rows = stmt
+            .query_map(params![template_name], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })
+            .map_err(|e| ProvisionrError::Database(format!("Query failed: {}", e)))?;
+
+        let mut results = Vec::new();
+        for row in rows {
+            let (version, config_json, changed_by, changed_at) =
+                row.map_err(|e| ProvisionrError::Database(format!("Row error: {}", e)))?;
+            let config = serde_json::from_str(&config_json).map_err(|e| {
+                ProvisionrError::Database(format!("Corrupt config history version {}: {}", version, e))
+            })?;
+            results.push(ConfigHistoryEntry {
+                version,
+                config,
+                changed_by,
+                changed_at,
+            });
+        }
+
+        Ok(results)
+    }
+
+    fn unique_value_owner(
+        &self,
+        template_name: &str,
+        field_name: &str,
+        value: &str,
+    ) -> Result<Option<String>, ProvisionrError> {
+        self.conn
+            .query_row(
+                "SELECT id_field_value FROM unique_values
+                 WHERE template_name = ?1 AND field_name = ?2 AND value_hash = ?3",
+                params![template_name, field_name, content_checksum(value)],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| ProvisionrError::Database(format!("Database query failed: {}", e)))
+    }
+
+    fn index_unique_values(
+        &self,
+        template_name: &str,
+        id_field_value: &str,
+        values: &HashMap<String, String>,
+    ) -> Result<(), ProvisionrError> {
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| ProvisionrError::Database(format!("Failed to start transaction: {}", e)))?;
+
+        tx.execute(
+            "DELETE FROM unique_values WHERE template_name = ?1 AND id_field_value = ?2",
+            params![template_name, id_field_value],
+        )
+        .map_err(|e| ProvisionrError::Database(format!("Failed to clear unique values: {}", e)))?;
+
+        for (field_name, value) in values {
+            tx.execute(
+                "INSERT INTO unique_values (template_name, field_name, value_hash, id_field_value)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![template_name, field_name, content_checksum(value), id_field_value],
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to index unique value: {}", e)))?;
+        }
+
+        tx.commit()
+            .map_err(|e| ProvisionrError::Database(format!("Failed to commit unique values: {}", e)))
+    }
+
+    fn check_consistency(&self, repair: bool) -> Result<ConsistencyReport, ProvisionrError> {
+        self.check_consistency_in_batches(repair, CONSISTENCY_BATCH_SIZE)
+    }
+
+    fn store_device_values(
+        &self,
+        template_name: &str,
+        devices: &[(String, HashMap<String, String>)],
+    ) -> Result<usize, ProvisionrError> {
+        let tx = self
+            .conn
+            .unchecked_transaction()
+            .map_err(|e| ProvisionrError::Database(format!("Failed to start transaction: {}", e)))?;
+
+        for (id_field_value, values) in devices {
+            let values_json = serde_json::to_string(values)
+                .map_err(|e| ProvisionrError::Database(format!("Failed to serialise device values: {}", e)))?;
+            tx.execute(
+                "INSERT OR REPLACE INTO device_values (template_name, id_field_value, vals, updated_at)
+                 VALUES (?1, ?2, ?3, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))",
+                params![template_name, id_field_value, values_json],
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to store device values: {}", e)))?;
+        }
+
+        tx.commit()
+            .map_err(|e| ProvisionrError::Database(format!("Failed to commit device values: {}", e)))?;
+        Ok(devices.len())
+    }
+
+    fn device_values(
+        &self,
+        template_name: &str,
+        id_field_value: &str,
+    ) -> Result<Option<HashMap<String, String>>, ProvisionrError> {
+        let values_json: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT vals FROM device_values WHERE template_name = ?1 AND id_field_value = ?2",
+                params![template_name, id_field_value],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| ProvisionrError::Database(format!("Database query failed: {}", e)))?;
+
+        values_json
+            .map(|json| {
+                serde_json::from_str(&json)
+                    .map_err(|e| ProvisionrError::Database(format!("Stored device values are not valid JSON: {}", e)))
+            })
+            .transpose()
+    }
+
+    fn set_group_members(&self, group_name: &str, members: &[String]) -> Result<(), ProvisionrError> {
+        let members_json = serde_json::to_string(members)
+            .map_err(|e| ProvisionrError::Database(format!("Failed to serialise group members: {}", e)))?;
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO template_groups (group_name, members, updated_at)
+                 VALUES (?1, ?2, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))",
+                params![group_name, members_json],
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to store group members: {}", e)))?;
+        Ok(())
+    }
+
+    fn group_members(&self, group_name: &str) -> Result<Option<Vec<String>>, ProvisionrError> {
+        let members_json: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT members FROM template_groups WHERE group_name = ?1",
+                params![group_name],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| ProvisionrError::Database(format!("Database query failed: {}", e)))?;
+
+        members_json
+            .map(|json| {
+                serde_json::from_str(&json)
+                    .map_err(|e| ProvisionrError::Database(format!("Stored group members are not valid JSON: {}", e)))
+            })
+            .transpose()
+    }
+
+    fn store_group_values(
+        &self,
+        group_name: &str,
+        id_field_value: &str,
+        values: &HashMap<String, String>,
+    ) -> Result<(), ProvisionrError> {
+        let values_json = serde_json::to_string(values)
+            .map_err(|e| ProvisionrError::Database(format!("Failed to serialise group values: {}", e)))?;
+        self.conn
+            .execute(
+                "INSERT OR REPLACE INTO group_values (group_name, id_field_value, vals, updated_at)
+                 VALUES (?1, ?2, ?3, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))",
+                params![group_name, id_field_value, values_json],
+            )
+            .map_err(|e| ProvisionrError::Database(format!("Failed to store group values: {}", e)))?;
+        Ok(())
+    }
+
+    fn group_values(
+        &self,
+        group_name: &str,
+        id_field_value: &str,
+    ) -> Result<Option<HashMap<String, String>>, ProvisionrError> {
+        let values_json: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT vals FROM group_values WHERE group_name = ?1 AND id_field_value = ?2",
+                params![group_name, id_field_value],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| ProvisionrError::Database(format!("Database query failed: {}", e)))?;
+
+        values_json
+            .map(|json| {
+                serde_json::from_str(&json)
+                    .map_err(|e| ProvisionrError::Database(format!("Stored group values are not valid JSON: {}", e)))
+            })
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::storage::models::ValueSource;
+
+    fn create_store() -> SqliteRenderedStore {
+        let store = SqliteRenderedStore::new(":memory:").unwrap();
+        store.init().unwrap();
+        store
+    }
+
+    #[test]
+    fn rename_template_moves_every_row_of_the_template() {
+        let store = create_store();
+        store.store_rendered("old", "AA", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+        store.store_rendered("other", "AA", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+        store
+            .index_unique_values("old", "AA", &HashMap::from([("token".to_string(), "abc".to_string())]))
+            .unwrap();
+        store.record_config("old", &TemplateConfig::default(), None).unwrap();
+        store
+            .store_device_values("old", &[("AA".to_string(), HashMap::from([("vlan".to_string(), "10".to_string())]))])
+            .unwrap();
+        store.set_group_members("site", &["other".to_string(), "old".to_string()]).unwrap();
+
+        assert_eq!(store.rename_template("old", "new").unwrap(), 1);
+
+        assert!(store.get_rendered("old", "AA").unwrap().is_none());
+        assert!(store.get_rendered("new", "AA").unwrap().is_some());
+        assert_eq!(store.count_rendered("other").unwrap(), 1);
+        assert_eq!(store.unique_value_owner("new", "token", "abc").unwrap().as_deref(), Some("AA"));
+        assert_eq!(store.config_history("new").unwrap().len(), 1);
+        assert!(store.config_history("old").unwrap().is_empty());
+        assert!(store.device_values("new", "AA").unwrap().is_some());
+        assert_eq!(store.group_members("site").unwrap(), Some(vec!["other".to_string(), "new".to_string()]));
+    }
+
+    #[test]
+    fn rename_template_refuses_a_name_with_leftover_rows() {
+        let store = create_store();
+        store.store_rendered("old", "AA", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+        store.store_rendered("deleted", "BB", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+
+        let result = store.rename_template("old", "deleted");
+
+        assert!(matches!(result, Err(ProvisionrError::TemplateExists(_))), "{:?}", result);
+        assert_eq!(store.count_rendered("old").unwrap(), 1);
+        assert_eq!(store.count_rendered("deleted").unwrap(), 1);
+    }
+
+    #[test]
+    fn get_rendered_fields_reads_only_the_selected_columns() {
+        let store = create_store();
+        store
+            .store_rendered("template", "AA:BB", "content", "token: abc\n", "", &ValueSources::default(), "sum", 3)
+            .unwrap();
+        // Content that cannot be read as text fails any query that reads the column
+        store
+            .conn
+            .execute("UPDATE rendered_templates SET rendered_content = X'FF00'", [])
+            .unwrap();
+        assert!(store.get_rendered("template", "AA:BB").is_err());
+
+        let fields = [RenderedField::GeneratedValues, RenderedField::CacheGeneration, RenderedField::LastServedAt];
+        let object = store.get_rendered_fields("template", "AA:BB", &fields).unwrap().unwrap();
+
+        let keys: Vec<&str> = object.keys().map(String::as_str).collect();
+        assert_eq!(
+            keys,
+            vec!["cache_generation", "generated_values", "id", "id_field_value", "last_served_at", "template_name"]
+        );
+        assert_eq!(object["template_name"], "template");
+        assert_eq!(object["id_field_value"], "AA:BB");
+        assert_eq!(object["generated_values"], "token: abc\n");
+        assert_eq!(object["cache_generation"], 3);
+        assert_eq!(store.get_rendered_fields("template", "CC:DD", &fields).unwrap(), None);
+    }
+
+    #[test]
+    fn created_at_is_rfc3339_utc() {
+        let store = create_store();
+        store.store_rendered("template", "AA:BB", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+
+        let rendered = store.get_rendered("template", "AA:BB").unwrap().unwrap();
+        assert!(rendered.created_at.ends_with('Z'), "Expected Z suffix: {}", rendered.created_at);
+        assert_eq!(rendered.created_at.as_bytes()[10], b'T');
+    }
+
+    #[test]
+    fn init_migrates_legacy_timestamps() {
+        let store = create_store();
+        store
+            .conn
+            .execute(
+                "INSERT INTO rendered_templates
+                 (template_name, id_field_value, rendered_content, generated_values, created_at)
+                 VALUES ('template', 'legacy', 'content', '', '2024-01-01 12:30:00')",
+                [],
+            )
+            .unwrap();
+
+        store.init().unwrap();
+
+        let rendered = store.get_rendered("template", "legacy").unwrap().unwrap();
+        assert_eq!(rendered.created_at, "2024-01-01T12:30:00.000Z");
+    }
+
+    #[test]
+    fn list_orders_across_migration_boundary() {
+        let store = create_store();
+        store
+            .conn
+            .execute(
+                "INSERT INTO rendered_templates
+                 (template_name, id_field_value, rendered_content, generated_values, created_at)
+                 VALUES ('template', 'legacy', 'content', '', '2099-01-01 00:00:00')",
+                [],
+            )
+            .unwrap();
+        store.init().unwrap();
+        store.store_rendered("template", "new", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+
+        let list = store.list_rendered_paged("template", &RenderedFilter::default(), 100, 0).unwrap();
+        let ids: Vec<&str> = list.iter().map(|r| r.id_field_value.as_str()).collect();
+        assert_eq!(ids, vec!["legacy", "new"]);
+        assert!(list.iter().all(|r| r.created_at.ends_with('Z')));
+    }
+
+    #[test]
+    fn init_adds_serve_columns_to_legacy_table() {
+        let store = SqliteRenderedStore::new(":memory:").unwrap();
+        store
+            .conn
+            .execute(
+                "CREATE TABLE rendered_templates (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    template_name TEXT NOT NULL,
+                    id_field_value TEXT NOT NULL,
+                    rendered_content TEXT NOT NULL,
+                    generated_values TEXT NOT NULL,
+                    created_at TEXT NOT NULL,
+                    UNIQUE(template_name, id_field_value)
+                )",
+                [],
+            )
+            .unwrap();
+        store
+            .conn
+            .execute(
+                "INSERT INTO rendered_templates
+                 (template_name, id_field_value, rendered_content, generated_values, created_at)
+                 VALUES ('template', 'legacy', 'content', '', '2024-01-01 12:30:00')",
+                [],
+            )
+            .unwrap();
+
+        store.init().unwrap();
+
+        let rendered = store.get_rendered("template", "legacy").unwrap().unwrap();
+        assert_eq!(rendered.render_count, 0);
+        assert_eq!(rendered.last_served_at, None);
+        assert_eq!(rendered.value_sources, None);
+        assert_eq!(rendered.input_values, None);
+    }
+
+    #[test]
+    fn mark_unserved_resets_serves() {
+        let store = create_store();
+        store.store_rendered("template", "AA", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+
+        assert!(store.mark_unserved("template", "AA").unwrap());
+        let rendered = store.get_rendered("template", "AA").unwrap().unwrap();
+        assert_eq!(rendered.render_count, 0);
+        assert_eq!(rendered.last_served_at, None);
+        assert!(!store.mark_unserved("template", "BB").unwrap());
+    }
+
+    #[test]
+    fn input_values_round_trip() {
+        let store = create_store();
+        store
+            .store_rendered("template", "AA", "content", "", "vlan: '10'\n", &ValueSources::default(), "", 0)
+            .unwrap();
+
+        let rendered = store.get_rendered("template", "AA").unwrap().unwrap();
+        assert_eq!(rendered.input_values.as_deref(), Some("vlan: '10'\n"));
+        let fields = store.get_rendered_fields("template", "AA", &[RenderedField::InputValues]).unwrap().unwrap();
+        assert_eq!(fields["input_values"], "vlan: '10'\n");
+    }
+
+    #[test]
+    fn value_sources_round_trip() {
+        let store = create_store();
+        let sources = ValueSources {
+            sources: [
+                ("mac_address".to_string(), ValueSource::Query),
+                ("vlan".to_string(), ValueSource::ParamDefault),
+            ]
+            .into(),
+            omitted: 2,
+        };
+        store.store_rendered("template", "AA", "content", "", "", &sources, "", 0).unwrap();
+
+        let rendered = store.get_rendered("template", "AA").unwrap().unwrap();
+        assert_eq!(rendered.value_sources, Some(sources));
+    }
+
+    #[test]
+    fn store_rendered_counts_initial_serve() {
+        let store = create_store();
+        store.store_rendered("template", "AA:BB", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+
+        let rendered = store.get_rendered("template", "AA:BB").unwrap().unwrap();
+        assert_eq!(rendered.render_count, 1);
+        assert!(rendered.last_served_at.is_some());
+    }
+
+    #[test]
+    fn store_rendered_records_content_hash() {
+        let store = create_store();
+        store.store_rendered("template", "AA:BB", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+
+        let rendered = store.get_rendered("template", "AA:BB").unwrap().unwrap();
+        assert_eq!(rendered.content_hash, Some(content_checksum("content")));
+    }
+
+    #[test]
+    fn renders_of_an_older_generation_are_kept_in_history() {
+        let store = create_store();
+        let store_at = |content: &str, generation: u64| {
+            store
+                .store_rendered("template", "AA", content, "pw: a", "", &ValueSources::default(), "", generation)
+                .unwrap();
+        };
+        store_at("first", 0);
+        store_at("same generation", 0);
+        store_at("bumped", 1);
+
+        let current = store.get_rendered("template", "AA").unwrap().unwrap();
+        assert_eq!(current.rendered_content, "bumped");
+        assert_eq!(current.cache_generation, 1);
+        let history: Vec<(String, i64)> = store
+            .conn
+            .prepare("SELECT rendered_content, cache_generation FROM rendered_history ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(history, vec![("same generation".to_string(), 0)]);
+    }
+
+    #[test]
+    fn renders_of_other_template_content_are_kept_in_history() {
+        let store = create_store();
+        store.store_rendered("template", "AA", "first", "pw: a", "", &ValueSources::default(), "sum1", 0).unwrap();
+        store.store_rendered("template", "AA", "second", "pw: a", "", &ValueSources::default(), "sum2", 0).unwrap();
+
+        let archived: String = store
+            .conn
+            .query_row("SELECT rendered_content FROM rendered_history", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(archived, "first");
+    }
+
+    #[test]
+    fn replace_rendered_content_keeps_generated_values() {
+        let store = create_store();
+        store.store_rendered("template", "AA", "old", "pw: a", "", &ValueSources::default(), "sum1", 0).unwrap();
+        store.record_serve("template", "AA", None).unwrap();
+        let before = store.get_rendered("template", "AA").unwrap().unwrap();
+
+        assert!(store
+            .replace_rendered_content("template", "AA", "new", None, &ValueSources::default(), "sum2", 1)
+            .unwrap());
+        assert!(!store
+            .replace_rendered_content("template", "BB", "new", None, &ValueSources::default(), "sum2", 1)
+            .unwrap());
+
+        let after = store.get_rendered("template", "AA").unwrap().unwrap();
+        assert_eq!(after.rendered_content, "new");
+        assert_eq!(after.content_hash, Some(content_checksum("new")));
+        assert_eq!(after.template_checksum.as_deref(), Some("sum2"));
+        assert_eq!(after.cache_generation, 1);
+        assert_eq!(after.generated_values, before.generated_values);
+        assert_eq!(after.created_at, before.created_at);
+        assert_eq!(after.render_count, 2);
+        let archived: String = store
+            .conn
+            .query_row("SELECT rendered_content FROM rendered_history", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(archived, "old");
+    }
+
+    #[test]
+    fn replace_rendered_content_replaces_given_generated_values() {
+        let store = create_store();
+        store.store_rendered("template", "AA", "old a", "pw: a", "", &ValueSources::default(), "sum", 0).unwrap();
+
+        assert!(store
+            .replace_rendered_content("template", "AA", "new b", Some("pw: b"), &ValueSources::default(), "sum", 0)
+            .unwrap());
+
+        assert_eq!(store.get_rendered("template", "AA").unwrap().unwrap().generated_values, "pw: b");
+        let archived: String = store
+            .conn
+            .query_row("SELECT generated_values FROM rendered_history", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(archived, "pw: a");
+    }
+
+    #[test]
+    fn clone_rendered_copies_render_unserved() {
+        let store = create_store();
+        store.store_rendered("template", "AA", "host AA", "pw: a", "vlan: '10'", &ValueSources::default(), "sum", 2).unwrap();
+        store.record_serve("template", "AA", None).unwrap();
+        store.store_rendered("template", "BB", "old BB", "pw: b", "", &ValueSources::default(), "sum", 2).unwrap();
+        store
+            .index_unique_values("template", "BB", &HashMap::from([("pw".to_string(), "b".to_string())]))
+            .unwrap();
+
+        assert!(store.clone_rendered("template", "AA", "BB", None).unwrap());
+        assert!(!store.clone_rendered("template", "CC", "DD", None).unwrap());
+
+        let copy = store.get_rendered("template", "BB").unwrap().unwrap();
+        assert_eq!(copy.rendered_content, "host AA");
+        assert_eq!(copy.generated_values, "pw: a");
+        assert_eq!(copy.input_values.as_deref(), Some("vlan: '10'"));
+        assert_eq!((copy.template_checksum.as_deref(), copy.cache_generation), (Some("sum"), 2));
+        assert_eq!((copy.render_count, copy.last_served_at), (0, None));
+        assert_eq!(store.get_rendered("template", "AA").unwrap().unwrap().render_count, 2);
+        // The replaced render is archived and its unique value released
+        let archived: String = store
+            .conn
+            .query_row("SELECT rendered_content FROM rendered_history", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(archived, "old BB");
+        assert_eq!(store.unique_value_owner("template", "pw", "b").unwrap(), None);
+        assert!(store.get_rendered("template", "DD").unwrap().is_none());
+    }
+
+    #[test]
+    fn clone_rendered_moves_unique_values_to_the_copy() {
+        let store = create_store();
+        store.store_rendered("template", "AA", "host AA", "pw: a", "", &ValueSources::default(), "sum", 0).unwrap();
+        store
+            .index_unique_values("template", "AA", &HashMap::from([("pw".to_string(), "a".to_string())]))
+            .unwrap();
+
+        assert!(store.clone_rendered("template", "AA", "BB", Some("vlan: '20'")).unwrap());
+        assert_eq!(
+            store.get_rendered("template", "BB").unwrap().unwrap().input_values.as_deref(),
+            Some("vlan: '20'")
+        );
+        assert!(store.delete_rendered("template", "AA").unwrap());
+
+        assert_eq!(store.unique_value_owner("template", "pw", "a").unwrap(), Some("BB".to_string()));
+    }
+
+    #[test]
+    fn record_serve_increments_counter() {
+        let store = create_store();
+        store.store_rendered("template", "AA:BB", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+
+        assert!(store.record_serve("template", "AA:BB", None).unwrap());
+        assert!(store.record_serve("template", "AA:BB", Some(0)).unwrap());
+
+        let list = store.list_rendered_paged("template", &RenderedFilter::default(), 100, 0).unwrap();
+        assert_eq!(list[0].render_count, 3);
+    }
+
+    #[test]
+    fn record_serve_throttles_within_interval() {
+        let store = create_store();
+        store.store_rendered("template", "AA:BB", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+
+        assert!(!store.record_serve("template", "AA:BB", Some(3600)).unwrap());
+
+        let rendered = store.get_rendered("template", "AA:BB").unwrap().unwrap();
+        assert_eq!(rendered.render_count, 1);
+    }
+
+    #[test]
+    fn record_serve_allows_after_interval() {
+        let store = create_store();
+        store.store_rendered("template", "AA:BB", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+        store
+            .conn
+            .execute(
+                "UPDATE rendered_templates SET last_served_at = '2024-01-01T00:00:00.000Z'",
+                [],
+            )
+            .unwrap();
+
+        assert!(store.record_serve("template", "AA:BB", Some(3600)).unwrap());
+        let rendered = store.get_rendered("template", "AA:BB").unwrap().unwrap();
+        assert_eq!(rendered.render_count, 2);
+        assert!(rendered.last_served_at.unwrap().as_str() > "2024-01-01T00:00:00.000Z");
+    }
+
+    fn unique(field: &str, value: &str) -> HashMap<String, String> {
+        HashMap::from([(field.to_string(), value.to_string())])
+    }
+
+    #[test]
+    fn unique_value_owner_is_scoped_to_template_and_field() {
+        let store = create_store();
+        store.store_rendered("template", "AA", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+        store.index_unique_values("template", "AA", &unique("token", "abc")).unwrap();
+
+        assert_eq!(
+            store.unique_value_owner("template", "token", "abc").unwrap(),
+            Some("AA".to_string())
+        );
+        assert_eq!(store.unique_value_owner("template", "token", "xyz").unwrap(), None);
+        assert_eq!(store.unique_value_owner("template", "other", "abc").unwrap(), None);
+        assert_eq!(store.unique_value_owner("other", "token", "abc").unwrap(), None);
+    }
+
+    #[test]
+    fn delete_rendered_removes_one_render_and_its_unique_values() {
+        let store = create_store();
+        store.store_rendered("template", "AA", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+        store.store_rendered("template", "BB", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+        store.index_unique_values("template", "AA", &unique("token", "abc")).unwrap();
+
+        assert!(store.delete_rendered("template", "AA").unwrap());
+        assert!(!store.delete_rendered("template", "AA").unwrap());
+        assert!(!store.delete_rendered("other", "BB").unwrap());
+
+        assert!(store.get_rendered("template", "AA").unwrap().is_none());
+        assert!(store.get_rendered("template", "BB").unwrap().is_some());
+        assert_eq!(store.unique_value_owner("template", "token", "abc").unwrap(), None);
+    }
+
+    #[test]
+    fn unique_index_does_not_store_plaintext() {
+        let store = create_store();
+        store.index_unique_values("template", "AA", &unique("token", "secret-token")).unwrap();
+
+        let stored: String = store
+            .conn
+            .query_row("SELECT value_hash FROM unique_values", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stored, content_checksum("secret-token"));
+    }
+
+    #[test]
+    fn reindexing_a_render_replaces_its_entries() {
+        let store = create_store();
+        store.index_unique_values("template", "AA", &unique("token", "old")).unwrap();
+        store.index_unique_values("template", "AA", &unique("token", "new")).unwrap();
+
+        assert_eq!(store.unique_value_owner("template", "token", "old").unwrap(), None);
+        assert_eq!(
+            store.unique_value_owner("template", "token", "new").unwrap(),
+            Some("AA".to_string())
+        );
+    }
+
+    #[test]
+    fn index_rejects_value_held_by_another_render() {
+        let store = create_store();
+        store.index_unique_values("template", "AA", &unique("token", "abc")).unwrap();
+
+        let result = store.index_unique_values("template", "BB", &unique("token", "abc"));
+        assert!(matches!(result, Err(ProvisionrError::Database(_))));
+        assert_eq!(
+            store.unique_value_owner("template", "token", "abc").unwrap(),
+            Some("AA".to_string())
+        );
+    }
+
+    #[test]
+    fn deleting_a_render_removes_its_unique_values() {
+        let store = create_store();
+        store.store_rendered("template", "AA", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+        store.store_rendered("template", "BB", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+        store.index_unique_values("template", "AA", &unique("token", "abc")).unwrap();
+        store.index_unique_values("template", "BB", &unique("token", "def")).unwrap();
+
+        store
+            .conn
+            .execute(
+                "DELETE FROM rendered_templates WHERE template_name = 'template' AND id_field_value = 'AA'",
+                [],
+            )
+            .unwrap();
+
+        assert_eq!(store.unique_value_owner("template", "token", "abc").unwrap(), None);
+        assert_eq!(
+            store.unique_value_owner("template", "token", "def").unwrap(),
+            Some("BB".to_string())
+        );
+    }
+
+    #[test]
+    fn count_rendered_is_per_template() {
+        let store = create_store();
+        store.store_rendered("template", "AA", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+        store.store_rendered("template", "BB", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+        store.store_rendered("other", "AA", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+
+        assert_eq!(store.count_rendered("template").unwrap(), 2);
+        assert_eq!(store.count_rendered("missing").unwrap(), 0);
+    }
+
+    #[test]
+    fn list_rendered_paged_skips_and_limits() {
+        let store = create_store();
+        for id in ["AA", "BB", "CC"] {
+            store.store_rendered("template", id, "content", "", "", &ValueSources::default(), "", 0).unwrap();
+        }
+
+        let ids = |limit, offset| -> Vec<String> {
+            store
+                .list_rendered_paged("template", &RenderedFilter::default(), limit, offset)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.id_field_value)
+                .collect()
+        };
+        assert_eq!(ids(2, 0), vec!["CC", "BB"]);
+        assert_eq!(ids(2, 2), vec!["AA"]);
+        assert!(ids(2, 10).is_empty());
+    }
+
+    #[test]
+    fn list_rendered_paged_filters_by_creation_time() {
+        let store = create_store();
+        for (id, created_at) in [
+            ("mon", "2024-01-01T09:00:00.000Z"),
+            ("tue", "2024-01-02T09:00:00.000Z"),
+            ("wed", "2024-01-03T09:00:00.000Z"),
+        ] {
+            store.store_rendered("template", id, "content", "", "", &ValueSources::default(), "", 0).unwrap();
+            store
+                .conn
+                .execute(
+                    "UPDATE rendered_templates SET created_at = ?1 WHERE id_field_value = ?2",
+                    params![created_at, id],
+                )
+                .unwrap();
+        }
+        let filter = |since: Option<&str>, until: Option<&str>| RenderedFilter {
+            since: since.map(str::to_string),
+            until: until.map(str::to_string),
+            ..RenderedFilter::default()
+        };
+        let ids = |filter: &RenderedFilter| -> Vec<String> {
+            store
+                .list_rendered_paged("template", filter, 100, 0)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.id_field_value)
+                .collect()
+        };
+
+        let tuesday = filter(Some("2024-01-02T00:00:00.000Z"), Some("2024-01-02T23:59:59.999Z"));
+        assert_eq!(ids(&tuesday), vec!["tue"]);
+        assert_eq!(store.count_rendered_matching("template", &tuesday).unwrap(), 1);
+
+        // Both bounds are inclusive
+        assert_eq!(ids(&filter(Some("2024-01-02T09:00:00.000Z"), None)), vec!["wed", "tue"]);
+        assert_eq!(ids(&filter(None, Some("2024-01-02T09:00:00.000Z"))), vec!["tue", "mon"]);
+        assert_eq!(store.count_rendered_matching("template", &RenderedFilter::default()).unwrap(), 3);
+    }
+
+    #[test]
+    fn list_rendered_after_pages_by_cursor_despite_new_renders() {
+        let store = create_store();
+        for id in ["AA", "BB", "CC"] {
+            store.store_rendered("template", id, "content", "", "", &ValueSources::default(), "", 0).unwrap();
+        }
+        store
+            .conn
+            .execute("UPDATE rendered_templates SET created_at = '2024-01-01T00:00:00.000Z'", [])
+            .unwrap();
+        let ids = |rows: &[RenderedTemplate]| rows.iter().map(|r| r.id_field_value.clone()).collect::<Vec<_>>();
+        let cursor = |row: &RenderedTemplate| RenderedCursor {
+            created_at: row.created_at.clone(),
+            id: row.id,
+        };
+
+        let first = store.list_rendered_after("template", None, 2).unwrap();
+        assert_eq!(ids(&first), vec!["CC", "BB"]);
+        // A render stored mid-walk is newer than the cursor, so nothing shifts
+        store.store_rendered("template", "DD", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+        let second = store.list_rendered_after("template", Some(&cursor(&first[1])), 2).unwrap();
+        assert_eq!(ids(&second), vec!["AA"]);
+        assert!(store.list_rendered_after("template", Some(&cursor(&second[0])), 2).unwrap().is_empty());
+        assert_eq!(ids(&store.list_rendered_after("template", None, 1).unwrap()), vec!["DD"]);
+    }
+
+    #[test]
+    fn list_rendered_paged_filters_by_literal_id_prefix() {
+        let store = create_store();
+        for id in ["AA:01", "AA:02", "aa:03", "AB:01", "A%_1", "AX_1", "A@1", "A[1"] {
+            store.store_rendered("template", id, "content", "", "", &ValueSources::default(), "", 0).unwrap();
+        }
+        store.store_rendered("other", "AA:09", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+        let ids = |prefix: &str| -> Vec<String> {
+            let filter = RenderedFilter {
+                id_prefix: Some(prefix.to_string()),
+                ..RenderedFilter::default()
+            };
+            let mut ids: Vec<String> = store
+                .list_rendered_paged("template", &filter, 100, 0)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.id_field_value)
+                .collect();
+            ids.sort();
+            assert_eq!(store.count_rendered_matching("template", &filter).unwrap(), ids.len() as i64);
+            ids
+        };
+
+        assert_eq!(ids("AA:"), vec!["AA:01", "AA:02", "aa:03"]);
+        assert_eq!(ids("AA:0"), vec!["AA:01", "AA:02", "aa:03"]);
+        assert_eq!(ids("AB"), vec!["AB:01"]);
+        // Wildcards in the prefix match only themselves
+        assert_eq!(ids("A%"), vec!["A%_1"]);
+        assert_eq!(ids("A_"), Vec::<String>::new());
+        assert_eq!(ids("AX_"), vec!["AX_1"]);
+        assert_eq!(ids("a@"), vec!["A@1"]);
+        assert_eq!(ids("01"), Vec::<String>::new());
+        assert_eq!(ids("").len(), 8);
+    }
+
+    #[test]
+    fn id_prefix_search_uses_an_index() {
+        let store = create_store();
+        let plan: Vec<String> = store
+            .conn
+            .prepare(&format!(
+                "EXPLAIN QUERY PLAN SELECT id_field_value FROM rendered_templates WHERE {}",
+                RENDERED_FILTER_CLAUSE
+            ))
+            .unwrap()
+            .query_map(params!["template", None::<String>, None::<String>, "aa", "ab"], |row| {
+                row.get::<_, String>(3)
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert!(
+            plan.iter().any(|step| step.contains(
+                "USING INDEX idx_rendered_id_nocase (template_name=? AND id_field_value>? AND id_field_value<?)"
+            )),
+            "{:?}",
+            plan
+        );
+    }
+
+    #[test]
+    fn normalize_timestamp_converts_offsets_to_utc() {
+        assert_eq!(
+            normalize_timestamp("2024-01-02T00:00:00Z").as_deref(),
+            Some("2024-01-02T00:00:00.000Z")
+        );
+        assert_eq!(
+            normalize_timestamp("2024-01-02T01:30:00.25+02:00").as_deref(),
+            Some("2024-01-01T23:30:00.250Z")
+        );
+        assert_eq!(
+            normalize_timestamp("2024-01-01T20:00:00-05:00").as_deref(),
+            Some("2024-01-02T01:00:00.000Z")
+        );
+        for invalid in ["", "yesterday", "2024-01-02", "2024-01-02T00:00:00", "2024-01-02T00:00:00+25:00"] {
+            assert_eq!(normalize_timestamp(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn trim_rendered_keeps_the_newest_renders_of_the_template() {
+        let store = create_store();
+        for id in ["AA", "BB", "CC", "DD"] {
+            store.store_rendered("template", id, "content", "", "", &ValueSources::default(), "", 0).unwrap();
+        }
+        store.store_rendered("other", "AA", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+        store.index_unique_values("template", "AA", &unique("token", "abc")).unwrap();
+
+        assert_eq!(store.trim_rendered("template", 2).unwrap(), 2);
+        assert_eq!(store.trim_rendered("template", 2).unwrap(), 0);
+
+        assert!(store.get_rendered("template", "AA").unwrap().is_none());
+        assert!(store.get_rendered("template", "BB").unwrap().is_none());
+        assert!(store.get_rendered("template", "CC").unwrap().is_some());
+        assert!(store.get_rendered("template", "DD").unwrap().is_some());
+        assert!(store.get_rendered("other", "AA").unwrap().is_some());
+        assert_eq!(store.unique_value_owner("template", "token", "abc").unwrap(), None);
+    }
+
+    #[test]
+    fn delete_older_than_removes_only_earlier_renders_of_the_template() {
+        let store = create_store();
+        for (template, id, created_at) in [
+            ("template", "old", "2024-01-01T00:00:00.000Z"),
+            ("template", "new", "2024-01-03T00:00:00.000Z"),
+            ("other", "old", "2024-01-01T00:00:00.000Z"),
+        ] {
+            store.store_rendered(template, id, "content", "", "", &ValueSources::default(), "", 0).unwrap();
+            store
+                .conn
+                .execute(
+                    "UPDATE rendered_templates SET created_at = ?1 WHERE template_name = ?2 AND id_field_value = ?3",
+                    params![created_at, template, id],
+                )
+                .unwrap();
+        }
+
+        assert_eq!(store.delete_older_than("template", "2024-01-02T00:00:00.000Z").unwrap(), 1);
+        assert!(store.get_rendered("template", "old").unwrap().is_none());
+        assert!(store.get_rendered("template", "new").unwrap().is_some());
+        assert!(store.get_rendered("other", "old").unwrap().is_some());
+    }
+
+    #[test]
+    fn rendered_counts_and_delete_are_per_template() {
+        let store = create_store();
+        store.store_rendered("template", "AA", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+        store.store_rendered("template", "BB", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+        store.store_rendered("other", "AA", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+
+        assert_eq!(
+            store.rendered_counts().unwrap(),
+            vec![("other".to_string(), 1), ("template".to_string(), 2)]
+        );
+
+        assert_eq!(store.delete_rendered_for("template").unwrap(), 2);
+        assert_eq!(store.delete_rendered_for("template").unwrap(), 0);
+        assert_eq!(store.rendered_counts().unwrap(), vec![("other".to_string(), 1)]);
+    }
+
+    #[test]
+    fn config_history_accumulates_versions_per_template() {
+        let store = create_store();
+        let first = TemplateConfig::default();
+        let second = TemplateConfig {
+            id_field: "serial".to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(store.record_config("template", &first, None).unwrap(), 1);
+        assert_eq!(
+            store.record_config("template", &second, Some("10.0.0.5".to_string())).unwrap(),
+            2
+        );
+        assert_eq!(store.record_config("other", &first, None).unwrap(), 1);
+
+        let history = store.config_history("template").unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].version, 1);
+        assert_eq!(history[0].config, first);
+        assert_eq!(history[0].changed_by, None);
+        assert_eq!(history[1].config, second);
+        assert_eq!(history[1].changed_by.as_deref(), Some("10.0.0.5"));
+        assert!(history[1].changed_at.ends_with('Z'));
+
+        assert!(store.config_history("missing").unwrap().is_empty());
+    }
+
+    fn device(id: &str, hostname: &str) -> (String, HashMap<String, String>) {
+        (id.to_string(), HashMap::from([("hostname".to_string(), hostname.to_string())]))
+    }
+
+    #[test]
+    fn device_values_are_replaced_per_device() {
+        let store = create_store();
+        store.store_device_values("kickstart", &[device("AA", "sw01"), device("BB", "sw02")]).unwrap();
+        assert_eq!(store.store_device_values("kickstart", &[device("AA", "sw03")]).unwrap(), 1);
+
+        assert_eq!(store.device_values("kickstart", "AA").unwrap(), Some(device("AA", "sw03").1));
+        assert_eq!(store.device_values("kickstart", "BB").unwrap(), Some(device("BB", "sw02").1));
+        assert_eq!(store.device_values("pxe", "AA").unwrap(), None);
+    }
+
+    #[test]
+    fn group_members_and_values_are_replaced() {
+        let store = create_store();
+        assert_eq!(store.group_members("switch").unwrap(), None);
+
+        store.set_group_members("switch", &["config".to_string(), "certs".to_string()]).unwrap();
+        store.set_group_members("switch", &["config".to_string(), "script".to_string()]).unwrap();
+        assert_eq!(
+            store.group_members("switch").unwrap(),
+            Some(vec!["config".to_string(), "script".to_string()])
+        );
+
+        store.store_group_values("switch", "AA", &device("AA", "sw01").1).unwrap();
+        store.store_group_values("switch", "AA", &device("AA", "sw02").1).unwrap();
+        assert_eq!(store.group_values("switch", "AA").unwrap(), Some(device("AA", "sw02").1));
+        assert_eq!(store.group_values("switch", "BB").unwrap(), None);
+    }
+
+    /// Store with one healthy unique render and one of each kind of corruption.
+    fn corrupted_store() -> SqliteRenderedStore {
+        let store = create_store();
+        store.store_rendered("template", "ok", "content", "token: abc", "", &ValueSources::default(), "", 0).unwrap();
+        store.index_unique_values("template", "ok", &unique("token", "abc")).unwrap();
+        store.store_rendered("template", "plain", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+
+        store.store_rendered("template", "bad-yaml", "content", "token: [abc", "", &ValueSources::default(), "", 0).unwrap();
+        store.store_rendered("template", "bad-time", "content", "{}", "", &ValueSources::default(), "", 0).unwrap();
+        store
+            .conn
+            .execute(
+                "UPDATE rendered_templates SET created_at = 'yesterday' WHERE id_field_value = 'bad-time'",
+                [],
+            )
+            .unwrap();
+        store.store_rendered("template", "drifted", "content", "token: new", "", &ValueSources::default(), "", 0).unwrap();
+        store.index_unique_values("template", "drifted", &unique("token", "old")).unwrap();
+
+        store.conn.execute("DROP TRIGGER unique_values_cleanup", []).unwrap();
+        store.store_rendered("template", "gone", "content", "token: def", "", &ValueSources::default(), "", 0).unwrap();
+        store.index_unique_values("template", "gone", &unique("token", "def")).unwrap();
+        store
+            .conn
+            .execute("DELETE FROM rendered_templates WHERE id_field_value = 'gone'", [])
+            .unwrap();
+        store
+    }
+
+    fn checks(report: &ConsistencyReport) -> Vec<(ConsistencyCheck, &str, bool)> {
+        report
+            .violations
+            .iter()
+            .map(|v| (v.check, v.id_field_value.as_str(), v.repaired))
+            .collect()
+    }
+
+    #[test]
+    fn consistency_check_reports_each_violation_across_batches() {
+        let store = corrupted_store();
+
+        let report = store.check_consistency_in_batches(false, 2).unwrap();
+
+        assert_eq!(report.rows_checked, 5);
+        assert_eq!(report.index_entries_checked, 3);
+        assert_eq!(
+            checks(&report),
+            vec![
+                (ConsistencyCheck::GeneratedValues, "bad-yaml", false),
+                (ConsistencyCheck::Timestamp, "bad-time", false),
+                (ConsistencyCheck::UniqueIndex, "drifted", false),
+                (ConsistencyCheck::UniqueIndex, "gone", false),
+            ]
+        );
+        assert_eq!(report.violation_count, 4);
+        assert_eq!(report.repaired, 0);
+        assert_eq!(report.outstanding(), 4);
+        assert_eq!(report.violations[3].row_id, None);
+        assert!(report.violations.iter().all(|v| !v.detail.contains("abc") && !v.detail.contains("old")));
+
+        // Reporting alone changes nothing
+        assert_eq!(store.check_consistency_in_batches(false, 2).unwrap(), report);
+    }
+
+    #[test]
+    fn consistency_repair_fixes_generated_values_and_orphaned_index_entries() {
+        let store = corrupted_store();
+
+        let report = store.check_consistency(true).unwrap();
+        assert_eq!(report.violation_count, 4);
+        assert_eq!(report.repaired, 2);
+        assert_eq!(
+            checks(&report),
+            vec![
+                (ConsistencyCheck::GeneratedValues, "bad-yaml", true),
+                (ConsistencyCheck::Timestamp, "bad-time", false),
+                (ConsistencyCheck::UniqueIndex, "drifted", false),
+                (ConsistencyCheck::UniqueIndex, "gone", true),
+            ]
+        );
+
+        let repaired = store.get_rendered("template", "bad-yaml").unwrap().unwrap();
+        assert_eq!(repaired.generated_values, "{}");
+        assert_eq!(store.unique_value_owner("template", "token", "def").unwrap(), None);
+
+        let recheck = store.check_consistency(true).unwrap();
+        assert_eq!(recheck.violation_count, 2);
+        assert_eq!(recheck.repaired, 0);
+    }
+
+    #[test]
+    fn consistency_report_lists_only_the_first_violations() {
+        let store = create_store();
+        for i in 0..MAX_REPORTED_VIOLATIONS + 5 {
+            store
+                .store_rendered("template", &i.to_string(), "content", "[", "", &ValueSources::default(), "", 0)
+                .unwrap();
+        }
+
+        let report = store.check_consistency(false).unwrap();
+        assert_eq!(report.violation_count, MAX_REPORTED_VIOLATIONS as i64 + 5);
+        assert_eq!(report.violations.len(), MAX_REPORTED_VIOLATIONS);
+    }
 }
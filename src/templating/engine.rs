@@ -1,22 +1,194 @@
-use minijinja::{context, Environment, Value};
-use std::collections::HashMap;
+use minijinja::machinery::{self, Instruction};
+use minijinja::{context, Environment, ErrorKind, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use crate::storage::models::content_checksum;
+use crate::templating::filters;
+use crate::templating::secrets::{self, Secrets};
+
+/// Compiled templates kept before the cache is cleared and rebuilt on demand
+const MAX_COMPILED_TEMPLATES: usize = 256;
+
+/// Names minijinja resolves itself when called as functions rather than through
+/// the environment's globals
+const BUILTIN_CALLABLES: [&str; 3] = ["super", "loop", "caller"];
+
+/// Rendered output allowed when neither the config nor the template sets a limit
+pub const DEFAULT_MAX_OUTPUT_BYTES: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderError {
+    /// The template failed to render; the message is ready to show to clients
+    Failed(String),
+    /// Output grew past `limit` bytes and rendering was stopped
+    OutputTooLarge { limit: usize },
+}
+
+/// Output buffer that refuses writes past `limit` bytes, so a runaway loop stops
+/// at the limit instead of growing the response until the process runs out of memory.
+struct BoundedBuffer {
+    output: Vec<u8>,
+    limit: usize,
+    exceeded: bool,
+}
+
+impl io::Write for BoundedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.output.len() + buf.len() > self.limit {
+            self.exceeded = true;
+            return Err(io::Error::other("rendered output limit exceeded"));
+        }
+        self.output.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
 
 #[cfg_attr(test, mockall::automock)]
 pub trait TemplateEngine: Send {
-    fn validate(&self, template_content: &str) -> Result<(), String>;
+    /// Checks that the template parses and, unless `allow_unknown_names` is set, that
+    /// every filter, test and function it uses is registered.
+    fn validate(&self, template_content: &str, allow_unknown_names: bool) -> Result<(), String>;
+    /// Renders with `values` as the context, stopping once the output would exceed
+    /// `max_output_bytes`.
     fn render(
         &self,
         template_content: &str,
         values: &HashMap<String, String>,
-    ) -> Result<String, String>;
+        max_output_bytes: usize,
+    ) -> Result<String, RenderError>;
+    /// Returns the sorted root names of variables the template reads but never defines.
+    fn undeclared_variables(&self, template_content: &str) -> Result<Vec<String>, String>;
+    /// Parses `template_content` into the compiled-template cache ahead of its first render.
+    fn precompile(&self, template_content: &str) -> Result<(), String>;
 }
 
-pub struct MiniJinjaEngine;
+pub struct MiniJinjaEngine {
+    secrets: Arc<Secrets>,
+    /// Environments holding one parsed template each, keyed by content checksum
+    compiled: Mutex<HashMap<String, Arc<Environment<'static>>>>,
+}
 
 impl MiniJinjaEngine {
     pub fn new() -> Self {
-        Self
+        Self::with_secrets(Secrets::default())
+    }
+
+    /// Engine whose templates can read `secrets` through `secret("name")`.
+    pub fn with_secrets(secrets: Secrets) -> Self {
+        Self {
+            secrets: Arc::new(secrets),
+            compiled: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Environment with provisionr's filters and functions, shared by validation
+    /// and rendering so templates using them pass upload.
+    fn environment(&self) -> Environment<'static> {
+        let mut env = Environment::new();
+        filters::register(&mut env);
+        secrets::register(&mut env, Arc::clone(&self.secrets));
+        env
     }
+
+    /// Environment with `template_content` parsed as "template", from the cache
+    /// when the same content was compiled before.
+    fn compiled(&self, template_content: &str) -> Result<Arc<Environment<'static>>, String> {
+        let key = content_checksum(template_content);
+        if let Some(env) = self.compiled.lock().unwrap().get(&key) {
+            return Ok(Arc::clone(env));
+        }
+
+        let mut env = self.environment();
+        env.add_template_owned("template", template_content.to_string())
+            .map_err(|e| format!("Template parse error: {}", e))?;
+        let env = Arc::new(env);
+
+        let mut compiled = self.compiled.lock().unwrap();
+        if compiled.len() >= MAX_COMPILED_TEMPLATES {
+            compiled.clear();
+        }
+        compiled.insert(key, Arc::clone(&env));
+        Ok(env)
+    }
+
+    /// Whether `template_content` is already in the compiled-template cache.
+    #[cfg(test)]
+    pub fn is_compiled(&self, template_content: &str) -> bool {
+        self.compiled
+            .lock()
+            .unwrap()
+            .contains_key(&content_checksum(template_content))
+    }
+}
+
+/// Filters, tests and functions used by the environment's "template" that the
+/// environment cannot resolve, as "unknown filter 'name' on line N" in source order.
+///
+/// MiniJinja only looks these up at render time, so this walks the compiled
+/// instructions of the root and every block. Filters and tests are not listed by
+/// the environment; each distinct name is probed with a one-expression render, which
+/// fails with `UnknownFilter`/`UnknownTest` only when the name is missing. Functions
+/// may also be macros, loop variables or other names the template assigns itself.
+fn unknown_names(env: &Environment<'_>) -> Result<Vec<String>, String> {
+    let template = env
+        .get_template("template")
+        .map_err(|e| format!("Template retrieval error: {}", e))?;
+    let compiled = machinery::get_compiled_template(&template);
+    let instruction_sets: Vec<_> = std::iter::once(&compiled.instructions)
+        .chain(compiled.blocks.values())
+        .collect();
+
+    let mut assigned = BTreeSet::new();
+    for instructions in &instruction_sets {
+        for idx in 0..instructions.len() as u32 {
+            if let Some(Instruction::StoreLocal(name)) = instructions.get(idx) {
+                assigned.insert(*name);
+            }
+        }
+    }
+    let globals: BTreeSet<&str> = env.globals().map(|(name, _)| name).collect();
+
+    let mut checked = HashMap::new();
+    let mut unknown = BTreeSet::new();
+    for instructions in &instruction_sets {
+        for idx in 0..instructions.len() as u32 {
+            let (kind, name) = match instructions.get(idx) {
+                Some(Instruction::ApplyFilter(name, ..)) => ("filter", *name),
+                Some(Instruction::PerformTest(name, ..)) => ("test", *name),
+                Some(Instruction::CallFunction(name, _)) => ("function", *name),
+                _ => continue,
+            };
+            let known = *checked.entry((kind, name)).or_insert_with(|| match kind {
+                "filter" => probe(env, &format!("{{{{ none | {} }}}}", name), ErrorKind::UnknownFilter),
+                "test" => probe(env, &format!("{{{{ none is {} }}}}", name), ErrorKind::UnknownTest),
+                _ => {
+                    globals.contains(name) || assigned.contains(name) || BUILTIN_CALLABLES.contains(&name)
+                }
+            });
+            if !known {
+                let line = instructions.get_line(idx).unwrap_or_default();
+                unknown.insert((line, kind, name));
+            }
+        }
+    }
+
+    let mut reported = BTreeSet::new();
+    Ok(unknown
+        .into_iter()
+        .filter(|(_, kind, name)| reported.insert((*kind, *name)))
+        .map(|(line, kind, name)| format!("unknown {} '{}' on line {}", kind, name, line))
+        .collect())
+}
+
+/// Whether rendering `source` gets past name resolution, i.e. fails with anything but `missing`.
+fn probe(env: &Environment<'_>, source: &str, missing: ErrorKind) -> bool {
+    env.render_str(source, ()).map_or_else(|e| e.kind() != missing, |_| true)
 }
 
 impl Default for MiniJinjaEngine {
@@ -26,34 +198,71 @@ impl Default for MiniJinjaEngine {
 }
 
 impl TemplateEngine for MiniJinjaEngine {
-    fn validate(&self, template_content: &str) -> Result<(), String> {
-        let mut env = Environment::new();
+    fn validate(&self, template_content: &str, allow_unknown_names: bool) -> Result<(), String> {
+        let mut env = self.environment();
         env.add_template("template", template_content)
             .map_err(|e| format!("Template validation error: {}", e))?;
-        Ok(())
+        if allow_unknown_names {
+            return Ok(());
+        }
+
+        let unknown = unknown_names(&env)?;
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Template validation error: {}", unknown.join("; ")))
+        }
     }
 
     fn render(
         &self,
         template_content: &str,
         values: &HashMap<String, String>,
-    ) -> Result<String, String> {
-        let mut env = Environment::new();
-        env.add_template("template", template_content)
-            .map_err(|e| format!("Template parse error: {}", e))?;
-
+        max_output_bytes: usize,
+    ) -> Result<String, RenderError> {
+        let env = self.compiled(template_content).map_err(RenderError::Failed)?;
         let template = env
             .get_template("template")
-            .map_err(|e| format!("Template retrieval error: {}", e))?;
+            .map_err(|e| RenderError::Failed(format!("Template retrieval error: {}", e)))?;
 
         let ctx: HashMap<&str, Value> = values
             .iter()
             .map(|(k, v)| (k.as_str(), Value::from(v.clone())))
             .collect();
 
-        template
-            .render(context!(..ctx))
-            .map_err(|e| format!("Template render error: {}", e))
+        let mut buffer = BoundedBuffer {
+            output: Vec::new(),
+            limit: max_output_bytes,
+            exceeded: false,
+        };
+        match template.render_to_write(context!(..ctx), &mut buffer) {
+            Ok(_) => String::from_utf8(buffer.output)
+                .map_err(|e| RenderError::Failed(format!("Template render error: {}", e))),
+            Err(_) if buffer.exceeded => Err(RenderError::OutputTooLarge { limit: max_output_bytes }),
+            Err(e) => Err(RenderError::Failed(
+                self.secrets.redact(&format!("Template render error: {}", e)),
+            )),
+        }
+    }
+
+    fn undeclared_variables(&self, template_content: &str) -> Result<Vec<String>, String> {
+        let env = self.environment();
+        let template = env
+            .template_from_str(template_content)
+            .map_err(|e| format!("Template parse error: {}", e))?;
+
+        let globals: Vec<&str> = env.globals().map(|(name, _)| name).collect();
+        let mut names: Vec<String> = template
+            .undeclared_variables(false)
+            .into_iter()
+            .filter(|name| !globals.contains(&name.as_str()))
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    fn precompile(&self, template_content: &str) -> Result<(), String> {
+        self.compiled(template_content).map(|_| ())
     }
 }
 
@@ -65,13 +274,42 @@ mod tests {
     #[test]
     fn validate_valid_template() {
         let engine = MiniJinjaEngine::new();
-        assert!(engine.validate("Hello, {{ name }}!").is_ok());
+        assert!(engine.validate("Hello, {{ name }}!", false).is_ok());
     }
 
     #[test]
     fn validate_invalid_template() {
         let engine = MiniJinjaEngine::new();
-        assert!(engine.validate("Hello, {{ name }").is_err());
+        assert!(engine.validate("Hello, {{ name }", false).is_err());
+    }
+
+    #[test]
+    fn validate_accepts_builtin_and_registered_names() {
+        let engine = MiniJinjaEngine::new();
+        let template = "{% macro port(n) %}ge-0/0/{{ n }}{% endmacro %}\
+            {% for i in range(2) %}{{ port(i) }} {{ name | upper | default('x') }}{% endfor %}\
+            {% if name is defined %}{{ name | short_hash }}{% endif %}";
+        assert!(engine.validate(template, false).is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_unknown_names_with_their_line() {
+        let engine = MiniJinjaEngine::new();
+        let template = "hostname {{ name }}\n{{ config | to_yaml }}\n{% if x is odd_port %}{{ lookup('x') }}{% endif %}\n{{ other | to_yaml }}";
+
+        let error = engine.validate(template, false).unwrap_err();
+        assert_eq!(
+            error,
+            "Template validation error: unknown filter 'to_yaml' on line 2; \
+             unknown function 'lookup' on line 3; unknown test 'odd_port' on line 3"
+        );
+    }
+
+    #[test]
+    fn validate_allows_unknown_names_when_asked() {
+        let engine = MiniJinjaEngine::new();
+        assert!(engine.validate("{{ config | to_yaml }}", true).is_ok());
+        assert!(engine.validate("{{ config | to_yaml }", true).is_err());
     }
 
     #[quickcheck]
@@ -80,7 +318,7 @@ mod tests {
         let mut values = HashMap::new();
         values.insert("name".to_string(), value.clone());
 
-        let result = engine.render("{{ name }}", &values);
+        let result = engine.render("{{ name }}", &values, DEFAULT_MAX_OUTPUT_BYTES);
         result.map(|r| r == value).unwrap_or(false)
     }
 
@@ -91,12 +329,63 @@ mod tests {
         values.insert("a".to_string(), a.clone());
         values.insert("b".to_string(), b.clone());
 
-        let result = engine.render("{{ a }}|{{ b }}", &values);
+        let result = engine.render("{{ a }}|{{ b }}", &values, DEFAULT_MAX_OUTPUT_BYTES);
         result
             .map(|r| r == format!("{}|{}", a, b))
             .unwrap_or(false)
     }
 
+    #[test]
+    fn precompile_fills_the_cache_used_by_render() {
+        let engine = MiniJinjaEngine::new();
+        let content = "Hello, {{ name }}!";
+        assert!(!engine.is_compiled(content));
+
+        engine.precompile(content).unwrap();
+        assert!(engine.is_compiled(content));
+        assert!(!engine.is_compiled("Hello, {{ other }}!"));
+
+        let values = HashMap::from([("name".to_string(), "sw01".to_string())]);
+        assert_eq!(engine.render(content, &values, DEFAULT_MAX_OUTPUT_BYTES).unwrap(), "Hello, sw01!");
+        assert!(engine.precompile("Hello, {{ name }").is_err());
+        assert!(!engine.is_compiled("Hello, {{ name }"));
+    }
+
+    #[test]
+    fn render_stops_at_the_output_limit() {
+        let engine = MiniJinjaEngine::new();
+        let template = "{% for i in range(count | int) %}0123456789{% endfor %}";
+        let values = |count: &str| HashMap::from([("count".to_string(), count.to_string())]);
+
+        assert_eq!(engine.render(template, &values("10"), 100).unwrap().len(), 100);
+        assert_eq!(
+            engine.render(template, &values("11"), 100),
+            Err(RenderError::OutputTooLarge { limit: 100 })
+        );
+        assert_eq!(
+            engine.render(template, &values("100000"), 100),
+            Err(RenderError::OutputTooLarge { limit: 100 })
+        );
+    }
+
+    #[test]
+    fn undeclared_variables_reports_root_names() {
+        let engine = MiniJinjaEngine::new();
+        let result = engine
+            .undeclared_variables("{{ device.name }} {{ hostname }}{% for x in items %}{{ x }}{% endfor %}")
+            .unwrap();
+        assert_eq!(result, vec!["device", "hostname", "items"]);
+    }
+
+    #[test]
+    fn undeclared_variables_ignores_globals() {
+        let engine = MiniJinjaEngine::new();
+        let result = engine
+            .undeclared_variables("{% for i in range(3) %}{{ i }}{% endfor %}")
+            .unwrap();
+        assert!(result.is_empty());
+    }
+
     #[test]
     fn render_with_conditionals() {
         let engine = MiniJinjaEngine::new();
@@ -105,7 +394,47 @@ mod tests {
 
         let template =
             r#"{% if enable_feature == "yes" %}Feature enabled{% else %}Feature disabled{% endif %}"#;
-        let result = engine.render(template, &values);
+        let result = engine.render(template, &values, DEFAULT_MAX_OUTPUT_BYTES);
         assert_eq!(result.unwrap(), "Feature enabled");
     }
+
+    #[test]
+    fn custom_filters_pass_validation_and_render() {
+        let engine = MiniJinjaEngine::new();
+        let template = "ap-{{ mac_address | short_hash }}.{{ site | slugify }}";
+        assert!(engine.validate(template, false).is_ok());
+
+        let mut values = HashMap::new();
+        values.insert("mac_address".to_string(), "AA:BB:CC:DD:EE:FF".to_string());
+        values.insert("site".to_string(), "London DC 2".to_string());
+        assert_eq!(engine.render(template, &values, DEFAULT_MAX_OUTPUT_BYTES).unwrap(), "ap-261900.london-dc-2");
+        assert_eq!(
+            engine.undeclared_variables(template).unwrap(),
+            vec!["mac_address", "site"]
+        );
+    }
+
+    #[test]
+    fn secret_function_resolves_process_secrets() {
+        let secrets = Secrets::load(
+            None,
+            [("PROVISIONR_SECRET_RADIUS_KEY".to_string(), "s3cret".to_string())],
+        )
+        .unwrap();
+        let engine = MiniJinjaEngine::with_secrets(secrets);
+        let template = "key {{ secret('radius_key') }} for {{ hostname }}";
+        assert!(engine.validate(template, false).is_ok());
+        assert_eq!(engine.undeclared_variables(template).unwrap(), vec!["hostname"]);
+
+        let values = HashMap::from([("hostname".to_string(), "sw01".to_string())]);
+        assert_eq!(engine.render(template, &values, DEFAULT_MAX_OUTPUT_BYTES).unwrap(), "key s3cret for sw01");
+
+        let error = engine
+            .render("{{ secret('ntp_key') }}", &values, DEFAULT_MAX_OUTPUT_BYTES)
+            .unwrap_err();
+        let RenderError::Failed(error) = error else {
+            panic!("expected a render failure, got {:?}", error);
+        };
+        assert!(error.contains("unknown secret 'ntp_key' (available: radius_key)"), "{}", error);
+    }
 }
@@ -0,0 +1,163 @@
+use minijinja::value::Kwargs;
+use minijinja::{Environment, Error, ErrorKind};
+use sha2::{Digest, Sha256};
+
+const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+const DEFAULT_SHORT_HASH_LENGTH: usize = 6;
+/// Longest label DNS allows
+const MAX_LABEL_LENGTH: usize = 63;
+
+/// Registers provisionr's custom filters on `env`.
+pub fn register(env: &mut Environment<'_>) {
+    env.add_filter("short_hash", short_hash);
+    env.add_filter("slugify", slugify);
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Unpadded lowercase RFC 4648 base32.
+fn base32(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(5) * 8);
+    let mut buffer: u16 = 0;
+    let mut bits = 0;
+    for &byte in bytes {
+        buffer = (buffer << 8) | u16::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[usize::from((buffer >> bits) & 0x1f)] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[usize::from((buffer << (5 - bits)) & 0x1f)] as char);
+    }
+    out
+}
+
+/// `{{ mac_address | short_hash(length=6, alphabet="hex") }}`: the first `length`
+/// characters of the input's SHA-256, encoded as `hex` (default) or `base32`.
+fn short_hash(value: String, kwargs: Kwargs) -> Result<String, Error> {
+    let length: Option<usize> = kwargs.get("length")?;
+    let alphabet: Option<String> = kwargs.get("alphabet")?;
+    kwargs.assert_all_used()?;
+
+    let digest = Sha256::digest(value.as_bytes());
+    let encoded = match alphabet.as_deref().unwrap_or("hex") {
+        "hex" => hex(&digest),
+        "base32" => base32(&digest),
+        other => {
+            return Err(Error::new(
+                ErrorKind::InvalidOperation,
+                format!("short_hash alphabet must be 'hex' or 'base32', got '{}'", other),
+            ));
+        }
+    };
+
+    let length = length.unwrap_or(DEFAULT_SHORT_HASH_LENGTH);
+    if length == 0 || length > encoded.len() {
+        return Err(Error::new(
+            ErrorKind::InvalidOperation,
+            format!("short_hash length must be between 1 and {}", encoded.len()),
+        ));
+    }
+    Ok(encoded[..length].to_string())
+}
+
+/// Turns a string into a DNS-safe label: lowercase `[a-z0-9-]`, runs of other
+/// characters collapsed to a single `-`, no leading or trailing `-`, at most 63 characters.
+fn slugify(value: String) -> String {
+    let mut slug = String::with_capacity(value.len());
+    for c in value.chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_lowercase() || c.is_ascii_digit() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.truncate(MAX_LABEL_LENGTH);
+    slug.trim_end_matches('-').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use minijinja::context;
+    use quickcheck_macros::quickcheck;
+
+    fn render(template: &str, value: &str) -> Result<String, Error> {
+        let mut env = Environment::new();
+        register(&mut env);
+        env.render_str(template, context!(value => value))
+    }
+
+    #[test]
+    fn short_hash_defaults_to_six_hex_characters() {
+        assert_eq!(render("{{ value | short_hash }}", "hello").unwrap(), "2cf24d");
+        assert_eq!(
+            render("ap-{{ value | short_hash }}", "AA:BB:CC:DD:EE:FF").unwrap(),
+            "ap-261900"
+        );
+    }
+
+    #[test]
+    fn short_hash_accepts_length_and_base32() {
+        assert_eq!(
+            render("{{ value | short_hash(length=12) }}", "hello").unwrap(),
+            "2cf24dba5fb0"
+        );
+        assert_eq!(
+            render("{{ value | short_hash(alphabet='base32') }}", "hello").unwrap(),
+            "ftze3o"
+        );
+        assert_eq!(
+            render("{{ value | short_hash(length=10, alphabet='base32') }}", "AA:BB:CC:DD:EE:FF").unwrap(),
+            "eymqb6yrco"
+        );
+    }
+
+    #[test]
+    fn short_hash_rejects_bad_arguments() {
+        assert!(render("{{ value | short_hash(length=0) }}", "hello").is_err());
+        assert!(render("{{ value | short_hash(length=65) }}", "hello").is_err());
+        assert!(render("{{ value | short_hash(alphabet='base64') }}", "hello").is_err());
+        assert!(render("{{ value | short_hash(size=4) }}", "hello").is_err());
+    }
+
+    #[test]
+    fn base32_matches_rfc4648_vectors() {
+        assert_eq!(base32(b""), "");
+        assert_eq!(base32(b"f"), "my");
+        assert_eq!(base32(b"fo"), "mzxq");
+        assert_eq!(base32(b"foo"), "mzxw6");
+        assert_eq!(base32(b"foob"), "mzxw6yq");
+        assert_eq!(base32(b"fooba"), "mzxw6ytb");
+        assert_eq!(base32(b"foobar"), "mzxw6ytboi");
+    }
+
+    #[test]
+    fn slugify_produces_dns_labels() {
+        assert_eq!(slugify("Core Switch #1".to_string()), "core-switch-1");
+        assert_eq!(slugify("--rack__A/12--".to_string()), "rack-a-12");
+        assert_eq!(slugify("Café Wi-Fi".to_string()), "caf-wi-fi");
+        assert_eq!(slugify("!!!".to_string()), "");
+    }
+
+    #[test]
+    fn slugify_trims_to_label_length_without_trailing_dash() {
+        let long = format!("{}-{}", "a".repeat(62), "b".repeat(10));
+        assert_eq!(slugify(long), "a".repeat(62));
+        assert_eq!(slugify("x".repeat(100)).len(), 63);
+    }
+
+    #[quickcheck]
+    fn slugify_output_is_always_a_valid_label(input: String) -> bool {
+        let slug = slugify(input);
+        slug.len() <= MAX_LABEL_LENGTH
+            && slug.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+            && !slug.starts_with('-')
+            && !slug.ends_with('-')
+            && !slug.contains("--")
+    }
+}
@@ -1,6 +1,8 @@
 pub mod engine;
+pub mod filters;
+pub mod secrets;
 
-pub use engine::{MiniJinjaEngine, TemplateEngine};
+pub use engine::{MiniJinjaEngine, RenderError, TemplateEngine, DEFAULT_MAX_OUTPUT_BYTES};
 
 #[cfg(test)]
 pub use engine::MockTemplateEngine;
@@ -0,0 +1,199 @@
+use minijinja::{Environment, Error, ErrorKind};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::redact;
+
+/// Environment variables with this prefix define a secret; the rest of the
+/// variable name, lowercased, is the secret's name.
+pub const ENV_PREFIX: &str = "PROVISIONR_SECRET_";
+
+/// Process-level secrets shared by every template, read with `secret("name")`.
+/// Values are only ever resolved inside a render: they are not part of the render
+/// context, so they never reach generated values, provenance or logs.
+#[derive(Clone, Default)]
+pub struct Secrets {
+    values: BTreeMap<String, String>,
+}
+
+impl fmt::Debug for Secrets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Secrets").field("names", &self.names()).finish()
+    }
+}
+
+impl Secrets {
+    /// Reads `path`, a YAML mapping of secret names to string values, then applies
+    /// `PROVISIONR_SECRET_*` variables from `vars` on top.
+    pub fn load(
+        path: Option<&Path>,
+        vars: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<Self, String> {
+        let mut values = match path {
+            Some(path) => {
+                let content = fs::read_to_string(path)
+                    .map_err(|e| format!("Failed to read secrets file {:?}: {}", path, e))?;
+                serde_yaml::from_str::<BTreeMap<String, String>>(&content)
+                    .map_err(|e| {
+                        format!("Failed to parse secrets file {:?}: {}", path, redact::redact_quoted(&e.to_string()))
+                    })?
+            }
+            None => BTreeMap::new(),
+        };
+
+        for (key, value) in vars {
+            if let Some(name) = key.strip_prefix(ENV_PREFIX)
+                && !name.is_empty()
+            {
+                values.insert(name.to_lowercase(), value);
+            }
+        }
+        Ok(Self { values })
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.values.keys().map(String::as_str).collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Replaces every secret value in `text` with `<redacted:name>`.
+    pub fn redact(&self, text: &str) -> String {
+        redact::redact_named(text, &self.values)
+    }
+
+    fn get(&self, name: &str) -> Result<String, Error> {
+        self.values.get(name).cloned().ok_or_else(|| {
+            let available = if self.values.is_empty() {
+                "none are configured".to_string()
+            } else {
+                format!("available: {}", self.names().join(", "))
+            };
+            Error::new(
+                ErrorKind::InvalidOperation,
+                format!("unknown secret '{}' ({})", name, available),
+            )
+        })
+    }
+}
+
+/// Registers the `secret(name)` function on `env`.
+pub fn register(env: &mut Environment<'_>, secrets: Arc<Secrets>) {
+    env.add_function("secret", move |name: String| secrets.get(&name));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use minijinja::context;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    /// Writes `content` to a file unique to this test process and `name`.
+    fn secrets_file(name: &str, content: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("provisionr-{}-{}.yaml", std::process::id(), name));
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn render(secrets: Secrets, template: &str) -> Result<String, Error> {
+        let mut env = Environment::new();
+        register(&mut env, Arc::new(secrets));
+        env.render_str(template, context!())
+    }
+
+    #[test]
+    fn env_vars_with_prefix_become_lowercase_secrets() {
+        let secrets = Secrets::load(
+            None,
+            vars(&[
+                ("PROVISIONR_SECRET_RADIUS_KEY", "s3cret"),
+                ("PROVISIONR_SECRET_", "ignored"),
+                ("PROVISIONR_PORT", "3000"),
+                ("HOME", "/root"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(secrets.names(), vec!["radius_key"]);
+        assert_eq!(render(secrets, "{{ secret('radius_key') }}").unwrap(), "s3cret");
+    }
+
+    #[test]
+    fn env_vars_override_secrets_file() {
+        let path = secrets_file("override", "radius_key: from-file\nntp_key: ntp\n");
+
+        let secrets =
+            Secrets::load(Some(&path), vars(&[("PROVISIONR_SECRET_RADIUS_KEY", "from-env")])).unwrap();
+
+        assert_eq!(
+            render(secrets, "{{ secret('radius_key') }} {{ secret('ntp_key') }}").unwrap(),
+            "from-env ntp"
+        );
+    }
+
+    #[test]
+    fn unknown_secret_lists_names_but_not_values() {
+        let secrets = Secrets::load(
+            None,
+            vars(&[("PROVISIONR_SECRET_RADIUS_KEY", "s3cret"), ("PROVISIONR_SECRET_NTP_KEY", "hunter2")]),
+        )
+        .unwrap();
+
+        let error = render(secrets, "{{ secret('snmp') }}").unwrap_err().to_string();
+        assert!(error.contains("unknown secret 'snmp'"), "{}", error);
+        assert!(error.contains("ntp_key, radius_key"), "{}", error);
+        assert!(!error.contains("s3cret") && !error.contains("hunter2"), "{}", error);
+    }
+
+    #[test]
+    fn unknown_secret_without_any_configured() {
+        let error = render(Secrets::default(), "{{ secret('snmp') }}").unwrap_err().to_string();
+        assert!(error.contains("none are configured"), "{}", error);
+    }
+
+    #[test]
+    fn debug_output_hides_values() {
+        let secrets = Secrets::load(None, vars(&[("PROVISIONR_SECRET_RADIUS_KEY", "s3cret")])).unwrap();
+        let debug = format!("{:?}", secrets);
+        assert!(debug.contains("radius_key"));
+        assert!(!debug.contains("s3cret"));
+    }
+
+    #[test]
+    fn secret_values_are_redacted_from_text() {
+        let secrets = Secrets::load(None, vars(&[("PROVISIONR_SECRET_RADIUS_KEY", "s3cret")])).unwrap();
+        assert_eq!(
+            secrets.redact("template \"s3cret\" not found"),
+            "template \"<redacted:radius_key>\" not found"
+        );
+    }
+
+    #[test]
+    fn parse_errors_do_not_echo_values() {
+        let path = secrets_file("mistyped", "radius_key: s3cret
+ntp_key: [hunter2]
+snmp: {community: public}
+");
+        let error = Secrets::load(Some(&path), Vec::new()).unwrap_err();
+        assert!(!error.contains("s3cret") && !error.contains("hunter2"), "{}", error);
+    }
+
+    #[test]
+    fn invalid_secrets_file_is_an_error() {
+        let path = secrets_file("invalid", "- not\n- a map\n");
+
+        assert!(Secrets::load(Some(&path), Vec::new()).is_err());
+        assert!(Secrets::load(Some(&path.with_extension("missing")), Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,697 @@
+//! Read-only TFTP (RFC 1350) listener for devices that can only fetch their
+//! config over TFTP. Read requests are mapped onto a template and id value and
+//! rendered through the same pipeline as `GET /api/v1/template/{name}`.
+
+use serde::Deserialize;
+use std::net::SocketAddr;
+
+fn default_filename_pattern() -> String {
+    "{template}/{id}".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct TftpConfig {
+    /// Address to listen on, e.g. `0.0.0.0:69`
+    pub bind: SocketAddr,
+    /// Requested filename layout; must contain `{template}` and `{id}`
+    #[serde(default = "default_filename_pattern")]
+    pub filename_pattern: String,
+}
+
+#[cfg(feature = "tftp")]
+pub use server::TftpServer;
+
+#[cfg(feature = "tftp")]
+mod server {
+    use log::{debug, info, warn};
+    use std::collections::{HashMap, HashSet};
+    use std::io;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::net::UdpSocket;
+    use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+    use tokio_util::sync::CancellationToken;
+
+    use super::TftpConfig;
+    use crate::commands::models::Command;
+    use crate::error::ProvisionrError;
+    use crate::rest::command::{send_command, CommandError};
+    use crate::rest::state::AppState;
+    use crate::rest::template::render;
+    use crate::storage::models::{FetchSource, TemplateData};
+
+    const OP_RRQ: u16 = 1;
+    const OP_WRQ: u16 = 2;
+    const OP_DATA: u16 = 3;
+    const OP_ACK: u16 = 4;
+    const OP_ERROR: u16 = 5;
+
+    const ERR_UNDEFINED: u16 = 0;
+    const ERR_FILE_NOT_FOUND: u16 = 1;
+    const ERR_ACCESS_VIOLATION: u16 = 2;
+    const ERR_ILLEGAL_OPERATION: u16 = 4;
+
+    const BLOCK_SIZE: usize = 512;
+    /// Largest request we accept; RRQ packets are a filename, a mode and options
+    const MAX_REQUEST_SIZE: usize = 1024;
+    const ACK_TIMEOUT: Duration = Duration::from_secs(2);
+    const MAX_RETRIES: usize = 5;
+    /// Transfers served at once; further read requests get an error until one ends
+    const MAX_TRANSFERS: usize = 64;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    enum Segment {
+        Literal(String),
+        Template,
+        Id,
+    }
+
+    /// Filename layout with `{template}` and `{id}` placeholders.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct FilenamePattern {
+        segments: Vec<Segment>,
+    }
+
+    impl FilenamePattern {
+        pub fn parse(pattern: &str) -> Result<Self, String> {
+            let mut segments = Vec::new();
+            let mut rest = pattern;
+            while let Some(start) = rest.find('{') {
+                if start > 0 {
+                    segments.push(Segment::Literal(rest[..start].to_string()));
+                }
+                let end = rest[start..]
+                    .find('}')
+                    .map(|i| start + i)
+                    .ok_or_else(|| format!("Unclosed placeholder in TFTP filename pattern '{}'", pattern))?;
+                segments.push(match &rest[start + 1..end] {
+                    "template" => Segment::Template,
+                    "id" => Segment::Id,
+                    other => {
+                        return Err(format!(
+                            "Unknown placeholder '{{{}}}' in TFTP filename pattern '{}'",
+                            other, pattern
+                        ));
+                    }
+                });
+                rest = &rest[end + 1..];
+            }
+            if !rest.is_empty() {
+                segments.push(Segment::Literal(rest.to_string()));
+            }
+
+            let count = |wanted: &Segment| segments.iter().filter(|s| *s == wanted).count();
+            if count(&Segment::Template) != 1 || count(&Segment::Id) != 1 {
+                return Err(format!(
+                    "TFTP filename pattern '{}' must contain {{template}} and {{id}} exactly once",
+                    pattern
+                ));
+            }
+            if segments
+                .windows(2)
+                .any(|pair| !matches!(pair, [Segment::Literal(_), _] | [_, Segment::Literal(_)]))
+            {
+                return Err(format!(
+                    "TFTP filename pattern '{}' needs a separator between {{template}} and {{id}}",
+                    pattern
+                ));
+            }
+            Ok(Self { segments })
+        }
+
+        /// Splits a requested filename into `(template, id)`. Each placeholder takes
+        /// the shortest match that lets the following literal match.
+        pub fn match_filename(&self, filename: &str) -> Option<(String, String)> {
+            let mut rest = filename.trim_start_matches('/');
+            let mut template = None;
+            let mut id = None;
+
+            for (i, segment) in self.segments.iter().enumerate() {
+                match segment {
+                    Segment::Literal(literal) => rest = rest.strip_prefix(literal.as_str())?,
+                    placeholder => {
+                        let value = match self.segments.get(i + 1) {
+                            Some(Segment::Literal(next)) => {
+                                let end = rest.find(next.as_str())?;
+                                let (value, remainder) = rest.split_at(end);
+                                rest = remainder;
+                                value
+                            }
+                            _ => std::mem::take(&mut rest),
+                        };
+                        if value.is_empty() {
+                            return None;
+                        }
+                        match placeholder {
+                            Segment::Template => template = Some(value.to_string()),
+                            _ => id = Some(value.to_string()),
+                        }
+                    }
+                }
+            }
+
+            if !rest.is_empty() {
+                return None;
+            }
+            Some((template?, id?))
+        }
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Request {
+        Read { filename: String, netascii: bool },
+        Write,
+    }
+
+    /// Parses an RRQ/WRQ packet; the error is a TFTP error code and message.
+    fn parse_request(packet: &[u8]) -> Result<Request, (u16, String)> {
+        let illegal = |msg: &str| (ERR_ILLEGAL_OPERATION, msg.to_string());
+        if packet.len() < 2 {
+            return Err(illegal("Truncated packet"));
+        }
+        let opcode = u16::from_be_bytes([packet[0], packet[1]]);
+        match opcode {
+            OP_WRQ => Ok(Request::Write),
+            OP_RRQ => {
+                let mut fields = packet[2..].split(|b| *b == 0);
+                let filename = fields
+                    .next()
+                    .and_then(|f| std::str::from_utf8(f).ok())
+                    .filter(|f| !f.is_empty())
+                    .ok_or_else(|| illegal("Missing filename"))?;
+                let mode = fields
+                    .next()
+                    .and_then(|m| std::str::from_utf8(m).ok())
+                    .ok_or_else(|| illegal("Missing transfer mode"))?;
+                let netascii = if mode.eq_ignore_ascii_case("netascii") {
+                    true
+                } else if mode.eq_ignore_ascii_case("octet") {
+                    false
+                } else {
+                    return Err(illegal(&format!("Unsupported transfer mode '{}'", mode)));
+                };
+                Ok(Request::Read {
+                    filename: filename.to_string(),
+                    netascii,
+                })
+            }
+            _ => Err(illegal("Expected a read request")),
+        }
+    }
+
+    fn error_packet(code: u16, message: &str) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(5 + message.len());
+        packet.extend_from_slice(&OP_ERROR.to_be_bytes());
+        packet.extend_from_slice(&code.to_be_bytes());
+        packet.extend_from_slice(message.as_bytes());
+        packet.push(0);
+        packet
+    }
+
+    fn data_packet(block: u16, data: &[u8]) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(4 + data.len());
+        packet.extend_from_slice(&OP_DATA.to_be_bytes());
+        packet.extend_from_slice(&block.to_be_bytes());
+        packet.extend_from_slice(data);
+        packet
+    }
+
+    fn is_ack(packet: &[u8], block: u16) -> bool {
+        packet.len() >= 4
+            && u16::from_be_bytes([packet[0], packet[1]]) == OP_ACK
+            && u16::from_be_bytes([packet[2], packet[3]]) == block
+    }
+
+    /// Netascii line endings are CR LF.
+    fn to_netascii(content: &str) -> Vec<u8> {
+        content.replace("\r\n", "\n").replace('\n', "\r\n").into_bytes()
+    }
+
+    fn command_error(e: &CommandError) -> (u16, String) {
+        match e {
+            CommandError::Handler(ProvisionrError::TemplateNotFound(_)) => {
+                (ERR_FILE_NOT_FOUND, "File not found".to_string())
+            }
+            other => (ERR_UNDEFINED, other.to_string()),
+        }
+    }
+
+    /// Peer and filename of each transfer in progress
+    type ActiveTransfers = Arc<Mutex<HashSet<(SocketAddr, String)>>>;
+
+    /// Holds a transfer slot and its entry in the active set until the transfer ends.
+    struct TransferGuard {
+        active: ActiveTransfers,
+        key: (SocketAddr, String),
+        _permit: OwnedSemaphorePermit,
+    }
+
+    impl Drop for TransferGuard {
+        fn drop(&mut self) {
+            if let Ok(mut active) = self.active.lock() {
+                active.remove(&self.key);
+            }
+        }
+    }
+
+    pub struct TftpServer {
+        socket: UdpSocket,
+        pattern: FilenamePattern,
+        state: AppState,
+        transfers: Arc<Semaphore>,
+        active: ActiveTransfers,
+    }
+
+    impl TftpServer {
+        pub async fn bind(config: &TftpConfig, state: AppState) -> Result<Self, String> {
+            let pattern = FilenamePattern::parse(&config.filename_pattern)?;
+            let socket = UdpSocket::bind(config.bind)
+                .await
+                .map_err(|e| format!("Failed to bind TFTP listener on {}: {}", config.bind, e))?;
+            Ok(Self {
+                socket,
+                pattern,
+                state,
+                transfers: Arc::new(Semaphore::new(MAX_TRANSFERS)),
+                active: ActiveTransfers::default(),
+            })
+        }
+
+        pub fn local_addr(&self) -> io::Result<SocketAddr> {
+            self.socket.local_addr()
+        }
+
+        /// Accepts requests until `cancel` fires; each transfer runs on its own socket.
+        /// Malformed requests, writes and requests beyond `MAX_TRANSFERS` are answered
+        /// from the listening socket, and a read request repeating one in progress from
+        /// the same peer is dropped, so clients retransmitting it get a single transfer.
+        pub async fn run(self, cancel: CancellationToken) {
+            let mut buf = [0u8; MAX_REQUEST_SIZE];
+            loop {
+                let (len, peer) = tokio::select! {
+                    _ = cancel.cancelled() => break,
+                    received = self.socket.recv_from(&mut buf) => match received {
+                        Ok(received) => received,
+                        Err(e) => {
+                            warn!("TFTP receive failed: {}", e);
+                            continue;
+                        }
+                    },
+                };
+
+                let (filename, netascii) = match parse_request(&buf[..len]) {
+                    Ok(Request::Read { filename, netascii }) => (filename, netascii),
+                    Ok(Request::Write) => {
+                        self.reply_error(peer, ERR_ACCESS_VIOLATION, "Writes are not supported").await;
+                        continue;
+                    }
+                    Err((code, message)) => {
+                        self.reply_error(peer, code, &message).await;
+                        continue;
+                    }
+                };
+                let key = (peer, filename.clone());
+                if self.active.lock().is_ok_and(|active| active.contains(&key)) {
+                    debug!("Ignoring repeated TFTP read request for '{}' from {}", filename, peer);
+                    continue;
+                }
+                let Ok(permit) = self.transfers.clone().try_acquire_owned() else {
+                    self.reply_error(peer, ERR_UNDEFINED, "Server busy").await;
+                    continue;
+                };
+                if let Ok(mut active) = self.active.lock() {
+                    active.insert(key.clone());
+                }
+                let guard = TransferGuard {
+                    active: self.active.clone(),
+                    key,
+                    _permit: permit,
+                };
+
+                let pattern = self.pattern.clone();
+                let state = self.state.clone();
+                let cancel = cancel.clone();
+                let local_ip = self.socket.local_addr().map(|a| a.ip());
+                tokio::spawn(async move {
+                    let _guard = guard;
+                    let socket = match local_ip {
+                        Ok(ip) => UdpSocket::bind(SocketAddr::new(ip, 0)).await,
+                        Err(e) => Err(e),
+                    };
+                    let socket = match socket {
+                        Ok(socket) => socket,
+                        Err(e) => {
+                            warn!("Failed to open TFTP transfer socket for {}: {}", peer, e);
+                            return;
+                        }
+                    };
+                    tokio::select! {
+                        _ = cancel.cancelled() => {}
+                        _ = transfer(&socket, peer, &filename, netascii, &pattern, &state) => {}
+                    }
+                });
+            }
+            info!("TFTP listener stopped");
+        }
+
+        async fn reply_error(&self, peer: SocketAddr, code: u16, message: &str) {
+            debug!("TFTP request from {} failed: {}", peer, message);
+            let _ = self.socket.send_to(&error_packet(code, message), peer).await;
+        }
+    }
+
+    async fn transfer(
+        socket: &UdpSocket,
+        peer: SocketAddr,
+        filename: &str,
+        netascii: bool,
+        pattern: &FilenamePattern,
+        state: &AppState,
+    ) {
+        debug!("TFTP read request for '{}' from {}", filename, peer);
+        let content = resolve(filename, pattern, state, peer).await.map(|content| {
+            if netascii {
+                to_netascii(&content)
+            } else {
+                content.into_bytes()
+            }
+        });
+
+        let content = match content {
+            Ok(content) => content,
+            Err((code, message)) => {
+                debug!("TFTP request from {} failed: {}", peer, message);
+                let _ = socket.send_to(&error_packet(code, &message), peer).await;
+                return;
+            }
+        };
+
+        match send_blocks(socket, peer, &content).await {
+            Ok(()) => info!("Served {} bytes over TFTP to {}", content.len(), peer),
+            Err(e) => warn!("TFTP transfer to {} failed: {}", peer, e),
+        }
+    }
+
+    /// Renders the requested file exactly as an HTTP request with the template's id field would.
+    async fn resolve(
+        filename: &str,
+        pattern: &FilenamePattern,
+        state: &AppState,
+        peer: SocketAddr,
+    ) -> Result<String, (u16, String)> {
+        let (name, id) = pattern
+            .match_filename(filename)
+            .ok_or_else(|| (ERR_FILE_NOT_FOUND, "File not found".to_string()))?;
+
+        let config = send_command(state, |tx| Command::GetConfig {
+            name: name.clone(),
+            response: tx,
+        })
+        .await
+        .map_err(|e| command_error(&e))?;
+
+        // Templates not held locally may still be read through from an upstream,
+        // which only works if they use the default id field.
+        let id_field = config.map_or_else(|| TemplateData::default().id_field, |c| c.id_field);
+        render(state, name, HashMap::from([(id_field, id)]), FetchSource::new(Some(peer.ip()), None))
+            .await
+            .map(|result| result.content)
+            .map_err(|e| command_error(&e))
+    }
+
+    /// Lock-step DATA/ACK exchange; a final short (possibly empty) block ends the transfer.
+    async fn send_blocks(socket: &UdpSocket, peer: SocketAddr, content: &[u8]) -> io::Result<()> {
+        let mut block: u16 = 1;
+        let mut offset = 0;
+        let mut buf = [0u8; MAX_REQUEST_SIZE];
+        loop {
+            let end = (offset + BLOCK_SIZE).min(content.len());
+            let packet = data_packet(block, &content[offset..end]);
+
+            let mut acked = false;
+            for _ in 0..MAX_RETRIES {
+                socket.send_to(&packet, peer).await?;
+                let deadline = tokio::time::Instant::now() + ACK_TIMEOUT;
+                while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await {
+                    let (len, from) = received?;
+                    if from == peer && is_ack(&buf[..len], block) {
+                        acked = true;
+                        break;
+                    }
+                }
+                if acked {
+                    break;
+                }
+            }
+            if !acked {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, format!("no ACK for block {}", block)));
+            }
+
+            if end - offset < BLOCK_SIZE {
+                return Ok(());
+            }
+            offset = end;
+            block = block.wrapping_add(1);
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::commands::commander::ConcreteCommander;
+        use crate::rest::template::DEFAULT_MAX_TEMPLATE_BYTES;
+        use crate::storage::models::{DynamicFieldConfig, GeneratorType, HashingAlgorithm};
+        use crate::storage::{DashMapTemplateStore, RenderedStore, SqliteRenderedStore, TemplateStore};
+        use crate::templating::MiniJinjaEngine;
+        use crate::threads::handler::{ConcreteHandler, Handler};
+        use tokio::sync::mpsc;
+
+        #[test]
+        fn pattern_matches_default_layout() {
+            let pattern = FilenamePattern::parse("{template}/{id}").unwrap();
+            assert_eq!(
+                pattern.match_filename("/switch/AA:BB:CC:DD:EE:FF"),
+                Some(("switch".to_string(), "AA:BB:CC:DD:EE:FF".to_string()))
+            );
+            assert_eq!(pattern.match_filename("switch/"), None);
+            assert_eq!(pattern.match_filename("switch"), None);
+        }
+
+        #[test]
+        fn pattern_matches_embedded_id() {
+            let pattern = FilenamePattern::parse("cfg-{id}.{template}.txt").unwrap();
+            assert_eq!(
+                pattern.match_filename("cfg-0011aabb.core-switch.txt"),
+                Some(("core-switch".to_string(), "0011aabb".to_string()))
+            );
+            assert_eq!(pattern.match_filename("cfg-0011aabb.core-switch.cfg"), None);
+            assert_eq!(pattern.match_filename("x-0011aabb.core-switch.txt"), None);
+        }
+
+        #[test]
+        fn pattern_rejects_invalid_layouts() {
+            assert!(FilenamePattern::parse("{template}").is_err());
+            assert!(FilenamePattern::parse("{template}/{id}/{id}").is_err());
+            assert!(FilenamePattern::parse("{template}{id}").is_err());
+            assert!(FilenamePattern::parse("{template}/{mac}").is_err());
+            assert!(FilenamePattern::parse("{template}/{id").is_err());
+        }
+
+        #[test]
+        fn parses_read_and_write_requests() {
+            assert_eq!(
+                parse_request(b"\x00\x01switch/AA\x00octet\x00").unwrap(),
+                Request::Read {
+                    filename: "switch/AA".to_string(),
+                    netascii: false
+                }
+            );
+            assert_eq!(
+                parse_request(b"\x00\x01switch/AA\x00NETASCII\x00blksize\x001428\x00").unwrap(),
+                Request::Read {
+                    filename: "switch/AA".to_string(),
+                    netascii: true
+                }
+            );
+            assert_eq!(parse_request(b"\x00\x02switch/AA\x00octet\x00").unwrap(), Request::Write);
+            assert_eq!(parse_request(b"\x00\x01a\x00mail\x00").unwrap_err().0, ERR_ILLEGAL_OPERATION);
+            assert_eq!(parse_request(b"\x00\x04\x00\x01").unwrap_err().0, ERR_ILLEGAL_OPERATION);
+        }
+
+        #[test]
+        fn netascii_uses_crlf() {
+            assert_eq!(to_netascii("a\nb\r\nc"), b"a\r\nb\r\nc");
+        }
+
+        async fn bind_server(content: &str) -> TftpServer {
+            let mut template_store = DashMapTemplateStore::new();
+            template_store.init_template(
+                "switch",
+                TemplateData {
+                    template_content: content.to_string(),
+                    dynamic_fields: vec![DynamicFieldConfig {
+                        field_name: "token".to_string(),
+                        generator_type: GeneratorType::Alphanumeric { length: 16 },
+                        hashing_algorithm: HashingAlgorithm::None,
+                        unique: false,
+                    }],
+                    ..TemplateData::default()
+                },
+            );
+            let rendered_store = SqliteRenderedStore::new(":memory:").unwrap();
+            rendered_store.init().unwrap();
+            let commander = ConcreteCommander::new(MiniJinjaEngine::new());
+            let (tx, rx) = mpsc::channel(16);
+            tokio::spawn(async move {
+                let mut handler = ConcreteHandler::new(commander, template_store, rendered_store, rx);
+                handler.main_loop().await;
+            });
+
+            let state = AppState {
+                command_tx: tx,
+                max_template_bytes: DEFAULT_MAX_TEMPLATE_BYTES,
+                #[cfg(feature = "http-client")]
+                upstream: None,
+            };
+            let config = TftpConfig {
+                bind: "127.0.0.1:0".parse().unwrap(),
+                filename_pattern: "{template}/{id}".to_string(),
+            };
+            TftpServer::bind(&config, state).await.unwrap()
+        }
+
+        fn spawn_server(server: TftpServer) -> (SocketAddr, CancellationToken) {
+            let addr = server.local_addr().unwrap();
+            let cancel = CancellationToken::new();
+            tokio::spawn(server.run(cancel.clone()));
+            (addr, cancel)
+        }
+
+        async fn start(content: &str) -> (SocketAddr, CancellationToken) {
+            spawn_server(bind_server(content).await)
+        }
+
+        fn read_request(filename: &str) -> Vec<u8> {
+            let mut packet = OP_RRQ.to_be_bytes().to_vec();
+            packet.extend_from_slice(filename.as_bytes());
+            packet.extend_from_slice(b"\x00octet\x00");
+            packet
+        }
+
+        /// Minimal client: reads a file to completion, or returns the server's error code.
+        async fn fetch(server: SocketAddr, filename: &str) -> Result<Vec<u8>, u16> {
+            let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            socket.send_to(&read_request(filename), server).await.unwrap();
+
+            let mut content = Vec::new();
+            let mut buf = [0u8; 4 + BLOCK_SIZE];
+            loop {
+                let (len, from) = tokio::time::timeout(Duration::from_secs(5), socket.recv_from(&mut buf))
+                    .await
+                    .expect("TFTP server did not respond")
+                    .unwrap();
+                let opcode = u16::from_be_bytes([buf[0], buf[1]]);
+                let block = u16::from_be_bytes([buf[2], buf[3]]);
+                if opcode == OP_ERROR {
+                    return Err(block);
+                }
+                assert_eq!(opcode, OP_DATA);
+                content.extend_from_slice(&buf[4..len]);
+
+                let mut ack = OP_ACK.to_be_bytes().to_vec();
+                ack.extend_from_slice(&block.to_be_bytes());
+                socket.send_to(&ack, from).await.unwrap();
+                if len - 4 < BLOCK_SIZE {
+                    return Ok(content);
+                }
+            }
+        }
+
+        #[tokio::test]
+        async fn serves_rendered_template_and_reuses_cache() {
+            let (addr, cancel) = start("hostname {{ mac_address }} {{ token }}").await;
+
+            let first = fetch(addr, "switch/AA").await.unwrap();
+            assert!(String::from_utf8(first.clone()).unwrap().starts_with("hostname AA"));
+            assert_eq!(fetch(addr, "switch/AA").await.unwrap(), first);
+
+            cancel.cancel();
+        }
+
+        #[tokio::test]
+        async fn serves_multi_block_files() {
+            // 1024 bytes needs a trailing empty block to mark the end
+            let (addr, cancel) = start(&"x".repeat(2 * BLOCK_SIZE)).await;
+            assert_eq!(fetch(addr, "switch/AA").await.unwrap().len(), 2 * BLOCK_SIZE);
+            cancel.cancel();
+        }
+
+        #[tokio::test]
+        async fn unknown_files_and_writes_are_rejected() {
+            let (addr, cancel) = start("hello").await;
+            assert_eq!(fetch(addr, "missing/AA").await, Err(ERR_FILE_NOT_FOUND));
+            assert_eq!(fetch(addr, "not-a-match").await, Err(ERR_FILE_NOT_FOUND));
+
+            let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let mut wrq = read_request("switch/AA");
+            wrq[1] = OP_WRQ as u8;
+            socket.send_to(&wrq, addr).await.unwrap();
+            let mut buf = [0u8; 64];
+            let (len, _) = socket.recv_from(&mut buf).await.unwrap();
+            assert_eq!(&buf[..4], &[0, OP_ERROR as u8, 0, ERR_ACCESS_VIOLATION as u8]);
+            assert!(len > 4);
+
+            cancel.cancel();
+        }
+
+        #[tokio::test]
+        async fn repeated_read_requests_start_one_transfer() {
+            let (addr, cancel) = start("hello").await;
+            let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            socket.send_to(&read_request("switch/AA"), addr).await.unwrap();
+            socket.send_to(&read_request("switch/AA"), addr).await.unwrap();
+
+            // The transfer waits for an ACK, so a second one would send its own block 1
+            let mut buf = [0u8; 64];
+            let (_, from) = socket.recv_from(&mut buf).await.unwrap();
+            assert_eq!(&buf[..4], &[0, OP_DATA as u8, 0, 1]);
+            let second = tokio::time::timeout(Duration::from_millis(300), socket.recv_from(&mut buf)).await;
+            assert!(second.is_err(), "a second transfer was started");
+
+            socket.send_to(&[0, OP_ACK as u8, 0, 1], from).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert_eq!(fetch(addr, "switch/AA").await.unwrap(), b"hello");
+            cancel.cancel();
+        }
+
+        #[tokio::test]
+        async fn requests_beyond_the_transfer_limit_are_refused() {
+            let mut server = bind_server("hello").await;
+            server.transfers = Arc::new(Semaphore::new(1));
+            let (addr, cancel) = spawn_server(server);
+
+            // Never ACKed, so it holds the only slot
+            let waiting = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            waiting.send_to(&read_request("switch/AA"), addr).await.unwrap();
+            let mut buf = [0u8; 64];
+            waiting.recv_from(&mut buf).await.unwrap();
+            assert_eq!(fetch(addr, "switch/BB").await, Err(ERR_UNDEFINED));
+
+            cancel.cancel();
+        }
+
+        #[tokio::test]
+        async fn listener_stops_on_cancellation() {
+            let (addr, cancel) = start("hello").await;
+            cancel.cancel();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+
+            let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            socket.send_to(&read_request("switch/AA"), addr).await.unwrap();
+            let mut buf = [0u8; 64];
+            let reply = tokio::time::timeout(Duration::from_millis(300), socket.recv_from(&mut buf)).await;
+            assert!(reply.is_err() || reply.unwrap().is_err());
+        }
+    }
+}
@@ -0,0 +1,88 @@
+//! Durations of the phases of recent renders, for the render latency endpoint.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::commands::models::{PhaseLatency, RenderPhase};
+use crate::metrics;
+
+/// Samples kept per phase
+pub const LATENCY_SAMPLES: usize = 256;
+
+#[derive(Debug, Default)]
+pub struct RenderLatency {
+    samples: BTreeMap<RenderPhase, VecDeque<Duration>>,
+}
+
+impl RenderLatency {
+    pub fn record(&mut self, phase: RenderPhase, duration: Duration) {
+        metrics::record_render_phase(phase.label(), duration);
+        let samples = self.samples.entry(phase).or_default();
+        if samples.len() == LATENCY_SAMPLES {
+            samples.pop_front();
+        }
+        samples.push_back(duration);
+    }
+
+    /// Records the time since `started` as `phase` and returns now, the start of
+    /// the next phase.
+    pub fn record_since(&mut self, phase: RenderPhase, started: Instant) -> Instant {
+        let now = Instant::now();
+        self.record(phase, now - started);
+        now
+    }
+
+    /// Percentiles of each phase with samples, in phase order
+    pub fn summary(&self) -> Vec<PhaseLatency> {
+        self.samples
+            .iter()
+            .filter(|(_, samples)| !samples.is_empty())
+            .map(|(&phase, samples)| {
+                let recent_ms: Vec<f64> = samples.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+                let mut sorted = recent_ms.clone();
+                sorted.sort_by(f64::total_cmp);
+                PhaseLatency {
+                    phase,
+                    samples: sorted.len(),
+                    p50_ms: percentile(&sorted, 50),
+                    p90_ms: percentile(&sorted, 90),
+                    p99_ms: percentile(&sorted, 99),
+                    max_ms: sorted[sorted.len() - 1],
+                    recent_ms,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Nearest-rank percentile of non-empty `sorted`
+fn percentile(sorted: &[f64], percent: usize) -> f64 {
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_latest_samples_and_reports_percentiles() {
+        let mut latency = RenderLatency::default();
+        for ms in 1..=(LATENCY_SAMPLES as u64 + 100) {
+            latency.record(RenderPhase::Render, Duration::from_millis(ms));
+        }
+        latency.record(RenderPhase::CacheLookup, Duration::from_millis(2));
+
+        let summary = latency.summary();
+        assert_eq!(summary.len(), 2);
+        assert_eq!(summary[0].phase, RenderPhase::CacheLookup);
+        assert_eq!(summary[0].p99_ms, 2.0);
+
+        let render = &summary[1];
+        assert_eq!(render.samples, LATENCY_SAMPLES);
+        assert_eq!(render.recent_ms[0], 101.0);
+        assert_eq!(render.p50_ms, 228.0);
+        assert_eq!(render.p90_ms, 331.0);
+        assert_eq!(render.max_ms, 356.0);
+    }
+}
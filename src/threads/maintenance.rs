@@ -0,0 +1,231 @@
+use log::{info, warn};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{sleep_until, Instant};
+use tokio_util::sync::CancellationToken;
+
+use crate::commands::models::{Command, MaintenanceJob, MaintenanceRun};
+
+/// Seconds between `expire_renders` runs when the `maintenance` config does not list it
+pub const DEFAULT_EXPIRE_INTERVAL_SECONDS: u64 = 300;
+
+/// Job to run periodically, from the `maintenance` config list
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct MaintenanceSchedule {
+    pub job: MaintenanceJob,
+    pub interval_seconds: u64,
+}
+
+impl MaintenanceSchedule {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.interval_seconds == 0 {
+            return Err(format!("interval_seconds of maintenance job {:?} must be greater than 0", self.job));
+        }
+        Ok(())
+    }
+
+    fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_seconds)
+    }
+}
+
+/// `schedules` plus an `expire_renders` run every [`DEFAULT_EXPIRE_INTERVAL_SECONDS`]
+/// unless one is configured. Any template may be given a `cache_ttl_seconds`, also
+/// through the config API, and its expired renders are deleted without further setup.
+pub fn with_default_jobs(mut schedules: Vec<MaintenanceSchedule>) -> Vec<MaintenanceSchedule> {
+    if !schedules.iter().any(|s| s.job == MaintenanceJob::ExpireRenders) {
+        schedules.push(MaintenanceSchedule {
+            job: MaintenanceJob::ExpireRenders,
+            interval_seconds: DEFAULT_EXPIRE_INTERVAL_SECONDS,
+        });
+    }
+    schedules
+}
+
+/// Runs each scheduled job through the handler every `interval_seconds`, the first
+/// run one interval after startup. The next run of a job is timed from the end of
+/// the previous one, so a slow run never queues another behind it. Returns once
+/// `cancel_token` fires or the handler is gone.
+pub async fn run(tx: mpsc::Sender<Command>, schedules: Vec<MaintenanceSchedule>, cancel_token: CancellationToken) {
+    let start = Instant::now();
+    let mut next_runs: Vec<Instant> = schedules.iter().map(|s| start + s.interval()).collect();
+
+    while let Some((index, due)) = next_runs.iter().copied().enumerate().min_by_key(|(_, due)| *due) {
+        let job = schedules[index].job;
+        let run = tokio::select! {
+            biased;
+            _ = cancel_token.cancelled() => {
+                info!("Maintenance scheduler stopped");
+                return;
+            }
+            run = async {
+                sleep_until(due).await;
+                send_run(&tx, job).await
+            } => run,
+        };
+        let Some(run) = run else {
+            warn!("Maintenance scheduler stopped: handler unavailable");
+            return;
+        };
+
+        match &run.error {
+            Some(error) => warn!("Maintenance job {:?} failed: {}", job, error),
+            None => info!(
+                "Maintenance job {:?} finished in {} ms, {} row(s) affected",
+                job, run.duration_ms, run.rows_affected
+            ),
+        }
+        next_runs[index] = Instant::now() + schedules[index].interval();
+    }
+}
+
+async fn send_run(tx: &mpsc::Sender<Command>, job: MaintenanceJob) -> Option<MaintenanceRun> {
+    let (response, rx) = oneshot::channel();
+    tx.send(Command::RunMaintenance { job, response }).await.ok()?;
+    rx.await.ok()?.ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::commander::ConcreteCommander;
+    use crate::storage::models::{TemplateData, ValueSources};
+    use crate::storage::{DashMapTemplateStore, RenderedStore, SqliteRenderedStore, TemplateStore};
+    use crate::templating::MiniJinjaEngine;
+    use crate::threads::handler::{ConcreteHandler, Handler};
+    use std::sync::{Arc, Mutex};
+
+    fn every(seconds: u64) -> Vec<MaintenanceSchedule> {
+        vec![MaintenanceSchedule {
+            job: MaintenanceJob::PurgeOrphans,
+            interval_seconds: seconds,
+        }]
+    }
+
+    /// Answers every maintenance command, recording when it arrived relative to the
+    /// start of the test and failing the second run.
+    fn spawn_handler(mut rx: mpsc::Receiver<Command>) -> Arc<Mutex<Vec<Duration>>> {
+        let start = Instant::now();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorded = seen.clone();
+        tokio::spawn(async move {
+            while let Some(command) = rx.recv().await {
+                if let Command::RunMaintenance { job, response } = command {
+                    let mut seen = recorded.lock().unwrap();
+                    seen.push(start.elapsed());
+                    let _ = response.send(Ok(MaintenanceRun {
+                        job,
+                        started_at: "2024-01-01T00:00:00.000Z".to_string(),
+                        duration_ms: 0,
+                        rows_affected: 1,
+                        error: (seen.len() == 2).then(|| "Database error: locked".to_string()),
+                    }));
+                }
+            }
+        });
+        seen
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn runs_jobs_every_interval_and_keeps_going_after_failures() {
+        let (tx, rx) = mpsc::channel(4);
+        let seen = spawn_handler(rx);
+        let token = CancellationToken::new();
+        let scheduler = tokio::spawn(run(tx, every(60), token.clone()));
+
+        tokio::time::sleep(Duration::from_secs(200)).await;
+        token.cancel();
+        scheduler.await.unwrap();
+
+        let seconds: Vec<u64> = seen.lock().unwrap().iter().map(Duration::as_secs).collect();
+        assert_eq!(seconds, vec![60, 120, 180]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancelled_scheduler_sends_nothing() {
+        let (tx, mut rx) = mpsc::channel(4);
+        let token = CancellationToken::new();
+        token.cancel();
+
+        run(tx, every(1), token).await;
+
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_when_handler_is_gone() {
+        let (tx, rx) = mpsc::channel(4);
+        drop(rx);
+
+        run(tx, every(1), CancellationToken::new()).await;
+    }
+
+    #[test]
+    fn expire_renders_is_scheduled_unless_configured() {
+        assert_eq!(
+            with_default_jobs(every(60)),
+            vec![
+                every(60)[0].clone(),
+                MaintenanceSchedule {
+                    job: MaintenanceJob::ExpireRenders,
+                    interval_seconds: DEFAULT_EXPIRE_INTERVAL_SECONDS,
+                },
+            ]
+        );
+        let configured = vec![MaintenanceSchedule {
+            job: MaintenanceJob::ExpireRenders,
+            interval_seconds: 60,
+        }];
+        assert_eq!(with_default_jobs(configured.clone()), configured);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expired_renders_are_deleted_without_maintenance_config() {
+        let path = std::env::temp_dir().join(format!("provisionr-expire-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let db = path.to_str().unwrap();
+        let rendered_store = SqliteRenderedStore::new(db).unwrap();
+        rendered_store.init().unwrap();
+        rendered_store.store_rendered("switch", "AA", "content", "", "", &ValueSources::default(), "", 0).unwrap();
+        let count = || -> i64 {
+            rusqlite::Connection::open(db)
+                .unwrap()
+                .query_row("SELECT COUNT(*) FROM rendered_templates", [], |row| row.get(0))
+                .unwrap()
+        };
+        rusqlite::Connection::open(db)
+            .unwrap()
+            .execute("UPDATE rendered_templates SET created_at = '2000-01-01T00:00:00.000Z'", [])
+            .unwrap();
+        let mut template_store = DashMapTemplateStore::new();
+        template_store.init_template(
+            "switch",
+            TemplateData {
+                cache_ttl_seconds: Some(60),
+                ..TemplateData::default()
+            },
+        );
+
+        let (tx, rx) = mpsc::channel(4);
+        let token = CancellationToken::new();
+        let commander = ConcreteCommander::new(MiniJinjaEngine::new());
+        let mut handler = ConcreteHandler::new_with_token(commander, template_store, rendered_store, rx, token.clone());
+        tokio::spawn(async move { handler.main_loop().await });
+        let scheduler = tokio::spawn(run(tx, with_default_jobs(Vec::new()), token.clone()));
+
+        assert_eq!(count(), 1);
+        tokio::time::sleep(Duration::from_secs(DEFAULT_EXPIRE_INTERVAL_SECONDS + 1)).await;
+        token.cancel();
+        scheduler.await.unwrap();
+
+        assert_eq!(count(), 0);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn zero_interval_is_rejected() {
+        assert!(every(0)[0].validate().is_err());
+        assert!(every(1)[0].validate().is_ok());
+    }
+}
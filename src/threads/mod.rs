@@ -1 +1,5 @@
 pub mod handler;
+pub mod latency;
+pub mod maintenance;
+pub mod warmup;
+pub mod webhook;
@@ -0,0 +1,148 @@
+use log::{info, warn};
+use serde::Deserialize;
+use std::time::Instant;
+use tokio::sync::{mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
+
+use crate::commands::models::{Command, WarmupResult};
+
+/// Template to warm after startup, from the `warmup` config list
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct WarmupTarget {
+    pub template: String,
+    /// Ids whose cached renders are re-rendered to check they still match
+    #[serde(default)]
+    pub ids: Vec<String>,
+}
+
+/// Warms `targets` in order through the handler, one command per template so
+/// requests arriving meanwhile are not held up, then logs a summary. Stops at the
+/// next template once `cancel_token` fires and returns what was warmed so far.
+pub async fn run(
+    tx: mpsc::Sender<Command>,
+    targets: Vec<WarmupTarget>,
+    cancel_token: CancellationToken,
+) -> Vec<WarmupResult> {
+    let started = Instant::now();
+    let total = targets.len();
+    let mut results = Vec::with_capacity(total);
+
+    for target in targets {
+        let (response, rx) = oneshot::channel();
+        let command = Command::WarmTemplate {
+            name: target.template,
+            ids: target.ids,
+            response,
+        };
+        let result = tokio::select! {
+            biased;
+            _ = cancel_token.cancelled() => {
+                info!("Warm-up cancelled after {} of {} template(s)", results.len(), total);
+                return results;
+            }
+            result = async {
+                tx.send(command).await.ok()?;
+                rx.await.ok()?.ok()
+            } => result,
+        };
+        let Some(result) = result else {
+            warn!("Warm-up stopped after {} of {} template(s): handler unavailable", results.len(), total);
+            return results;
+        };
+
+        if let Some(error) = &result.error {
+            warn!("Warm-up of template '{}' failed: {}", result.template, error);
+        }
+        if !result.stale_ids.is_empty() {
+            warn!(
+                "Cached renders of template '{}' no longer match a fresh render: {}",
+                result.template,
+                result.stale_ids.join(", ")
+            );
+        }
+        results.push(result);
+    }
+
+    let ready = results.iter().filter(|r| r.ok).count();
+    let verified: usize = results.iter().map(|r| r.verified_ids.len()).sum();
+    let stale: usize = results.iter().map(|r| r.stale_ids.len()).sum();
+    info!(
+        "Warm-up finished in {:?}: {} of {} template(s) ready, {} cached render(s) verified, {} stale",
+        started.elapsed(),
+        ready,
+        total,
+        verified,
+        stale
+    );
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets(names: &[&str]) -> Vec<WarmupTarget> {
+        names
+            .iter()
+            .map(|name| WarmupTarget {
+                template: name.to_string(),
+                ids: vec!["AA".to_string()],
+            })
+            .collect()
+    }
+
+    /// Answers every warm-up command, failing templates named "broken".
+    fn spawn_handler(mut rx: mpsc::Receiver<Command>) -> tokio::task::JoinHandle<Vec<String>> {
+        tokio::spawn(async move {
+            let mut seen = Vec::new();
+            while let Some(command) = rx.recv().await {
+                if let Command::WarmTemplate { name, ids, response } = command {
+                    let ok = name != "broken";
+                    seen.push(name.clone());
+                    let _ = response.send(Ok(WarmupResult {
+                        template: name,
+                        ok,
+                        error: (!ok).then(|| "Template not found: broken".to_string()),
+                        verified_ids: ids,
+                        ..WarmupResult::default()
+                    }));
+                }
+            }
+            seen
+        })
+    }
+
+    #[tokio::test]
+    async fn warms_every_target_in_order_and_keeps_going_after_failures() {
+        let (tx, rx) = mpsc::channel(4);
+        let handler = spawn_handler(rx);
+
+        let results = run(tx, targets(&["pxe", "broken", "kickstart"]), CancellationToken::new()).await;
+
+        let outcome: Vec<(&str, bool)> = results.iter().map(|r| (r.template.as_str(), r.ok)).collect();
+        assert_eq!(outcome, vec![("pxe", true), ("broken", false), ("kickstart", true)]);
+        assert_eq!(results[0].verified_ids, vec!["AA"]);
+        assert_eq!(handler.await.unwrap(), vec!["pxe", "broken", "kickstart"]);
+    }
+
+    #[tokio::test]
+    async fn cancelled_warmup_sends_nothing() {
+        let (tx, mut rx) = mpsc::channel(4);
+        let token = CancellationToken::new();
+        token.cancel();
+
+        let results = run(tx, targets(&["pxe", "kickstart"]), token).await;
+
+        assert!(results.is_empty());
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn stops_when_handler_is_gone() {
+        let (tx, rx) = mpsc::channel(4);
+        drop(rx);
+
+        let results = run(tx, targets(&["pxe"]), CancellationToken::new()).await;
+        assert!(results.is_empty());
+    }
+}
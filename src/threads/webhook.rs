@@ -0,0 +1,261 @@
+use log::{debug, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::storage::models::FailureWebhook;
+
+#[cfg(feature = "http-client")]
+const DELIVERY_TIMEOUT_SECS: u64 = 5;
+
+/// Payload POSTed to a template's failure webhook.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RenderFailure {
+    pub template: String,
+    /// Value of the template's id field, when the request supplied one
+    pub id_value: Option<String>,
+    pub error_code: String,
+    pub error: String,
+    /// RFC 3339 UTC time of the failure
+    pub timestamp: String,
+}
+
+impl RenderFailure {
+    pub fn new(template: &str, id_value: Option<&str>, error_code: &str, error: String) -> Self {
+        Self {
+            template: template.to_string(),
+            id_value: id_value.map(str::to_string),
+            error_code: error_code.to_string(),
+            error,
+            timestamp: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct WebhookDelivery {
+    pub url: String,
+    pub payload: RenderFailure,
+}
+
+type DedupKey = (String, Option<String>, String);
+
+/// Queues failure notifications for asynchronous delivery, suppressing repeats of
+/// the same template, id value and error code inside the webhook's dedup window.
+pub struct FailureNotifier {
+    tx: Option<UnboundedSender<WebhookDelivery>>,
+    suppressed_until: HashMap<DedupKey, Instant>,
+}
+
+impl FailureNotifier {
+    /// Creates a notifier and the receiver to pass to [`deliver_failures`].
+    pub fn new() -> (Self, UnboundedReceiver<WebhookDelivery>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let notifier = Self {
+            tx: Some(tx),
+            suppressed_until: HashMap::new(),
+        };
+        (notifier, rx)
+    }
+
+    /// Notifier that drops every notification.
+    pub fn disabled() -> Self {
+        Self {
+            tx: None,
+            suppressed_until: HashMap::new(),
+        }
+    }
+
+    /// Queues `failure` for delivery unless an identical failure was queued within the
+    /// dedup window. Returns whether it was queued.
+    pub fn notify(&mut self, webhook: &FailureWebhook, failure: RenderFailure) -> bool {
+        self.notify_at(webhook, failure, Instant::now())
+    }
+
+    fn notify_at(&mut self, webhook: &FailureWebhook, failure: RenderFailure, now: Instant) -> bool {
+        let Some(tx) = &self.tx else {
+            return false;
+        };
+
+        self.suppressed_until.retain(|_, until| *until > now);
+
+        let key = (
+            failure.template.clone(),
+            failure.id_value.clone(),
+            failure.error_code.clone(),
+        );
+        if self.suppressed_until.contains_key(&key) {
+            debug!(
+                "Suppressing duplicate failure webhook for {}:{} ({})",
+                key.0,
+                key.1.as_deref().unwrap_or("-"),
+                key.2
+            );
+            return false;
+        }
+        if webhook.dedup_window_seconds > 0 {
+            self.suppressed_until
+                .insert(key, now + Duration::from_secs(webhook.dedup_window_seconds));
+        }
+
+        tx.send(WebhookDelivery {
+            url: webhook.url.clone(),
+            payload: failure,
+        })
+        .is_ok()
+    }
+}
+
+/// POSTs queued failures until every notifier is dropped. Delivery errors are logged only.
+#[cfg(feature = "http-client")]
+pub async fn deliver_failures(mut rx: UnboundedReceiver<WebhookDelivery>) {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(DELIVERY_TIMEOUT_SECS))
+        .build()
+        .expect("Failed to build webhook HTTP client");
+
+    while let Some(delivery) = rx.recv().await {
+        let result = client
+            .post(&delivery.url)
+            .json(&delivery.payload)
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status());
+        match result {
+            Ok(_) => debug!(
+                "Delivered failure webhook for template '{}' to {}",
+                delivery.payload.template, delivery.url
+            ),
+            Err(e) => warn!(
+                "Failed to deliver failure webhook for template '{}' to {}: {}",
+                delivery.payload.template, delivery.url, e
+            ),
+        }
+    }
+}
+
+/// Without an HTTP client failures can only be logged.
+#[cfg(not(feature = "http-client"))]
+pub async fn deliver_failures(mut rx: UnboundedReceiver<WebhookDelivery>) {
+    while let Some(delivery) = rx.recv().await {
+        warn!(
+            "Not delivering failure webhook for template '{}' to {}: built without the http-client feature",
+            delivery.payload.template, delivery.url
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn webhook(url: &str, window: u64) -> FailureWebhook {
+        FailureWebhook {
+            url: url.to_string(),
+            dedup_window_seconds: window,
+        }
+    }
+
+    fn failure(id: &str, code: &str) -> RenderFailure {
+        RenderFailure::new("kickstart", Some(id), code, format!("{} failed", code))
+    }
+
+    #[test]
+    fn repeats_are_suppressed_within_window() {
+        let (mut notifier, mut rx) = FailureNotifier::new();
+        let hook = webhook("http://localhost/hook", 60);
+        let start = Instant::now();
+
+        assert!(notifier.notify_at(&hook, failure("AA", "missing_field"), start));
+        assert!(!notifier.notify_at(&hook, failure("AA", "missing_field"), start + Duration::from_secs(59)));
+        assert!(notifier.notify_at(&hook, failure("AA", "missing_field"), start + Duration::from_secs(61)));
+
+        assert_eq!(std::iter::from_fn(|| rx.try_recv().ok()).count(), 2);
+    }
+
+    #[test]
+    fn dedup_is_keyed_by_template_id_and_code() {
+        let (mut notifier, _rx) = FailureNotifier::new();
+        let hook = webhook("http://localhost/hook", 60);
+        let now = Instant::now();
+
+        assert!(notifier.notify_at(&hook, failure("AA", "missing_field"), now));
+        assert!(notifier.notify_at(&hook, failure("BB", "missing_field"), now));
+        assert!(notifier.notify_at(&hook, failure("AA", "template_render"), now));
+        let other_template = RenderFailure::new("cloud-init", Some("AA"), "missing_field", String::new());
+        assert!(notifier.notify_at(&hook, other_template, now));
+        assert!(!notifier.notify_at(&hook, failure("AA", "missing_field"), now));
+    }
+
+    #[test]
+    fn zero_window_disables_dedup() {
+        let (mut notifier, _rx) = FailureNotifier::new();
+        let hook = webhook("http://localhost/hook", 0);
+        let now = Instant::now();
+
+        assert!(notifier.notify_at(&hook, failure("AA", "missing_field"), now));
+        assert!(notifier.notify_at(&hook, failure("AA", "missing_field"), now));
+    }
+
+    #[test]
+    fn disabled_notifier_drops_everything() {
+        let mut notifier = FailureNotifier::disabled();
+        assert!(!notifier.notify(&webhook("http://localhost/hook", 60), failure("AA", "missing_field")));
+    }
+
+    #[cfg(feature = "http-client")]
+    mod delivery {
+        use super::*;
+        use serde_json::json;
+        use wiremock::matchers::{body_partial_json, method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        #[tokio::test]
+        async fn delivers_payload_to_webhook() {
+            let server = MockServer::start().await;
+            Mock::given(method("POST"))
+                .and(path("/hook"))
+                .and(body_partial_json(json!({
+                    "template": "kickstart",
+                    "id_value": "AA",
+                    "error_code": "missing_field",
+                    "error": "missing_field failed",
+                })))
+                .respond_with(ResponseTemplate::new(204))
+                .expect(1)
+                .mount(&server)
+                .await;
+
+            let (mut notifier, rx) = FailureNotifier::new();
+            let hook = webhook(&format!("{}/hook", server.uri()), 60);
+            notifier.notify(&hook, failure("AA", "missing_field"));
+            notifier.notify(&hook, failure("AA", "missing_field"));
+            drop(notifier);
+
+            deliver_failures(rx).await;
+
+            let requests = server.received_requests().await.unwrap();
+            let body: serde_json::Value = requests[0].body_json().unwrap();
+            assert!(body["timestamp"].as_str().unwrap().ends_with('Z'));
+        }
+
+        #[tokio::test]
+        async fn delivery_failure_does_not_stop_later_deliveries() {
+            let server = MockServer::start().await;
+            Mock::given(method("POST"))
+                .respond_with(ResponseTemplate::new(500))
+                .expect(2)
+                .mount(&server)
+                .await;
+
+            let (mut notifier, rx) = FailureNotifier::new();
+            let hook = webhook(&server.uri(), 60);
+            notifier.notify(&hook, failure("AA", "missing_field"));
+            notifier.notify(&hook, failure("BB", "missing_field"));
+            drop(notifier);
+
+            deliver_failures(rx).await;
+        }
+    }
+}
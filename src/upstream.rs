@@ -0,0 +1,329 @@
+//! Read-through mode: templates missing locally are pulled from an upstream
+//! provisionr's bundle endpoint, installed through the normal commands and then
+//! rendered locally.
+
+use serde::Deserialize;
+
+fn default_ttl_seconds() -> u64 {
+    300
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct UpstreamConfig {
+    /// Base URL of the upstream provisionr, e.g. `http://central:3000`
+    pub url: String,
+    /// How long a pulled template is used before it is refreshed from the upstream
+    #[serde(default = "default_ttl_seconds")]
+    pub ttl_seconds: u64,
+}
+
+#[cfg(feature = "http-client")]
+pub use client::Upstream;
+
+#[cfg(feature = "http-client")]
+mod client {
+    use dashmap::DashMap;
+    use log::{info, warn};
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    use super::UpstreamConfig;
+    use crate::commands::models::{Command, RenderResult, SetTemplateOptions};
+    use crate::rest::command::{send_command, CommandError};
+    use crate::rest::state::AppState;
+    use crate::storage::models::{FetchSource, TemplateBundle, ValuesFormat};
+
+    const FETCH_TIMEOUT_SECS: u64 = 5;
+
+    pub struct Upstream {
+        client: reqwest::Client,
+        base_url: reqwest::Url,
+        ttl: Duration,
+        /// When each template was last pulled; templates created locally never appear here
+        pulled_at: DashMap<String, Instant>,
+    }
+
+    impl Upstream {
+        pub fn new(config: &UpstreamConfig) -> Result<Self, String> {
+            let base_url = reqwest::Url::parse(&config.url)
+                .map_err(|e| format!("Invalid upstream URL '{}': {}", config.url, e))?;
+            let client = reqwest::Client::builder()
+                .timeout(Duration::from_secs(FETCH_TIMEOUT_SECS))
+                .build()
+                .map_err(|e| format!("Failed to build upstream HTTP client: {}", e))?;
+            Ok(Self {
+                client,
+                base_url,
+                ttl: Duration::from_secs(config.ttl_seconds),
+                pulled_at: DashMap::new(),
+            })
+        }
+
+        /// Renders `name`, pulling it from the upstream first if the local copy is
+        /// missing or older than the TTL. Upstream failures fall back to the local
+        /// result, so an unreachable upstream looks like a plain "not found", or a
+        /// render of the fallback template when one is configured.
+        pub async fn render(
+            &self,
+            state: &AppState,
+            name: String,
+            query_values: HashMap<String, String>,
+            source: FetchSource,
+        ) -> Result<RenderResult, CommandError> {
+            // A missing template is pulled before rendering, so that a configured
+            // fallback template is only rendered, and cached, when the upstream lacks it too
+            let local = send_command(state, |tx| Command::TemplateExists {
+                name: name.clone(),
+                response: tx,
+            })
+            .await?;
+            if !local {
+                if let Err(e) = self.pull(state, &name).await {
+                    warn!("Template '{}' not available from upstream: {}", name, e);
+                }
+            } else if self.is_stale(&name)
+                && let Err(e) = self.pull(state, &name).await
+            {
+                warn!("Keeping local copy of '{}', refresh from upstream failed: {}", name, e);
+            }
+
+            self.render_local(state, name, query_values, source).await
+        }
+
+        fn is_stale(&self, name: &str) -> bool {
+            self.pulled_at
+                .get(name)
+                .is_some_and(|pulled| pulled.elapsed() >= self.ttl)
+        }
+
+        async fn render_local(
+            &self,
+            state: &AppState,
+            name: String,
+            query_values: HashMap<String, String>,
+            source: FetchSource,
+        ) -> Result<RenderResult, CommandError> {
+            send_command(state, |tx| Command::RenderTemplate {
+                name,
+                query_values,
+                source,
+                sent_at: Instant::now(),
+                response: tx,
+            })
+            .await
+        }
+
+        async fn fetch_bundle(&self, name: &str) -> Result<TemplateBundle, String> {
+            let mut url = self.base_url.clone();
+            url.path_segments_mut()
+                .map_err(|_| format!("Upstream URL '{}' cannot be a base", self.base_url))?
+                .pop_if_empty()
+                .extend(["api", "v1", "template", name, "bundle"]);
+
+            self.client
+                .get(url)
+                .send()
+                .await
+                .and_then(|resp| resp.error_for_status())
+                .map_err(|e| e.to_string())?
+                .json::<TemplateBundle>()
+                .await
+                .map_err(|e| format!("Invalid bundle: {}", e))
+        }
+
+        /// Fetches the bundle and installs it through the same commands the REST API uses,
+        /// so upstream content gets the same validation as a local upload.
+        async fn pull(&self, state: &AppState, name: &str) -> Result<(), String> {
+            let bundle = self.fetch_bundle(name).await?;
+
+            send_command(state, |tx| Command::SetTemplate {
+                name: name.to_string(),
+                content: bundle.template_content,
+                options: SetTemplateOptions {
+                    overwrite: true,
+                    ..SetTemplateOptions::default()
+                },
+                response: tx,
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+
+            send_command(state, |tx| Command::SetConfig {
+                name: name.to_string(),
+                config: Box::new(bundle.config),
+                changed_by: Some(format!("upstream {}", self.base_url)),
+                response: tx,
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+
+            if let Some(yaml) = bundle.values_yaml {
+                send_command(state, |tx| Command::SetValues {
+                    name: name.to_string(),
+                    content: yaml,
+                    format: ValuesFormat::Yaml,
+                    response: tx,
+                })
+                .await
+                .map_err(|e| e.to_string())?;
+            }
+
+            send_command(state, |tx| Command::SetTestCases {
+                name: name.to_string(),
+                cases: bundle.test_cases,
+                response: tx,
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+
+            self.pulled_at.insert(name.to_string(), Instant::now());
+            info!("Pulled template '{}' from upstream {}", name, self.base_url);
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::commands::commander::ConcreteCommander;
+        use crate::error::ProvisionrError;
+        use crate::rest::template::DEFAULT_MAX_TEMPLATE_BYTES;
+        use crate::storage::{DashMapTemplateStore, RenderedStore, SqliteRenderedStore};
+        use crate::templating::MiniJinjaEngine;
+        use crate::threads::handler::{ConcreteHandler, Handler};
+        use serde_json::json;
+        use std::sync::Arc;
+        use tokio::sync::mpsc;
+        use wiremock::matchers::{method, path};
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        fn start_local(upstream_url: &str, ttl_seconds: u64) -> AppState {
+            let rendered_store = SqliteRenderedStore::new(":memory:").unwrap();
+            rendered_store.init().unwrap();
+            let commander = ConcreteCommander::new(MiniJinjaEngine::new());
+            let (tx, rx) = mpsc::channel(16);
+            tokio::spawn(async move {
+                let mut handler =
+                    ConcreteHandler::new(commander, DashMapTemplateStore::new(), rendered_store, rx);
+                handler.main_loop().await;
+            });
+
+            let upstream = Upstream::new(&UpstreamConfig {
+                url: upstream_url.to_string(),
+                ttl_seconds,
+            })
+            .unwrap();
+            AppState {
+                command_tx: tx,
+                max_template_bytes: DEFAULT_MAX_TEMPLATE_BYTES,
+                upstream: Some(Arc::new(upstream)),
+            }
+        }
+
+        fn bundle(content: &str) -> serde_json::Value {
+            json!({
+                "template_content": content,
+                "values_yaml": "site: london",
+                "config": { "id_field": "mac_address", "param_defaults": { "vlan": "100" } },
+                "test_cases": []
+            })
+        }
+
+        fn query(mac: &str) -> HashMap<String, String> {
+            HashMap::from([("mac_address".to_string(), mac.to_string())])
+        }
+
+        #[tokio::test]
+        async fn pulls_unknown_template_once_and_renders_locally() {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .and(path("/api/v1/template/kickstart/bundle"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(bundle("{{ site }} vlan {{ vlan }}")))
+                .expect(1)
+                .mount(&server)
+                .await;
+
+            let state = start_local(&server.uri(), 300);
+            let upstream = state.upstream.clone().unwrap();
+
+            let first = upstream.render(&state, "kickstart".to_string(), query("AA"), FetchSource::default()).await;
+            assert_eq!(first.ok().map(|r| r.content).as_deref(), Some("london vlan 100"));
+
+            let second = upstream.render(&state, "kickstart".to_string(), query("BB"), FetchSource::default()).await;
+            assert_eq!(second.ok().map(|r| r.content).as_deref(), Some("london vlan 100"));
+        }
+
+        #[tokio::test]
+        async fn missing_upstream_template_is_not_found() {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .respond_with(ResponseTemplate::new(404))
+                .mount(&server)
+                .await;
+
+            let state = start_local(&server.uri(), 300);
+            let upstream = state.upstream.clone().unwrap();
+
+            let result = upstream.render(&state, "missing".to_string(), query("AA"), FetchSource::default()).await;
+            assert!(matches!(
+                result,
+                Err(CommandError::Handler(ProvisionrError::TemplateNotFound(_)))
+            ));
+        }
+
+        #[tokio::test]
+        async fn unreachable_upstream_is_not_found() {
+            let state = start_local("http://127.0.0.1:9", 300);
+            let upstream = state.upstream.clone().unwrap();
+
+            let result = upstream.render(&state, "kickstart".to_string(), query("AA"), FetchSource::default()).await;
+            assert!(matches!(
+                result,
+                Err(CommandError::Handler(ProvisionrError::TemplateNotFound(_)))
+            ));
+        }
+
+        #[tokio::test]
+        async fn invalid_upstream_template_is_not_installed() {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(bundle("{{ broken")))
+                .mount(&server)
+                .await;
+
+            let state = start_local(&server.uri(), 300);
+            let upstream = state.upstream.clone().unwrap();
+
+            let result = upstream.render(&state, "kickstart".to_string(), query("AA"), FetchSource::default()).await;
+            assert!(matches!(
+                result,
+                Err(CommandError::Handler(ProvisionrError::TemplateNotFound(_)))
+            ));
+        }
+
+        #[tokio::test]
+        async fn stale_copy_is_refreshed_after_ttl() {
+            let server = MockServer::start().await;
+            Mock::given(method("GET"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(bundle("v1")))
+                .up_to_n_times(1)
+                .expect(1)
+                .mount(&server)
+                .await;
+            Mock::given(method("GET"))
+                .respond_with(ResponseTemplate::new(200).set_body_json(bundle("v2")))
+                .expect(1)
+                .mount(&server)
+                .await;
+
+            let state = start_local(&server.uri(), 0);
+            let upstream = state.upstream.clone().unwrap();
+
+            let first = upstream.render(&state, "kickstart".to_string(), query("AA"), FetchSource::default()).await;
+            assert_eq!(first.ok().map(|r| r.content).as_deref(), Some("v1"));
+
+            let second = upstream.render(&state, "kickstart".to_string(), query("BB"), FetchSource::default()).await;
+            assert_eq!(second.ok().map(|r| r.content).as_deref(), Some("v2"));
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use assert_cmd::Command;
+use rusqlite::Connection;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn temp_db_path() -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "provisionr-cli-{}-{}.db",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::SeqCst)
+    ))
+}
+
+/// Database with the server's tables, one healthy render and one whose generated
+/// values are not YAML.
+fn seeded_db() -> PathBuf {
+    let path = temp_db_path();
+    let conn = Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE rendered_templates (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            template_name TEXT NOT NULL,
+            id_field_value TEXT NOT NULL,
+            rendered_content TEXT NOT NULL,
+            generated_values TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            render_count INTEGER NOT NULL DEFAULT 0,
+            last_served_at TEXT,
+            UNIQUE(template_name, id_field_value)
+        );
+        CREATE TABLE unique_values (
+            template_name TEXT NOT NULL,
+            field_name TEXT NOT NULL,
+            value_hash TEXT NOT NULL,
+            id_field_value TEXT NOT NULL,
+            PRIMARY KEY (template_name, field_name, value_hash)
+        );
+        INSERT INTO rendered_templates (template_name, id_field_value, rendered_content, generated_values, created_at)
+        VALUES ('kickstart', 'AA', 'ok', '{}', '2024-01-01T00:00:00.000Z'),
+               ('kickstart', 'BB', 'broken', 'token: [', '2024-01-01T00:00:00.000Z');",
+    )
+    .unwrap();
+    path
+}
+
+fn check(db: &PathBuf, extra: &[&str]) -> Command {
+    let mut cmd = Command::cargo_bin("provisionr").unwrap();
+    cmd.arg("--check").arg("--db").arg(db).args(extra);
+    cmd
+}
+
+fn json_document(output: &std::process::Output) -> Value {
+    serde_json::from_slice(&output.stdout).expect("stdout should be a single JSON document")
+}
+
+#[test]
+fn json_check_reports_violations_with_validation_exit_code() {
+    let db = seeded_db();
+
+    let output = check(&db, &["--output", "json"]).output().unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+    let document = json_document(&output);
+    assert_eq!(document["status"], "error");
+    assert_eq!(document["exit_code"], 2);
+    assert_eq!(document["result"]["rows_checked"], 2);
+    assert_eq!(document["result"]["violations"][0]["id_field_value"], "BB");
+    assert_eq!(document["errors"][0]["code"], "consistency_violations");
+    std::fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn json_check_with_repair_succeeds() {
+    let db = seeded_db();
+
+    let output = check(&db, &["--repair", "--output", "json"]).output().unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    let document = json_document(&output);
+    assert_eq!(document["status"], "ok");
+    assert_eq!(document["result"]["repaired"], 1);
+    assert_eq!(document["warnings"][0], "1 violation(s) repaired");
+    assert_eq!(document["errors"], serde_json::json!([]));
+
+    let output = check(&db, &["--output", "json"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    std::fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn text_check_prints_summary_on_stdout_and_diagnostics_on_stderr() {
+    let db = seeded_db();
+
+    let output = check(&db, &[]).output().unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stdout.starts_with("Checked 2 cached render(s)"), "{}", stdout);
+    assert!(stderr.contains("row 2 kickstart/BB: generated_values is not valid YAML"), "{}", stderr);
+    assert!(stderr.contains("error[consistency_violations]: 1 violation(s) not repaired"), "{}", stderr);
+    std::fs::remove_file(db).unwrap();
+}
+
+#[test]
+fn missing_database_is_an_io_error_and_is_not_created() {
+    let db = temp_db_path();
+
+    let output = check(&db, &["--output", "json"]).output().unwrap();
+
+    assert_eq!(output.status.code(), Some(4));
+    let document = json_document(&output);
+    assert_eq!(document["errors"][0]["code"], "database");
+    assert!(!db.exists());
+}